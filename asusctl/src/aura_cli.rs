@@ -1,8 +1,11 @@
 use std::str::FromStr;
 
+use asusd::ctrl_aura::config::{LidState, RuleBrightness};
 use gumdrop::Options;
 use rog_aura::error::Error;
 use rog_aura::{AuraEffect, AuraModeNum, AuraZone, Colour, Direction, Speed};
+use rog_platform::platform::ThrottlePolicy;
+use rog_platform::power::PowerSource;
 
 #[derive(Options, Debug)]
 pub struct LedPowerCommand1 {
@@ -20,6 +23,80 @@ pub struct LedPowerCommand1 {
     pub sleep: Option<bool>,
 }
 
+#[derive(Options, Debug)]
+pub struct LedSceneCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(help = "list the saved scenes")]
+    pub list: bool,
+    #[options(
+        meta = "",
+        help = "save the lighting of all Aura devices as a scene, replacing one of the same name"
+    )]
+    pub save: Option<String>,
+    #[options(meta = "", help = "set the lighting of all Aura devices from a scene")]
+    pub apply: Option<String>,
+    #[options(meta = "", help = "delete a scene")]
+    pub delete: Option<String>,
+}
+
+#[derive(Options, Debug)]
+pub struct LedRuleCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(help = "list the rules of each Aura device")]
+    pub list: bool,
+    #[options(
+        meta = "",
+        help = "add or replace the named rule with the options below"
+    )]
+    pub set: Option<String>,
+    #[options(
+        meta = "",
+        help = "when the rule is used, joined by '+': quiet, balanced, performance, ac, battery, \
+                lid-open, lid-closed. Any state matches if none of its kind are given"
+    )]
+    pub when: Option<RuleConditions>,
+    #[options(meta = "", help = "brightness set by the rule <off, low, med, high>")]
+    pub brightness: Option<RuleBrightness>,
+    #[options(
+        meta = "",
+        help = "mode shown by the rule, with the colours and speed last set for it by led-mode"
+    )]
+    pub mode: Option<String>,
+    #[options(meta = "", help = "remove the named rule")]
+    pub remove: Option<String>,
+}
+
+/// The states an Aura rule matches, parsed from a list such as
+/// `battery+quiet`
+#[derive(Debug, Default, Clone)]
+pub struct RuleConditions {
+    pub policies: Vec<ThrottlePolicy>,
+    pub power: Vec<PowerSource>,
+    pub lid: Vec<LidState>,
+}
+
+impl FromStr for RuleConditions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut conditions = Self::default();
+        for part in s.split('+') {
+            if let Ok(policy) = ThrottlePolicy::from_str(part) {
+                conditions.policies.push(policy);
+            } else if let Ok(power) = PowerSource::from_str(part) {
+                conditions.power.push(power);
+            } else if let Ok(lid) = LidState::from_str(part) {
+                conditions.lid.push(lid);
+            } else {
+                return Err(format!("Unknown rule condition: {part}"));
+            }
+        }
+        Ok(conditions)
+    }
+}
+
 #[derive(Options, Debug)]
 pub struct LedPowerCommand2 {
     #[options(help = "print help message")]
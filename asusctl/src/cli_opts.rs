@@ -1,8 +1,12 @@
+use asusd::charge_limit::ChargeSchedule;
 use gumdrop::Options;
 use rog_platform::platform::ThrottlePolicy;
 
 use crate::anime_cli::AnimeCommand;
-use crate::aura_cli::{LedBrightness, LedPowerCommand1, LedPowerCommand2, SetAuraBuiltin};
+use crate::aura_cli::{
+    LedBrightness, LedPowerCommand1, LedPowerCommand2, LedRuleCommand, LedSceneCommand,
+    SetAuraBuiltin,
+};
 use crate::fan_curve_cli::FanCurveCommand;
 
 #[derive(Default, Options)]
@@ -21,6 +25,30 @@ pub struct CliStart {
     pub prev_kbd_bright: bool,
     #[options(meta = "", help = "Set your battery charge limit <20-100>")]
     pub chg_limit: Option<u8>,
+    #[options(
+        meta = "",
+        no_short,
+        help = "Charge to 100% once, until unplugged or after <hours> (0 for no time limit)"
+    )]
+    pub chg_full: Option<u32>,
+    #[options(
+        no_short,
+        help = "Cancel charging to 100% and return to the charge limit"
+    )]
+    pub chg_full_cancel: bool,
+    #[options(no_short, help = "Show the charge limit schedules")]
+    pub chg_schedules: bool,
+    #[options(
+        meta = "",
+        no_short,
+        help = "Add or replace a charge limit schedule <name,days,HH:MM-HH:MM,limit>, e.g. \
+                travel,fri,18:00-23:59,100 or work,mon-fri,00:00-00:00,60"
+    )]
+    pub chg_schedule: Option<ChargeSchedule>,
+    #[options(meta = "", no_short, help = "Remove the named charge limit schedule")]
+    pub chg_schedule_remove: Option<String>,
+    #[options(no_short, help = "Show battery health and charge")]
+    pub battery: bool,
     #[options(command)]
     pub command: Option<CliCommand>,
 }
@@ -33,6 +61,10 @@ pub enum CliCommand {
     LedPow1(LedPowerCommand1),
     #[options(help = "Set the LED power states")]
     LedPow2(LedPowerCommand2),
+    #[options(help = "Save and apply named scenes of the lighting of all Aura devices")]
+    LedScene(LedSceneCommand),
+    #[options(help = "Change the Aura mode and brightness with the profile, power, or lid")]
+    LedRule(LedRuleCommand),
     #[options(help = "Set or select platform_profile")]
     Profile(ProfileCommand),
     #[options(help = "Set, select, or modify fan curves if supported")]
@@ -43,6 +75,8 @@ pub enum CliCommand {
     Anime(AnimeCommand),
     #[options(help = "Change bios settings")]
     Bios(BiosCommand),
+    #[options(help = "Set CPU boost, max frequency, and SMT")]
+    Cpu(CpuCommand),
 }
 
 #[derive(Debug, Clone, Options)]
@@ -113,3 +147,30 @@ pub struct BiosCommand {
     #[options(no_long, short = "o", help = "get panel overdrive")]
     pub panel_overdrive_get: bool,
 }
+
+#[derive(Options, Debug)]
+pub struct CpuCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(help = "show the current CPU settings")]
+    pub show: bool,
+    #[options(meta = "", no_short, help = "allow CPU boost/turbo <true/false>")]
+    pub boost: Option<bool>,
+    #[options(meta = "", no_short, help = "set the max CPU frequency in kHz")]
+    pub max_freq: Option<u32>,
+    #[options(meta = "", no_short, help = "enable SMT <true/false>")]
+    pub smt: Option<bool>,
+    #[options(
+        meta = "",
+        no_short,
+        help = "store and apply these settings per profile <true/false>"
+    )]
+    pub link_profile: Option<bool>,
+    #[options(
+        meta = "",
+        no_short,
+        help = "set or show boost, max frequency, and SMT for this profile instead of the active \
+                settings"
+    )]
+    pub profile: Option<ThrottlePolicy>,
+}
@@ -1,6 +1,8 @@
+use std::path::PathBuf;
+
 use gumdrop::Options;
 use rog_platform::platform::ThrottlePolicy;
-use rog_profiles::fan_curve_set::CurveData;
+use rog_profiles::fan_curve_set::{CurvePoints, CurveResample};
 use rog_profiles::FanCurvePU;
 
 #[derive(Debug, Clone, Options)]
@@ -43,7 +45,105 @@ pub struct FanCurveCommand {
     #[options(
         meta = "",
         help = "data format = 30c:1%,49c:2%,59c:3%,69c:4%,79c:31%,89c:49%,99c:56%,109c:58%. \
-                `--mod-profile` required. If '%' is omitted the fan range is 0-255"
+                `--mod-profile` required. If '%' is omitted the fan range is 0-255. Any number of \
+                points from 2 may be given, they are spread over 8"
+    )]
+    pub data: Option<CurvePoints>,
+
+    #[options(
+        no_short,
+        meta = "",
+        help = "how `--data` with other than 8 points is spread over 8 <linear/cubic>, default \
+                linear"
+    )]
+    pub resample: Option<CurveResample>,
+
+    #[options(
+        no_short,
+        meta = "",
+        help = "multiply the fan speeds of the curves of `--mod-profile`, or only `--fan`, for \
+                example 0.9 for 10% quieter"
+    )]
+    pub scale: Option<f32>,
+
+    #[options(
+        no_short,
+        meta = "",
+        help = "move the curves of `--mod-profile`, or only `--fan`, by degrees C. Positive \
+                speeds the fans up later, use `--shift=-5` for negative"
+    )]
+    pub shift: Option<i16>,
+
+    #[options(
+        no_short,
+        help = "show or change the curves `--mod-profile` uses on battery. Battery curves start \
+                as a copy of the AC curves"
+    )]
+    pub battery: bool,
+
+    #[options(no_short, help = "use the AC curves of `--mod-profile` on battery too")]
+    pub clear_battery: bool,
+
+    #[options(
+        no_short,
+        help = "measure the speed of each fan over a range of fan powers to find the lowest at \
+                which it spins. Takes a few minutes, keep the laptop idle"
+    )]
+    pub calibrate: bool,
+
+    #[options(
+        no_short,
+        help = "show the speed, power, and temperature of each fan until interrupted"
+    )]
+    pub status: bool,
+
+    #[options(
+        no_short,
+        meta = "",
+        help = "export all fan curves and presets to a .json or .toml file"
+    )]
+    pub export: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        meta = "",
+        help = "replace the curves and presets present in a .json or .toml file, leaving the rest \
+                unchanged"
+    )]
+    pub import: Option<PathBuf>,
+
+    #[options(no_short, help = "list the named fan curve presets")]
+    pub presets: bool,
+
+    #[options(
+        no_short,
+        meta = "",
+        help = "named preset to use. Applies the preset now if no other preset options are given, \
+                or sets a curve of the preset with `--fan` and `--data`"
+    )]
+    pub preset: Option<String>,
+
+    #[options(
+        no_short,
+        help = "create the `--preset` from the curves of `--mod-profile`"
+    )]
+    pub create: bool,
+
+    #[options(no_short, meta = "", help = "rename the `--preset`")]
+    pub rename: Option<String>,
+
+    #[options(no_short, help = "delete the `--preset`")]
+    pub delete: bool,
+
+    #[options(
+        no_short,
+        help = "use the `--preset` in place of the curves of `--mod-profile`"
+    )]
+    pub bind: bool,
+
+    #[options(
+        no_short,
+        help = "return `--mod-profile` to its own curves, no `--preset` required"
     )]
-    pub data: Option<CurveData>,
+    pub unbind: bool,
 }
@@ -5,8 +5,9 @@ use std::process::Command;
 use std::thread::sleep;
 
 use anime_cli::{AnimeActions, AnimeCommand};
+use asusd::ctrl_aura::config::{AuraRule, RuleBrightness};
 use asusd::ctrl_fancurves::FAN_CURVE_ZBUS_NAME;
-use aura_cli::{LedPowerCommand1, LedPowerCommand2};
+use aura_cli::{LedPowerCommand1, LedPowerCommand2, LedRuleCommand, LedSceneCommand};
 use dmi_id::DMIID;
 use fan_curve_cli::FanCurveCommand;
 use gumdrop::{Opt, Options};
@@ -16,9 +17,14 @@ use rog_aura::power::KbAuraPowerState;
 use rog_aura::usb::{AuraDevRog1, AuraDevTuf, AuraDevice, AuraPowerDev};
 use rog_aura::{self, AuraEffect};
 use rog_dbus::zbus_aura::AuraProxyBlocking;
+use rog_dbus::zbus_platform::PlatformProxyBlocking;
 use rog_dbus::RogDbusClientBlocking;
+use rog_platform::error::PlatformError;
 use rog_platform::platform::{GpuMode, Properties, ThrottlePolicy};
+use rog_platform::power::PowerSource;
 use rog_profiles::error::ProfileError;
+use rog_profiles::fan_curve_export::FanCurveFormat;
+use rog_profiles::fan_status::FanStatus;
 
 use crate::aura_cli::{AuraPowerStates, LedBrightness};
 use crate::cli_opts::*;
@@ -149,6 +155,8 @@ fn do_parsed(
         Some(CliCommand::LedMode(mode)) => handle_led_mode(&find_aura_iface()?, mode)?,
         Some(CliCommand::LedPow1(pow)) => handle_led_power1(&find_aura_iface()?, pow)?,
         Some(CliCommand::LedPow2(pow)) => handle_led_power2(&find_aura_iface()?, pow)?,
+        Some(CliCommand::LedScene(cmd)) => handle_led_scene(dbus, cmd)?,
+        Some(CliCommand::LedRule(cmd)) => handle_led_rule(&find_aura_iface()?, cmd)?,
         Some(CliCommand::Profile(cmd)) => handle_throttle_profile(dbus, supported_properties, cmd)?,
         Some(CliCommand::FanCurve(cmd)) => {
             handle_fan_curve(dbus, supported_interfaces, cmd)?;
@@ -156,10 +164,17 @@ fn do_parsed(
         Some(CliCommand::Graphics(_)) => do_gfx(),
         Some(CliCommand::Anime(cmd)) => handle_anime(dbus, cmd)?,
         Some(CliCommand::Bios(cmd)) => handle_platform_properties(dbus, supported_properties, cmd)?,
+        Some(CliCommand::Cpu(cmd)) => handle_cpu(dbus, supported_properties, cmd)?,
         None => {
             if (!parsed.show_supported
                 && parsed.kbd_bright.is_none()
                 && parsed.chg_limit.is_none()
+                && parsed.chg_full.is_none()
+                && !parsed.chg_full_cancel
+                && !parsed.chg_schedules
+                && parsed.chg_schedule.is_none()
+                && parsed.chg_schedule_remove.is_none()
+                && !parsed.battery
                 && !parsed.next_kbd_bright
                 && !parsed.prev_kbd_bright)
                 || parsed.help
@@ -266,6 +281,12 @@ fn do_parsed(
             .set_charge_control_end_threshold(chg_limit)?;
     }
 
+    handle_charge_limit(dbus, parsed)?;
+
+    if parsed.battery {
+        handle_battery(dbus, supported_properties)?;
+    }
+
     Ok(())
 }
 
@@ -553,6 +574,103 @@ fn handle_led_mode(
     Ok(())
 }
 
+fn handle_led_scene(
+    dbus: &RogDbusClientBlocking<'_>,
+    cmd: &LedSceneCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if (!cmd.list && cmd.save.is_none() && cmd.apply.is_none() && cmd.delete.is_none()) || cmd.help
+    {
+        if !cmd.help {
+            println!("Missing arg or command\n");
+        }
+        println!("{}", cmd.self_usage());
+        return Ok(());
+    }
+
+    let scenes = dbus.proxies().aura_scenes();
+    if let Some(name) = cmd.save.as_deref() {
+        scenes.save_aura_scene(name)?;
+    }
+    if let Some(name) = cmd.apply.as_deref() {
+        scenes.apply_aura_scene(name)?;
+    }
+    if let Some(name) = cmd.delete.as_deref() {
+        scenes.delete_aura_scene(name)?;
+    }
+    if cmd.list {
+        let names = scenes.aura_scenes()?;
+        if names.is_empty() {
+            println!("No Aura scenes");
+        }
+        for name in names {
+            println!("{name}");
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_led_rule(
+    aura: &[AuraProxyBlocking],
+    cmd: &LedRuleCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if (!cmd.list && cmd.set.is_none() && cmd.remove.is_none()) || cmd.help {
+        if !cmd.help {
+            println!("Missing arg or command\n");
+        }
+        println!("{}", cmd.self_usage());
+        return Ok(());
+    }
+
+    for aura in aura {
+        if let Some(name) = cmd.set.as_ref() {
+            let mut effects = Vec::new();
+            if let Some(mode) = cmd.mode.as_deref() {
+                let modes = aura.all_mode_data()?;
+                let Some(effect) = modes
+                    .iter()
+                    .find(|(m, _)| <&str>::from(*m).eq_ignore_ascii_case(mode))
+                    .map(|(_, e)| e.clone())
+                else {
+                    println!("Mode {mode} is not supported, must be one of:");
+                    for m in modes.keys() {
+                        println!("  {}", <&str>::from(m).to_lowercase());
+                    }
+                    return Ok(());
+                };
+                effects.push(effect);
+            }
+            let when = cmd.when.clone().unwrap_or_default();
+            aura.set_aura_rule(&AuraRule {
+                name: name.clone(),
+                policies: when.policies,
+                power: when.power,
+                lid: when.lid,
+                effects,
+                brightness: cmd.brightness.unwrap_or(RuleBrightness::Keep),
+            })?;
+        }
+        if let Some(name) = cmd.remove.as_deref() {
+            aura.remove_aura_rule(name)?;
+        }
+        if cmd.list {
+            let rules = aura.aura_rules()?;
+            if rules.is_empty() {
+                println!("No Aura rules");
+            }
+            for rule in rules {
+                let modes: Vec<&str> = rule.effects.iter().map(|e| (&e.mode).into()).collect();
+                println!(
+                    "{}: policies {:?}, power {:?}, lid {:?}, modes {:?}, brightness {:?}",
+                    rule.name, rule.policies, rule.power, rule.lid, modes, rule.brightness
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn handle_led_power1(
     aura: &[AuraProxyBlocking],
     power: &LedPowerCommand1,
@@ -772,6 +890,65 @@ fn handle_fan_curve(
         return Err(ProfileError::NotSupported.into());
     }
 
+    if cmd.presets || cmd.preset.is_some() || cmd.unbind {
+        return handle_fan_curve_preset(dbus, cmd);
+    }
+
+    if cmd.calibrate {
+        let proxy = dbus.proxies().fan_curves();
+        proxy.calibrate_fans()?;
+        println!("Calibrating fans, this takes a few minutes...");
+        // Wait for the start to be seen in case the first item is from before
+        let mut started = false;
+        for calibrating in proxy.receive_fan_calibrating_changed() {
+            if calibrating.get()? {
+                started = true;
+            } else if started {
+                break;
+            }
+        }
+        for calibration in proxy.fan_calibration()? {
+            println!("{}", calibration.report());
+        }
+        let error = proxy.fan_calibration_error()?;
+        if !error.is_empty() {
+            println!("{error}");
+        }
+        return Ok(());
+    }
+
+    if cmd.status {
+        let proxy = dbus.proxies().fan_curves();
+        if proxy.fan_status_interval()? == 0 {
+            println!("Fan status updates are off, showing the current status only");
+            print_fan_status(&proxy.fan_status()?);
+            return Ok(());
+        }
+        // The first item is the current status
+        for status in proxy.receive_fan_status_changed() {
+            print_fan_status(&status.get()?);
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = cmd.export.as_deref() {
+        let format = FanCurveFormat::from_path(path)?;
+        let data = dbus.proxies().fan_curves().export_fan_curves(format)?;
+        std::fs::write(path, data)?;
+        println!("Fan curves exported to {}", path.display());
+        return Ok(());
+    }
+
+    if let Some(path) = cmd.import.as_deref() {
+        let format = FanCurveFormat::from_path(path)?;
+        let data = std::fs::read_to_string(path)?;
+        dbus.proxies()
+            .fan_curves()
+            .import_fan_curves(&data, format)?;
+        println!("Fan curves imported from {}", path.display());
+        return Ok(());
+    }
+
     if !cmd.get_enabled && !cmd.default && cmd.mod_profile.is_none() {
         if !cmd.help {
             println!("Missing arg or command\n");
@@ -784,12 +961,18 @@ fn handle_fan_curve(
         return Ok(());
     }
 
-    if (cmd.enable_fan_curves.is_some() || cmd.fan.is_some() || cmd.data.is_some())
+    if (cmd.enable_fan_curves.is_some()
+        || cmd.fan.is_some()
+        || cmd.data.is_some()
+        || cmd.scale.is_some()
+        || cmd.shift.is_some()
+        || cmd.battery
+        || cmd.clear_battery)
         && cmd.mod_profile.is_none()
     {
         println!(
-            "--enable-fan-curves, --enable-fan-curve, --fan, and --data options require \
-             --mod-profile"
+            "--enable-fan-curves, --enable-fan-curve, --fan, --data, --scale, --shift, --battery, \
+             and --clear-battery options require --mod-profile"
         );
         return Ok(());
     }
@@ -808,10 +991,30 @@ fn handle_fan_curve(
     }
 
     if let Some(profile) = cmd.mod_profile {
-        if cmd.enable_fan_curves.is_none() && cmd.data.is_none() {
-            let data = dbus.proxies().fan_curves().fan_curve_data(profile)?;
+        let power = if cmd.battery {
+            PowerSource::Battery
+        } else {
+            PowerSource::Ac
+        };
+
+        if cmd.clear_battery {
+            dbus.proxies()
+                .fan_curves()
+                .clear_battery_fan_curves(profile)?;
+        }
+
+        if cmd.enable_fan_curves.is_none()
+            && cmd.data.is_none()
+            && cmd.scale.is_none()
+            && cmd.shift.is_none()
+            && !cmd.clear_battery
+        {
+            let data = dbus
+                .proxies()
+                .fan_curves()
+                .fan_curve_data_for_power(profile, power)?;
             let data = toml::to_string(&data)?;
-            println!("\nFan curves for {:?}\n\n{}", profile, data);
+            println!("\nFan curves for {:?} on {:?}\n\n{}", profile, power, data);
         }
 
         if let Some(enabled) = cmd.enable_fan_curves {
@@ -833,13 +1036,133 @@ fn handle_fan_curve(
             }
         }
 
-        if let Some(mut curve) = cmd.data.clone() {
-            let fan = cmd.fan.unwrap_or_default();
-            curve.set_fan(fan);
-            dbus.proxies().fan_curves().set_fan_curve(profile, curve)?;
+        if let Some(points) = &cmd.data {
+            let curve = points.curve(
+                cmd.fan.unwrap_or_default(),
+                cmd.resample.unwrap_or_default(),
+            )?;
+            dbus.proxies()
+                .fan_curves()
+                .set_fan_curve_for_power(profile, power, curve)?;
+        }
+
+        if cmd.scale.is_some() || cmd.shift.is_some() {
+            let curves = dbus
+                .proxies()
+                .fan_curves()
+                .fan_curve_data_for_power(profile, power)?;
+            for mut curve in curves {
+                if cmd.fan.is_some_and(|fan| fan != curve.fan) {
+                    continue;
+                }
+                if let Some(factor) = cmd.scale {
+                    curve.scale(factor);
+                }
+                if let Some(degrees) = cmd.shift {
+                    curve.shift(degrees);
+                }
+                println!("{}", String::from(&curve));
+                dbus.proxies()
+                    .fan_curves()
+                    .set_fan_curve_for_power(profile, power, curve)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_fan_status(status: &[FanStatus]) {
+    let status: Vec<String> = status
+        .iter()
+        .map(|s| {
+            format!(
+                "{:?}: {} RPM, {}% at {}c",
+                s.fan,
+                s.rpm,
+                s.pwm as u32 * 100 / 255,
+                s.temp
+            )
+        })
+        .collect();
+    println!("{}", status.join(", "));
+}
+
+fn handle_fan_curve_preset(
+    dbus: &RogDbusClientBlocking<'_>,
+    cmd: &FanCurveCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if cmd.presets {
+        let presets = dbus.proxies().fan_curves().fan_curve_presets()?;
+        if presets.is_empty() {
+            println!("No fan curve presets");
+        }
+        for preset in presets {
+            if preset.policies.is_empty() {
+                println!("{}:", preset.name);
+            } else {
+                println!("{} (bound to {:?}):", preset.name, preset.policies);
+            }
+            for curve in &preset.curves {
+                println!("  {}", String::from(curve));
+            }
+        }
+    }
+
+    if cmd.unbind {
+        let Some(profile) = cmd.mod_profile else {
+            println!("--unbind requires --mod-profile");
+            return Ok(());
+        };
+        dbus.proxies()
+            .fan_curves()
+            .unbind_fan_curve_preset(profile)?;
+    }
+
+    let Some(name) = cmd.preset.as_deref() else {
+        return Ok(());
+    };
+    if (cmd.create || cmd.bind) && cmd.mod_profile.is_none() {
+        println!("--create and --bind require --mod-profile");
+        return Ok(());
+    }
+
+    if cmd.create {
+        if let Some(profile) = cmd.mod_profile {
+            dbus.proxies()
+                .fan_curves()
+                .create_fan_curve_preset(name, profile)?;
+        }
+    }
+
+    if let Some(points) = &cmd.data {
+        let curve = points.curve(
+            cmd.fan.unwrap_or_default(),
+            cmd.resample.unwrap_or_default(),
+        )?;
+        dbus.proxies()
+            .fan_curves()
+            .set_preset_fan_curve(name, curve)?;
+    }
+
+    if cmd.bind {
+        if let Some(profile) = cmd.mod_profile {
+            dbus.proxies()
+                .fan_curves()
+                .bind_fan_curve_preset(name, profile)?;
         }
     }
 
+    if let Some(new_name) = cmd.rename.as_deref() {
+        dbus.proxies()
+            .fan_curves()
+            .rename_fan_curve_preset(name, new_name)?;
+    } else if cmd.delete {
+        dbus.proxies().fan_curves().delete_fan_curve_preset(name)?;
+    } else if !cmd.create && !cmd.bind && cmd.data.is_none() {
+        dbus.proxies().fan_curves().apply_fan_curve_preset(name)?;
+    }
+
     Ok(())
 }
 
@@ -904,6 +1227,209 @@ fn handle_platform_properties(
     Ok(())
 }
 
+fn handle_cpu(
+    dbus: &RogDbusClientBlocking<'_>,
+    supported: &[Properties],
+    cmd: &CpuCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !supported.contains(&Properties::CpuBoost)
+        && !supported.contains(&Properties::CpuMaxFreq)
+        && !supported.contains(&Properties::Smt)
+    {
+        println!("CPU control not supported by either this kernel or by the laptop.");
+        return Err(PlatformError::NotSupported.into());
+    }
+
+    if (!cmd.show
+        && cmd.boost.is_none()
+        && cmd.max_freq.is_none()
+        && cmd.smt.is_none()
+        && cmd.link_profile.is_none())
+        || cmd.help
+    {
+        if !cmd.help {
+            println!("Missing arg or command\n");
+        }
+        println!("{}", CpuCommand::usage());
+        return Ok(());
+    }
+
+    let platform = dbus.proxies().platform();
+    // Must be first so the following are stored in the right place
+    if let Some(linked) = cmd.link_profile {
+        platform.set_throttle_policy_linked_cpu(linked)?;
+    }
+    if let Some(profile) = cmd.profile {
+        return handle_cpu_for_profile(platform, supported, cmd, profile);
+    }
+    if let Some(on) = cmd.boost {
+        platform.set_cpu_boost(on)?;
+    }
+    if let Some(khz) = cmd.max_freq {
+        platform.set_cpu_max_freq(khz)?;
+    }
+    if let Some(on) = cmd.smt {
+        platform.set_smt(on)?;
+    }
+
+    if cmd.show {
+        println!(
+            "Linked to profile: {}",
+            platform.throttle_policy_linked_cpu()?
+        );
+        if supported.contains(&Properties::CpuBoost) {
+            println!("Boost on: {}", platform.cpu_boost()?);
+        }
+        if supported.contains(&Properties::CpuMaxFreq) {
+            println!("Max frequency: {}kHz", platform.cpu_max_freq()?);
+        }
+        if supported.contains(&Properties::Smt) {
+            println!("SMT on: {}", platform.smt()?);
+        }
+    }
+    Ok(())
+}
+
+/// Set or show the CPU settings stored for a profile, used while linked
+fn handle_cpu_for_profile(
+    platform: &PlatformProxyBlocking<'_>,
+    supported: &[Properties],
+    cmd: &CpuCommand,
+    profile: ThrottlePolicy,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(on) = cmd.boost {
+        match profile {
+            ThrottlePolicy::Balanced => platform.set_throttle_balanced_cpu_boost(on)?,
+            ThrottlePolicy::Performance => platform.set_throttle_performance_cpu_boost(on)?,
+            ThrottlePolicy::Quiet => platform.set_throttle_quiet_cpu_boost(on)?,
+        }
+    }
+    if let Some(khz) = cmd.max_freq {
+        match profile {
+            ThrottlePolicy::Balanced => platform.set_throttle_balanced_cpu_max_freq(khz)?,
+            ThrottlePolicy::Performance => platform.set_throttle_performance_cpu_max_freq(khz)?,
+            ThrottlePolicy::Quiet => platform.set_throttle_quiet_cpu_max_freq(khz)?,
+        }
+    }
+    if let Some(on) = cmd.smt {
+        match profile {
+            ThrottlePolicy::Balanced => platform.set_throttle_balanced_smt(on)?,
+            ThrottlePolicy::Performance => platform.set_throttle_performance_smt(on)?,
+            ThrottlePolicy::Quiet => platform.set_throttle_quiet_smt(on)?,
+        }
+    }
+
+    if cmd.show {
+        println!(
+            "Linked to profile: {}",
+            platform.throttle_policy_linked_cpu()?
+        );
+        println!("{profile} profile:");
+        if supported.contains(&Properties::CpuBoost) {
+            let on = match profile {
+                ThrottlePolicy::Balanced => platform.throttle_balanced_cpu_boost()?,
+                ThrottlePolicy::Performance => platform.throttle_performance_cpu_boost()?,
+                ThrottlePolicy::Quiet => platform.throttle_quiet_cpu_boost()?,
+            };
+            println!("  Boost on: {on}");
+        }
+        if supported.contains(&Properties::CpuMaxFreq) {
+            let khz = match profile {
+                ThrottlePolicy::Balanced => platform.throttle_balanced_cpu_max_freq()?,
+                ThrottlePolicy::Performance => platform.throttle_performance_cpu_max_freq()?,
+                ThrottlePolicy::Quiet => platform.throttle_quiet_cpu_max_freq()?,
+            };
+            println!("  Max frequency: {khz}kHz");
+        }
+        if supported.contains(&Properties::Smt) {
+            let on = match profile {
+                ThrottlePolicy::Balanced => platform.throttle_balanced_smt()?,
+                ThrottlePolicy::Performance => platform.throttle_performance_smt()?,
+                ThrottlePolicy::Quiet => platform.throttle_quiet_smt()?,
+            };
+            println!("  SMT on: {on}");
+        }
+    }
+    Ok(())
+}
+
+fn handle_charge_limit(
+    dbus: &RogDbusClientBlocking<'_>,
+    parsed: &CliStart,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let platform = dbus.proxies().platform();
+    if let Some(schedule) = parsed.chg_schedule.as_ref() {
+        platform.set_charge_schedule(schedule)?;
+    }
+    if let Some(name) = parsed.chg_schedule_remove.as_ref() {
+        platform.remove_charge_schedule(name)?;
+    }
+    if parsed.chg_full_cancel {
+        platform.cancel_charge_to_full()?;
+    }
+    if let Some(hours) = parsed.chg_full {
+        platform.charge_to_full_once(hours)?;
+    }
+
+    if parsed.chg_schedules {
+        println!(
+            "Charge limit: {}%",
+            platform.charge_control_end_threshold()?
+        );
+        println!("Charging to full: {}", platform.charge_to_full_active()?);
+        for schedule in platform.charge_schedules()? {
+            let days = if schedule.days.is_empty() {
+                "every day".to_owned()
+            } else {
+                format!("{:?}", schedule.days)
+            };
+            println!(
+                "{}: {}% {}-{} on {days}",
+                schedule.name, schedule.limit, schedule.start, schedule.end
+            );
+        }
+    }
+    Ok(())
+}
+
+fn handle_battery(
+    dbus: &RogDbusClientBlocking<'_>,
+    supported: &[Properties],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !supported.contains(&Properties::BatteryInfo) {
+        println!("Battery info not supported by either this kernel or by the laptop.");
+        return Err(PlatformError::NotSupported.into());
+    }
+
+    let platform = dbus.proxies().platform();
+    let hours_mins = |secs: u32| format!("{}h {:02}m", secs / 3600, secs % 3600 / 60);
+    println!("Status: {:?}", platform.battery_status()?);
+    println!("Charge: {}%", platform.battery_capacity()?);
+    println!(
+        "Power: {:.1}W",
+        platform.battery_power_now()? as f64 / 1000.0
+    );
+    let empty = platform.battery_time_to_empty()?;
+    if empty != 0 {
+        println!("Time to empty: {}", hours_mins(empty));
+    }
+    let full = platform.battery_time_to_full()?;
+    if full != 0 {
+        println!("Time to full: {}", hours_mins(full));
+    }
+    println!(
+        "Capacity: {:.1}Wh of {:.1}Wh design",
+        platform.battery_energy_full()? as f64 / 1000.0,
+        platform.battery_energy_full_design()? as f64 / 1000.0
+    );
+    println!("Wear: {:.1}%", platform.battery_wear()?);
+    let cycles = platform.battery_cycle_count()?;
+    if cycles != 0 {
+        println!("Cycle count: {cycles}");
+    }
+    Ok(())
+}
+
 fn check_systemd_unit_active(name: &str) -> bool {
     if let Ok(out) = Command::new("systemctl")
         .arg("is-active")
@@ -0,0 +1,366 @@
+//! Light the keyboard with the colours of the screen. Zoned and basic
+//! keyboards take one colour for the whole screen, per-key keyboards take the
+//! colour of the screen region above each key.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use rog_aura::advanced::LedCode;
+use rog_aura::layouts::KeyLayout;
+use rog_aura::{AdvancedAuraType, Colour};
+
+use crate::config::{AmbientSampling, AmbientSource, ConfigAmbient};
+use crate::error::Error;
+use crate::led_output::{frame_interval, LedFrame, LedOutput};
+
+#[cfg(feature = "screencast")]
+mod screencast;
+
+/// A downscaled frame of the screen
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    /// Row by row from the top left
+    pub pixels: Vec<Colour>,
+}
+
+impl Frame {
+    /// The colour of the region from `x0, y0` to `x1, y1`, given as fractions
+    /// of the frame size. A region smaller than a pixel takes the pixel it is
+    /// in.
+    pub fn sample(&self, x0: f32, y0: f32, x1: f32, y1: f32, sampling: AmbientSampling) -> Colour {
+        if self.width == 0 || self.height == 0 {
+            return Colour { r: 0, g: 0, b: 0 };
+        }
+        let to_px = |v: f32, len: usize| ((v.clamp(0.0, 1.0) * len as f32) as usize).min(len - 1);
+        let (left, top) = (to_px(x0, self.width), to_px(y0, self.height));
+        let right = to_px(x1, self.width).max(left);
+        let bottom = to_px(y1, self.height).max(top);
+        let pixels = (top..=bottom).flat_map(|y| {
+            self.pixels[y * self.width + left..=y * self.width + right]
+                .iter()
+                .copied()
+        });
+        match sampling {
+            AmbientSampling::Average => average(pixels),
+            AmbientSampling::Dominant => dominant(pixels),
+        }
+    }
+
+    /// The colour of the whole frame
+    pub fn sample_all(&self, sampling: AmbientSampling) -> Colour {
+        self.sample(0.0, 0.0, 1.0, 1.0, sampling)
+    }
+}
+
+fn average(pixels: impl Iterator<Item = Colour>) -> Colour {
+    let (mut r, mut g, mut b, mut count) = (0u64, 0u64, 0u64, 0u64);
+    for c in pixels {
+        r += c.r as u64;
+        g += c.g as u64;
+        b += c.b as u64;
+        count += 1;
+    }
+    let count = count.max(1);
+    Colour {
+        r: (r / count) as u8,
+        g: (g / count) as u8,
+        b: (b / count) as u8,
+    }
+}
+
+/// The average of the most common group of similar colours
+fn dominant(pixels: impl Iterator<Item = Colour>) -> Colour {
+    let mut groups: HashMap<u16, Vec<Colour>> = HashMap::new();
+    for c in pixels {
+        let key = (c.r as u16 >> 4) << 8 | (c.g as u16 >> 4) << 4 | c.b as u16 >> 4;
+        groups.entry(key).or_default().push(c);
+    }
+    let largest = groups
+        .into_values()
+        .max_by_key(Vec::len)
+        .unwrap_or_default();
+    average(largest.into_iter())
+}
+
+/// Gives frames of the screen, or something standing in for it
+pub trait FrameSource: Send {
+    /// The newest frame, `None` if there is none since the last call
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error>;
+}
+
+/// Colour bars scrolling to the left over a dark strip along the bottom
+pub struct TestPattern {
+    start: Instant,
+}
+
+impl TestPattern {
+    const BARS: [Colour; 7] = [
+        Colour {
+            r: 255,
+            g: 255,
+            b: 255,
+        },
+        Colour {
+            r: 255,
+            g: 255,
+            b: 0,
+        },
+        Colour {
+            r: 0,
+            g: 255,
+            b: 255,
+        },
+        Colour { r: 0, g: 255, b: 0 },
+        Colour {
+            r: 255,
+            g: 0,
+            b: 255,
+        },
+        Colour { r: 255, g: 0, b: 0 },
+        Colour { r: 0, g: 0, b: 255 },
+    ];
+    const HEIGHT: usize = 36;
+    /// Pixels scrolled each second
+    const SPEED: f32 = 8.0;
+    const WIDTH: usize = 64;
+
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for TestPattern {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameSource for TestPattern {
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        let offset = (self.start.elapsed().as_secs_f32() * Self::SPEED) as usize;
+        let bars = Self::BARS.len();
+        let mut pixels = Vec::with_capacity(Self::WIDTH * Self::HEIGHT);
+        for y in 0..Self::HEIGHT {
+            for x in 0..Self::WIDTH {
+                if y >= Self::HEIGHT * 3 / 4 {
+                    pixels.push(Colour {
+                        r: 16,
+                        g: 16,
+                        b: 16,
+                    });
+                } else {
+                    let bar = (x + offset) % Self::WIDTH * bars / Self::WIDTH;
+                    pixels.push(Self::BARS[bar]);
+                }
+            }
+        }
+        Ok(Some(Frame {
+            width: Self::WIDTH,
+            height: Self::HEIGHT,
+            pixels,
+        }))
+    }
+}
+
+/// Open the configured source. The screencast asks the desktop for a screen
+/// to share, which may wait on the user.
+pub fn open_source(source: AmbientSource) -> Result<Box<dyn FrameSource>, Error> {
+    match source {
+        AmbientSource::TestPattern => Ok(Box::new(TestPattern::new())),
+        #[cfg(feature = "screencast")]
+        AmbientSource::Screencast => Ok(Box::new(screencast::ScreencastSource::start()?)),
+        #[cfg(not(feature = "screencast"))]
+        AmbientSource::Screencast => Err(Error::Screencast(
+            "asusd-user was built without the screencast feature".to_owned(),
+        )),
+    }
+}
+
+pub struct AmbientRenderer {
+    config: ConfigAmbient,
+    source: Box<dyn FrameSource>,
+    /// The scaled position of each LED, empty on zoned and basic keyboards
+    leds: Vec<(LedCode, f32, f32)>,
+    output: LedOutput,
+}
+
+impl AmbientRenderer {
+    pub fn new(config: ConfigAmbient, layout: &KeyLayout, source: Box<dyn FrameSource>) -> Self {
+        let leds = match layout.advanced_type() {
+            AdvancedAuraType::PerKey => layout.led_positions_scaled(),
+            _ => Vec::new(),
+        };
+        Self {
+            output: LedOutput::new("ambient lighting", config.threshold),
+            config,
+            source,
+            leds,
+        }
+    }
+
+    fn colours(&self, frame: &Frame) -> LedFrame {
+        let sampling = self.config.sampling;
+        if self.leds.is_empty() {
+            return LedFrame::Static(frame.sample_all(sampling));
+        }
+        let (half_w, half_h) = (
+            0.5 / self.config.columns.max(1) as f32,
+            0.5 / self.config.rows.max(1) as f32,
+        );
+        LedFrame::PerKey(
+            self.leds
+                .iter()
+                .map(|(led, x, y)| {
+                    let colour =
+                        frame.sample(x - half_w, y - half_h, x + half_w, y + half_h, sampling);
+                    (*led, colour)
+                })
+                .collect(),
+        )
+    }
+
+    /// Run the ambient lighting until the source fails
+    pub fn run(&mut self) -> Result<(), Error> {
+        let interval = frame_interval(self.config.max_rate.min(30), !self.leds.is_empty());
+        loop {
+            if !self.output.ready() {
+                continue;
+            }
+            let start = Instant::now();
+            if let Some(frame) = self.source.next_frame()? {
+                let colours = self.colours(&frame);
+                self.output.send(colours);
+            }
+            let elapsed = start.elapsed();
+            if elapsed < interval {
+                std::thread::sleep(interval - elapsed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rog_aura::advanced::LedCode;
+    use rog_aura::aura_detection::LaptopLedData;
+    use rog_aura::layouts::KeyLayout;
+    use rog_aura::{AdvancedAuraType, Colour};
+
+    use super::{AmbientRenderer, Frame, FrameSource, TestPattern};
+    use crate::config::{AmbientSampling, ConfigAmbient};
+    use crate::led_output::LedFrame;
+
+    const RED: Colour = Colour { r: 255, g: 0, b: 0 };
+    const BLUE: Colour = Colour { r: 0, g: 0, b: 255 };
+
+    fn test_pattern() -> Frame {
+        TestPattern::new().next_frame().unwrap().unwrap()
+    }
+
+    #[test]
+    fn sample_regions() {
+        // Red on the left half, blue on the right
+        let frame = Frame {
+            width: 4,
+            height: 2,
+            pixels: vec![RED, RED, BLUE, BLUE, RED, RED, BLUE, BLUE],
+        };
+        let average = AmbientSampling::Average;
+        assert_eq!(frame.sample(0.0, 0.0, 0.25, 1.0, average), RED);
+        assert_eq!(frame.sample(0.75, 0.0, 1.0, 1.0, average), BLUE);
+        assert_eq!(
+            frame.sample_all(average),
+            Colour {
+                r: 127,
+                g: 0,
+                b: 127
+            }
+        );
+        // Smaller than a pixel, or outside the frame
+        assert_eq!(frame.sample(0.9, 0.9, 0.9, 0.9, average), BLUE);
+        assert_eq!(frame.sample(-1.0, -1.0, -0.5, -0.5, average), RED);
+
+        let empty = Frame {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        };
+        assert_eq!(empty.sample_all(average), Colour { r: 0, g: 0, b: 0 });
+    }
+
+    #[test]
+    fn dominant_takes_the_most_common_colours() {
+        let near_red = Colour { r: 251, g: 6, b: 0 };
+        let frame = Frame {
+            width: 4,
+            height: 1,
+            pixels: vec![RED, near_red, RED, BLUE],
+        };
+        assert_eq!(
+            frame.sample_all(AmbientSampling::Dominant),
+            Colour { r: 253, g: 2, b: 0 }
+        );
+        assert_eq!(
+            frame.sample_all(AmbientSampling::Average),
+            Colour {
+                r: 190,
+                g: 1,
+                b: 63
+            }
+        );
+    }
+
+    #[test]
+    fn static_colour_of_the_whole_screen() {
+        let renderer = AmbientRenderer::new(
+            ConfigAmbient::default(),
+            &KeyLayout::default_layout(),
+            Box::new(TestPattern::new()),
+        );
+        let frame = test_pattern();
+        assert_eq!(
+            renderer.colours(&frame),
+            LedFrame::Static(frame.sample_all(AmbientSampling::Average))
+        );
+    }
+
+    #[test]
+    fn per_key_colours_of_the_screen_above() {
+        let led_data = LaptopLedData {
+            layout_name: "g513i-per-key".to_owned(),
+            advanced_type: AdvancedAuraType::PerKey,
+            ..Default::default()
+        };
+        let mut data = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        data.push("../rog-aura/data");
+        let layout = KeyLayout::find_layout(led_data, data).unwrap();
+        let renderer = AmbientRenderer::new(
+            ConfigAmbient::default(),
+            &layout,
+            Box::new(TestPattern::new()),
+        );
+
+        let LedFrame::PerKey(colours) = renderer.colours(&test_pattern()) else {
+            panic!("Expected per-key colours");
+        };
+        assert_eq!(colours.len(), layout.led_positions_scaled().len());
+        let colour = |led| colours.iter().find(|(l, _)| *l == led).unwrap().1;
+        // The top row is under the colour bars, the bottom under the dark strip
+        let esc = colour(LedCode::Esc);
+        assert!(esc.r.max(esc.g).max(esc.b) >= 127, "{esc:?}");
+        assert_eq!(
+            colour(LedCode::LCtrl),
+            Colour {
+                r: 16,
+                g: 16,
+                b: 16
+            }
+        );
+    }
+}
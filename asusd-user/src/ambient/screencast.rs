@@ -0,0 +1,267 @@
+//! Frames of a screen shared through the desktop portal. The portal gives a
+//! PipeWire stream which is read on its own thread, keeping only the newest
+//! frame.
+
+use std::os::fd::OwnedFd;
+use std::sync::{Arc, Mutex};
+
+use ashpd::desktop::screencast::{CursorMode, PersistMode, Screencast, SourceType};
+use ashpd::desktop::Session;
+use ashpd::WindowIdentifier;
+use log::{info, warn};
+use pipewire as pw;
+use pw::properties::properties;
+use pw::spa::param::video::{VideoFormat, VideoInfoRaw};
+use pw::spa::param::ParamType;
+use pw::spa::pod::Pod;
+use rog_aura::Colour;
+
+use super::{Frame, FrameSource};
+use crate::error::Error;
+
+/// Frames are downscaled to at most this wide as they are copied out
+const MAX_WIDTH: usize = 128;
+
+#[derive(Default)]
+struct Shared {
+    frame: Option<Frame>,
+    error: Option<String>,
+}
+
+pub struct ScreencastSource {
+    shared: Arc<Mutex<Shared>>,
+    /// The screencast lasts as long as the session
+    _session: Session<'static>,
+}
+
+impl ScreencastSource {
+    /// Ask the desktop for a screen to share and start reading it
+    pub fn start() -> Result<Self, Error> {
+        let (session, fd, node) =
+            smol::block_on(open_portal()).map_err(|e| Error::Screencast(e.to_string()))?;
+        info!("Sharing screen through PipeWire node {node}");
+
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let thread_shared = shared.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = run_stream(fd, node, thread_shared.clone()) {
+                warn!("Screencast stream stopped: {e}");
+                if let Ok(mut shared) = thread_shared.lock() {
+                    shared.error = Some(e.to_string());
+                }
+            }
+        });
+        Ok(Self {
+            shared,
+            _session: session,
+        })
+    }
+}
+
+impl FrameSource for ScreencastSource {
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        let mut shared = self
+            .shared
+            .lock()
+            .map_err(|e| Error::Screencast(e.to_string()))?;
+        if let Some(e) = shared.error.take() {
+            return Err(Error::Screencast(e));
+        }
+        Ok(shared.frame.take())
+    }
+}
+
+async fn open_portal() -> ashpd::Result<(Session<'static>, OwnedFd, u32)> {
+    let proxy = Screencast::new().await?;
+    let session = proxy.create_session().await?;
+    proxy
+        .select_sources(
+            &session,
+            CursorMode::Hidden,
+            SourceType::Monitor.into(),
+            false,
+            None,
+            PersistMode::DoNot,
+        )
+        .await?
+        .response()?;
+    let streams = proxy
+        .start(&session, &WindowIdentifier::default())
+        .await?
+        .response()?;
+    let Some(stream) = streams.streams().first() else {
+        return Err(ashpd::Error::NoResponse);
+    };
+    let node = stream.pipe_wire_node_id();
+    let fd = proxy.open_pipe_wire_remote(&session).await?;
+    Ok((session, fd, node))
+}
+
+/// Copy a frame of 4 byte pixels, keeping every few pixels so the frame is
+/// no wider than `MAX_WIDTH`
+fn to_frame(
+    bytes: &[u8],
+    format: VideoFormat,
+    width: usize,
+    height: usize,
+    stride: usize,
+) -> Option<Frame> {
+    // The offset of red, green, and blue in each pixel
+    let (r, g, b) = if format == VideoFormat::BGRx || format == VideoFormat::BGRA {
+        (2, 1, 0)
+    } else if format == VideoFormat::RGBx || format == VideoFormat::RGBA {
+        (0, 1, 2)
+    } else {
+        return None;
+    };
+    // Some producers leave the stride unset for tightly packed rows
+    let stride = if stride == 0 { width * 4 } else { stride };
+    let step = width.div_ceil(MAX_WIDTH).max(1);
+    let (out_width, out_height) = (width / step, height / step);
+    if out_width == 0 || out_height == 0 || stride < width * 4 || bytes.len() < stride * height {
+        return None;
+    }
+    let mut pixels = Vec::with_capacity(out_width * out_height);
+    for y in 0..out_height {
+        let row = &bytes[y * step * stride..];
+        for x in 0..out_width {
+            let px = &row[x * step * 4..x * step * 4 + 4];
+            pixels.push(Colour {
+                r: px[r],
+                g: px[g],
+                b: px[b],
+            });
+        }
+    }
+    Some(Frame {
+        width: out_width,
+        height: out_height,
+        pixels,
+    })
+}
+
+fn run_stream(fd: OwnedFd, node: u32, shared: Arc<Mutex<Shared>>) -> Result<(), pw::Error> {
+    pw::init();
+    let mainloop = pw::main_loop::MainLoop::new(None)?;
+    let context = pw::context::Context::new(&mainloop)?;
+    let core = context.connect_fd(fd, None)?;
+
+    let stream = pw::stream::Stream::new(
+        &core,
+        "asusd-user-ambient",
+        properties! {
+            *pw::keys::MEDIA_TYPE => "Video",
+            *pw::keys::MEDIA_CATEGORY => "Capture",
+            *pw::keys::MEDIA_ROLE => "Screen",
+        },
+    )?;
+
+    let _listener = stream
+        .add_local_listener_with_user_data(VideoInfoRaw::default())
+        .param_changed(|_, format, id, param| {
+            let Some(param) = param else {
+                return;
+            };
+            if id != ParamType::Format.as_raw() {
+                return;
+            }
+            if let Err(e) = format.parse(param) {
+                warn!("Screencast gave a format that can't be read: {e}");
+            }
+        })
+        .process(move |stream, format| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let Some(data) = buffer.datas_mut().first_mut() else {
+                return;
+            };
+            let stride = data.chunk().stride().max(0) as usize;
+            let size = format.size();
+            let format = format.format();
+            let frame = data.data().and_then(|bytes| {
+                to_frame(
+                    bytes,
+                    format,
+                    size.width as usize,
+                    size.height as usize,
+                    stride,
+                )
+            });
+            if let (Some(frame), Ok(mut shared)) = (frame, shared.lock()) {
+                shared.frame = Some(frame);
+            }
+        })
+        .register()?;
+
+    let format = pw::spa::pod::object!(
+        pw::spa::utils::SpaTypes::ObjectParamFormat,
+        ParamType::EnumFormat,
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::MediaType,
+            Id,
+            pw::spa::param::format::MediaType::Video
+        ),
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::MediaSubtype,
+            Id,
+            pw::spa::param::format::MediaSubtype::Raw
+        ),
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::VideoFormat,
+            Choice,
+            Enum,
+            Id,
+            VideoFormat::BGRx,
+            VideoFormat::BGRx,
+            VideoFormat::RGBx,
+            VideoFormat::BGRA,
+            VideoFormat::RGBA,
+        ),
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::VideoSize,
+            Choice,
+            Range,
+            Rectangle,
+            pw::spa::utils::Rectangle {
+                width: 1920,
+                height: 1080
+            },
+            pw::spa::utils::Rectangle {
+                width: 1,
+                height: 1
+            },
+            pw::spa::utils::Rectangle {
+                width: 8192,
+                height: 8192
+            }
+        ),
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::VideoFramerate,
+            Choice,
+            Range,
+            Fraction,
+            pw::spa::utils::Fraction { num: 30, denom: 1 },
+            pw::spa::utils::Fraction { num: 0, denom: 1 },
+            pw::spa::utils::Fraction { num: 144, denom: 1 }
+        ),
+    );
+    let values: Vec<u8> = pw::spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &pw::spa::pod::Value::Object(format),
+    )
+    .map_err(|_| pw::Error::CreationFailed)?
+    .0
+    .into_inner();
+    let mut params = [Pod::from_bytes(&values).ok_or(pw::Error::CreationFailed)?];
+
+    stream.connect(
+        pw::spa::utils::Direction::Input,
+        Some(node),
+        pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+        &mut params,
+    )?;
+
+    mainloop.run();
+    Ok(())
+}
@@ -0,0 +1,517 @@
+//! Capture what is playing and split it in to the levels of frequency bands,
+//! for the audio spectrum on the AniMe and the keyboard.
+
+use std::f32::consts::PI;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use config_traits::StdConfig;
+use log::{info, warn};
+use rog_aura::advanced::LedCode;
+use rog_aura::effects::{colour_mix, OFF};
+use rog_aura::layouts::KeyLayout;
+use rog_aura::AdvancedAuraType;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::config::{AudioSource, AudioStyle, ConfigAudio, ConfigAuraAudio, ConfigBase};
+use crate::error::Error;
+use crate::led_output::{frame_interval, LedFrame, LedOutput};
+
+/// The rate audio is captured at from the monitor
+const MONITOR_RATE: u32 = 48000;
+const FFT_SIZE: usize = 2048;
+/// Samples read between each analysis, about 94 a second at 48kHz
+const HOP: usize = FFT_SIZE / 4;
+const MIN_FREQ: f32 = 50.0;
+const MAX_FREQ: f32 = 16000.0;
+/// The level of a sine, in dB below full scale, that shows as an empty band
+const FLOOR_DB: f32 = -60.0;
+/// How much of a band's level is kept each analysis when the sound drops
+const DECAY: f32 = 0.9;
+/// Changes of any colour channel up to this are not sent to the keyboard, so
+/// a steady sound doesn't write every frame
+const THRESHOLD: u8 = 6;
+
+/// Splits audio in to logarithmically spaced frequency bands
+pub struct Spectrum {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// Scales a bin magnitude to the amplitude of a sine
+    scale: f32,
+    buffer: Vec<Complex<f32>>,
+    /// The FFT bins of each band, end exclusive
+    bands: Vec<(usize, usize)>,
+    gain: f32,
+    levels: Vec<f32>,
+}
+
+impl Spectrum {
+    pub fn new(sample_rate: u32, bands: usize, gain: f32) -> Self {
+        let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+        // Hann window
+        let window: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        let scale = 2.0 / window.iter().sum::<f32>();
+
+        let bands = bands.max(1);
+        let max_freq = MAX_FREQ.min(sample_rate as f32 / 2.0);
+        let bin = |freq: f32| {
+            ((freq * FFT_SIZE as f32 / sample_rate as f32) as usize).clamp(1, FFT_SIZE / 2)
+        };
+        let edges: Vec<usize> = (0..=bands)
+            .map(|i| bin(MIN_FREQ * (max_freq / MIN_FREQ).powf(i as f32 / bands as f32)))
+            .collect();
+        let bands = edges
+            .windows(2)
+            .map(|edge| (edge[0], edge[1].max(edge[0] + 1)))
+            .collect::<Vec<_>>();
+
+        Self {
+            fft,
+            window,
+            scale,
+            buffer: vec![Complex::default(); FFT_SIZE],
+            levels: vec![0.0; bands.len()],
+            bands,
+            gain,
+        }
+    }
+
+    /// Analyse the newest `FFT_SIZE` samples, giving the level of each band
+    /// from 0.0 to 1.0, bass first. Levels fall slowly rather than dropping
+    /// straight to the next.
+    pub fn update(&mut self, samples: &[f32]) -> &[f32] {
+        for ((out, sample), w) in self.buffer.iter_mut().zip(samples).zip(&self.window) {
+            *out = Complex::new(sample * w, 0.0);
+        }
+        self.fft.process(&mut self.buffer);
+        for (level, (start, end)) in self.levels.iter_mut().zip(&self.bands) {
+            let peak = self.buffer[*start..*end]
+                .iter()
+                .map(|c| c.norm())
+                .fold(0.0, f32::max);
+            let amplitude = peak * self.scale * self.gain;
+            let db = 20.0 * amplitude.max(1e-9).log10();
+            let new = ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0);
+            *level = new.max(*level * DECAY);
+        }
+        &self.levels
+    }
+}
+
+/// Mono samples from an audio source
+trait SampleReader: Send {
+    fn sample_rate(&self) -> u32;
+
+    /// Fill `out`, waiting until there are enough samples
+    fn read(&mut self, out: &mut [f32]) -> Result<(), Error>;
+}
+
+/// The monitor of the default output, recorded by `parec`
+struct MonitorReader {
+    child: Child,
+    stdout: ChildStdout,
+    bytes: Vec<u8>,
+}
+
+impl MonitorReader {
+    fn start() -> Result<Self, Error> {
+        let mut child = Command::new("parec")
+            .args([
+                "--device=@DEFAULT_MONITOR@",
+                "--format=s16le",
+                "--channels=1",
+                "--raw",
+                "--latency-msec=20",
+            ])
+            .arg(format!("--rate={MONITOR_RATE}"))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| Error::Audio(format!("could not run parec: {e}")))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| Error::Audio("parec has no output".to_owned()))?;
+        Ok(Self {
+            child,
+            stdout,
+            bytes: Vec::new(),
+        })
+    }
+}
+
+impl Drop for MonitorReader {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+impl SampleReader for MonitorReader {
+    fn sample_rate(&self) -> u32 {
+        MONITOR_RATE
+    }
+
+    fn read(&mut self, out: &mut [f32]) -> Result<(), Error> {
+        self.bytes.resize(out.len() * 2, 0);
+        self.stdout.read_exact(&mut self.bytes)?;
+        for (sample, bytes) in out.iter_mut().zip(self.bytes.chunks_exact(2)) {
+            *sample = i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0;
+        }
+        Ok(())
+    }
+}
+
+/// A WAV file played in a loop at its own speed
+struct WavReader {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    pos: usize,
+    start: Instant,
+    total_read: u64,
+}
+
+impl WavReader {
+    fn open(path: &Path) -> Result<Self, Error> {
+        let wav_err = |e: hound::Error| Error::Audio(format!("{path:?}: {e}"));
+        let mut reader = hound::WavReader::open(path).map_err(wav_err)?;
+        let spec = reader.spec();
+        let interleaved: Result<Vec<f32>, hound::Error> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect(),
+            hound::SampleFormat::Int => {
+                let full_scale = 2f32.powi(spec.bits_per_sample as i32 - 1);
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 / full_scale))
+                    .collect()
+            }
+        };
+        let interleaved = interleaved.map_err(wav_err)?;
+
+        let channels = (spec.channels as usize).max(1);
+        let samples: Vec<f32> = interleaved
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+        if samples.is_empty() {
+            return Err(Error::Audio(format!("{path:?} has no samples")));
+        }
+        Ok(Self {
+            samples,
+            sample_rate: spec.sample_rate.max(1),
+            pos: 0,
+            start: Instant::now(),
+            total_read: 0,
+        })
+    }
+}
+
+impl SampleReader for WavReader {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self, out: &mut [f32]) -> Result<(), Error> {
+        for sample in out.iter_mut() {
+            *sample = self.samples[self.pos];
+            self.pos = (self.pos + 1) % self.samples.len();
+        }
+        // Keep to the speed the file would play at
+        self.total_read += out.len() as u64;
+        let due = Duration::from_secs_f64(self.total_read as f64 / self.sample_rate as f64);
+        let elapsed = self.start.elapsed();
+        if due > elapsed {
+            std::thread::sleep(due - elapsed);
+        }
+        Ok(())
+    }
+}
+
+/// The newest level of each band, shared with everything drawing them
+#[derive(Debug, Clone)]
+pub struct AudioLevels(Arc<Mutex<Vec<f32>>>);
+
+impl AudioLevels {
+    /// The level of each band from 0.0 to 1.0, bass first
+    pub fn get(&self) -> Vec<f32> {
+        self.0.lock().map(|l| l.clone()).unwrap_or_default()
+    }
+}
+
+/// Start capturing and analysing audio on its own thread. A relative WAV
+/// path is taken from the user config directory.
+pub fn start_analyser(config: &ConfigAudio) -> Result<AudioLevels, Error> {
+    let mut reader: Box<dyn SampleReader> = match &config.source {
+        AudioSource::Monitor => Box::new(MonitorReader::start()?),
+        AudioSource::Wav(path) => Box::new(WavReader::open(&ConfigBase::config_dir().join(path))?),
+    };
+    info!("Capturing audio from {:?}", config.source);
+
+    let bands = config.bands.clamp(1, 64) as usize;
+    let mut spectrum = Spectrum::new(reader.sample_rate(), bands, config.gain);
+    let levels = AudioLevels(Arc::new(Mutex::new(vec![0.0; bands])));
+    let shared = levels.clone();
+    std::thread::spawn(move || {
+        let mut history = vec![0.0; FFT_SIZE];
+        let mut hop = vec![0.0; HOP];
+        loop {
+            if let Err(e) = reader.read(&mut hop) {
+                warn!("Audio capture stopped: {e}");
+                if let Ok(mut levels) = shared.0.lock() {
+                    levels.fill(0.0);
+                }
+                return;
+            }
+            history.copy_within(HOP.., 0);
+            history[FFT_SIZE - HOP..].copy_from_slice(&hop);
+            let new = spectrum.update(&history);
+            if let Ok(mut levels) = shared.0.lock() {
+                levels.copy_from_slice(new);
+            }
+        }
+    });
+    Ok(levels)
+}
+
+/// Draws the audio levels on the keyboard
+pub struct AudioRenderer {
+    config: ConfigAuraAudio,
+    levels: AudioLevels,
+    /// The scaled position of each LED, empty on zoned and basic keyboards
+    leds: Vec<(LedCode, f32, f32)>,
+    output: LedOutput,
+}
+
+impl AudioRenderer {
+    pub fn new(config: ConfigAuraAudio, layout: &KeyLayout, levels: AudioLevels) -> Self {
+        let leds = match layout.advanced_type() {
+            AdvancedAuraType::PerKey => layout.led_positions_scaled(),
+            _ => Vec::new(),
+        };
+        Self {
+            config,
+            levels,
+            leds,
+            output: LedOutput::new("audio lighting", THRESHOLD),
+        }
+    }
+
+    fn colours(&self, levels: &[f32]) -> LedFrame {
+        let (low, high) = (self.config.colour_low, self.config.colour_high);
+        let overall = levels.iter().sum::<f32>() / levels.len().max(1) as f32;
+        if self.leds.is_empty() {
+            let c = colour_mix(low, high, overall);
+            return LedFrame::Static(colour_mix(OFF, c, overall));
+        }
+        let colours = self
+            .leds
+            .iter()
+            .map(|(led, x, y)| {
+                let colour = match self.config.style {
+                    AudioStyle::Spectrum => {
+                        let band = ((x * levels.len() as f32) as usize)
+                            .min(levels.len().saturating_sub(1));
+                        let height = 1.0 - y;
+                        if levels.get(band).is_some_and(|level| height < *level) {
+                            colour_mix(low, high, height)
+                        } else {
+                            OFF
+                        }
+                    }
+                    AudioStyle::Vu => {
+                        if *x < overall {
+                            colour_mix(low, high, *x)
+                        } else {
+                            OFF
+                        }
+                    }
+                };
+                (*led, colour)
+            })
+            .collect();
+        LedFrame::PerKey(colours)
+    }
+
+    /// Draw the levels forever
+    pub fn run(&mut self) {
+        let interval = frame_interval(self.config.max_rate, !self.leds.is_empty());
+        loop {
+            if !self.output.ready() {
+                continue;
+            }
+            let start = Instant::now();
+            let colours = self.colours(&self.levels.get());
+            self.output.send(colours);
+            let elapsed = start.elapsed();
+            if elapsed < interval {
+                std::thread::sleep(interval - elapsed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    use rog_aura::advanced::LedCode;
+    use rog_aura::aura_detection::LaptopLedData;
+    use rog_aura::effects::{colour_mix, OFF};
+    use rog_aura::layouts::KeyLayout;
+    use rog_aura::AdvancedAuraType;
+
+    use super::{AudioLevels, AudioRenderer, SampleReader, Spectrum, WavReader, DECAY, FFT_SIZE};
+    use crate::config::{AudioStyle, ConfigAuraAudio};
+    use crate::led_output::LedFrame;
+
+    const RATE: u32 = 48000;
+    const BANDS: usize = 8;
+
+    /// Write a stereo sine at half of full scale and read it back as mono
+    fn sine(freq: f32) -> Vec<f32> {
+        let path =
+            std::env::temp_dir().join(format!("asusd-user-sine-{}-{freq}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..FFT_SIZE {
+            let sample = 0.5 * (2.0 * PI * freq * i as f32 / RATE as f32).sin();
+            let sample = (sample * i16::MAX as f32) as i16;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut reader = WavReader::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reader.sample_rate(), RATE);
+        let mut samples = vec![0.0; FFT_SIZE];
+        reader.read(&mut samples).unwrap();
+        samples
+    }
+
+    fn loudest(levels: &[f32]) -> usize {
+        levels
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn sine_lights_its_band() {
+        // The bands run from 50Hz to 16kHz, each about twice the last
+        for (freq, band) in [(300.0, 2), (2500.0, 5), (10000.0, 7)] {
+            let mut spectrum = Spectrum::new(RATE, BANDS, 1.0);
+            let levels = spectrum.update(&sine(freq));
+            assert_eq!(loudest(levels), band, "{freq}Hz: {levels:?}");
+            assert!(levels[band] > 0.8, "{freq}Hz: {levels:?}");
+            for (i, level) in levels.iter().enumerate() {
+                if i.abs_diff(band) > 1 {
+                    assert!(*level < 0.3, "{freq}Hz band {i}: {levels:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn levels_decay_after_the_sound_stops() {
+        let mut spectrum = Spectrum::new(RATE, BANDS, 1.0);
+        let level = spectrum.update(&sine(300.0))[2];
+        let silence = vec![0.0; FFT_SIZE];
+        let decayed = spectrum.update(&silence)[2];
+        assert!((decayed - level * DECAY).abs() < 1e-6);
+
+        let mut quiet = Spectrum::new(RATE, BANDS, 1.0);
+        assert!(quiet.update(&silence).iter().all(|l| *l == 0.0));
+    }
+
+    fn renderer(style: AudioStyle, layout: &KeyLayout) -> AudioRenderer {
+        let config = ConfigAuraAudio {
+            style,
+            ..Default::default()
+        };
+        AudioRenderer::new(
+            config,
+            layout,
+            AudioLevels(Arc::new(Mutex::new(Vec::new()))),
+        )
+    }
+
+    fn per_key_layout() -> KeyLayout {
+        let led_data = LaptopLedData {
+            layout_name: "g513i-per-key".to_owned(),
+            advanced_type: AdvancedAuraType::PerKey,
+            ..Default::default()
+        };
+        let mut data = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        data.push("../rog-aura/data");
+        KeyLayout::find_layout(led_data, data).unwrap()
+    }
+
+    #[test]
+    fn static_colour_of_the_level() {
+        let renderer = renderer(AudioStyle::Spectrum, &KeyLayout::default_layout());
+        let (low, high) = (renderer.config.colour_low, renderer.config.colour_high);
+        assert_eq!(renderer.colours(&[0.0; 4]), LedFrame::Static(OFF));
+        assert_eq!(renderer.colours(&[1.0; 4]), LedFrame::Static(high));
+        let half = colour_mix(OFF, colour_mix(low, high, 0.5), 0.5);
+        assert_eq!(
+            renderer.colours(&[1.0, 1.0, 0.0, 0.0]),
+            LedFrame::Static(half)
+        );
+    }
+
+    #[test]
+    fn per_key_bars() {
+        let layout = per_key_layout();
+        let positions = layout.led_positions_scaled();
+        let position = |led| {
+            positions
+                .iter()
+                .find(|(l, ..)| *l == led)
+                .map(|(_, x, y)| (*x, *y))
+                .unwrap()
+        };
+        let colour = |frame: LedFrame, led| {
+            let LedFrame::PerKey(colours) = frame else {
+                panic!("Expected per-key colours");
+            };
+            colours.iter().find(|(l, _)| *l == led).unwrap().1
+        };
+        // Only the bass band, on the left, is sounding, and reaches half way
+        // up the keyboard
+        let levels = [0.5, 0.0, 0.0, 0.0];
+
+        let spectrum = renderer(AudioStyle::Spectrum, &layout);
+        let (low, high) = (spectrum.config.colour_low, spectrum.config.colour_high);
+        let (_, y) = position(LedCode::LCtrl);
+        assert_eq!(
+            colour(spectrum.colours(&levels), LedCode::LCtrl),
+            colour_mix(low, high, 1.0 - y)
+        );
+        assert_eq!(colour(spectrum.colours(&levels), LedCode::Esc), OFF);
+        assert_eq!(colour(spectrum.colours(&levels), LedCode::RCtrl), OFF);
+
+        let vu = renderer(AudioStyle::Vu, &layout);
+        let (x, _) = position(LedCode::Esc);
+        assert_eq!(
+            colour(vu.colours(&levels), LedCode::Esc),
+            colour_mix(low, high, x)
+        );
+        assert_eq!(colour(vu.colours(&levels), LedCode::RCtrl), OFF);
+    }
+}
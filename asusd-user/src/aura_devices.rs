@@ -0,0 +1,177 @@
+//! The Aura devices of asusd, for the parts of the user daemon which light the
+//! keyboard. asusd may not be running yet or may restart, so the devices are
+//! found again once it is lost, and each failure waits longer before the next
+//! try.
+
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use rog_dbus::zbus_aura::AuraProxyBlocking;
+use rog_dbus::DBUS_NAME;
+use zbus::blocking::fdo::ObjectManagerProxy;
+use zbus::blocking::Connection;
+use zbus::{fdo, CacheProperties};
+
+/// The first wait after a failure, doubled on each failure until a call works
+const RETRY_MIN: Duration = Duration::from_millis(500);
+const RETRY_MAX: Duration = Duration::from_secs(30);
+
+pub struct AuraDevices {
+    /// What the devices are used for, for the logs
+    name: &'static str,
+    conn: Option<Connection>,
+    aura: Vec<AuraProxyBlocking<'static>>,
+    retry: Duration,
+    /// Nothing is tried before this after a failure
+    retry_at: Option<Instant>,
+}
+
+impl AuraDevices {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            conn: None,
+            aura: Vec::new(),
+            retry: RETRY_MIN,
+            retry_at: None,
+        }
+    }
+
+    /// The system bus connection, if connected
+    pub fn conn(&self) -> Option<&Connection> {
+        self.conn.as_ref()
+    }
+
+    /// The devices found, empty until `ready()` has found them
+    pub fn devices(&self) -> &[AuraProxyBlocking<'static>] {
+        &self.aura
+    }
+
+    /// The time left to wait after a failure before trying again
+    pub fn retry_in(&self) -> Duration {
+        self.retry_at
+            .map(|at| at.saturating_duration_since(Instant::now()))
+            .unwrap_or_default()
+    }
+
+    /// Find the devices if not yet found. False while waiting after a
+    /// failure, or if asusd can't be reached or has no Aura devices.
+    pub fn ready(&mut self) -> bool {
+        if !self.retry_in().is_zero() {
+            return false;
+        }
+        if self.aura.is_empty() {
+            match self.find() {
+                Ok(aura) if !aura.is_empty() => {
+                    info!("{}: found the Aura devices of asusd", self.name);
+                    self.aura = aura;
+                }
+                Ok(_) => self.back_off("asusd has no Aura devices"),
+                Err(e) => self.failed(e),
+            }
+        }
+        !self.aura.is_empty()
+    }
+
+    fn find(&mut self) -> zbus::Result<Vec<AuraProxyBlocking<'static>>> {
+        let conn = match self.conn.take() {
+            Some(conn) => conn,
+            None => Connection::system()?,
+        };
+        let aura = find_aura(&conn);
+        self.conn = Some(conn);
+        aura
+    }
+
+    /// A call to the devices worked, the next failure is tried again soon
+    pub fn succeeded(&mut self) {
+        self.retry = RETRY_MIN;
+        self.retry_at = None;
+    }
+
+    /// A call to the devices failed. The next try waits longer than the last,
+    /// and if asusd is gone the devices are found again.
+    pub fn failed(&mut self, e: zbus::Error) {
+        if asusd_lost(&e) {
+            self.conn = None;
+            self.aura.clear();
+        }
+        self.back_off(e);
+    }
+
+    fn back_off(&mut self, reason: impl Display) {
+        warn!("{}: {reason}, trying again in {:?}", self.name, self.retry);
+        self.retry_at = Some(Instant::now() + self.retry);
+        self.retry = (self.retry * 2).min(RETRY_MAX);
+    }
+}
+
+/// True if the error means the connection or asusd is gone, or asusd
+/// restarted and the device is no longer there, rather than asusd refusing
+/// the call
+fn asusd_lost(e: &zbus::Error) -> bool {
+    let lost = |e: &fdo::Error| {
+        matches!(
+            e,
+            fdo::Error::ServiceUnknown(_)
+                | fdo::Error::NameHasNoOwner(_)
+                | fdo::Error::NoReply(_)
+                | fdo::Error::Disconnected(_)
+                | fdo::Error::UnknownObject(_)
+                | fdo::Error::IOError(_)
+        )
+    };
+    match e {
+        zbus::Error::InputOutput(_) => true,
+        zbus::Error::FDO(e) => lost(e),
+        zbus::Error::MethodError(..) => lost(&fdo::Error::from(e.clone())),
+        _ => false,
+    }
+}
+
+/// Every Aura device served by asusd. The properties are not cached as the
+/// brightness can also be changed by the firmware.
+fn find_aura(conn: &Connection) -> zbus::Result<Vec<AuraProxyBlocking<'static>>> {
+    let manager = ObjectManagerProxy::new(conn, DBUS_NAME, "/org")?;
+    let mut aura = Vec::new();
+    for (path, interfaces) in manager.get_managed_objects()? {
+        if interfaces
+            .keys()
+            .any(|i| i.as_str() == "org.asuslinux.Aura")
+        {
+            aura.push(
+                AuraProxyBlocking::builder(conn)
+                    .destination(DBUS_NAME)?
+                    .path(path)?
+                    .cache_properties(CacheProperties::No)
+                    .build()?,
+            );
+        }
+    }
+    Ok(aura)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::Arc;
+
+    use zbus::fdo;
+
+    use super::asusd_lost;
+
+    #[test]
+    fn lost_only_when_asusd_is_gone() {
+        let io = io::Error::new(io::ErrorKind::BrokenPipe, "closed");
+        assert!(asusd_lost(&zbus::Error::InputOutput(Arc::new(io))));
+        let gone = fdo::Error::ServiceUnknown("org.asuslinux.Daemon".to_owned());
+        assert!(asusd_lost(&zbus::Error::FDO(Box::new(gone))));
+        let restarted = fdo::Error::UnknownObject("/org/asuslinux/aura".to_owned());
+        assert!(asusd_lost(&zbus::Error::FDO(Box::new(restarted))));
+
+        let refused = fdo::Error::InvalidArgs("mode".to_owned());
+        assert!(!asusd_lost(&zbus::Error::FDO(Box::new(refused))));
+        assert!(!asusd_lost(&zbus::Error::Failure("busy".to_owned())));
+    }
+}
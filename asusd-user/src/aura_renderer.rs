@@ -0,0 +1,63 @@
+//! Runs the per-key effects of a `ConfigAura`, writing each frame to asusd
+//! at a steady rate.
+
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use rog_aura::layouts::KeyLayout;
+
+use crate::config::ConfigAura;
+use crate::key_input::KeyEvent;
+use crate::led_output::{LedFrame, LedOutput};
+
+pub struct AuraRenderer {
+    config: ConfigAura,
+    layout: KeyLayout,
+    keys: Receiver<KeyEvent>,
+    output: LedOutput,
+}
+
+impl AuraRenderer {
+    pub fn new(config: ConfigAura, layout: KeyLayout, keys: Receiver<KeyEvent>) -> Self {
+        Self {
+            config,
+            layout,
+            keys,
+            // A frame which is the same as the last isn't written
+            output: LedOutput::new("Per-key effects", 0),
+        }
+    }
+
+    fn frame_time(&self) -> Duration {
+        Duration::from_secs(1) / self.config.fps.clamp(1, 120)
+    }
+
+    /// Run the effects forever
+    pub fn run(&mut self) {
+        let mut next_frame = Instant::now();
+        loop {
+            if !self.output.ready() {
+                // Key presses while paused or without asusd are not shown later
+                while self.keys.try_recv().is_ok() {}
+                next_frame = Instant::now();
+                continue;
+            }
+
+            while let Ok((led, pressed)) = self.keys.try_recv() {
+                self.config.aura.key_event(led, pressed);
+            }
+            self.config.aura.next_state(&self.layout);
+            self.output
+                .send(LedFrame::Packets(self.config.aura.create_packets()));
+
+            // Keep to the frame rate, but don't rush to catch up after a stall
+            next_frame += self.frame_time();
+            let now = Instant::now();
+            if next_frame > now {
+                std::thread::sleep(next_frame - now);
+            } else {
+                next_frame = now;
+            }
+        }
+    }
+}
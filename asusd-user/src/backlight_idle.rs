@@ -0,0 +1,287 @@
+//! Fade the keyboard backlight out after a time without input, and bring it
+//! back on the next input.
+
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use rog_aura::LedBrightness;
+use rog_platform::power::AsusPower;
+
+use crate::aura_devices::AuraDevices;
+use crate::config::ConfigIdle;
+use crate::session::session_idle;
+
+/// How often to check the idle time and power source
+const POLL: Duration = Duration::from_millis(500);
+
+/// What to do with the backlight after checking the idle time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IdleAction {
+    Nothing,
+    FadeOut,
+    Restore,
+}
+
+/// Fade out once idle for `timeout` if not already faded, and restore once
+/// not idle or the timeout is removed, such as when plugged in to AC
+fn idle_action(idle: Duration, timeout: Option<Duration>, faded: bool) -> IdleAction {
+    match timeout {
+        Some(timeout) if idle >= timeout => {
+            if faded {
+                IdleAction::Nothing
+            } else {
+                IdleAction::FadeOut
+            }
+        }
+        _ if faded => IdleAction::Restore,
+        _ => IdleAction::Nothing,
+    }
+}
+
+/// The brightness of a device before it was faded, and what it was left at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Faded {
+    before: LedBrightness,
+    now: LedBrightness,
+}
+
+impl Faded {
+    /// `None` if the device is already off and has nothing to fade
+    fn new(before: LedBrightness) -> Option<Self> {
+        (before != LedBrightness::Off).then_some(Self {
+            before,
+            now: before,
+        })
+    }
+
+    /// Turn down one step, returning the brightness to set or `None` once off
+    fn step(&mut self) -> Option<LedBrightness> {
+        if self.now == LedBrightness::Off {
+            return None;
+        }
+        self.now = self.now.prev();
+        Some(self.now)
+    }
+
+    /// The brightness to set back to, `None` if the device is no longer at
+    /// the faded brightness as something else changed it
+    fn restore(&self, current: LedBrightness) -> Option<LedBrightness> {
+        (current == self.now).then_some(self.before)
+    }
+}
+
+/// The number of steps to fade every device to off
+fn fade_steps(faded: &HashMap<String, Faded>) -> u32 {
+    faded.values().map(|f| f.now as u32).max().unwrap_or(0)
+}
+
+pub struct BacklightIdle {
+    config: ConfigIdle,
+    /// Activity from the input devices, `None` if none can be read and the
+    /// idle time comes from logind instead
+    activity: Option<Receiver<()>>,
+    last_input: Instant,
+    power: Option<AsusPower>,
+    devices: AuraDevices,
+    /// Set while faded out, by dbus path of each device that was turned down
+    faded: Option<HashMap<String, Faded>>,
+}
+
+impl BacklightIdle {
+    pub fn new(config: ConfigIdle, activity: Option<Receiver<()>>) -> Self {
+        let power = AsusPower::new()
+            .map_err(|e| warn!("Backlight idle: can't read the power source, using AC: {e}"))
+            .ok();
+        Self {
+            config,
+            activity,
+            last_input: Instant::now(),
+            power,
+            devices: AuraDevices::new("Backlight idle"),
+            faded: None,
+        }
+    }
+
+    fn on_ac(&self) -> bool {
+        match self.power.as_ref().map(AsusPower::get_online) {
+            Some(Ok(online)) => online > 0,
+            _ => true,
+        }
+    }
+
+    /// Wait up to `timeout` for input. Returns true if there was any.
+    fn wait_for_input(&mut self, timeout: Duration) -> bool {
+        let Some(activity) = self.activity.as_ref() else {
+            std::thread::sleep(timeout);
+            return false;
+        };
+        match activity.recv_timeout(timeout) {
+            Ok(()) => {
+                while activity.try_recv().is_ok() {}
+                self.last_input = Instant::now();
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => {
+                warn!("Backlight idle: stopped reading input, using the logind idle hint");
+                self.activity = None;
+                false
+            }
+        }
+    }
+
+    /// The time since the last input. Without input devices this is what the
+    /// desktop told logind, or none if that can't be read.
+    fn idle_time(&self) -> zbus::Result<Duration> {
+        if self.activity.is_some() {
+            return Ok(self.last_input.elapsed());
+        }
+        let Some(conn) = self.devices.conn() else {
+            return Ok(Duration::ZERO);
+        };
+        Ok(session_idle(conn)?.unwrap_or_default())
+    }
+
+    /// Step the brightness of every device down to off, stopping and
+    /// restoring it if there is input on the way
+    fn fade_out(&mut self) -> zbus::Result<()> {
+        let mut faded = HashMap::new();
+        for aura in self.devices.devices() {
+            if let Some(f) = Faded::new(aura.brightness()?) {
+                faded.insert(aura.inner().path().to_string(), f);
+            }
+        }
+        let steps = fade_steps(&faded);
+        self.faded = Some(faded);
+        if steps == 0 {
+            return Ok(());
+        }
+        info!("Backlight idle: fading out");
+
+        let step_time = Duration::from_millis(self.config.fade_ms.into()) / steps;
+        for _ in 0..steps {
+            let faded = self.faded.get_or_insert_with(HashMap::new);
+            for aura in self.devices.devices() {
+                if let Some(now) = faded
+                    .get_mut(aura.inner().path().as_str())
+                    .and_then(Faded::step)
+                {
+                    aura.set_brightness(now)?;
+                }
+            }
+            if self.wait_for_input(step_time) {
+                return self.restore();
+            }
+        }
+        Ok(())
+    }
+
+    /// Set each device back to the brightness it had before fading, unless
+    /// it was changed by something else while faded
+    fn restore(&mut self) -> zbus::Result<()> {
+        let Some(faded) = self.faded.as_ref() else {
+            return Ok(());
+        };
+        if !faded.is_empty() {
+            info!("Backlight idle: restoring brightness");
+        }
+        for aura in self.devices.devices() {
+            if let Some(f) = faded.get(aura.inner().path().as_str()) {
+                if let Some(before) = f.restore(aura.brightness()?) {
+                    aura.set_brightness(before)?;
+                }
+            }
+        }
+        self.faded = None;
+        Ok(())
+    }
+
+    fn update(&mut self) -> zbus::Result<()> {
+        let idle = self.idle_time()?;
+        let timeout = self.config.timeout(self.on_ac());
+        match idle_action(idle, timeout, self.faded.is_some()) {
+            IdleAction::FadeOut => self.fade_out(),
+            IdleAction::Restore => self.restore(),
+            IdleAction::Nothing => Ok(()),
+        }
+    }
+
+    /// Follow the input activity forever
+    pub fn run(&mut self) {
+        loop {
+            self.wait_for_input(POLL);
+            // Finding asusd backs off while it is missing or has no devices
+            if !self.devices.ready() {
+                continue;
+            }
+            match self.update() {
+                Ok(()) => self.devices.succeeded(),
+                Err(e) => self.devices.failed(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use rog_aura::LedBrightness;
+
+    use super::{fade_steps, idle_action, Faded, IdleAction};
+    use crate::config::ConfigIdle;
+
+    #[test]
+    fn timeout_by_power_source() {
+        let config = ConfigIdle {
+            ac_timeout_secs: 0,
+            battery_timeout_secs: 30,
+            ..Default::default()
+        };
+        assert!(config.enabled());
+        assert_eq!(config.timeout(true), None);
+        assert_eq!(config.timeout(false), Some(Duration::from_secs(30)));
+        assert!(!ConfigIdle::default().enabled());
+    }
+
+    #[test]
+    fn fade_and_restore_on_idle() {
+        let timeout = Some(Duration::from_secs(30));
+        let secs = Duration::from_secs;
+        assert_eq!(idle_action(secs(10), timeout, false), IdleAction::Nothing);
+        assert_eq!(idle_action(secs(30), timeout, false), IdleAction::FadeOut);
+        assert_eq!(idle_action(secs(40), timeout, true), IdleAction::Nothing);
+        assert_eq!(idle_action(secs(0), timeout, true), IdleAction::Restore);
+        // Plugged in to AC with no AC timeout while faded
+        assert_eq!(idle_action(secs(40), None, true), IdleAction::Restore);
+        assert_eq!(idle_action(secs(40), None, false), IdleAction::Nothing);
+    }
+
+    #[test]
+    fn fade_steps_to_off() {
+        assert_eq!(Faded::new(LedBrightness::Off), None);
+        let mut faded = Faded::new(LedBrightness::Med).unwrap();
+        let low = Faded::new(LedBrightness::Low).unwrap();
+        let devices = HashMap::from([("a".to_owned(), faded), ("b".to_owned(), low)]);
+        assert_eq!(fade_steps(&devices), 2);
+
+        assert_eq!(faded.step(), Some(LedBrightness::Low));
+        assert_eq!(faded.step(), Some(LedBrightness::Off));
+        assert_eq!(faded.step(), None);
+        assert_eq!(faded.now, LedBrightness::Off);
+        assert_eq!(faded.before, LedBrightness::Med);
+    }
+
+    #[test]
+    fn restore_only_if_unchanged() {
+        let mut faded = Faded::new(LedBrightness::High).unwrap();
+        faded.step();
+        assert_eq!(faded.restore(LedBrightness::Med), Some(LedBrightness::High));
+        // Changed by the user or firmware while faded
+        assert_eq!(faded.restore(LedBrightness::Low), None);
+        assert_eq!(faded.restore(LedBrightness::High), None);
+    }
+}
@@ -118,10 +118,121 @@ impl StdConfig for ConfigAnime {
 
 impl StdConfigLoad for ConfigAnime {}
 
+fn default_fps() -> u32 {
+    30
+}
+
+/// Where the ambient colours are sampled from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AmbientSource {
+    /// The screen, through the desktop portal. The desktop asks which screen
+    /// to share when started.
+    #[default]
+    Screencast,
+    /// Scrolling colour bars, to try the ambient mode without a screen
+    TestPattern,
+}
+
+/// How the colour of a part of the screen is picked
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AmbientSampling {
+    /// The mean of all pixels
+    #[default]
+    Average,
+    /// The most common colour, which keeps a bright colour on a mostly dark
+    /// screen
+    Dominant,
+}
+
+/// Lighting the keyboard with the colours of the screen
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConfigAmbient {
+    pub source: AmbientSource,
+    pub sampling: AmbientSampling,
+    /// The most updates per second sent to the keyboard, 1-30. Zoned and
+    /// basic keyboards are further limited to 4.
+    pub max_rate: u32,
+    /// The smallest change of any colour channel which is sent
+    pub threshold: u8,
+    /// Per-key keyboards take the colour of the screen region above each key,
+    /// with the screen split into this many columns
+    pub columns: u32,
+    /// and this many rows
+    pub rows: u32,
+}
+
+impl Default for ConfigAmbient {
+    fn default() -> Self {
+        Self {
+            source: AmbientSource::default(),
+            sampling: AmbientSampling::default(),
+            max_rate: 10,
+            threshold: 6,
+            columns: 16,
+            rows: 6,
+        }
+    }
+}
+
+/// How the audio levels are drawn on the keyboard
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AudioStyle {
+    /// A bar rising in each column for the frequency band under it, bass on
+    /// the left
+    #[default]
+    Spectrum,
+    /// One bar growing from the left with the overall level
+    Vu,
+}
+
+/// Lighting the keyboard with what is playing, from the audio capture in the
+/// main config
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConfigAuraAudio {
+    pub style: AudioStyle,
+    /// The colour at the bottom of a spectrum bar or left of the VU bar
+    pub colour_low: Colour,
+    /// The colour at the top of a spectrum bar or right of the VU bar
+    pub colour_high: Colour,
+    /// The most updates per second sent to the keyboard, 1-60. Zoned and
+    /// basic keyboards show the overall level as one colour, limited to 4.
+    pub max_rate: u32,
+}
+
+impl Default for ConfigAuraAudio {
+    fn default() -> Self {
+        Self {
+            style: AudioStyle::default(),
+            colour_low: Colour { r: 0, g: 255, b: 0 },
+            colour_high: Colour { r: 255, g: 0, b: 0 },
+            max_rate: 30,
+        }
+    }
+}
+
+/// What `asusd-user` shows on the keyboard
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub enum AuraMode {
+    /// The effects and layers of the Aura config
+    #[default]
+    Effects,
+    /// The colours of the screen
+    Ambient(ConfigAmbient),
+    /// The levels of what is playing
+    Audio(ConfigAuraAudio),
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ConfigAura {
     pub name: String,
+    #[serde(default)]
+    pub mode: AuraMode,
     pub aura: AuraSequences,
+    /// Frames per second to run the effects at, 1-120
+    #[serde(default = "default_fps")]
+    pub fps: u32,
 }
 
 impl ConfigAura {
@@ -129,6 +240,12 @@ impl ConfigAura {
         self.name = name;
         self
     }
+
+    /// Compile the scripts of scripted effects. Script paths are relative to
+    /// the user config directory the aura config is in.
+    pub fn load_scripts(&mut self) -> Result<(), Error> {
+        Ok(self.aura.load_scripts(&Self::config_dir())?)
+    }
 }
 
 impl Default for ConfigAura {
@@ -182,7 +299,9 @@ impl Default for ConfigAura {
 
         Self {
             name: "aura-default".to_owned(),
+            mode: AuraMode::default(),
             aura: seq,
+            fps: default_fps(),
         }
     }
 }
@@ -203,6 +322,78 @@ impl StdConfig for ConfigAura {
 
 impl StdConfigLoad for ConfigAura {}
 
+/// Fading the keyboard backlight out when the laptop isn't being used
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConfigIdle {
+    /// Seconds without input before fading out while on AC, 0 to never
+    pub ac_timeout_secs: u32,
+    /// Seconds without input before fading out while on battery, 0 to never
+    pub battery_timeout_secs: u32,
+    /// How long the fade out takes
+    pub fade_ms: u32,
+}
+
+impl Default for ConfigIdle {
+    fn default() -> Self {
+        Self {
+            ac_timeout_secs: 0,
+            battery_timeout_secs: 0,
+            fade_ms: 1500,
+        }
+    }
+}
+
+impl ConfigIdle {
+    /// If a timeout is set for either power source
+    pub fn enabled(&self) -> bool {
+        self.ac_timeout_secs > 0 || self.battery_timeout_secs > 0
+    }
+
+    /// The time without input before fading out, `None` to never
+    pub fn timeout(&self, on_ac: bool) -> Option<Duration> {
+        let secs = if on_ac {
+            self.ac_timeout_secs
+        } else {
+            self.battery_timeout_secs
+        };
+        (secs > 0).then(|| Duration::from_secs(secs.into()))
+    }
+}
+
+/// Where audio is captured from for the audio spectrum modes
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AudioSource {
+    /// Whatever is playing on the default output, through `parec` which
+    /// works with both PulseAudio and PipeWire
+    #[default]
+    Monitor,
+    /// A WAV file played in a loop, to try the modes without sound
+    Wav(PathBuf),
+}
+
+/// The audio capture shared by the AniMe `AudioEq` action and the Aura audio
+/// mode
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConfigAudio {
+    pub source: AudioSource,
+    /// How many frequency bands the sound is split in to
+    pub bands: u32,
+    /// Multiplies the sound level, for quiet sources
+    pub gain: f32,
+}
+
+impl Default for ConfigAudio {
+    fn default() -> Self {
+        Self {
+            source: AudioSource::default(),
+            bands: 16,
+            gain: 1.0,
+        }
+    }
+}
+
 #[derive(Debug, Default, Deserialize, Serialize)]
 #[serde(default)]
 pub struct ConfigBase {
@@ -210,6 +401,10 @@ pub struct ConfigBase {
     pub active_anime: Option<String>,
     /// Name of active aura config file in the user config directory
     pub active_aura: Option<String>,
+    /// Turn the keyboard backlight off after a time without input
+    pub backlight_idle: ConfigIdle,
+    /// Audio capture for the audio spectrum modes
+    pub audio: ConfigAudio,
 }
 
 impl StdConfig for ConfigBase {
@@ -217,6 +412,8 @@ impl StdConfig for ConfigBase {
         Self {
             active_anime: Some("anime-default".to_owned()),
             active_aura: Some("aura-default".to_owned()),
+            backlight_idle: ConfigIdle::default(),
+            audio: ConfigAudio::default(),
         }
     }
 
@@ -6,15 +6,21 @@ use std::time::{Duration, Instant};
 
 use config_traits::StdConfig;
 use rog_anime::error::AnimeError;
-use rog_anime::{ActionData, ActionLoader, AnimTime, Fade, Sequences, Vec2};
+use rog_anime::{
+    ActionData, ActionLoader, AnimTime, AnimeDiagonal, AnimeType, Fade, Sequences, Vec2,
+};
 use rog_dbus::RogDbusClientBlocking;
 use serde_derive::{Deserialize, Serialize};
 use zbus::interface;
 use zbus::zvariant::{ObjectPath, Type};
 
+use crate::audio::AudioLevels;
 use crate::config::ConfigAnime;
 use crate::error::Error;
 
+/// The time between frames of the audio spectrum
+const AUDIO_FRAME_TIME: Duration = Duration::from_millis(33);
+
 #[derive(Debug, Clone, Deserialize, Serialize, Type)]
 pub struct Timer {
     type_of: TimeType,
@@ -61,23 +67,49 @@ pub enum TimeType {
 /// thread and a zbus server behind `Arc<Mutex<T>>`
 pub struct CtrlAnimeInner<'a> {
     sequences: Sequences,
+    anime_type: AnimeType,
     client: RogDbusClientBlocking<'a>,
+    /// The audio levels for `AudioEq` actions, which are skipped if `None`
+    audio: Option<AudioLevels>,
     do_early_return: Arc<AtomicBool>,
 }
 
 impl<'a> CtrlAnimeInner<'static> {
     pub fn new(
         sequences: Sequences,
+        anime_type: AnimeType,
         client: RogDbusClientBlocking<'static>,
+        audio: Option<AudioLevels>,
         do_early_return: Arc<AtomicBool>,
     ) -> Result<Self, Error> {
         Ok(Self {
             sequences,
+            anime_type,
             client,
+            audio,
             do_early_return,
         })
     }
 
+    /// Draw the audio spectrum until `time` is up or the sequence changes
+    fn run_audio_eq(&self, audio: &AudioLevels, time: Option<Duration>, brightness: f32) {
+        let start = Instant::now();
+        loop {
+            if self.do_early_return.load(Ordering::SeqCst) {
+                return;
+            }
+            if time.is_some_and(|time| start.elapsed() > time) {
+                return;
+            }
+            let frame = AnimeDiagonal::from_bars(self.anime_type, &audio.get(), brightness)
+                .into_data_buffer(self.anime_type);
+            if let Ok(frame) = frame {
+                self.client.proxies().anime().write(frame).ok();
+            }
+            sleep(AUDIO_FRAME_TIME);
+        }
+    }
+
     /// To be called on each main loop iteration to pump out commands to the
     /// anime
     pub fn run(&'a self) -> Result<(), Error> {
@@ -119,10 +151,12 @@ impl<'a> CtrlAnimeInner<'static> {
                         sleep(Duration::from_millis(1));
                     }
                 }
-                ActionData::AudioEq
-                | ActionData::SystemInfo
-                | ActionData::TimeDate
-                | ActionData::Matrix => {}
+                ActionData::AudioEq { time, brightness } => {
+                    if let Some(audio) = &self.audio {
+                        self.run_audio_eq(audio, *time, *brightness);
+                    }
+                }
+                ActionData::SystemInfo | ActionData::TimeDate | ActionData::Matrix => {}
             }
         }
 
@@ -1,12 +1,21 @@
+use std::cell::OnceCell;
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::channel;
 use std::sync::{Arc, Mutex};
 
+use asusd_user::ambient::{open_source, AmbientRenderer};
+use asusd_user::audio::{start_analyser, AudioRenderer};
+use asusd_user::aura_renderer::AuraRenderer;
+use asusd_user::backlight_idle::BacklightIdle;
 use asusd_user::config::*;
 use asusd_user::ctrl_anime::{CtrlAnime, CtrlAnimeInner};
+use asusd_user::key_input::{watch_activity, watch_keyboards};
 use config_traits::{StdConfig, StdConfigLoad};
+use log::{error, warn};
 use rog_anime::usb::get_anime_type;
+use rog_anime::ActionLoader;
 use rog_aura::aura_detection::LaptopLedData;
 use rog_aura::layouts::KeyLayout;
 use rog_dbus::{RogDbusClientBlocking, DBUS_NAME};
@@ -42,6 +51,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = ConfigBase::new().load();
     let executor = Executor::new();
 
+    // The audio capture is started by the first mode which needs it
+    let audio = OnceCell::new();
+    let audio_levels = || {
+        audio
+            .get_or_init(|| {
+                start_analyser(&config.audio)
+                    .map_err(|e| error!("Audio spectrum: {e}"))
+                    .ok()
+            })
+            .clone()
+    };
+
     let early_return = Arc::new(AtomicBool::new(false));
     // Set up the anime data and run loop/thread
     if supported {
@@ -49,6 +70,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let anime_type = get_anime_type()?;
             let anime_config = ConfigAnime::new().set_name(cfg).load();
             let anime = anime_config.create(anime_type)?;
+            let anime_audio = anime_config
+                .anime
+                .iter()
+                .any(|action| matches!(action, ActionLoader::AudioEq { .. }))
+                .then(audio_levels)
+                .flatten();
             let anime_config = Arc::new(Mutex::new(anime_config));
 
             executor
@@ -59,7 +86,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                     // Inner behind mutex required for thread safety
                     let inner = Arc::new(Mutex::new(
-                        CtrlAnimeInner::new(anime, client, early_return.clone()).unwrap(),
+                        CtrlAnimeInner::new(
+                            anime,
+                            anime_type,
+                            client,
+                            anime_audio,
+                            early_return.clone(),
+                        )
+                        .unwrap(),
                     ));
                     // Need new client object for dbus control part
                     let (client, _) = RogDbusClientBlocking::new().unwrap();
@@ -76,9 +110,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    if config.backlight_idle.enabled() {
+        let (activity_sender, activity) = channel();
+        let activity = if watch_activity(&activity_sender) == 0 {
+            warn!("No input device could be read, the backlight idle timeout uses logind");
+            None
+        } else {
+            Some(activity)
+        };
+        let mut idle = BacklightIdle::new(config.backlight_idle.clone(), activity);
+        std::thread::spawn(move || idle.run());
+    }
+
     // if supported.keyboard_led.per_key_led_mode {
     if let Some(cfg) = config.active_aura {
         let mut aura_config = ConfigAura::new().set_name(cfg).load();
+        if let Err(e) = aura_config.load_scripts() {
+            error!("Aura scripts: {e}");
+        }
         // let baord_name = std::fs::read_to_string(BOARD_NAME)?;
 
         let led_support = LaptopLedData::get_data();
@@ -89,26 +138,37 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             })
             .unwrap_or_else(|_| KeyLayout::default_layout());
 
-        executor
-            .spawn(async move {
-                // Create server
-                let (client, _) = RogDbusClientBlocking::new().unwrap();
-                // let connection = Connection::session().await.unwrap();
-                // connection.request_name(DBUS_NAME).await.unwrap();
-
-                loop {
-                    aura_config.aura.next_state(&layout);
-                    let packets = aura_config.aura.create_packets();
-
-                    client
-                        .proxies()
-                        .aura()
-                        .direct_addressing_raw(packets)
-                        .unwrap();
-                    std::thread::sleep(std::time::Duration::from_millis(33));
+        match aura_config.mode.clone() {
+            AuraMode::Effects => {
+                let (key_sender, keys) = channel();
+                if watch_keyboards(&key_sender) == 0 {
+                    warn!(
+                        "No keyboard could be read, reactive Aura effects need the user in the \
+                         input group"
+                    );
                 }
-            })
-            .detach();
+
+                let mut renderer = AuraRenderer::new(aura_config, layout, keys);
+                std::thread::spawn(move || renderer.run());
+            }
+            AuraMode::Ambient(ambient) => match open_source(ambient.source) {
+                Ok(source) => {
+                    let mut renderer = AmbientRenderer::new(ambient, &layout, source);
+                    std::thread::spawn(move || {
+                        if let Err(e) = renderer.run() {
+                            error!("Ambient lighting stopped: {e}");
+                        }
+                    });
+                }
+                Err(e) => error!("Ambient lighting: {e}"),
+            },
+            AuraMode::Audio(aura_audio) => {
+                if let Some(levels) = audio_levels() {
+                    let mut renderer = AudioRenderer::new(aura_audio, &layout, levels);
+                    std::thread::spawn(move || renderer.run());
+                }
+            }
+        }
     }
     // }
 
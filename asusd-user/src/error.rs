@@ -1,6 +1,7 @@
 use std::fmt;
 
 use rog_anime::error::AnimeError;
+use rog_aura::error::Error as AuraError;
 
 #[derive(Debug)]
 pub enum Error {
@@ -9,6 +10,9 @@ pub enum Error {
     ConfigLockFail,
     XdgVars,
     Anime(AnimeError),
+    Aura(AuraError),
+    Screencast(String),
+    Audio(String),
 }
 
 impl fmt::Display for Error {
@@ -20,6 +24,9 @@ impl fmt::Display for Error {
             Error::ConfigLockFail => write!(f, "Failed to lock user config"),
             Error::XdgVars => write!(f, "XDG environment vars appear unset"),
             Error::Anime(err) => write!(f, "Anime error: {}", err),
+            Error::Aura(err) => write!(f, "Aura error: {}", err),
+            Error::Screencast(err) => write!(f, "Screencast failed: {}", err),
+            Error::Audio(err) => write!(f, "Audio capture failed: {}", err),
         }
     }
 }
@@ -38,6 +45,12 @@ impl From<AnimeError> for Error {
     }
 }
 
+impl From<AuraError> for Error {
+    fn from(err: AuraError) -> Self {
+        Error::Aura(err)
+    }
+}
+
 impl From<Error> for zbus::fdo::Error {
     fn from(err: Error) -> Self {
         zbus::fdo::Error::Failed(format!("Anime zbus error: {}", err))
@@ -0,0 +1,125 @@
+//! Read key presses from the laptop keyboard to drive the reactive Aura
+//! effects, and watch all input devices for activity.
+
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use evdev::{BusType, Device, EventType, InputEvent, Key, RelativeAxisType};
+use log::{debug, info, warn};
+use rog_aura::advanced::LedCode;
+
+/// The USB vendor ID of the ASUS N-KEY keyboards
+const ASUS_VENDOR: u16 = 0x0b05;
+
+/// The shortest time between two activity messages from one device
+pub const ACTIVITY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An LED whose key was pressed (true) or released (false)
+pub type KeyEvent = (LedCode, bool);
+
+/// The built in keyboard is either an ASUS USB device or on the i8042 port.
+/// External keyboards are ignored.
+fn is_laptop_keyboard(device: &Device) -> bool {
+    let id = device.input_id();
+    let has_keys = device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(Key::KEY_A) && keys.contains(Key::KEY_SPACE));
+    has_keys && (id.vendor() == ASUS_VENDOR || id.bus_type() == BusType::BUS_I8042)
+}
+
+/// A keyboard, mouse, or touchpad. Other devices such as sensors can send
+/// events without anyone using the laptop.
+fn is_user_input(device: &Device) -> bool {
+    let keyboard = device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(Key::KEY_A) || keys.contains(Key::BTN_LEFT));
+    let mouse = device
+        .supported_relative_axes()
+        .is_some_and(|axes| axes.contains(RelativeAxisType::REL_X));
+    keyboard || mouse
+}
+
+/// Read events from `device` on a new thread until `handle` returns false
+fn spawn_reader(
+    path: PathBuf,
+    mut device: Device,
+    mut handle: impl FnMut(InputEvent) -> bool + Send + 'static,
+) {
+    std::thread::spawn(move || loop {
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Stopped reading input from {path:?}: {e}");
+                return;
+            }
+        };
+        for event in events {
+            if !handle(event) {
+                debug!("Input receiver closed, stopped reading {path:?}");
+                return;
+            }
+        }
+    });
+}
+
+/// Start a thread for each laptop keyboard which sends every LED pressed or
+/// released to `sender`. Key repeats are skipped. Returns the number of
+/// keyboards found, reading them needs the user to be in the `input` group.
+pub fn watch_keyboards(sender: &Sender<KeyEvent>) -> usize {
+    let mut count = 0;
+    for (path, device) in evdev::enumerate() {
+        if !is_laptop_keyboard(&device) {
+            continue;
+        }
+        info!(
+            "Reading keys from {} at {path:?}",
+            device.name().unwrap_or_default()
+        );
+        count += 1;
+        let sender = sender.clone();
+        spawn_reader(path, device, move |event| {
+            // 0 is a release, 1 a press, and 2 a repeat
+            if event.event_type() != EventType::KEY || event.value() > 1 {
+                return true;
+            }
+            LedCode::from_key_code(event.code())
+                .iter()
+                .all(|led| sender.send((*led, event.value() == 1)).is_ok())
+        });
+    }
+    count
+}
+
+/// Start a thread for each keyboard, mouse, and touchpad which sends to
+/// `sender` while it is being used, at most every [`ACTIVITY_INTERVAL`].
+/// Returns the number of devices found.
+pub fn watch_activity(sender: &Sender<()>) -> usize {
+    let mut count = 0;
+    for (path, device) in evdev::enumerate() {
+        if !is_user_input(&device) {
+            continue;
+        }
+        debug!(
+            "Watching activity of {} at {path:?}",
+            device.name().unwrap_or_default()
+        );
+        count += 1;
+        let sender = sender.clone();
+        let mut last: Option<Instant> = None;
+        spawn_reader(path, device, move |event| {
+            if !matches!(
+                event.event_type(),
+                EventType::KEY | EventType::RELATIVE | EventType::ABSOLUTE
+            ) {
+                return true;
+            }
+            if last.is_some_and(|last| last.elapsed() < ACTIVITY_INTERVAL) {
+                return true;
+            }
+            last = Some(Instant::now());
+            sender.send(()).is_ok()
+        });
+    }
+    count
+}
@@ -0,0 +1,183 @@
+//! Send the colours of the user daemon's own keyboard modes to the Aura
+//! devices of asusd. Writes too small to see are skipped, and nothing is sent
+//! while the backlight is off or the session is locked.
+
+use std::time::{Duration, Instant};
+
+use log::info;
+use rog_aura::advanced::{LedCode, LedUsbPackets, UsbPackets};
+use rog_aura::{AuraEffect, AuraModeNum, AuraZone, Colour, LedBrightness};
+
+use crate::aura_devices::AuraDevices;
+use crate::session::session_locked;
+
+/// Each mode write to zoned and basic keyboards goes through the keyboard's
+/// firmware, so they are updated less often than per-key
+const STATIC_MIN_INTERVAL: Duration = Duration::from_millis(250);
+/// How often to check if the backlight is off or the session locked
+const PAUSE_CHECK: Duration = Duration::from_secs(1);
+
+/// The colours of one update of the keyboard
+#[derive(Debug, Clone, PartialEq)]
+pub enum LedFrame {
+    /// One colour for the whole keyboard, as the Static mode
+    Static(Colour),
+    /// The colour of each key on a per-key keyboard
+    PerKey(Vec<(LedCode, Colour)>),
+    /// Packets for a per-key or zoned keyboard made elsewhere, such as by
+    /// `AdvancedEffects`. Only sent if they changed at all.
+    Packets(UsbPackets),
+}
+
+fn differs(a: Colour, b: Colour, threshold: u8) -> bool {
+    a.r.abs_diff(b.r) > threshold || a.g.abs_diff(b.g) > threshold || a.b.abs_diff(b.b) > threshold
+}
+
+impl LedFrame {
+    fn differs(&self, other: &LedFrame, threshold: u8) -> bool {
+        match (self, other) {
+            (LedFrame::Static(a), LedFrame::Static(b)) => differs(*a, *b, threshold),
+            (LedFrame::PerKey(a), LedFrame::PerKey(b)) => {
+                a.len() != b.len()
+                    || a.iter()
+                        .zip(b)
+                        .any(|((la, a), (lb, b))| la != lb || differs(*a, *b, threshold))
+            }
+            (LedFrame::Packets(a), LedFrame::Packets(b)) => a != b,
+            _ => true,
+        }
+    }
+}
+
+/// The shortest time between updates at `max_rate` per second (1-60)
+pub fn frame_interval(max_rate: u32, per_key: bool) -> Duration {
+    let interval = Duration::from_secs(1) / max_rate.clamp(1, 60);
+    if per_key {
+        interval
+    } else {
+        interval.max(STATIC_MIN_INTERVAL)
+    }
+}
+
+pub struct LedOutput {
+    /// What is being shown, for the logs
+    name: &'static str,
+    /// The smallest change of any colour channel which is sent
+    threshold: u8,
+    devices: AuraDevices,
+    last_sent: Option<LedFrame>,
+    paused: bool,
+    last_pause_check: Option<Instant>,
+}
+
+impl LedOutput {
+    pub fn new(name: &'static str, threshold: u8) -> Self {
+        Self {
+            name,
+            threshold,
+            devices: AuraDevices::new(name),
+            last_sent: None,
+            paused: false,
+            last_pause_check: None,
+        }
+    }
+
+    /// Paused while the keyboard backlight is off, as asusd turns it back on
+    /// for each write, or while the session is locked
+    fn should_pause(&self) -> bool {
+        let off = self
+            .devices
+            .devices()
+            .first()
+            .is_some_and(|aura| aura.brightness().is_ok_and(|b| b == LedBrightness::Off));
+        let locked = self
+            .devices
+            .conn()
+            .is_some_and(|conn| session_locked(conn).unwrap_or(false));
+        off || locked
+    }
+
+    /// Find asusd and check if paused. Returns false, after waiting a while,
+    /// if nothing can be sent now.
+    pub fn ready(&mut self) -> bool {
+        if !self.devices.ready() {
+            // Whatever asusd shows once it is back, the next frame is sent
+            self.last_sent = None;
+            std::thread::sleep(self.devices.retry_in());
+            return false;
+        }
+        let check_pause = match self.last_pause_check {
+            Some(last) => last.elapsed() >= PAUSE_CHECK,
+            None => true,
+        };
+        if check_pause {
+            self.last_pause_check = Some(Instant::now());
+            let paused = self.should_pause();
+            if paused != self.paused {
+                info!(
+                    "{} {}",
+                    self.name,
+                    if paused { "paused" } else { "resumed" }
+                );
+                self.paused = paused;
+                self.last_sent = None;
+            }
+        }
+        if self.paused {
+            std::thread::sleep(PAUSE_CHECK);
+            return false;
+        }
+        true
+    }
+
+    /// Send `frame` to every Aura device, unless it is too close to the last
+    pub fn send(&mut self, frame: LedFrame) {
+        if let Some(last) = &self.last_sent {
+            if !last.differs(&frame, self.threshold) {
+                return;
+            }
+        }
+        match self.write(&frame) {
+            Ok(()) => {
+                self.devices.succeeded();
+                self.last_sent = Some(frame);
+            }
+            Err(e) => {
+                self.last_sent = None;
+                self.devices.failed(e);
+            }
+        }
+    }
+
+    fn write(&self, frame: &LedFrame) -> zbus::Result<()> {
+        match frame {
+            LedFrame::Static(colour) => {
+                let effect = AuraEffect {
+                    mode: AuraModeNum::Static,
+                    zone: AuraZone::None,
+                    colour1: *colour,
+                    ..Default::default()
+                };
+                for aura in self.devices.devices() {
+                    aura.direct_effect(&effect)?;
+                }
+            }
+            LedFrame::PerKey(colours) => {
+                let mut packets = LedUsbPackets::new_per_key();
+                for (led, c) in colours {
+                    packets.set(*led, c.r, c.g, c.b);
+                }
+                let packets = packets.get();
+                for aura in self.devices.devices() {
+                    aura.direct_addressing_raw(packets.clone())?;
+                }
+            }
+            LedFrame::Packets(packets) => {
+                for aura in self.devices.devices() {
+                    aura.direct_addressing_raw(packets.clone())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -6,4 +6,20 @@ pub mod ctrl_anime;
 
 pub mod zbus_anime;
 
+pub mod key_input;
+
+pub mod aura_devices;
+
+pub mod aura_renderer;
+
+pub mod session;
+
+pub mod backlight_idle;
+
+pub mod led_output;
+
+pub mod ambient;
+
+pub mod audio;
+
 pub static VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,44 @@
+//! The logind session of the desktop this user daemon runs in
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::OwnedObjectPath;
+
+/// The graphical session of this user
+fn display_session(conn: &Connection) -> zbus::Result<Proxy<'static>> {
+    let user = Proxy::new(
+        conn,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1/user/self",
+        "org.freedesktop.login1.User",
+    )?;
+    let (_, session): (String, OwnedObjectPath) = user.get_property("Display")?;
+    Proxy::new(
+        conn,
+        "org.freedesktop.login1",
+        session,
+        "org.freedesktop.login1.Session",
+    )
+}
+
+/// If the graphical session of this user is locked
+pub fn session_locked(conn: &Connection) -> zbus::Result<bool> {
+    display_session(conn)?.get_property("LockedHint")
+}
+
+/// How long the desktop has told logind the graphical session of this user
+/// has been idle, `None` if it is in use
+pub fn session_idle(conn: &Connection) -> zbus::Result<Option<Duration>> {
+    let session = display_session(conn)?;
+    if !session.get_property::<bool>("IdleHint")? {
+        return Ok(None);
+    }
+    // Microseconds since the epoch
+    let since: u64 = session.get_property("IdleSinceHint")?;
+    let idle = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .saturating_sub(Duration::from_micros(since));
+    Ok(Some(idle))
+}
@@ -0,0 +1,361 @@
+//! Selection of the battery charge limit to use right now.
+//!
+//! The limit saved in the config is used unless a one-shot "charge to full"
+//! [`ChargeOverride`] is active, or a [`ChargeSchedule`] covers the current
+//! time. The override ends on the next unplug, or when it expires.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{Datelike, Local, Timelike};
+use serde_derive::{Deserialize, Serialize};
+use zbus::zvariant::Type;
+
+use crate::error::RogError;
+
+/// The charge limit used by a "charge to full" override
+pub const CHARGE_FULL: u8 = 100;
+
+#[derive(Deserialize, Serialize, Type, Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
+#[zvariant(signature = "s")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn previous(self) -> Self {
+        Self::ALL[(self.index() + 6) % 7]
+    }
+}
+
+impl From<chrono::Weekday> for Weekday {
+    fn from(day: chrono::Weekday) -> Self {
+        Self::ALL[day.num_days_from_monday() as usize]
+    }
+}
+
+impl FromStr for Weekday {
+    type Err = RogError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mon" | "monday" => Ok(Self::Mon),
+            "tue" | "tuesday" => Ok(Self::Tue),
+            "wed" | "wednesday" => Ok(Self::Wed),
+            "thu" | "thursday" => Ok(Self::Thu),
+            "fri" | "friday" => Ok(Self::Fri),
+            "sat" | "saturday" => Ok(Self::Sat),
+            "sun" | "sunday" => Ok(Self::Sun),
+            _ => Err(RogError::ChargeSchedule(format!("unknown day: {s}"))),
+        }
+    }
+}
+
+/// A local time of day
+#[derive(
+    Deserialize, Serialize, Type, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl TimeOfDay {
+    pub fn new(hour: u8, minute: u8) -> Self {
+        Self { hour, minute }
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = RogError;
+
+    /// Parse `HH:MM` in 24 hour time
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || RogError::ChargeSchedule(format!("time must be HH:MM: {s}"));
+        let (hour, minute) = s.trim().split_once(':').ok_or_else(err)?;
+        let hour: u8 = hour.parse().map_err(|_| err())?;
+        let minute: u8 = minute.parse().map_err(|_| err())?;
+        if hour > 23 || minute > 59 {
+            return Err(err());
+        }
+        Ok(Self { hour, minute })
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+/// A charge limit to use at a set time on some days of the week
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq, PartialOrd)]
+pub struct ChargeSchedule {
+    /// Used to identify the schedule over dbus
+    pub name: String,
+    /// The days this starts on, empty for every day
+    pub days: Vec<Weekday>,
+    pub start: TimeOfDay,
+    /// If this is earlier than `start` the schedule ends on the following day.
+    /// If equal it covers the whole day.
+    pub end: TimeOfDay,
+    pub limit: u8,
+}
+
+impl ChargeSchedule {
+    fn starts_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    /// Check if the schedule covers this day and time
+    pub fn is_active(&self, day: Weekday, time: TimeOfDay) -> bool {
+        if self.start < self.end {
+            self.starts_on(day) && self.start <= time && time < self.end
+        } else if self.start > self.end {
+            (self.starts_on(day) && self.start <= time)
+                || (self.starts_on(day.previous()) && time < self.end)
+        } else {
+            self.starts_on(day)
+        }
+    }
+}
+
+impl FromStr for ChargeSchedule {
+    type Err = RogError;
+
+    /// Parse `name,days,HH:MM-HH:MM,limit` where days is `all`, a range such
+    /// as `mon-fri`, or a list such as `sat+sun`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').map(|p| p.trim()).collect();
+        let [name, days, times, limit] = parts[..] else {
+            return Err(RogError::ChargeSchedule(format!(
+                "expected name,days,HH:MM-HH:MM,limit: {s}"
+            )));
+        };
+        if name.is_empty() {
+            return Err(RogError::ChargeSchedule("name is empty".to_owned()));
+        }
+
+        let days = if days.eq_ignore_ascii_case("all") {
+            Vec::new()
+        } else if let Some((first, last)) = days.split_once('-') {
+            let first = Weekday::from_str(first)?.index();
+            let last = Weekday::from_str(last)?.index();
+            let len = (last + 7 - first) % 7 + 1;
+            (first..first + len).map(|i| Weekday::ALL[i % 7]).collect()
+        } else {
+            days.split('+')
+                .map(Weekday::from_str)
+                .collect::<Result<_, _>>()?
+        };
+
+        let (start, end) = times.split_once('-').ok_or_else(|| {
+            RogError::ChargeSchedule(format!("times must be HH:MM-HH:MM: {times}"))
+        })?;
+        let limit: u8 = limit
+            .parse()
+            .map_err(|_| RogError::ChargeSchedule(format!("invalid limit: {limit}")))?;
+        if !(20..=100).contains(&limit) {
+            return Err(RogError::ChargeLimit(limit));
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            days,
+            start: start.parse()?,
+            end: end.parse()?,
+            limit,
+        })
+    }
+}
+
+/// A one-shot "charge to full" which is used in place of the saved limit and
+/// any schedule
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+pub struct ChargeOverride {
+    /// Seconds since the unix epoch when this ends, `0` to only end on unplug
+    pub expires: u64,
+}
+
+impl ChargeOverride {
+    /// Create an override lasting until the next unplug, or `hours` from
+    /// `now` if that is sooner. An `hours` of `0` only ends on unplug.
+    pub fn new(hours: u32, now: u64) -> Self {
+        let expires = if hours == 0 {
+            0
+        } else {
+            now + hours as u64 * 60 * 60
+        };
+        Self { expires }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires != 0 && now >= self.expires
+    }
+}
+
+/// The moment a charge limit is selected for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChargeTime {
+    pub day: Weekday,
+    pub time: TimeOfDay,
+    /// Seconds since the unix epoch
+    pub unix: u64,
+}
+
+impl ChargeTime {
+    pub fn now() -> Self {
+        let local = Local::now();
+        Self {
+            day: local.weekday().into(),
+            time: TimeOfDay::new(local.hour() as u8, local.minute() as u8),
+            unix: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Where the charge limit in use came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChargeLimitSource {
+    Saved,
+    Override,
+    /// The name of the schedule
+    Schedule(String),
+}
+
+/// Pick the charge limit for `now`. An expired override is ignored, the first
+/// active schedule is used if there are several.
+pub fn select_limit(
+    saved: u8,
+    charge_override: Option<&ChargeOverride>,
+    schedules: &[ChargeSchedule],
+    now: &ChargeTime,
+) -> (u8, ChargeLimitSource) {
+    if charge_override.is_some_and(|o| !o.is_expired(now.unix)) {
+        return (CHARGE_FULL, ChargeLimitSource::Override);
+    }
+    if let Some(schedule) = schedules.iter().find(|s| s.is_active(now.day, now.time)) {
+        return (
+            schedule.limit,
+            ChargeLimitSource::Schedule(schedule.name.clone()),
+        );
+    }
+    (saved, ChargeLimitSource::Saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn at(day: Weekday, hour: u8, minute: u8) -> ChargeTime {
+        ChargeTime {
+            day,
+            time: TimeOfDay::new(hour, minute),
+            unix: 1000,
+        }
+    }
+
+    #[test]
+    fn schedule_from_str() {
+        let s = ChargeSchedule::from_str("travel,fri,18:00-23:59,100").unwrap();
+        assert_eq!(s.days, vec![Weekday::Fri]);
+        assert_eq!(s.start, TimeOfDay::new(18, 0));
+        assert_eq!(s.end, TimeOfDay::new(23, 59));
+        assert_eq!(s.limit, 100);
+
+        let s = ChargeSchedule::from_str("weekdays, mon-fri, 00:00-00:00, 60").unwrap();
+        assert_eq!(s.days.len(), 5);
+        let s = ChargeSchedule::from_str("weekend,sat-mon,00:00-00:00,80").unwrap();
+        assert_eq!(s.days, vec![Weekday::Sat, Weekday::Sun, Weekday::Mon]);
+        let s = ChargeSchedule::from_str("x,sat+sun,1:00-2:00,80").unwrap();
+        assert_eq!(s.days, vec![Weekday::Sat, Weekday::Sun]);
+        assert!(ChargeSchedule::from_str("x,all,1:00-2:00,80")
+            .unwrap()
+            .days
+            .is_empty());
+
+        assert!(ChargeSchedule::from_str("x,fri,18:00-23:59").is_err());
+        assert!(ChargeSchedule::from_str("x,fry,18:00-23:59,80").is_err());
+        assert!(ChargeSchedule::from_str("x,fri,24:00-23:59,80").is_err());
+        assert!(matches!(
+            ChargeSchedule::from_str("x,fri,18:00-23:59,10"),
+            Err(RogError::ChargeLimit(10))
+        ));
+    }
+
+    #[test]
+    fn schedule_active_over_midnight() {
+        let s = ChargeSchedule::from_str("night,fri,22:00-06:00,100").unwrap();
+        assert!(!s.is_active(Weekday::Fri, TimeOfDay::new(21, 59)));
+        assert!(s.is_active(Weekday::Fri, TimeOfDay::new(22, 0)));
+        assert!(s.is_active(Weekday::Sat, TimeOfDay::new(5, 59)));
+        assert!(!s.is_active(Weekday::Sat, TimeOfDay::new(6, 0)));
+        assert!(!s.is_active(Weekday::Fri, TimeOfDay::new(5, 0)));
+        assert!(!s.is_active(Weekday::Sat, TimeOfDay::new(22, 0)));
+    }
+
+    #[test]
+    fn select_limit_order() {
+        let schedules = vec![
+            ChargeSchedule::from_str("travel,fri,18:00-23:59,100").unwrap(),
+            ChargeSchedule::from_str("weekdays,mon-fri,00:00-00:00,60").unwrap(),
+        ];
+
+        assert_eq!(
+            select_limit(80, None, &schedules, &at(Weekday::Sat, 12, 0)),
+            (80, ChargeLimitSource::Saved)
+        );
+        assert_eq!(
+            select_limit(80, None, &schedules, &at(Weekday::Fri, 12, 0)),
+            (60, ChargeLimitSource::Schedule("weekdays".to_owned()))
+        );
+        assert_eq!(
+            select_limit(80, None, &schedules, &at(Weekday::Fri, 19, 0)),
+            (100, ChargeLimitSource::Schedule("travel".to_owned()))
+        );
+
+        let charge_override = ChargeOverride::new(2, 0);
+        assert_eq!(
+            select_limit(
+                80,
+                Some(&charge_override),
+                &schedules,
+                &at(Weekday::Mon, 9, 0)
+            ),
+            (CHARGE_FULL, ChargeLimitSource::Override)
+        );
+        let expired = ChargeOverride::new(0, 0);
+        assert!(!expired.is_expired(u64::MAX));
+        let expired = ChargeOverride { expires: 1000 };
+        assert_eq!(
+            select_limit(80, Some(&expired), &schedules, &at(Weekday::Mon, 9, 0)),
+            (60, ChargeLimitSource::Schedule("weekdays".to_owned()))
+        );
+    }
+}
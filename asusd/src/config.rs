@@ -1,22 +1,29 @@
-use config_traits::{StdConfig, StdConfigLoad3};
+use config_traits::{StdConfig, StdConfigLoad4};
+use cpuctl::CpuSettings;
 use rog_platform::cpu::CPUEPP;
 use rog_platform::platform::ThrottlePolicy;
 use serde_derive::{Deserialize, Serialize};
 
+use crate::charge_limit::{ChargeOverride, ChargeSchedule};
+use crate::hooks::{Hook, HookTrigger};
+
 const CONFIG_FILE: &str = "asusd.ron";
 
 #[derive(Deserialize, Serialize, Debug, PartialEq, PartialOrd)]
 pub struct Config {
     /// Save charge limit for restoring on boot/resume
     pub charge_control_end_threshold: u8,
+    /// A one-shot charge to full, used instead of the saved limit until the
+    /// next unplug or it expires
+    pub charge_override: Option<ChargeOverride>,
+    /// Charge limits used instead of the saved limit at set times
+    pub charge_schedules: Vec<ChargeSchedule>,
     pub panel_od: bool,
     pub boot_sound: bool,
     pub mini_led_mode: bool,
     pub disable_nvidia_powerd_on_battery: bool,
-    /// An optional command/script to run when power is changed to AC
-    pub ac_command: String,
-    /// An optional command/script to run when power is changed to battery
-    pub bat_command: String,
+    /// Commands to run on system events such as power source change
+    pub hooks: Vec<Hook>,
     /// Set true if energy_performance_preference should be set if the
     /// throttle/platform profile is changed
     pub throttle_policy_linked_epp: bool,
@@ -30,20 +37,19 @@ pub struct Config {
     pub throttle_balanced_epp: CPUEPP,
     /// The energy_performance_preference for this throttle/platform profile
     pub throttle_performance_epp: CPUEPP,
-    /// Defaults to `None` if not supported
-    pub ppt_pl1_spl: Option<u8>,
-    /// Defaults to `None` if not supported
-    pub ppt_pl2_sppt: Option<u8>,
-    /// Defaults to `None` if not supported
-    pub ppt_fppt: Option<u8>,
-    /// Defaults to `None` if not supported
-    pub ppt_apu_sppt: Option<u8>,
-    /// Defaults to `None` if not supported
-    pub ppt_platform_sppt: Option<u8>,
-    /// Defaults to `None` if not supported
-    pub nv_dynamic_boost: Option<u8>,
-    /// Defaults to `None` if not supported
-    pub nv_temp_target: Option<u8>,
+    /// Set true if the CPU settings should be stored and applied per
+    /// throttle/platform profile (in the `cpu` of each tunings) instead of
+    /// the global `cpu`
+    pub throttle_policy_linked_cpu: bool,
+    /// CPU settings restored on boot if not linked to throttle/platform
+    /// profile, if linked used for anything the profile has no value for
+    pub cpu: CpuSettings,
+    /// The PPT and Nvidia tunings applied with this throttle/platform profile
+    pub throttle_quiet_tunings: PolicyTunings,
+    /// The PPT and Nvidia tunings applied with this throttle/platform profile
+    pub throttle_balanced_tunings: PolicyTunings,
+    /// The PPT and Nvidia tunings applied with this throttle/platform profile
+    pub throttle_performance_tunings: PolicyTunings,
     /// Temporary state for AC/Batt
     #[serde(skip)]
     pub last_power_plugged: u8,
@@ -53,30 +59,91 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             charge_control_end_threshold: 100,
+            charge_override: None,
+            charge_schedules: Vec::new(),
             panel_od: false,
             boot_sound: false,
             mini_led_mode: false,
             disable_nvidia_powerd_on_battery: true,
-            ac_command: Default::default(),
-            bat_command: Default::default(),
+            hooks: Default::default(),
             throttle_policy_linked_epp: true,
             throttle_policy_on_battery: ThrottlePolicy::Quiet,
             throttle_policy_on_ac: ThrottlePolicy::Performance,
             throttle_quiet_epp: CPUEPP::Power,
             throttle_balanced_epp: CPUEPP::BalancePower,
             throttle_performance_epp: CPUEPP::Performance,
-            ppt_pl1_spl: Default::default(),
-            ppt_pl2_sppt: Default::default(),
-            ppt_fppt: Default::default(),
-            ppt_apu_sppt: Default::default(),
-            ppt_platform_sppt: Default::default(),
-            nv_dynamic_boost: Default::default(),
-            nv_temp_target: Default::default(),
+            throttle_policy_linked_cpu: false,
+            cpu: Default::default(),
+            throttle_quiet_tunings: Default::default(),
+            throttle_balanced_tunings: Default::default(),
+            throttle_performance_tunings: Default::default(),
             last_power_plugged: Default::default(),
         }
     }
 }
 
+impl Config {
+    /// The PPT and Nvidia tunings stored for this throttle/platform profile
+    pub fn tunings_for_throttle(&self, throttle: ThrottlePolicy) -> &PolicyTunings {
+        match throttle {
+            ThrottlePolicy::Balanced => &self.throttle_balanced_tunings,
+            ThrottlePolicy::Performance => &self.throttle_performance_tunings,
+            ThrottlePolicy::Quiet => &self.throttle_quiet_tunings,
+        }
+    }
+
+    pub fn tunings_for_throttle_mut(&mut self, throttle: ThrottlePolicy) -> &mut PolicyTunings {
+        match throttle {
+            ThrottlePolicy::Balanced => &mut self.throttle_balanced_tunings,
+            ThrottlePolicy::Performance => &mut self.throttle_performance_tunings,
+            ThrottlePolicy::Quiet => &mut self.throttle_quiet_tunings,
+        }
+    }
+
+    /// The CPU settings to apply with this throttle/platform profile. If
+    /// linked, anything not stored for the profile is taken from the global
+    /// `cpu` so it can't carry over from the previous profile.
+    pub fn cpu_for_throttle(&self, throttle: ThrottlePolicy) -> CpuSettings {
+        if self.throttle_policy_linked_cpu {
+            self.tunings_for_throttle(throttle).cpu.or(&self.cpu)
+        } else {
+            self.cpu
+        }
+    }
+
+    /// Store a CPU setting for this throttle/platform profile. If the global
+    /// `cpu` has no value for it yet it keeps `current`, the value before the
+    /// change, which the profiles without their own value return to.
+    pub fn set_cpu_for_throttle<T>(
+        &mut self,
+        throttle: ThrottlePolicy,
+        field: impl Fn(&mut CpuSettings) -> &mut Option<T>,
+        value: T,
+        current: Option<T>,
+    ) {
+        let global = field(&mut self.cpu);
+        if global.is_none() {
+            *global = current;
+        }
+        *field(&mut self.tunings_for_throttle_mut(throttle).cpu) = Some(value);
+    }
+}
+
+/// Power limits, dGPU, and CPU tunings which are written each time the
+/// matching throttle/platform profile is activated. A value of `None` is left
+/// as the firmware has it.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct PolicyTunings {
+    pub ppt_pl1_spl: Option<u8>,
+    pub ppt_pl2_sppt: Option<u8>,
+    pub ppt_fppt: Option<u8>,
+    pub ppt_apu_sppt: Option<u8>,
+    pub ppt_platform_sppt: Option<u8>,
+    pub nv_dynamic_boost: Option<u8>,
+    pub nv_temp_target: Option<u8>,
+    pub cpu: CpuSettings,
+}
+
 impl StdConfig for Config {
     fn new() -> Self {
         Config {
@@ -84,8 +151,6 @@ impl StdConfig for Config {
             disable_nvidia_powerd_on_battery: true,
             throttle_policy_on_battery: ThrottlePolicy::Quiet,
             throttle_policy_on_ac: ThrottlePolicy::Performance,
-            ac_command: String::new(),
-            bat_command: String::new(),
             ..Default::default()
         }
     }
@@ -99,7 +164,82 @@ impl StdConfig for Config {
     }
 }
 
-impl StdConfigLoad3<Config472, Config506, Config507> for Config {}
+/// Convert the old `ac_command` and `bat_command` to hooks
+fn hooks_from_commands(ac_command: &str, bat_command: &str) -> Vec<Hook> {
+    [
+        Hook::from_command("ac_command", HookTrigger::AcPlugged, ac_command),
+        Hook::from_command("bat_command", HookTrigger::Battery, bat_command),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+impl StdConfigLoad4<Config472, Config506, Config507, Config508> for Config {}
+
+#[derive(Deserialize, Serialize)]
+pub struct Config508 {
+    /// Save charge limit for restoring on boot/resume
+    pub charge_control_end_threshold: u8,
+    pub panel_od: bool,
+    pub boot_sound: bool,
+    pub mini_led_mode: bool,
+    pub disable_nvidia_powerd_on_battery: bool,
+    pub ac_command: String,
+    pub bat_command: String,
+    pub throttle_policy_linked_epp: bool,
+    pub throttle_policy_on_battery: ThrottlePolicy,
+    pub throttle_policy_on_ac: ThrottlePolicy,
+    pub throttle_quiet_epp: CPUEPP,
+    pub throttle_balanced_epp: CPUEPP,
+    pub throttle_performance_epp: CPUEPP,
+    //
+    pub ppt_pl1_spl: Option<u8>,
+    pub ppt_pl2_sppt: Option<u8>,
+    pub ppt_fppt: Option<u8>,
+    pub ppt_apu_sppt: Option<u8>,
+    pub ppt_platform_sppt: Option<u8>,
+    pub nv_dynamic_boost: Option<u8>,
+    pub nv_temp_target: Option<u8>,
+}
+
+impl From<Config508> for Config {
+    fn from(c: Config508) -> Self {
+        // The old tunings were global, so keep them for every policy
+        let tunings = PolicyTunings {
+            ppt_pl1_spl: c.ppt_pl1_spl,
+            ppt_pl2_sppt: c.ppt_pl2_sppt,
+            ppt_fppt: c.ppt_fppt,
+            ppt_apu_sppt: c.ppt_apu_sppt,
+            ppt_platform_sppt: c.ppt_platform_sppt,
+            nv_dynamic_boost: c.nv_dynamic_boost,
+            nv_temp_target: c.nv_temp_target,
+            cpu: Default::default(),
+        };
+        Self {
+            charge_control_end_threshold: c.charge_control_end_threshold,
+            charge_override: None,
+            charge_schedules: Vec::new(),
+            panel_od: c.panel_od,
+            boot_sound: c.boot_sound,
+            disable_nvidia_powerd_on_battery: c.disable_nvidia_powerd_on_battery,
+            hooks: hooks_from_commands(&c.ac_command, &c.bat_command),
+            mini_led_mode: c.mini_led_mode,
+            throttle_policy_linked_epp: c.throttle_policy_linked_epp,
+            throttle_policy_on_battery: c.throttle_policy_on_battery,
+            throttle_policy_on_ac: c.throttle_policy_on_ac,
+            throttle_quiet_epp: c.throttle_quiet_epp,
+            throttle_balanced_epp: c.throttle_balanced_epp,
+            throttle_performance_epp: c.throttle_performance_epp,
+            throttle_policy_linked_cpu: false,
+            cpu: Default::default(),
+            throttle_quiet_tunings: tunings,
+            throttle_balanced_tunings: tunings,
+            throttle_performance_tunings: tunings,
+            last_power_plugged: 0,
+        }
+    }
+}
 
 #[derive(Deserialize, Serialize)]
 pub struct Config507 {
@@ -125,13 +265,24 @@ pub struct Config507 {
 
 impl From<Config507> for Config {
     fn from(c: Config507) -> Self {
+        let tunings = PolicyTunings {
+            ppt_pl1_spl: c.ppt_pl1_spl,
+            ppt_pl2_sppt: c.ppt_pl2_sppt,
+            ppt_fppt: c.ppt_fppt,
+            ppt_apu_sppt: c.ppt_apu_sppt,
+            ppt_platform_sppt: c.ppt_platform_sppt,
+            nv_dynamic_boost: c.nv_dynamic_boost,
+            nv_temp_target: c.nv_temp_target,
+            cpu: Default::default(),
+        };
         Self {
             charge_control_end_threshold: c.charge_control_end_threshold,
+            charge_override: None,
+            charge_schedules: Vec::new(),
             panel_od: c.panel_od,
             boot_sound: false,
             disable_nvidia_powerd_on_battery: c.disable_nvidia_powerd_on_battery,
-            ac_command: c.ac_command,
-            bat_command: c.bat_command,
+            hooks: hooks_from_commands(&c.ac_command, &c.bat_command),
             mini_led_mode: c.mini_led_mode,
             throttle_policy_linked_epp: true,
             throttle_policy_on_battery: c.platform_policy_on_battery,
@@ -139,13 +290,11 @@ impl From<Config507> for Config {
             throttle_quiet_epp: CPUEPP::Power,
             throttle_balanced_epp: CPUEPP::BalancePower,
             throttle_performance_epp: CPUEPP::Performance,
-            ppt_pl1_spl: c.ppt_pl1_spl,
-            ppt_pl2_sppt: c.ppt_pl2_sppt,
-            ppt_fppt: c.ppt_fppt,
-            ppt_apu_sppt: c.ppt_apu_sppt,
-            ppt_platform_sppt: c.ppt_platform_sppt,
-            nv_dynamic_boost: c.nv_dynamic_boost,
-            nv_temp_target: c.nv_temp_target,
+            throttle_policy_linked_cpu: false,
+            cpu: Default::default(),
+            throttle_quiet_tunings: tunings,
+            throttle_balanced_tunings: tunings,
+            throttle_performance_tunings: tunings,
             last_power_plugged: 0,
         }
     }
@@ -177,13 +326,24 @@ pub struct Config506 {
 
 impl From<Config506> for Config {
     fn from(c: Config506) -> Self {
+        let tunings = PolicyTunings {
+            ppt_pl1_spl: c.ppt_pl1_spl,
+            ppt_pl2_sppt: c.ppt_pl2_sppt,
+            ppt_fppt: c.ppt_fppt,
+            ppt_apu_sppt: c.ppt_apu_sppt,
+            ppt_platform_sppt: c.ppt_platform_sppt,
+            nv_dynamic_boost: c.nv_dynamic_boost,
+            nv_temp_target: c.nv_temp_target,
+            cpu: Default::default(),
+        };
         Self {
             charge_control_end_threshold: c.charge_control_end_threshold,
+            charge_override: None,
+            charge_schedules: Vec::new(),
             panel_od: c.panel_od,
             boot_sound: false,
             disable_nvidia_powerd_on_battery: c.disable_nvidia_powerd_on_battery,
-            ac_command: c.ac_command,
-            bat_command: c.bat_command,
+            hooks: hooks_from_commands(&c.ac_command, &c.bat_command),
             mini_led_mode: c.mini_led_mode,
             throttle_policy_linked_epp: true,
             throttle_policy_on_battery: c.platform_policy_on_battery,
@@ -191,13 +351,11 @@ impl From<Config506> for Config {
             throttle_quiet_epp: CPUEPP::Power,
             throttle_balanced_epp: CPUEPP::BalancePower,
             throttle_performance_epp: CPUEPP::Performance,
-            ppt_pl1_spl: c.ppt_pl1_spl,
-            ppt_pl2_sppt: c.ppt_pl2_sppt,
-            ppt_fppt: c.ppt_fppt,
-            ppt_apu_sppt: c.ppt_apu_sppt,
-            ppt_platform_sppt: c.ppt_platform_sppt,
-            nv_dynamic_boost: c.nv_dynamic_boost,
-            nv_temp_target: c.nv_temp_target,
+            throttle_policy_linked_cpu: false,
+            cpu: Default::default(),
+            throttle_quiet_tunings: tunings,
+            throttle_balanced_tunings: tunings,
+            throttle_performance_tunings: tunings,
             last_power_plugged: 0,
         }
     }
@@ -220,9 +378,91 @@ impl From<Config472> for Config {
             charge_control_end_threshold: c.bat_charge_limit,
             panel_od: c.panel_od,
             disable_nvidia_powerd_on_battery: true,
-            ac_command: c.ac_command,
-            bat_command: c.bat_command,
+            hooks: hooks_from_commands(&c.ac_command, &c.bat_command),
             ..Default::default()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cpuctl::CpuSettings;
+    use rog_platform::platform::ThrottlePolicy;
+
+    use super::{Config, Config508};
+    use crate::hooks::HookTrigger;
+
+    #[test]
+    fn config508_convert() {
+        let old = r#"(
+            charge_control_end_threshold: 80,
+            panel_od: false,
+            boot_sound: false,
+            mini_led_mode: false,
+            disable_nvidia_powerd_on_battery: true,
+            ac_command: "",
+            bat_command: "notify-send battery",
+            throttle_policy_linked_epp: true,
+            throttle_policy_on_battery: Quiet,
+            throttle_policy_on_ac: Performance,
+            throttle_quiet_epp: Power,
+            throttle_balanced_epp: BalancePower,
+            throttle_performance_epp: Performance,
+            ppt_pl1_spl: Some(45),
+            ppt_pl2_sppt: Some(120),
+            ppt_fppt: None,
+            ppt_apu_sppt: None,
+            ppt_platform_sppt: None,
+            nv_dynamic_boost: Some(25),
+            nv_temp_target: None,
+        )"#;
+        assert!(ron::from_str::<Config>(old).is_err());
+
+        let config: Config = ron::from_str::<Config508>(old).unwrap().into();
+        assert_eq!(config.charge_control_end_threshold, 80);
+        assert_eq!(config.hooks.len(), 1);
+        assert_eq!(config.hooks[0].trigger, HookTrigger::Battery);
+        assert_eq!(config.hooks[0].argv, vec!["notify-send", "battery"]);
+        for policy in ThrottlePolicy::list() {
+            let tunings = config.tunings_for_throttle(policy);
+            assert_eq!(tunings.ppt_pl1_spl, Some(45));
+            assert_eq!(tunings.ppt_pl2_sppt, Some(120));
+            assert_eq!(tunings.nv_dynamic_boost, Some(25));
+            assert_eq!(tunings.nv_temp_target, None);
+        }
+    }
+
+    #[test]
+    fn linked_cpu_falls_back_to_global() {
+        let mut config = Config {
+            cpu: CpuSettings {
+                boost: Some(true),
+                smt: Some(true),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        config.set_cpu_for_throttle(ThrottlePolicy::Quiet, |cpu| &mut cpu.boost, false, None);
+        // The global max frequency keeps the value from before the change
+        config.set_cpu_for_throttle(
+            ThrottlePolicy::Quiet,
+            |cpu| &mut cpu.max_freq,
+            2000000,
+            Some(4000000),
+        );
+        assert_eq!(config.cpu.boost, Some(true));
+        assert_eq!(config.cpu.max_freq, Some(4000000));
+
+        // Unlinked only the global settings are used
+        assert_eq!(config.cpu_for_throttle(ThrottlePolicy::Quiet), config.cpu);
+
+        config.throttle_policy_linked_cpu = true;
+        let quiet = config.cpu_for_throttle(ThrottlePolicy::Quiet);
+        assert_eq!(quiet.boost, Some(false));
+        assert_eq!(quiet.max_freq, Some(2000000));
+        assert_eq!(quiet.smt, Some(true));
+        // Nothing set for performance, so everything returns to the global value
+        let performance = config.cpu_for_throttle(ThrottlePolicy::Performance);
+        assert_eq!(performance, config.cpu);
+    }
+}
@@ -219,7 +219,7 @@ impl CtrlAnime {
                                 }
                             }
                             ActionData::Pause(duration) => sleep(*duration),
-                            ActionData::AudioEq
+                            ActionData::AudioEq { .. }
                             | ActionData::SystemInfo
                             | ActionData::TimeDate
                             | ActionData::Matrix => {}
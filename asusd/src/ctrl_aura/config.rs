@@ -1,4 +1,5 @@
 use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
 
 use config_traits::{StdConfig, StdConfigLoad};
 use log::{debug, info};
@@ -6,7 +7,12 @@ use rog_aura::aura_detection::LaptopLedData;
 use rog_aura::power::AuraPower;
 use rog_aura::usb::{AuraDevRog1, AuraDevTuf, AuraDevice, AuraPowerDev};
 use rog_aura::{AuraEffect, AuraModeNum, AuraZone, Direction, LedBrightness, Speed, GRADIENT};
+use rog_platform::platform::ThrottlePolicy;
+use rog_platform::power::PowerSource;
 use serde_derive::{Deserialize, Serialize};
+use zbus::zvariant::Type;
+
+use crate::error::RogError;
 
 /// Enable/disable LED control in various states such as
 /// when the device is awake, suspended, shutting down or
@@ -117,6 +123,10 @@ pub struct AuraConfig {
     pub multizone: Option<BTreeMap<AuraModeNum, Vec<AuraEffect>>>,
     pub multizone_on: bool,
     pub enabled: AuraPowerConfig,
+    /// Lighting to show in place of the above while the laptop is in some
+    /// state. See [`AuraRule`].
+    #[serde(default)]
+    pub rules: Vec<AuraRule>,
 }
 
 impl AuraConfig {
@@ -176,6 +186,7 @@ impl AuraConfig {
             multizone: None,
             multizone_on: false,
             enabled,
+            rules: Vec::new(),
         };
 
         for n in &support_data.basic_modes {
@@ -245,15 +256,307 @@ impl AuraConfig {
         }
         None
     }
+
+    /// Copy the current lighting of this device for a scene
+    pub fn scene_state(&self, device: AuraDevice) -> AuraSceneDevice {
+        let multizone = if self.multizone_on {
+            self.get_multizone(self.current_mode).map(|m| m.to_vec())
+        } else {
+            None
+        };
+        AuraSceneDevice {
+            device,
+            brightness: self.brightness,
+            current_mode: self.current_mode,
+            effect: self.builtins.get(&self.current_mode).cloned(),
+            multizone,
+            enabled: self.enabled.clone(),
+        }
+    }
+
+    /// Set the current lighting from a scene. The device still needs the
+    /// mode, brightness and power states written to it.
+    pub fn apply_scene_state(&mut self, state: &AuraSceneDevice) {
+        self.brightness = state.brightness;
+        self.current_mode = state.current_mode;
+        if let Some(effect) = &state.effect {
+            self.builtins.insert(state.current_mode, effect.clone());
+        }
+        if let Some(zones) = &state.multizone {
+            self.multizone
+                .get_or_insert_with(BTreeMap::new)
+                .insert(state.current_mode, zones.clone());
+            self.multizone_on = true;
+        } else {
+            self.multizone_on = false;
+        }
+        self.enabled = state.enabled.clone();
+    }
+}
+
+impl AuraConfig {
+    /// The effects written for the current mode
+    pub fn current_effects(&self) -> Vec<AuraEffect> {
+        if self.multizone_on {
+            if let Some(zones) = self.get_multizone(self.current_mode) {
+                return zones.to_vec();
+            }
+        }
+        self.builtins
+            .get(&self.current_mode)
+            .cloned()
+            .into_iter()
+            .collect()
+    }
+
+    /// Add a rule, or replace the one with the same name
+    pub fn set_rule(&mut self, rule: AuraRule) -> Result<(), RogError> {
+        if rule.name.trim().is_empty() {
+            return Err(RogError::AuraRule("the name is empty".to_owned()));
+        }
+        match self.rules.iter_mut().find(|r| r.name == rule.name) {
+            Some(old) => *old = rule,
+            None => self.rules.push(rule),
+        }
+        Ok(())
+    }
+
+    pub fn remove_rule(&mut self, name: &str) -> Result<(), RogError> {
+        let len = self.rules.len();
+        self.rules.retain(|r| r.name != name);
+        if self.rules.len() == len {
+            return Err(RogError::NotFound(format!("Aura rule {name}")));
+        }
+        Ok(())
+    }
+
+    /// The effects and brightness to show in `state`, or `None` if no rule
+    /// matches it. Each comes from the first matching rule which sets it. The
+    /// effects otherwise come from the current config, and the brightness is
+    /// `None` to keep whatever the device has.
+    pub fn rule_lighting(
+        &self,
+        state: &AuraRuleState,
+    ) -> Option<(Vec<AuraEffect>, Option<LedBrightness>)> {
+        let mut rules = self.rules.iter().filter(|r| r.matches(state)).peekable();
+        rules.peek()?;
+
+        let mut effects = None;
+        let mut brightness = None;
+        for rule in rules {
+            if effects.is_none() && !rule.effects.is_empty() {
+                effects = Some(rule.effects.clone());
+            }
+            if brightness.is_none() {
+                brightness = rule.brightness.brightness();
+            }
+        }
+        Some((
+            effects.unwrap_or_else(|| self.current_effects()),
+            brightness,
+        ))
+    }
+}
+
+#[derive(Deserialize, Serialize, Type, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum LidState {
+    #[default]
+    Open,
+    Closed,
+}
+
+impl LidState {
+    pub fn from_closed(closed: bool) -> Self {
+        if closed {
+            Self::Closed
+        } else {
+            Self::Open
+        }
+    }
+}
+
+impl FromStr for LidState {
+    type Err = RogError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "lid-open" | "open" => Ok(Self::Open),
+            "lid-closed" | "closed" => Ok(Self::Closed),
+            _ => Err(RogError::AuraRule(format!("unknown lid state: {s}"))),
+        }
+    }
+}
+
+/// The brightness an [`AuraRule`] sets
+#[derive(Deserialize, Serialize, Type, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum RuleBrightness {
+    /// Leave the brightness as it is
+    #[default]
+    Keep,
+    Off,
+    Low,
+    Med,
+    High,
+}
+
+impl RuleBrightness {
+    pub fn brightness(self) -> Option<LedBrightness> {
+        match self {
+            Self::Keep => None,
+            Self::Off => Some(LedBrightness::Off),
+            Self::Low => Some(LedBrightness::Low),
+            Self::Med => Some(LedBrightness::Med),
+            Self::High => Some(LedBrightness::High),
+        }
+    }
+}
+
+impl FromStr for RuleBrightness {
+    type Err = RogError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "keep" => Ok(Self::Keep),
+            "off" => Ok(Self::Off),
+            "low" => Ok(Self::Low),
+            "med" => Ok(Self::Med),
+            "high" => Ok(Self::High),
+            _ => Err(RogError::AuraRule(format!("unknown brightness: {s}"))),
+        }
+    }
+}
+
+/// Lighting which is shown in place of the config while the laptop is in a
+/// state, such as on battery or with the lid closed. The lighting in the
+/// config is shown again once no rule matches.
+#[derive(Deserialize, Serialize, Type, Debug, Clone)]
+pub struct AuraRule {
+    /// Used to identify the rule over dbus
+    pub name: String,
+    /// Matches any of these throttle policies, empty for any
+    #[serde(default)]
+    pub policies: Vec<ThrottlePolicy>,
+    /// Matches any of these power sources, empty for any
+    #[serde(default)]
+    pub power: Vec<PowerSource>,
+    /// Matches any of these lid states, empty for any
+    #[serde(default)]
+    pub lid: Vec<LidState>,
+    /// The effects to write, one for each zone if multizone. Empty to keep
+    /// the current mode.
+    #[serde(default)]
+    pub effects: Vec<AuraEffect>,
+    #[serde(default)]
+    pub brightness: RuleBrightness,
+}
+
+impl AuraRule {
+    pub fn matches(&self, state: &AuraRuleState) -> bool {
+        (self.policies.is_empty() || self.policies.contains(&state.policy))
+            && (self.power.is_empty() || self.power.contains(&state.power))
+            && (self.lid.is_empty() || self.lid.contains(&state.lid))
+    }
+}
+
+/// The state of the laptop which rules are matched against
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AuraRuleState {
+    pub policy: ThrottlePolicy,
+    pub power: PowerSource,
+    pub lid: LidState,
+}
+
+/// The lighting of one Aura device as stored in a scene
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AuraSceneDevice {
+    /// Scenes are matched to devices by type, as the dbus path of a device
+    /// can change between boots
+    pub device: AuraDevice,
+    pub brightness: LedBrightness,
+    pub current_mode: AuraModeNum,
+    pub effect: Option<AuraEffect>,
+    /// The zones of `current_mode`, if multizone was on
+    pub multizone: Option<Vec<AuraEffect>>,
+    pub enabled: AuraPowerConfig,
+}
+
+/// A named snapshot of the lighting of all Aura devices
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AuraScene {
+    pub name: String,
+    pub devices: Vec<AuraSceneDevice>,
 }
 
+impl AuraScene {
+    pub fn device(&self, device: AuraDevice) -> Option<&AuraSceneDevice> {
+        self.devices.iter().find(|d| d.device == device)
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct AuraScenesConfig {
+    pub scenes: Vec<AuraScene>,
+}
+
+impl AuraScenesConfig {
+    pub fn scene(&self, name: &str) -> Result<&AuraScene, RogError> {
+        self.scenes
+            .iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| RogError::NotFound(format!("Aura scene {name}")))
+    }
+
+    /// Add a scene, replacing any with the same name
+    pub fn save_scene(&mut self, scene: AuraScene) -> Result<(), RogError> {
+        if scene.name.trim().is_empty() {
+            return Err(RogError::AuraScene("the name is empty".to_owned()));
+        }
+        match self.scenes.iter_mut().find(|s| s.name == scene.name) {
+            Some(old) => *old = scene,
+            None => self.scenes.push(scene),
+        }
+        Ok(())
+    }
+
+    pub fn delete_scene(&mut self, name: &str) -> Result<AuraScene, RogError> {
+        let index = self
+            .scenes
+            .iter()
+            .position(|s| s.name == name)
+            .ok_or_else(|| RogError::NotFound(format!("Aura scene {name}")))?;
+        Ok(self.scenes.remove(index))
+    }
+}
+
+impl StdConfig for AuraScenesConfig {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn file_name(&self) -> String {
+        "aura_scenes.ron".to_owned()
+    }
+
+    fn config_dir() -> std::path::PathBuf {
+        std::path::PathBuf::from(crate::CONFIG_PATH_BASE)
+    }
+}
+
+impl StdConfigLoad for AuraScenesConfig {}
+
 #[cfg(test)]
 mod tests {
     use rog_aura::aura_detection::LaptopLedData;
     use rog_aura::usb::AuraDevice;
-    use rog_aura::{AuraEffect, AuraModeNum, AuraZone, Colour};
+    use rog_aura::{AuraEffect, AuraModeNum, AuraZone, Colour, LedBrightness};
+    use rog_platform::platform::ThrottlePolicy;
+    use rog_platform::power::PowerSource;
 
-    use super::AuraConfig;
+    use super::{
+        AuraConfig, AuraRule, AuraRuleState, AuraScene, AuraScenesConfig, LidState, RuleBrightness,
+    };
 
     #[test]
     fn set_multizone_4key_config() {
@@ -393,4 +696,175 @@ mod tests {
         let sta = res.get(&AuraModeNum::Pulse).unwrap();
         assert_eq!(sta.len(), 1);
     }
+
+    #[test]
+    fn scene_restores_lighting() {
+        let mut config =
+            AuraConfig::from_default_support(AuraDevice::X19b6, &LaptopLedData::default());
+        let purple = Colour {
+            r: 0xff,
+            g: 0x00,
+            b: 0xff,
+        };
+        config.set_builtin(AuraEffect {
+            mode: AuraModeNum::Breathe,
+            colour1: purple,
+            ..Default::default()
+        });
+        config.brightness = LedBrightness::Low;
+        let meeting = config.scene_state(AuraDevice::X19b6);
+
+        config.set_builtin(AuraEffect {
+            zone: AuraZone::Key1,
+            ..Default::default()
+        });
+        config.brightness = LedBrightness::High;
+        let gaming = config.scene_state(AuraDevice::X19b6);
+        assert_eq!(gaming.multizone.as_ref().unwrap().len(), 1);
+
+        config.apply_scene_state(&meeting);
+        assert_eq!(config.current_mode, AuraModeNum::Breathe);
+        assert_eq!(config.brightness, LedBrightness::Low);
+        assert!(!config.multizone_on);
+        assert_eq!(
+            config.builtins.get(&AuraModeNum::Breathe).unwrap().colour1,
+            purple
+        );
+
+        config.apply_scene_state(&gaming);
+        assert_eq!(config.current_mode, AuraModeNum::Static);
+        assert_eq!(config.brightness, LedBrightness::High);
+        assert!(config.multizone_on);
+        assert_eq!(
+            config.get_multizone(AuraModeNum::Static).unwrap()[0].zone,
+            AuraZone::Key1
+        );
+    }
+
+    #[test]
+    fn save_and_delete_scenes() {
+        let config = AuraConfig::from_default_support(AuraDevice::X19b6, &LaptopLedData::default());
+        let scene = |name: &str, mode| {
+            let mut config = config.clone();
+            config.current_mode = mode;
+            AuraScene {
+                name: name.to_owned(),
+                devices: vec![config.scene_state(AuraDevice::X19b6)],
+            }
+        };
+
+        let mut scenes = AuraScenesConfig::default();
+        scenes
+            .save_scene(scene("meeting", AuraModeNum::Static))
+            .unwrap();
+        scenes
+            .save_scene(scene("gaming", AuraModeNum::Static))
+            .unwrap();
+        // Saving again replaces it
+        scenes
+            .save_scene(scene("meeting", AuraModeNum::Pulse))
+            .unwrap();
+        assert!(scenes.save_scene(scene(" ", AuraModeNum::Static)).is_err());
+        assert_eq!(scenes.scenes.len(), 2);
+
+        let s = ron::ser::to_string(&scenes).unwrap();
+        let mut scenes: AuraScenesConfig = ron::from_str(&s).unwrap();
+        let meeting = scenes.scene("meeting").unwrap();
+        assert_eq!(
+            meeting.device(AuraDevice::X19b6).unwrap().current_mode,
+            AuraModeNum::Pulse
+        );
+        assert!(meeting.device(AuraDevice::X1866).is_none());
+
+        scenes.delete_scene("meeting").unwrap();
+        assert!(scenes.scene("meeting").is_err());
+        assert!(scenes.delete_scene("meeting").is_err());
+        assert_eq!(scenes.scenes[0].name, "gaming");
+    }
+
+    #[test]
+    fn rules_match_state() {
+        let mut config =
+            AuraConfig::from_default_support(AuraDevice::X19b6, &LaptopLedData::default());
+        let red_breathe = AuraEffect {
+            mode: AuraModeNum::Breathe,
+            colour1: Colour {
+                r: 0xff,
+                g: 0,
+                b: 0,
+            },
+            ..Default::default()
+        };
+        config
+            .set_rule(AuraRule {
+                name: "lid".to_owned(),
+                policies: vec![],
+                power: vec![],
+                lid: vec![LidState::Closed],
+                effects: vec![],
+                brightness: RuleBrightness::Off,
+            })
+            .unwrap();
+        config
+            .set_rule(AuraRule {
+                name: "battery".to_owned(),
+                policies: vec![],
+                power: vec![PowerSource::Battery],
+                lid: vec![],
+                effects: vec![AuraEffect::default()],
+                brightness: RuleBrightness::Low,
+            })
+            .unwrap();
+        config
+            .set_rule(AuraRule {
+                name: "performance".to_owned(),
+                policies: vec![ThrottlePolicy::Performance],
+                power: vec![],
+                lid: vec![],
+                effects: vec![red_breathe],
+                brightness: RuleBrightness::Keep,
+            })
+            .unwrap();
+        assert!(config
+            .set_rule(AuraRule {
+                name: String::new(),
+                ..config.rules[0].clone()
+            })
+            .is_err());
+
+        let mut state = AuraRuleState::default();
+        assert!(config.rule_lighting(&state).is_none());
+
+        // Keeps the brightness of the config
+        state.policy = ThrottlePolicy::Performance;
+        let (effects, brightness) = config.rule_lighting(&state).unwrap();
+        assert_eq!(effects[0].mode, AuraModeNum::Breathe);
+        assert_eq!(brightness, None);
+
+        // The earlier rule wins for the effect and brightness
+        state.power = PowerSource::Battery;
+        let (effects, brightness) = config.rule_lighting(&state).unwrap();
+        assert_eq!(effects[0].mode, AuraModeNum::Static);
+        assert_eq!(brightness, Some(LedBrightness::Low));
+
+        // Only sets brightness, the effect comes from the next rule
+        state.lid = LidState::Closed;
+        let (effects, brightness) = config.rule_lighting(&state).unwrap();
+        assert_eq!(effects[0].mode, AuraModeNum::Static);
+        assert_eq!(brightness, Some(LedBrightness::Off));
+
+        config.remove_rule("lid").unwrap();
+        config.remove_rule("battery").unwrap();
+        assert!(config.remove_rule("battery").is_err());
+        let (effects, brightness) = config.rule_lighting(&state).unwrap();
+        assert_eq!(effects[0].mode, AuraModeNum::Breathe);
+        assert_eq!(brightness, None);
+
+        // Configs without rules still load
+        let mut s = ron::ser::to_string(&config).unwrap();
+        let start = s.find(",rules:").unwrap();
+        s.replace_range(start..s.len() - 1, "");
+        let old: AuraConfig = ron::from_str(&s).unwrap();
+        assert!(old.rules.is_empty());
+    }
 }
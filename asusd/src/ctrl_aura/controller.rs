@@ -6,12 +6,13 @@ use log::{debug, info, warn};
 use rog_aura::advanced::{LedUsbPackets, UsbPackets};
 use rog_aura::aura_detection::LaptopLedData;
 use rog_aura::usb::{AuraDevice, LED_APPLY, LED_SET};
-use rog_aura::{AuraEffect, Direction, LedBrightness, Speed, GRADIENT, LED_MSG_LEN};
+use rog_aura::{AuraEffect, AuraZone, Direction, LedBrightness, Speed, GRADIENT, LED_MSG_LEN};
 use rog_platform::hid_raw::HidRaw;
 use rog_platform::keyboard_led::KeyboardLed;
+use zbus::fdo::Error as ZbErr;
 use zbus::zvariant::OwnedObjectPath;
 
-use super::config::{AuraConfig, AuraPowerConfig};
+use super::config::{AuraConfig, AuraPowerConfig, AuraRuleState};
 use crate::ctrl_aura::manager::dbus_path_for_dev;
 use crate::error::RogError;
 
@@ -56,6 +57,11 @@ pub struct CtrlKbdLed {
     pub per_key_mode_active: bool,
     pub config: AuraConfig,
     pub dbus_path: OwnedObjectPath,
+    /// The state rules are matched against, `None` until it is known
+    pub rule_state: Option<AuraRuleState>,
+    /// The brightness to restore once no rule matches, `Some` while the
+    /// lighting of a rule is showing in place of the config
+    pub rule_restore: Option<LedBrightness>,
 }
 
 impl CtrlKbdLed {
@@ -146,6 +152,8 @@ impl CtrlKbdLed {
             per_key_mode_active: false,
             config: AuraConfig::default(),
             dbus_path,
+            rule_state: None,
+            rule_restore: None,
         };
         Ok(ctrl)
     }
@@ -187,6 +195,54 @@ impl CtrlKbdLed {
         config_loaded
     }
 
+    /// Error if the mode, or the zone when one is given, isn't supported by
+    /// this device
+    pub(super) fn check_effect_supported(&self, effect: &AuraEffect) -> Result<(), ZbErr> {
+        if !self.supported_data.basic_modes.contains(&effect.mode)
+            || effect.zone != AuraZone::None
+                && !self.supported_data.basic_zones.contains(&effect.zone)
+        {
+            return Err(ZbErr::NotSupported(format!(
+                "The Aura effect is not supported: {effect:?}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Show the lighting of the rules matching `rule_state`. If none match
+    /// and a rule was showing, the lighting from the config is restored.
+    pub(super) fn apply_rules(&mut self) -> Result<(), RogError> {
+        let Some(state) = self.rule_state else {
+            return Ok(());
+        };
+        match self.config.rule_lighting(&state) {
+            Some((effects, brightness)) => {
+                if self.rule_restore.is_none() {
+                    // Restore to the brightness in use now, which may have been
+                    // changed without the config
+                    self.rule_restore = Some(self.led_node.get_brightness()?.into());
+                }
+                debug!("Aura rules matched {state:?}");
+                // Rule lighting is never kept by the keyboard, so the config
+                // mode comes back if asusd stops while a rule is showing
+                for effect in &effects {
+                    self.write_transient_mode(effect)?;
+                }
+                if let Some(brightness) = brightness {
+                    self.led_node.set_brightness(brightness.into())?;
+                }
+            }
+            None => {
+                if let Some(restore) = self.rule_restore.take() {
+                    debug!("No Aura rules match {state:?}, restoring config");
+                    self.write_current_config_mode()?;
+                    self.led_node.set_brightness(restore.into())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Set combination state for boot animation/sleep animation/all leds/keys
     /// leds/side leds LED active
     pub(super) fn set_power_states(&mut self) -> Result<(), RogError> {
@@ -251,9 +307,20 @@ impl CtrlKbdLed {
     }
 
     pub fn write_mode(&mut self, mode: &AuraEffect) -> Result<(), RogError> {
+        self.write_mode_with(mode, true)
+    }
+
+    /// Show a mode which the keyboard doesn't keep after a reboot, for
+    /// lighting driven by a client. Neither the config nor the brightness
+    /// are changed.
+    pub fn write_transient_mode(&mut self, mode: &AuraEffect) -> Result<(), RogError> {
+        self.write_mode_with(mode, false)
+    }
+
+    fn write_mode_with(&mut self, mode: &AuraEffect, persist: bool) -> Result<(), RogError> {
         if let LEDNode::KbdLed(platform) = &self.led_node {
             let buf = [
-                1,
+                persist as u8,
                 mode.mode as u8,
                 mode.colour1.r,
                 mode.colour1.g,
@@ -266,7 +333,9 @@ impl CtrlKbdLed {
             hid_raw.write_bytes(&bytes)?;
             hid_raw.write_bytes(&LED_SET)?;
             // Changes won't persist unless apply is set
-            hid_raw.write_bytes(&LED_APPLY)?;
+            if persist {
+                hid_raw.write_bytes(&LED_APPLY)?;
+            }
         } else {
             return Err(RogError::NoAuraKeyboard);
         }
@@ -371,6 +440,8 @@ mod tests {
             per_key_mode_active: false,
             config,
             dbus_path: OwnedObjectPath::default(),
+            rule_state: None,
+            rule_restore: None,
         };
 
         assert!(controller.config.multizone.is_none());
@@ -409,6 +480,8 @@ mod tests {
             per_key_mode_active: false,
             config,
             dbus_path: OwnedObjectPath::default(),
+            rule_state: None,
+            rule_restore: None,
         };
 
         assert!(controller.config.multizone.is_none());
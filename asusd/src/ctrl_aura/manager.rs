@@ -4,7 +4,8 @@
 // - Add it to Zbus server
 // - If udev sees device removed then remove the zbus path
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::time::Duration;
 
 use log::{debug, error, info, warn};
@@ -14,12 +15,15 @@ use rog_aura::usb::AuraDevice;
 use rog_platform::hid_raw::HidRaw;
 use tokio::task::spawn_blocking;
 use udev::{Device, MonitorBuilder};
+use zbus::export::futures_util::lock::Mutex;
 // use zbus::fdo::ObjectManager;
 use zbus::object_server::SignalContext;
 use zbus::zvariant::{ObjectPath, OwnedObjectPath};
 use zbus::Connection;
 
 use crate::ctrl_aura::controller::CtrlKbdLed;
+use crate::ctrl_aura::rules::{AuraRuleStateLock, AuraRulesTask};
+use crate::ctrl_aura::scenes::{AuraDevices, CtrlAuraScenesZbus};
 use crate::ctrl_aura::trait_impls::{CtrlAuraZbus, AURA_ZBUS_PATH};
 use crate::error::RogError;
 use crate::{CtrlTask, Reloadable};
@@ -33,6 +37,8 @@ impl AuraManager {
         let conn_copy = connection.clone();
         let data = LaptopLedData::get_data();
         let mut interfaces = HashSet::new();
+        let devices: AuraDevices = Arc::new(Mutex::new(HashMap::new()));
+        let rule_state: AuraRuleStateLock = Arc::new(Mutex::new(None));
 
         // Do the initial keyboard detection:
         let all = CtrlKbdLed::find_all(&data)?;
@@ -42,9 +48,29 @@ impl AuraManager {
             let sig_ctx = CtrlAuraZbus::signal_context(&connection)?;
             let sig_ctx2 = sig_ctx.clone();
             let zbus = CtrlAuraZbus::new(ctrl, sig_ctx);
-            start_tasks(zbus, connection.clone(), sig_ctx2, path).await?;
+            start_tasks(
+                zbus,
+                connection.clone(),
+                sig_ctx2,
+                path,
+                devices.clone(),
+                rule_state.clone(),
+            )
+            .await?;
         }
 
+        connection
+            .object_server()
+            .at(AURA_ZBUS_PATH, CtrlAuraScenesZbus::new(devices.clone()))
+            .await?;
+
+        let rules = AuraRulesTask::new(devices.clone(), rule_state.clone());
+        rules
+            .create_tasks(AuraRulesTask::signal_context(&connection)?)
+            .await
+            .map_err(|e| warn!("Aura rules: {e}"))
+            .ok();
+
         let manager = Self {
             _connection: connection,
         };
@@ -98,7 +124,9 @@ impl AuraManager {
                         if interfaces.remove(&path) {
                             info!("AuraManager removing: {path:?}");
                             let conn_copy = conn_copy.clone();
+                            let devices = devices.clone();
                             tokio::spawn(async move {
+                                devices.lock().await.remove(&path);
                                 let res = conn_copy
                                     .object_server()
                                     .remove::<CtrlAuraZbus, _>(&path)
@@ -144,8 +172,18 @@ impl AuraManager {
                                     let zbus = CtrlAuraZbus::new(ctrl, sig_ctx);
                                     let sig_ctx = CtrlAuraZbus::signal_context(&conn_copy)?;
                                     let conn_copy = conn_copy.clone();
+                                    let devices = devices.clone();
+                                    let rule_state = rule_state.clone();
                                     tokio::spawn(async move {
-                                        start_tasks(zbus, conn_copy.clone(), sig_ctx, path).await
+                                        start_tasks(
+                                            zbus,
+                                            conn_copy.clone(),
+                                            sig_ctx,
+                                            path,
+                                            devices,
+                                            rule_state,
+                                        )
+                                        .await
                                     });
                                 }
                             }
@@ -185,12 +223,18 @@ async fn start_tasks(
     connection: Connection,
     _signal_ctx: SignalContext<'static>,
     path: OwnedObjectPath,
+    devices: AuraDevices,
+    rule_state: AuraRuleStateLock,
 ) -> Result<(), RogError> {
     // let task = zbus.clone();
     // let signal_ctx = signal_ctx.clone();
     zbus.reload()
         .await
         .unwrap_or_else(|err| warn!("Controller error: {}", err));
+    if let Some(state) = *rule_state.lock().await {
+        zbus.set_rule_state(state).await;
+    }
+    devices.lock().await.insert(path.clone(), zbus.clone());
     connection.object_server().at(path, zbus).await.unwrap();
     // TODO: skip this until we keep handles to tasks so they can be killed
     // task.create_tasks(signal_ctx).await
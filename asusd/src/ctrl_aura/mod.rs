@@ -1,5 +1,7 @@
 pub mod config;
 pub mod controller;
 pub mod manager;
+pub mod rules;
+pub mod scenes;
 /// Implements `CtrlTask`, `Reloadable`, `ZbusRun`
 pub mod trait_impls;
@@ -0,0 +1,129 @@
+//! Follows the throttle policy, power source and lid, and passes each change
+//! to the Aura devices to match against their rules.
+
+use std::sync::Arc;
+
+use futures_lite::StreamExt;
+use log::{debug, warn};
+use logind_zbus::manager::ManagerProxy;
+use rog_platform::platform::RogPlatform;
+use rog_platform::power::{AsusPower, PowerSource};
+use zbus::export::futures_util::lock::Mutex;
+use zbus::{CacheProperties, Connection, SignalContext};
+
+use super::config::{AuraRuleState, LidState};
+use super::scenes::AuraDevices;
+use super::trait_impls::AURA_ZBUS_PATH;
+use crate::error::RogError;
+use crate::CtrlTask;
+
+/// The last state rules were matched against, `None` until first read
+pub type AuraRuleStateLock = Arc<Mutex<Option<AuraRuleState>>>;
+
+#[derive(Clone)]
+pub struct AuraRulesTask {
+    devices: AuraDevices,
+    state: AuraRuleStateLock,
+}
+
+impl AuraRulesTask {
+    pub fn new(devices: AuraDevices, state: AuraRuleStateLock) -> Self {
+        Self { devices, state }
+    }
+
+    /// Change the state and match it against the rules of all devices
+    async fn update(&self, change: impl FnOnce(&mut AuraRuleState)) {
+        let state = {
+            let mut lock = self.state.lock().await;
+            let mut state = lock.unwrap_or_default();
+            change(&mut state);
+            if *lock == Some(state) {
+                return;
+            }
+            *lock = Some(state);
+            state
+        };
+        debug!("Aura rule state changed to {state:?}");
+        for ctrl in self.devices.lock().await.values() {
+            ctrl.set_rule_state(state).await;
+        }
+    }
+}
+
+impl CtrlTask for AuraRulesTask {
+    fn zbus_path() -> &'static str {
+        AURA_ZBUS_PATH
+    }
+
+    async fn create_tasks(&self, _: SignalContext<'static>) -> Result<(), RogError> {
+        let platform = RogPlatform::new()?;
+        let power = AsusPower::new()?;
+        let lid_closed = match Connection::system().await {
+            Ok(conn) => ManagerProxy::builder(&conn)
+                .cache_properties(CacheProperties::No)
+                .build()
+                .await?
+                .lid_closed()
+                .await
+                .unwrap_or_default(),
+            Err(e) => {
+                warn!("Aura rules: could not read the lid state: {e}");
+                false
+            }
+        };
+
+        let mut initial = AuraRuleState {
+            lid: LidState::from_closed(lid_closed),
+            ..Default::default()
+        };
+        if let Ok(policy) = platform.get_throttle_thermal_policy() {
+            initial.policy = policy.into();
+        }
+        if let Ok(online) = power.get_online() {
+            initial.power = PowerSource::from_plugged(online > 0);
+        }
+        self.update(|state| *state = initial).await;
+
+        let task1 = self.clone();
+        let task2 = self.clone();
+        self.create_sys_event_tasks(
+            move |_sleeping| async move {},
+            move |_shutting_down| async move {},
+            move |lid_closed| {
+                let task1 = task1.clone();
+                async move {
+                    task1
+                        .update(|state| state.lid = LidState::from_closed(lid_closed))
+                        .await;
+                }
+            },
+            move |power_plugged| {
+                let task2 = task2.clone();
+                async move {
+                    task2
+                        .update(|state| state.power = PowerSource::from_plugged(power_plugged))
+                        .await;
+                }
+            },
+        )
+        .await;
+
+        if platform.has_throttle_thermal_policy() {
+            let watch_throttle_thermal_policy = platform.monitor_throttle_thermal_policy()?;
+            let task = self.clone();
+            tokio::spawn(async move {
+                let mut buffer = [0; 32];
+                if let Ok(mut stream) = watch_throttle_thermal_policy.into_event_stream(&mut buffer)
+                {
+                    while (stream.next().await).is_some() {
+                        if let Ok(policy) = platform.get_throttle_thermal_policy() {
+                            task.update(|state| state.policy = policy.into()).await;
+                        }
+                    }
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+//! Named snapshots of the lighting of every Aura device, so a whole look can
+//! be switched to at once.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use config_traits::{StdConfig, StdConfigLoad};
+use log::warn;
+use zbus::export::futures_util::lock::Mutex;
+use zbus::fdo::Error as ZbErr;
+use zbus::interface;
+use zbus::zvariant::OwnedObjectPath;
+
+use super::config::{AuraScene, AuraScenesConfig};
+use super::trait_impls::CtrlAuraZbus;
+use crate::error::RogError;
+
+/// The Aura devices currently served, by dbus path
+pub type AuraDevices = Arc<Mutex<HashMap<OwnedObjectPath, CtrlAuraZbus>>>;
+
+pub struct CtrlAuraScenesZbus {
+    config: AuraScenesConfig,
+    devices: AuraDevices,
+}
+
+impl CtrlAuraScenesZbus {
+    pub fn new(devices: AuraDevices) -> Self {
+        Self {
+            config: AuraScenesConfig::new().load(),
+            devices,
+        }
+    }
+}
+
+#[interface(name = "org.asuslinux.AuraScenes")]
+impl CtrlAuraScenesZbus {
+    /// The names of all saved scenes
+    async fn aura_scenes(&self) -> Vec<String> {
+        self.config.scenes.iter().map(|s| s.name.clone()).collect()
+    }
+
+    /// Save the current lighting of all Aura devices as a scene, replacing
+    /// any scene with the same name
+    async fn save_aura_scene(&mut self, name: String) -> Result<(), ZbErr> {
+        let mut devices = Vec::new();
+        for ctrl in self.devices.lock().await.values() {
+            devices.push(ctrl.scene_state().await);
+        }
+        if devices.is_empty() {
+            return Err(RogError::NoAuraKeyboard.into());
+        }
+        self.config.save_scene(AuraScene { name, devices })?;
+        self.config.write();
+        Ok(())
+    }
+
+    /// Set the lighting of all Aura devices from a scene. Devices which are
+    /// not in the scene are left as they are.
+    async fn apply_aura_scene(&self, name: String) -> Result<(), ZbErr> {
+        let scene = self.config.scene(&name)?;
+        for ctrl in self.devices.lock().await.values() {
+            if let Some(state) = scene.device(ctrl.led_prod().await) {
+                ctrl.apply_scene_state(state).await.map_err(|e| {
+                    warn!("Aura scene {name}: {e}");
+                    e
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_aura_scene(&mut self, name: String) -> Result<(), ZbErr> {
+        self.config.delete_scene(&name)?;
+        self.config.write();
+        Ok(())
+    }
+}
@@ -12,6 +12,7 @@ use zbus::export::futures_util::StreamExt;
 use zbus::fdo::Error as ZbErr;
 use zbus::{interface, SignalContext};
 
+use super::config::{AuraRule, AuraRuleState, AuraSceneDevice};
 use super::controller::CtrlKbdLed;
 use crate::error::RogError;
 use crate::CtrlTask;
@@ -34,6 +35,44 @@ impl CtrlAuraZbus {
         lock.config.write();
         Ok(())
     }
+
+    pub(super) async fn led_prod(&self) -> AuraDevice {
+        self.0.lock().await.led_prod
+    }
+
+    /// Match the rules of this device against a new state
+    pub(super) async fn set_rule_state(&self, state: AuraRuleState) {
+        let mut ctrl = self.0.lock().await;
+        ctrl.rule_state = Some(state);
+        ctrl.apply_rules()
+            .map_err(|e| warn!("Aura rules: {e}"))
+            .ok();
+    }
+
+    /// The current lighting of this device, for a scene
+    pub(super) async fn scene_state(&self) -> AuraSceneDevice {
+        let ctrl = self.0.lock().await;
+        ctrl.config.scene_state(ctrl.led_prod)
+    }
+
+    /// Write the lighting of a scene to this device and store it as the
+    /// current config
+    pub(super) async fn apply_scene_state(&self, state: &AuraSceneDevice) -> Result<(), RogError> {
+        {
+            let mut ctrl = self.0.lock().await;
+            ctrl.config.apply_scene_state(state);
+            ctrl.write_current_config_mode()?;
+            ctrl.led_node
+                .set_brightness(ctrl.config.brightness.into())?;
+            ctrl.set_power_states().map_err(|err| warn!("{err}")).ok();
+            ctrl.config.write();
+        }
+        self.led_mode_invalidate(&self.1).await.ok();
+        self.led_mode_data_invalidate(&self.1).await.ok();
+        self.led_power_invalidate(&self.1).await.ok();
+        self.brightness_invalidate(&self.1).await.ok();
+        Ok(())
+    }
 }
 
 /// The main interface for changing, reading, or notfying
@@ -137,14 +176,7 @@ impl CtrlAuraZbus {
     #[zbus(property)]
     async fn set_led_mode_data(&mut self, effect: AuraEffect) -> Result<(), ZbErr> {
         let mut ctrl = self.0.lock().await;
-        if !ctrl.supported_data.basic_modes.contains(&effect.mode)
-            || effect.zone != AuraZone::None
-                && !ctrl.supported_data.basic_zones.contains(&effect.zone)
-        {
-            return Err(ZbErr::NotSupported(format!(
-                "The Aura effect is not supported: {effect:?}"
-            )));
-        }
+        ctrl.check_effect_supported(&effect)?;
 
         ctrl.write_mode(&effect)?;
         if ctrl.config.brightness == LedBrightness::Off {
@@ -159,6 +191,16 @@ impl CtrlAuraZbus {
         Ok(())
     }
 
+    /// Show an Aura effect without storing it, for lighting driven by a
+    /// client such as a screen or audio sync. The effect is replaced by the
+    /// next mode set or config reload, and the brightness is left as is.
+    async fn direct_effect(&self, effect: AuraEffect) -> Result<(), ZbErr> {
+        let mut ctrl = self.0.lock().await;
+        ctrl.check_effect_supported(&effect)?;
+        ctrl.write_transient_mode(&effect)?;
+        Ok(())
+    }
+
     /// Get the data set for every mode available
     async fn all_mode_data(&self) -> BTreeMap<AuraModeNum, AuraEffect> {
         let ctrl = self.0.lock().await;
@@ -194,6 +236,33 @@ impl CtrlAuraZbus {
         })?)
     }
 
+    /// The rules which change the lighting with the throttle policy, power
+    /// source, or lid
+    async fn aura_rules(&self) -> Vec<AuraRule> {
+        let ctrl = self.0.lock().await;
+        ctrl.config.rules.clone()
+    }
+
+    /// Add a rule, or replace the one with the same name. Where more than one
+    /// rule matches, the earlier rule is used for each of the effects and
+    /// brightness that it sets.
+    async fn set_aura_rule(&mut self, rule: AuraRule) -> Result<(), ZbErr> {
+        let mut ctrl = self.0.lock().await;
+        for effect in &rule.effects {
+            ctrl.check_effect_supported(effect)?;
+        }
+        ctrl.config.set_rule(rule)?;
+        ctrl.config.write();
+        Ok(ctrl.apply_rules()?)
+    }
+
+    async fn remove_aura_rule(&mut self, name: String) -> Result<(), ZbErr> {
+        let mut ctrl = self.0.lock().await;
+        ctrl.config.remove_rule(&name)?;
+        ctrl.config.write();
+        Ok(ctrl.apply_rules()?)
+    }
+
     /// On machine that have some form of either per-key keyboard or per-zone
     /// this can be used to write custom effects over dbus. The input is a
     /// nested `Vec<Vec<8>>` where `Vec<u8>` is a raw USB packet
@@ -1,12 +1,22 @@
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use config_traits::{StdConfig, StdConfigLoad};
 use futures_lite::StreamExt;
 use log::{debug, error, info, warn};
 use rog_platform::platform::{RogPlatform, ThrottlePolicy};
+use rog_platform::power::{AsusPower, PowerSource};
+use rog_platform::sysfs::sysfs_root;
 use rog_profiles::error::ProfileError;
+use rog_profiles::fan_calibration::{FanCalibration, FanCalibrator, CALIBRATION_STEPS};
+use rog_profiles::fan_curve_export::{FanCurveExport, FanCurveFormat};
+use rog_profiles::fan_curve_preset::FanCurvePreset;
+use rog_profiles::fan_curve_rules::FanCurveRules;
 use rog_profiles::fan_curve_set::CurveData;
+use rog_profiles::fan_status::{FanMonitor, FanStatus};
+use rog_profiles::software_fan::{FanSensors, SoftwareFanConfig, SoftwareFanLoop};
 use rog_profiles::{find_fan_curve_node, FanCurvePU, FanCurveProfiles};
 use serde_derive::{Deserialize, Serialize};
 use tokio::sync::Mutex;
@@ -18,11 +28,168 @@ use crate::{CtrlTask, CONFIG_PATH_BASE};
 pub const FAN_CURVE_ZBUS_NAME: &str = "FanCurves";
 pub const FAN_CURVE_ZBUS_PATH: &str = "/org/asuslinux";
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+/// Calibration stops if a fan's temperature sensor reaches this, in degrees C
+const CALIBRATION_MAX_TEMP: u8 = 85;
+/// Time for a fan to respond to a change of fan power during calibration
+const CALIBRATION_SETTLE: Duration = Duration::from_secs(2);
+
+fn default_status_interval() -> u32 {
+    1000
+}
+
+#[derive(Deserialize, Serialize, Debug)]
 pub struct FanCurveConfig {
     pub profiles: FanCurveProfiles,
+    /// Minimum fan speeds that enabled curves must meet
+    #[serde(default)]
+    pub safety_rules: FanCurveRules,
+    /// Allow enabled curves below the `safety_rules`. Curves must still be
+    /// increasing.
+    #[serde(default)]
+    pub expert_mode: bool,
+    /// Drive the fans from userspace if the firmware has no fan curves
+    #[serde(default)]
+    pub software_control: SoftwareFanConfig,
+    /// Milliseconds between checks of the fan status for the changed signal, 0
+    /// for no signal
+    #[serde(default = "default_status_interval")]
+    pub status_interval_ms: u32,
+    /// The last calibration of each fan, with the suggested minimum fan power
+    #[serde(default)]
+    pub calibration: Vec<FanCalibration>,
     #[serde(skip)]
     pub current: u8,
+    /// Which curves of each profile are used
+    #[serde(skip)]
+    pub power: PowerSource,
+    /// The fans under software control, empty if the firmware curves are used
+    #[serde(skip)]
+    pub software_fans: Vec<FanCurvePU>,
+    /// Preset curves applied under software control, until the next write of
+    /// the profile curves
+    #[serde(skip)]
+    pub applied: Option<Vec<CurveData>>,
+    /// Set while the fans are being calibrated, the curves can't be written
+    #[serde(skip)]
+    pub calibrating: bool,
+    /// Why the last calibration stopped early, empty if it didn't
+    #[serde(skip)]
+    pub calibration_error: String,
+}
+
+impl Default for FanCurveConfig {
+    fn default() -> Self {
+        Self {
+            profiles: FanCurveProfiles::default(),
+            safety_rules: FanCurveRules::default(),
+            expert_mode: false,
+            software_control: SoftwareFanConfig::default(),
+            status_interval_ms: default_status_interval(),
+            calibration: Vec::new(),
+            current: 0,
+            power: PowerSource::default(),
+            software_fans: Vec::new(),
+            applied: None,
+            calibrating: false,
+            calibration_error: String::new(),
+        }
+    }
+}
+
+impl FanCurveConfig {
+    /// Check a curve against the safety rules, unless in expert mode
+    pub fn check_curve(&self, curve: &CurveData) -> Result<(), ProfileError> {
+        self.safety_rules.check(curve, self.expert_mode)
+    }
+
+    /// Check the stored curves of a profile as they would be once enabled,
+    /// either all or only `fan`, on AC and on battery
+    fn check_enabling(
+        &self,
+        profile: ThrottlePolicy,
+        fan: Option<FanCurvePU>,
+    ) -> Result<(), ProfileError> {
+        for power in [PowerSource::Ac, PowerSource::Battery] {
+            for curve in self.profiles.curves_for(profile, power) {
+                if fan.is_none() || fan == Some(curve.fan) {
+                    let mut curve = curve.clone();
+                    curve.enabled = true;
+                    self.check_curve(&curve)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The fan curve device, for every write of curves to it. Fails while the
+    /// fans are being calibrated as a write would change the fan power being
+    /// measured, the curves in use are written once calibration ends.
+    fn curve_device(&self) -> Result<PathBuf, ProfileError> {
+        if self.calibrating {
+            return Err(ProfileError::Calibrating);
+        }
+        find_fan_curve_node()
+    }
+
+    /// Write the curves used for `profile` on the current power source. Under
+    /// software control the loop reads the stored curves on each step so
+    /// there is nothing to write.
+    pub fn write_profile_curves(&mut self, profile: ThrottlePolicy) -> Result<(), ProfileError> {
+        self.applied = None;
+        if !self.software_fans.is_empty() {
+            return Ok(());
+        }
+        let device = self.curve_device()?;
+        self.profiles
+            .write_profile_curve_to_platform(profile, self.power, &device)
+    }
+
+    /// Write the curves of a preset, active until the profile curves are next
+    /// written
+    pub fn write_preset_curves(&mut self, name: &str) -> Result<(), ProfileError> {
+        if !self.software_fans.is_empty() {
+            let preset = self
+                .profiles
+                .preset(name)
+                .ok_or_else(|| ProfileError::NotFound(format!("fan curve preset {name}")))?;
+            self.applied = Some(preset.curves.clone());
+            return Ok(());
+        }
+        let device = self.curve_device()?;
+        self.profiles.write_preset_to_platform(name, &device)
+    }
+
+    /// The curves in use for the active profile
+    fn curves_in_use(&self, active: ThrottlePolicy) -> Vec<CurveData> {
+        match &self.applied {
+            Some(curves) => curves.clone(),
+            None => self.profiles.curves_for(active, self.power).to_vec(),
+        }
+    }
+
+    /// Check the curves of an exported set against `fans` and the safety
+    /// rules, then replace the sections it has
+    pub fn import_curves(
+        &mut self,
+        export: FanCurveExport,
+        fans: &[FanCurvePU],
+    ) -> Result<(), ProfileError> {
+        for curve in export.curves() {
+            if !fans.contains(&curve.fan) {
+                return Err(ProfileError::NotFound(format!("{:?} fan", curve.fan)));
+            }
+            self.check_curve(curve)?;
+        }
+        self.profiles.import(export);
+        Ok(())
+    }
+
+    pub fn supported_fans(&self) -> Result<Vec<FanCurvePU>, ProfileError> {
+        if !self.software_fans.is_empty() {
+            return Ok(self.software_fans.clone());
+        }
+        FanCurveProfiles::supported_fans()
+    }
 }
 
 impl StdConfig for FanCurveConfig {
@@ -43,10 +210,24 @@ impl StdConfig for FanCurveConfig {
 
 impl StdConfigLoad for FanCurveConfig {}
 
+/// The running software fan loop, which hands the fans back to automatic
+/// control when dropped
+struct SoftwareFanGuard(SoftwareFanLoop);
+
+impl Drop for SoftwareFanGuard {
+    fn drop(&mut self) {
+        info!("Stopping software fan control, fans returned to automatic");
+        self.0.stop();
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CtrlFanCurveZbus {
     config: Arc<Mutex<FanCurveConfig>>,
     platform: RogPlatform,
+    /// Set if the fans are driven from userspace
+    software: Option<SoftwareFanLoop>,
+    monitor: FanMonitor,
 }
 
 // Non-zbus-derive impl
@@ -55,13 +236,37 @@ impl CtrlFanCurveZbus {
         let platform = RogPlatform::new()?;
         if platform.has_throttle_thermal_policy() {
             info!("Device has profile control available");
-            find_fan_curve_node()?;
-            info!("Device has fan curves available");
             let mut config = FanCurveConfig::new().load();
             let mut fan_curves = FanCurveProfiles::default();
 
-            // Only do defaults if the config doesn't already exist\
-            if config.profiles.balanced.is_empty() || !config.file_path().exists() {
+            let software = match find_fan_curve_node() {
+                Ok(_) => {
+                    info!("Device has fan curves available");
+                    None
+                }
+                Err(e) if config.software_control.enabled => {
+                    info!("No firmware fan curves ({e}), trying software fan control");
+                    Some(SoftwareFanLoop::find_in(sysfs_root())?)
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            if let Some(software) = &software {
+                info!("Device has software fan control available");
+                config.software_fans = software.fans().iter().map(|f| f.fan).collect();
+                if config.profiles.balanced.is_empty() || !config.file_path().exists() {
+                    for this in [
+                        ThrottlePolicy::Balanced,
+                        ThrottlePolicy::Performance,
+                        ThrottlePolicy::Quiet,
+                    ] {
+                        fan_curves.set_software_defaults(this, &config.software_fans);
+                    }
+                    config.profiles = fan_curves;
+                    config.write();
+                }
+            } else if config.profiles.balanced.is_empty() || !config.file_path().exists() {
+                // Only do defaults if the config doesn't already exist
                 info!("Fetching default fan curves");
 
                 let current = platform.get_throttle_thermal_policy()?;
@@ -74,8 +279,8 @@ impl CtrlFanCurveZbus {
                     // can read the existing values from hardware. The ACPI method used
                     // for this is what limits us.
                     platform.set_throttle_thermal_policy(this.into())?;
-                    let mut dev = find_fan_curve_node()?;
-                    fan_curves.set_active_curve_to_defaults(this, &mut dev)?;
+                    let dev = find_fan_curve_node()?;
+                    fan_curves.set_active_curve_to_defaults(this, &dev)?;
 
                     info!("{this:?}:");
                     for curve in fan_curves.get_fan_curves_for(this) {
@@ -89,15 +294,109 @@ impl CtrlFanCurveZbus {
                 info!("Fan curves previously stored, loading...");
                 config = config.load();
             }
+            if let Ok(online) = AsusPower::new().and_then(|p| p.get_online()) {
+                config.power = PowerSource::from_plugged(online > 0);
+            }
+
+            let monitor = match &software {
+                Some(software) => FanMonitor::for_software(sysfs_root(), software),
+                None => FanMonitor::find_in(sysfs_root(), &FanCurveProfiles::supported_fans()?),
+            }
+            .unwrap_or_else(|e| {
+                warn!("Fan status not available: {e}");
+                FanMonitor::default()
+            });
 
             return Ok(Self {
                 config: Arc::new(Mutex::new(config)),
                 platform,
+                software,
+                monitor,
             });
         }
 
         Err(ProfileError::NotSupported.into())
     }
+
+    async fn read_fan_status(&self) -> Vec<FanStatus> {
+        let active = self
+            .platform
+            .get_throttle_thermal_policy()
+            .map(ThrottlePolicy::from)
+            .unwrap_or_default();
+        let curves = self.config.lock().await.curves_in_use(active);
+        self.monitor.read(&curves)
+    }
+
+    /// Under software control there are no firmware defaults to read, so the
+    /// profile is given [`CurveData::software_default()`] curves instead.
+    /// Returns false if the firmware curves are used.
+    async fn set_software_defaults(&self, profile: ThrottlePolicy) -> bool {
+        let mut config = self.config.lock().await;
+        if config.software_fans.is_empty() {
+            return false;
+        }
+        let fans = config.software_fans.clone();
+        config.profiles.set_software_defaults(profile, &fans);
+        config.applied = None;
+        config.write();
+        true
+    }
+
+    /// Follow the curves of the active profile by writing the fan speeds
+    /// directly. The fans are handed back to automatic control when the task
+    /// is dropped as the daemon stops, and by a panic hook as release builds
+    /// abort on panic.
+    fn start_software_loop(&self, software: SoftwareFanLoop) {
+        let fans = software.fans().to_vec();
+        let released = Arc::new(AtomicBool::new(false));
+        let hook_released = released.clone();
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            for fan in &fans {
+                fan.set_auto().ok();
+            }
+            hook_released.store(true, Ordering::SeqCst);
+            hook(info);
+        }));
+
+        let platform = self.platform.clone();
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            let mut software = SoftwareFanGuard(software);
+            let interval = config.lock().await.software_control.interval_ms.max(100);
+            let mut interval = tokio::time::interval(Duration::from_millis(interval));
+            let mut active = ThrottlePolicy::Balanced;
+            loop {
+                interval.tick().await;
+                // A panic elsewhere, which was caught, handed the fans back
+                if released.swap(false, Ordering::SeqCst) {
+                    software.0.reset_manual();
+                }
+                if let Ok(policy) = platform.get_throttle_thermal_policy() {
+                    active = policy.into();
+                }
+                let config = config.lock().await;
+                software
+                    .0
+                    .step(&config.curves_in_use(active), &config.software_control);
+            }
+        });
+    }
+
+    /// Rewrite the curves of the active profile if `changed` returns true for
+    /// it, such as when a preset bound to it was changed
+    async fn write_active_if(
+        &self,
+        changed: impl Fn(&FanCurveProfiles, ThrottlePolicy) -> bool,
+    ) -> Result<(), RogError> {
+        let active: ThrottlePolicy = self.platform.get_throttle_thermal_policy()?.into();
+        let mut config = self.config.lock().await;
+        if changed(&config.profiles, active) {
+            config.write_profile_curves(active)?;
+        }
+        Ok(())
+    }
 }
 
 #[interface(name = "org.asuslinux.FanCurves")]
@@ -109,16 +408,15 @@ impl CtrlFanCurveZbus {
         profile: ThrottlePolicy,
         enabled: bool,
     ) -> zbus::fdo::Result<()> {
+        if enabled {
+            self.config.lock().await.check_enabling(profile, None)?;
+        }
         self.config
             .lock()
             .await
             .profiles
-            .set_profile_curves_enabled(profile, enabled);
-        self.config
-            .lock()
-            .await
-            .profiles
-            .write_profile_curve_to_platform(profile, &mut find_fan_curve_node()?)?;
+            .set_profile_curves_enabled(profile, enabled)?;
+        self.config.lock().await.write_profile_curves(profile)?;
         self.config.lock().await.write();
         Ok(())
     }
@@ -131,16 +429,18 @@ impl CtrlFanCurveZbus {
         fan: FanCurvePU,
         enabled: bool,
     ) -> zbus::fdo::Result<()> {
+        if enabled {
+            self.config
+                .lock()
+                .await
+                .check_enabling(profile, Some(fan))?;
+        }
         self.config
             .lock()
             .await
             .profiles
-            .set_profile_fan_curve_enabled(profile, fan, enabled);
-        self.config
-            .lock()
-            .await
-            .profiles
-            .write_profile_curve_to_platform(profile, &mut find_fan_curve_node()?)?;
+            .set_profile_fan_curve_enabled(profile, fan, enabled)?;
+        self.config.lock().await.write_profile_curves(profile)?;
         self.config.lock().await.write();
         Ok(())
     }
@@ -162,11 +462,16 @@ impl CtrlFanCurveZbus {
 
     /// Set the fan curve for the specified profile.
     /// Will also activate the fan curve if the user is in the same mode.
+    ///
+    /// The curve is rejected if it decreases at any point, or if enabled and
+    /// below the minimum fan speeds in `fan_curves.ron` (unless `expert_mode`
+    /// is set there).
     async fn set_fan_curve(
         &mut self,
         profile: ThrottlePolicy,
         curve: CurveData,
     ) -> zbus::fdo::Result<()> {
+        self.config.lock().await.check_curve(&curve)?;
         self.config
             .lock()
             .await
@@ -174,30 +479,191 @@ impl CtrlFanCurveZbus {
             .save_fan_curve(curve, profile)?;
         let active: ThrottlePolicy = self.platform.get_throttle_thermal_policy()?.into();
         if active == profile {
-            self.config
-                .lock()
-                .await
-                .profiles
-                .write_profile_curve_to_platform(profile, &mut find_fan_curve_node()?)?;
+            self.config.lock().await.write_profile_curves(profile)?;
         }
         self.config.lock().await.write();
         Ok(())
     }
 
+    /// Get the fan curves used for the profile on AC or on battery. A profile
+    /// with no battery curves uses the AC curves on battery.
+    async fn fan_curve_data_for_power(
+        &self,
+        profile: ThrottlePolicy,
+        power: PowerSource,
+    ) -> zbus::fdo::Result<Vec<CurveData>> {
+        Ok(self
+            .config
+            .lock()
+            .await
+            .profiles
+            .curves_for(profile, power)
+            .to_vec())
+    }
+
+    /// Set the fan curve for the profile on AC or on battery, checked as for
+    /// `set_fan_curve`. The first battery curve set for a profile starts its
+    /// battery curves from a copy of the AC curves.
+    async fn set_fan_curve_for_power(
+        &mut self,
+        profile: ThrottlePolicy,
+        power: PowerSource,
+        curve: CurveData,
+    ) -> zbus::fdo::Result<()> {
+        let mut config = self.config.lock().await;
+        config.check_curve(&curve)?;
+        config.profiles.save_fan_curve_for(curve, profile, power)?;
+        let active: ThrottlePolicy = self.platform.get_throttle_thermal_policy()?.into();
+        if active == profile {
+            config.write_profile_curves(profile)?;
+        }
+        config.write();
+        Ok(())
+    }
+
+    /// Measure the speed of each fan over a range of fan powers to find the
+    /// lowest at which it spins. This runs in the background for a few minutes,
+    /// `FanCalibrating` is true until done and the results are then in
+    /// `FanCalibration` and stored in `fan_curves.ron`. Calibration stops if a
+    /// fan's temperature reaches 85c, with the reason in
+    /// `FanCalibrationError`. Not available under software fan control.
+    async fn calibrate_fans(
+        &mut self,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> zbus::fdo::Result<()> {
+        if self.software.is_some() {
+            return Err(ProfileError::NotSupported.into());
+        }
+        let calibrator = FanCalibrator::find_in(sysfs_root())?;
+        let sensors = FanSensors::find_in(sysfs_root())?;
+        let fans = FanCurveProfiles::supported_fans()?;
+        {
+            let mut config = self.config.lock().await;
+            if config.calibrating {
+                return Err(zbus::fdo::Error::Failed(
+                    "Fan calibration is already running".to_owned(),
+                ));
+            }
+            config.calibrating = true;
+            config.calibration_error.clear();
+        }
+        self.fan_calibrating_changed(&ctxt).await.ok();
+
+        let ctrl = self.clone();
+        let ctxt = ctxt.to_owned();
+        tokio::spawn(async move {
+            let result = tokio::task::spawn_blocking(move || {
+                let mut results = Vec::new();
+                for fan in fans {
+                    info!("Calibrating the {fan:?} fan");
+                    let calibration = calibrator.calibrate(fan, &CALIBRATION_STEPS, |fan, _| {
+                        std::thread::sleep(CALIBRATION_SETTLE);
+                        let temp = sensors.read_for(fan)?;
+                        if temp >= CALIBRATION_MAX_TEMP {
+                            return Err(ProfileError::CalibrationStopped(
+                                fan,
+                                format!("the temperature reached {temp}c"),
+                            ));
+                        }
+                        Ok(())
+                    });
+                    match calibration {
+                        Ok(calibration) => {
+                            info!("{}", calibration.report());
+                            results.push(calibration);
+                        }
+                        Err(e) => return (results, Some(e)),
+                    }
+                }
+                (results, None)
+            })
+            .await;
+
+            {
+                let mut config = ctrl.config.lock().await;
+                config.calibrating = false;
+                match result {
+                    Ok((results, error)) => {
+                        for calibration in results {
+                            config.calibration.retain(|c| c.fan != calibration.fan);
+                            config.calibration.push(calibration);
+                        }
+                        if let Some(e) = error {
+                            warn!("Fan calibration: {e}");
+                            config.calibration_error = e.to_string();
+                        }
+                    }
+                    Err(e) => {
+                        error!("Fan calibration: {e}");
+                        config.calibration_error = e.to_string();
+                    }
+                }
+                config.write();
+            }
+            // Return the fans to the curves in use
+            ctrl.write_active_if(|_, _| true)
+                .await
+                .map_err(|e| error!("Fan calibration: {e}"))
+                .ok();
+            ctrl.fan_calibration_changed(&ctxt).await.ok();
+            ctrl.fan_calibration_error_changed(&ctxt).await.ok();
+            ctrl.fan_calibrating_changed(&ctxt).await.ok();
+        });
+        Ok(())
+    }
+
+    /// True while the fans are being calibrated
+    #[zbus(property)]
+    async fn fan_calibrating(&self) -> bool {
+        self.config.lock().await.calibrating
+    }
+
+    /// The last calibration of each fan
+    #[zbus(property)]
+    async fn fan_calibration(&self) -> Vec<FanCalibration> {
+        self.config.lock().await.calibration.clone()
+    }
+
+    /// Why the last calibration stopped early, empty if it didn't
+    #[zbus(property)]
+    async fn fan_calibration_error(&self) -> String {
+        self.config.lock().await.calibration_error.clone()
+    }
+
+    /// Remove the battery curves of the profile so the AC curves are used on
+    /// battery too
+    async fn clear_battery_fan_curves(&mut self, profile: ThrottlePolicy) -> zbus::fdo::Result<()> {
+        let mut config = self.config.lock().await;
+        if config.profiles.clear_battery_curves(profile) {
+            let active: ThrottlePolicy = self.platform.get_throttle_thermal_policy()?.into();
+            if active == profile {
+                config.write_profile_curves(profile)?;
+            }
+            config.write();
+        }
+        Ok(())
+    }
+
     /// Reset the stored (self) and device curves to the defaults of the
     /// platform.
     ///
     /// Each platform_profile has a different default and the default can be
     /// read only for the currently active profile.
     async fn set_curves_to_defaults(&mut self, profile: ThrottlePolicy) -> zbus::fdo::Result<()> {
+        if self.set_software_defaults(profile).await {
+            return Ok(());
+        }
+        let device = self.config.lock().await.curve_device()?;
         let active = self.platform.get_throttle_thermal_policy()?;
         self.platform.set_throttle_thermal_policy(profile.into())?;
-        self.config
+        let reset = self
+            .config
             .lock()
             .await
             .profiles
-            .set_active_curve_to_defaults(profile, &mut find_fan_curve_node()?)?;
+            .set_active_curve_to_defaults(profile, &device);
         self.platform.set_throttle_thermal_policy(active)?;
+        reset?;
         self.config.lock().await.write();
         Ok(())
     }
@@ -208,19 +674,179 @@ impl CtrlFanCurveZbus {
     /// Each platform_profile has a different default and the defualt can be
     /// read only for the currently active profile.
     async fn reset_profile_curves(&self, profile: ThrottlePolicy) -> zbus::fdo::Result<()> {
+        if self.set_software_defaults(profile).await {
+            return Ok(());
+        }
+        let device = self.config.lock().await.curve_device()?;
         let active = self.platform.get_throttle_thermal_policy()?;
 
         self.platform.set_throttle_thermal_policy(profile.into())?;
-        self.config
+        let reset = self
+            .config
             .lock()
             .await
             .profiles
-            .set_active_curve_to_defaults(active.into(), &mut find_fan_curve_node()?)?;
+            .set_active_curve_to_defaults(active.into(), &device);
         self.platform.set_throttle_thermal_policy(active)?;
+        reset?;
 
         self.config.lock().await.write();
         Ok(())
     }
+
+    /// The speed and power of each fan, and the temperature its curve follows
+    #[zbus(property)]
+    async fn fan_status(&self) -> Vec<FanStatus> {
+        self.read_fan_status().await
+    }
+
+    /// Milliseconds between checks of the fan status for the changed signal
+    #[zbus(property)]
+    async fn fan_status_interval(&self) -> u32 {
+        self.config.lock().await.status_interval_ms
+    }
+
+    /// Set the milliseconds between checks of the fan status for the changed
+    /// signal, 0 for no signal
+    #[zbus(property)]
+    async fn set_fan_status_interval(&mut self, interval: u32) {
+        let mut config = self.config.lock().await;
+        config.status_interval_ms = interval;
+        config.write();
+    }
+
+    /// Get all named fan curve presets
+    async fn fan_curve_presets(&self) -> Vec<FanCurvePreset> {
+        self.config.lock().await.profiles.presets.clone()
+    }
+
+    /// Create a named preset from a copy of the curves used by the profile
+    async fn create_fan_curve_preset(
+        &mut self,
+        name: String,
+        profile: ThrottlePolicy,
+    ) -> zbus::fdo::Result<()> {
+        let mut config = self.config.lock().await;
+        config.profiles.create_preset(&name, profile)?;
+        config.write();
+        Ok(())
+    }
+
+    /// Set the curve of one fan in a preset. The curve is checked the same
+    /// as in `set_fan_curve`. Will also activate the curve if the preset is
+    /// bound to the active profile.
+    async fn set_preset_fan_curve(
+        &mut self,
+        name: String,
+        curve: CurveData,
+    ) -> zbus::fdo::Result<()> {
+        {
+            let mut config = self.config.lock().await;
+            config.check_curve(&curve)?;
+            config.profiles.save_preset_fan_curve(&name, curve)?;
+            config.write();
+        }
+        self.write_active_if(|p, active| {
+            p.preset(&name)
+                .is_some_and(|p| p.policies.contains(&active))
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn rename_fan_curve_preset(
+        &mut self,
+        name: String,
+        new_name: String,
+    ) -> zbus::fdo::Result<()> {
+        let mut config = self.config.lock().await;
+        config.profiles.rename_preset(&name, &new_name)?;
+        config.write();
+        Ok(())
+    }
+
+    /// Delete a preset. Profiles it was bound to return to their own curves.
+    async fn delete_fan_curve_preset(&mut self, name: String) -> zbus::fdo::Result<()> {
+        let preset = {
+            let mut config = self.config.lock().await;
+            let preset = config.profiles.delete_preset(&name)?;
+            config.write();
+            preset
+        };
+        self.write_active_if(|_, active| preset.policies.contains(&active))
+            .await?;
+        Ok(())
+    }
+
+    /// Write the curves of a preset now. They stay active until the profile
+    /// changes.
+    async fn apply_fan_curve_preset(&self, name: String) -> zbus::fdo::Result<()> {
+        let mut config = self.config.lock().await;
+        if let Some(preset) = config.profiles.preset(&name) {
+            for curve in &preset.curves {
+                config.check_curve(curve)?;
+            }
+        }
+        config.write_preset_curves(&name)?;
+        Ok(())
+    }
+
+    /// Use a preset in place of the curves of the profile
+    async fn bind_fan_curve_preset(
+        &mut self,
+        name: String,
+        profile: ThrottlePolicy,
+    ) -> zbus::fdo::Result<()> {
+        {
+            let mut config = self.config.lock().await;
+            if let Some(preset) = config.profiles.preset(&name) {
+                for curve in &preset.curves {
+                    config.check_curve(curve)?;
+                }
+            }
+            config.profiles.bind_preset(&name, profile)?;
+            config.write();
+        }
+        self.write_active_if(|_, active| active == profile).await?;
+        Ok(())
+    }
+
+    /// Return the profile to its own curves
+    async fn unbind_fan_curve_preset(&mut self, profile: ThrottlePolicy) -> zbus::fdo::Result<()> {
+        let unbound = {
+            let mut config = self.config.lock().await;
+            let unbound = config.profiles.unbind_preset(profile);
+            config.write();
+            unbound
+        };
+        self.write_active_if(|_, active| unbound && active == profile)
+            .await?;
+        Ok(())
+    }
+
+    /// Export all fan curves and presets in a versioned JSON or TOML form
+    async fn export_fan_curves(&self, format: FanCurveFormat) -> zbus::fdo::Result<String> {
+        Ok(self.config.lock().await.profiles.export(format)?)
+    }
+
+    /// Replace the fan curves and presets in an exported set, keeping those
+    /// left out of it. Every curve must be for a fan this laptop has, and is
+    /// checked the same as in `set_fan_curve`.
+    async fn import_fan_curves(
+        &mut self,
+        data: String,
+        format: FanCurveFormat,
+    ) -> zbus::fdo::Result<()> {
+        let export = FanCurveExport::parse(&data, format)?;
+        {
+            let mut config = self.config.lock().await;
+            let fans = config.supported_fans()?;
+            config.import_curves(export, &fans)?;
+            config.write();
+        }
+        self.write_active_if(|_, _| true).await?;
+        Ok(())
+    }
 }
 
 impl crate::ZbusRun for CtrlFanCurveZbus {
@@ -234,7 +860,7 @@ impl CtrlTask for CtrlFanCurveZbus {
         FAN_CURVE_ZBUS_PATH
     }
 
-    async fn create_tasks(&self, _signal_ctxt: SignalContext<'static>) -> Result<(), RogError> {
+    async fn create_tasks(&self, signal_ctxt: SignalContext<'static>) -> Result<(), RogError> {
         let watch_throttle_thermal_policy = self.platform.monitor_throttle_thermal_policy()?;
         let platform = self.platform.clone();
         let config = self.config.clone();
@@ -252,12 +878,8 @@ impl CtrlTask for CtrlFanCurveZbus {
                             fan_curves
                                 .lock()
                                 .await
-                                .profiles
-                                .write_profile_curve_to_platform(
-                                    profile.into(),
-                                    &mut find_fan_curve_node().unwrap(),
-                                )
-                                .map_err(|e| warn!("write_profile_curve_to_platform, {}", e))
+                                .write_profile_curves(profile.into())
+                                .map_err(|e| warn!("write_profile_curves, {}", e))
                                 .ok();
                             config.lock().await.current = profile;
                         }
@@ -266,6 +888,50 @@ impl CtrlTask for CtrlFanCurveZbus {
             }
         });
 
+        let ctrl = self.clone();
+        self.create_sys_event_tasks(
+            move |_sleeping| async move {},
+            move |_shutting_down| async move {},
+            move |_lid_closed| async move {},
+            move |power_plugged| {
+                let ctrl = ctrl.clone();
+                // Use the curves for the new power source
+                async move {
+                    let power = PowerSource::from_plugged(power_plugged);
+                    ctrl.config.lock().await.power = power;
+                    debug!("Power source changed to {power:?}, writing fan curves");
+                    ctrl.write_active_if(|profiles, active| profiles.has_battery_curves(active))
+                        .await
+                        .map_err(|e| warn!("write_profile_curves, {}", e))
+                        .ok();
+                }
+            },
+        )
+        .await;
+
+        if let Some(software) = self.software.clone() {
+            self.start_software_loop(software);
+        }
+
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            let mut last = Vec::new();
+            loop {
+                let interval = ctrl.config.lock().await.status_interval_ms;
+                if interval == 0 {
+                    // Check again later in case the signal is turned on
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+                tokio::time::sleep(Duration::from_millis(interval as u64)).await;
+                let status = ctrl.read_fan_status().await;
+                if status != last {
+                    ctrl.fan_status_changed(&signal_ctxt).await.ok();
+                    last = status;
+                }
+            }
+        });
+
         Ok(())
     }
 }
@@ -274,15 +940,13 @@ impl crate::Reloadable for CtrlFanCurveZbus {
     /// Fetch the active profile and use that to set all related components up
     async fn reload(&mut self) -> Result<(), RogError> {
         let active = self.platform.get_throttle_thermal_policy()?.into();
-        if let Ok(mut device) = find_fan_curve_node() {
+        if find_fan_curve_node().is_ok() {
             // There is a possibility that the curve was default zeroed, so this call
             // initialises the data from system read and we need to save it
             // after
             loop {
                 if let Ok(mut config) = self.config.try_lock() {
-                    config
-                        .profiles
-                        .write_profile_curve_to_platform(active, &mut device)?;
+                    config.write_profile_curves(active)?;
                     break;
                 }
             }
@@ -290,3 +954,80 @@ impl crate::Reloadable for CtrlFanCurveZbus {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rog_platform::platform::ThrottlePolicy;
+    use rog_profiles::error::ProfileError;
+    use rog_profiles::fan_curve_export::{FanCurveExport, FanCurveFormat};
+    use rog_profiles::fan_curve_set::CurveData;
+    use rog_profiles::FanCurvePU;
+
+    use super::FanCurveConfig;
+
+    fn curve(fan: FanCurvePU, pwm: u8) -> CurveData {
+        CurveData {
+            fan,
+            pwm: [pwm; 8],
+            temp: [30, 40, 50, 60, 70, 80, 90, 100],
+            enabled: false,
+        }
+    }
+
+    #[test]
+    fn no_writes_while_calibrating() {
+        let mut config = FanCurveConfig {
+            calibrating: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.write_profile_curves(ThrottlePolicy::Balanced),
+            Err(ProfileError::Calibrating)
+        ));
+        assert!(matches!(
+            config.write_preset_curves("quiet"),
+            Err(ProfileError::Calibrating)
+        ));
+        assert!(matches!(
+            config.curve_device(),
+            Err(ProfileError::Calibrating)
+        ));
+    }
+
+    #[test]
+    fn partial_import_keeps_other_sections() {
+        let mut config = FanCurveConfig::default();
+        config.profiles.balanced = vec![curve(FanCurvePU::CPU, 10)];
+        config.profiles.performance = vec![curve(FanCurvePU::CPU, 20)];
+        config.profiles.quiet_battery = vec![curve(FanCurvePU::CPU, 30)];
+        config
+            .profiles
+            .create_preset("Render", rog_platform::platform::ThrottlePolicy::Balanced)
+            .unwrap();
+        let fans = [FanCurvePU::CPU, FanCurvePU::GPU];
+
+        let data = r#"
+version = 1
+
+[[quiet]]
+fan = "GPU"
+pwm = [0, 0, 0, 10, 20, 30, 40, 50]
+temp = [30, 40, 50, 60, 70, 80, 90, 100]
+enabled = false
+"#;
+        let export = FanCurveExport::parse(data, FanCurveFormat::Toml).unwrap();
+        config.import_curves(export, &fans).unwrap();
+        assert_eq!(config.profiles.quiet[0].fan, FanCurvePU::GPU);
+        assert_eq!(config.profiles.balanced, [curve(FanCurvePU::CPU, 10)]);
+        assert_eq!(config.profiles.performance, [curve(FanCurvePU::CPU, 20)]);
+        assert_eq!(config.profiles.quiet_battery, [curve(FanCurvePU::CPU, 30)]);
+        assert_eq!(config.profiles.presets.len(), 1);
+
+        // A curve for a fan the laptop doesn't have is refused and nothing
+        // is changed
+        let data = r#"{"version": 1, "balanced": [], "performance": [{"fan": "MID", "pwm": [0, 0, 0, 10, 20, 30, 40, 50], "temp": [30, 40, 50, 60, 70, 80, 90, 100], "enabled": false}]}"#;
+        let export = FanCurveExport::parse(data, FanCurveFormat::Json).unwrap();
+        assert!(config.import_curves(export, &fans).is_err());
+        assert_eq!(config.profiles.balanced, [curve(FanCurvePU::CPU, 10)]);
+    }
+}
@@ -1,25 +1,32 @@
 use std::path::Path;
-use std::process::Command;
 use std::sync::Arc;
+use std::time::Duration;
 
 use config_traits::StdConfig;
+use cpuctl::{CPUControl, CPUGovernor, CpuSettings, CPUEPP};
 use log::{debug, error, info, warn};
-use rog_platform::cpu::{CPUControl, CPUGovernor, CPUEPP};
+use rog_platform::error::Result as PlatformResult;
 use rog_platform::platform::{GpuMode, Properties, RogPlatform, ThrottlePolicy};
-use rog_platform::power::AsusPower;
+use rog_platform::power::{AsusPower, BatteryInfo, BatteryStatus};
 use zbus::export::futures_util::lock::Mutex;
 use zbus::fdo::Error as FdoErr;
 use zbus::{interface, Connection, ObjectServer, SignalContext};
 
+use crate::charge_limit::{self, ChargeLimitSource, ChargeOverride, ChargeSchedule, ChargeTime};
 use crate::config::Config;
 use crate::ctrl_anime::trait_impls::{CtrlAnimeZbus, ANIME_ZBUS_NAME, ANIME_ZBUS_PATH};
 use crate::ctrl_aura::trait_impls::{CtrlAuraZbus, AURA_ZBUS_NAME, AURA_ZBUS_PATH};
 use crate::ctrl_fancurves::{CtrlFanCurveZbus, FAN_CURVE_ZBUS_NAME, FAN_CURVE_ZBUS_PATH};
 use crate::error::RogError;
+use crate::hooks::{self, Hook, HookEvent, HookTrigger, SleepInhibitor};
 use crate::{task_watch_item, task_watch_item_notify, CtrlTask, ReloadAndNotify};
 
 const PLATFORM_ZBUS_NAME: &str = "Platform";
 const PLATFORM_ZBUS_PATH: &str = "/org/asuslinux";
+/// How often charge limit schedules and expiry are checked
+const CHARGE_LIMIT_INTERVAL: Duration = Duration::from_secs(60);
+/// How often the battery is checked for changes to signal
+const BATTERY_INFO_INTERVAL: Duration = Duration::from_secs(5);
 
 macro_rules! platform_get_value {
     ($self:ident, $property:tt, $prop_name:literal) => {
@@ -72,8 +79,9 @@ macro_rules! platform_ppt_set_value {
                         FdoErr::NotSupported(format!("RogPlatform: {} {err}", $prop_name))
                     })?;
                 });
+                let policy = $self.current_throttle_policy();
                 let mut lock = $self.config.lock().await;
-                lock.$property = Some($new_value);
+                lock.tunings_for_throttle_mut(policy).$property = Some($new_value);
                 lock.write();
                 Ok(())
             } else {
@@ -90,6 +98,8 @@ pub struct CtrlPlatform {
     platform: RogPlatform,
     cpu_control: Option<CPUControl>,
     config: Arc<Mutex<Config>>,
+    /// Where the last charge limit written by asusd came from
+    charge_limit_source: Arc<Mutex<ChargeLimitSource>>,
 }
 
 impl CtrlPlatform {
@@ -116,6 +126,7 @@ impl CtrlPlatform {
             cpu_control: CPUControl::new()
                 .map_err(|e| error!("Couldn't get CPU control sysfs: {e}"))
                 .ok(),
+            charge_limit_source: Arc::new(Mutex::new(ChargeLimitSource::Saved)),
         };
         let mut inotify_self = ret_self.clone();
 
@@ -183,39 +194,138 @@ impl CtrlPlatform {
         Ok(())
     }
 
-    async fn run_ac_or_bat_cmd(&self, power_plugged: bool) {
-        let prog: Vec<String> = if power_plugged {
-            // AC ONLINE
-            self.config
-                .lock()
-                .await
-                .ac_command
-                .split_whitespace()
-                .map(|s| s.to_string())
-                .collect()
-        } else {
-            // BATTERY
-            self.config
-                .lock()
-                .await
-                .bat_command
-                .split_whitespace()
-                .map(|s| s.to_string())
-                .collect()
-        };
-        if prog.len() > 1 {
-            let mut cmd = Command::new(&prog[0]);
-            for arg in prog.iter().skip(1) {
-                cmd.arg(arg);
+    /// Write the charge limit selected for now if where it comes from has
+    /// changed since it was last written, or always if `force`. An expired
+    /// override is removed. Returns true if the limit was written.
+    async fn apply_charge_limit(&self, force: bool) -> Result<bool, RogError> {
+        if !self.power.has_charge_control_end_threshold() {
+            return Ok(false);
+        }
+        let now = ChargeTime::now();
+        let (limit, source) = {
+            let mut lock = self.config.lock().await;
+            if lock.charge_override.is_some_and(|o| o.is_expired(now.unix)) {
+                info!("Charge to full has expired");
+                lock.charge_override = None;
+                lock.write();
             }
-            if let Err(e) = cmd.spawn() {
-                if power_plugged {
-                    error!("AC power command error: {e}");
-                } else {
-                    error!("Battery power command error: {e}");
-                }
+            charge_limit::select_limit(
+                lock.charge_control_end_threshold,
+                lock.charge_override.as_ref(),
+                &lock.charge_schedules,
+                &now,
+            )
+        };
+
+        let mut last_source = self.charge_limit_source.lock().await;
+        if !force && *last_source == source {
+            return Ok(false);
+        }
+        info!("Setting charge limit to {limit} from {source:?}");
+        self.power.set_charge_control_end_threshold(limit)?;
+        *last_source = source;
+        Ok(true)
+    }
+
+    /// A charge to full ends on the next unplug. Returns true if one was
+    /// active.
+    async fn end_charge_override(&self) -> bool {
+        let mut lock = self.config.lock().await;
+        if lock.charge_override.take().is_some() {
+            info!("Unplugged, ending charge to full");
+            lock.write();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// As `task_watch_item!` but the limit is only saved if not set by an
+    /// override or schedule
+    async fn watch_charge_control_end_threshold(
+        &self,
+        signal_ctxt: SignalContext<'static>,
+    ) -> Result<(), RogError> {
+        use zbus::export::futures_util::StreamExt;
+
+        let ctrl = self.clone();
+        match self.power.monitor_charge_control_end_threshold() {
+            Ok(watch) => {
+                tokio::spawn(async move {
+                    let mut buffer = [0; 32];
+                    watch
+                        .into_event_stream(&mut buffer)
+                        .unwrap()
+                        .for_each(|_| async {
+                            if let Ok(value) = ctrl.charge_control_end_threshold() {
+                                ctrl.charge_control_end_threshold_changed(&signal_ctxt)
+                                    .await
+                                    .ok();
+                                if *ctrl.charge_limit_source.lock().await
+                                    == ChargeLimitSource::Saved
+                                {
+                                    let mut lock = ctrl.config.lock().await;
+                                    lock.charge_control_end_threshold = value;
+                                    lock.write();
+                                }
+                            }
+                        })
+                        .await;
+                });
             }
+            Err(e) => info!(
+                "inotify watch failed: {}. You can ignore this if your device does not support \
+                 the feature",
+                e
+            ),
         }
+        Ok(())
+    }
+
+    /// Emit a changed signal for each battery property that differs
+    async fn notify_battery_info(
+        &self,
+        old: &BatteryInfo,
+        new: &BatteryInfo,
+        signal_ctxt: &SignalContext<'static>,
+    ) {
+        if old.capacity != new.capacity {
+            self.battery_capacity_changed(signal_ctxt).await.ok();
+        }
+        if old.status != new.status {
+            self.battery_status_changed(signal_ctxt).await.ok();
+        }
+        if old.energy_full != new.energy_full {
+            self.battery_energy_full_changed(signal_ctxt).await.ok();
+            self.battery_wear_changed(signal_ctxt).await.ok();
+        }
+        if old.energy_full_design != new.energy_full_design {
+            self.battery_energy_full_design_changed(signal_ctxt)
+                .await
+                .ok();
+        }
+        if old.cycle_count != new.cycle_count {
+            self.battery_cycle_count_changed(signal_ctxt).await.ok();
+        }
+        if old.power_now != new.power_now {
+            self.battery_power_now_changed(signal_ctxt).await.ok();
+        }
+        if old.time_to_empty() != new.time_to_empty() {
+            self.battery_time_to_empty_changed(signal_ctxt).await.ok();
+        }
+        if old.time_to_full() != new.time_to_full() {
+            self.battery_time_to_full_changed(signal_ctxt).await.ok();
+        }
+    }
+
+    async fn run_hooks(&self, event: HookEvent) {
+        let hooks = self.config.lock().await.hooks.clone();
+        hooks::run_hooks(&hooks, event);
+    }
+
+    async fn run_hooks_and_wait(&self, event: HookEvent) {
+        let hooks = self.config.lock().await.hooks.clone();
+        hooks::run_hooks_and_wait(&hooks, event).await;
     }
 
     fn check_and_set_epp(&self, enegy_pref: CPUEPP, change_epp: bool) {
@@ -247,6 +357,119 @@ impl CtrlPlatform {
         }
     }
 
+    /// The active throttle policy, or the default if it is not supported
+    fn current_throttle_policy(&self) -> ThrottlePolicy {
+        self.platform
+            .get_throttle_thermal_policy()
+            .map(ThrottlePolicy::from)
+            .unwrap_or_default()
+    }
+
+    /// Write the PPT, Nvidia, and CPU tunings stored for this throttle policy.
+    /// Must be done *after* the policy is set as the firmware may change them.
+    async fn apply_tunings_for_throttle(&self, throttle: ThrottlePolicy) {
+        let (tunings, linked_cpu, cpu_settings) = {
+            let lock = self.config.lock().await;
+            (
+                *lock.tunings_for_throttle(throttle),
+                lock.throttle_policy_linked_cpu,
+                lock.cpu_for_throttle(throttle),
+            )
+        };
+        debug!("Applying {throttle:?} tunings: {tunings:?}");
+
+        macro_rules! apply_tuning {
+            ($property:tt, $prop_name:literal) => {
+                concat_idents::concat_idents!(has = has_, $property {
+                    if let Some(value) = tunings.$property {
+                        if self.platform.has() {
+                            concat_idents::concat_idents!(set = set_, $property {
+                            self.platform
+                                .set(value)
+                                .map_err(|err| warn!("RogPlatform: {} {err}", $prop_name))
+                                .ok();});
+                        }
+                    }
+                })
+            }
+        }
+        apply_tuning!(ppt_pl1_spl, "ppt_pl1_spl");
+        apply_tuning!(ppt_pl2_sppt, "ppt_pl2_sppt");
+        apply_tuning!(ppt_fppt, "ppt_fppt");
+        apply_tuning!(ppt_apu_sppt, "ppt_apu_sppt");
+        apply_tuning!(ppt_platform_sppt, "ppt_platform_sppt");
+        apply_tuning!(nv_dynamic_boost, "nv_dynamic_boost");
+        apply_tuning!(nv_temp_target, "nv_temp_target");
+
+        if let Some(cpu) = self.cpu_control.as_ref() {
+            if linked_cpu {
+                cpu.apply(&cpu_settings).ok();
+            }
+        }
+    }
+
+    fn cpu_control(&self) -> Result<&CPUControl, FdoErr> {
+        self.cpu_control.as_ref().ok_or_else(|| {
+            FdoErr::NotSupported("RogPlatform: CPU control not supported".to_owned())
+        })
+    }
+
+    /// Store a changed CPU setting for the active throttle policy if linked,
+    /// otherwise in the global CPU settings. `current` is the value before
+    /// the change.
+    async fn store_cpu_setting<T>(
+        &self,
+        field: impl Fn(&mut CpuSettings) -> &mut Option<T>,
+        value: T,
+        current: Option<T>,
+    ) {
+        let policy = self.current_throttle_policy();
+        let mut lock = self.config.lock().await;
+        if lock.throttle_policy_linked_cpu {
+            lock.set_cpu_for_throttle(policy, field, value, current);
+        } else {
+            *field(&mut lock.cpu) = Some(value);
+        }
+        lock.write();
+    }
+
+    /// A CPU setting stored for this throttle policy, or the global one if it
+    /// has none, or the current value if neither is set
+    async fn throttle_cpu_setting<T>(
+        &self,
+        policy: ThrottlePolicy,
+        field: impl Fn(&CpuSettings) -> Option<T>,
+        get: impl Fn(&CPUControl) -> PlatformResult<T>,
+    ) -> Result<T, FdoErr> {
+        let cpu = self.cpu_control()?;
+        let lock = self.config.lock().await;
+        let stored = lock.tunings_for_throttle(policy).cpu.or(&lock.cpu);
+        match field(&stored) {
+            Some(value) => Ok(value),
+            None => Ok(get(cpu)?),
+        }
+    }
+
+    /// Store a CPU setting for this throttle policy, it is applied now only if
+    /// linked and the policy is active
+    async fn set_throttle_cpu_setting<T>(
+        &self,
+        policy: ThrottlePolicy,
+        field: impl Fn(&mut CpuSettings) -> &mut Option<T>,
+        value: T,
+        get: impl Fn(&CPUControl) -> PlatformResult<T>,
+    ) -> Result<(), FdoErr> {
+        let cpu = self.cpu_control()?;
+        let current = get(cpu).ok();
+        let mut lock = self.config.lock().await;
+        lock.set_cpu_for_throttle(policy, field, value, current);
+        lock.write();
+        if lock.throttle_policy_linked_cpu && policy == self.current_throttle_policy() {
+            cpu.apply(&lock.cpu_for_throttle(policy))?;
+        }
+        Ok(())
+    }
+
     async fn update_policy_ac_or_bat(&self, power_plugged: bool, change_epp: bool) {
         let throttle = if power_plugged {
             self.config.lock().await.throttle_policy_on_ac
@@ -259,6 +482,7 @@ impl CtrlPlatform {
             .set_throttle_thermal_policy(throttle.into())
             .ok();
         self.check_and_set_epp(epp, change_epp);
+        self.apply_tunings_for_throttle(throttle).await;
     }
 }
 
@@ -314,6 +538,21 @@ impl CtrlPlatform {
         platform_name!(nv_dynamic_boost, Properties::NvDynamicBoost);
         platform_name!(nv_temp_target, Properties::NvTempTarget);
 
+        if let Some(cpu) = self.cpu_control.as_ref() {
+            if cpu.has_boost() {
+                supported.push(Properties::CpuBoost);
+            }
+            if cpu.get_max_freq().is_ok() {
+                supported.push(Properties::CpuMaxFreq);
+            }
+            if cpu.has_smt() {
+                supported.push(Properties::Smt);
+            }
+        }
+        if self.power.get_battery_info().is_ok() {
+            supported.push(Properties::BatteryInfo);
+        }
+
         supported
     }
 
@@ -353,6 +592,30 @@ impl CtrlPlatform {
         interfaces
     }
 
+    /// Returns all hooks configured in asusd.ron
+    async fn hooks(&self) -> Vec<Hook> {
+        self.config.lock().await.hooks.clone()
+    }
+
+    /// Run the named hook now, with `ASUSD_HOOK_TEST=1` set. Returns the exit
+    /// code (or -1 if killed by a signal) and stderr
+    async fn test_hook(&self, name: String) -> Result<(i32, String), FdoErr> {
+        let hook = self
+            .config
+            .lock()
+            .await
+            .hooks
+            .iter()
+            .find(|h| h.name == name)
+            .cloned()
+            .ok_or_else(|| FdoErr::InvalidArgs(format!("No hook named {name}")))?;
+        let event = HookEvent::new(hook.trigger).with("ASUSD_HOOK_TEST", 1);
+        let output = tokio::task::spawn_blocking(move || hook.run_and_log(&event))
+            .await
+            .map_err(|e| FdoErr::Failed(format!("Hook task failed: {e}")))??;
+        Ok((output.code.unwrap_or(-1), output.stderr))
+    }
+
     #[zbus(property)]
     fn charge_control_end_threshold(&self) -> Result<u8, FdoErr> {
         let limit = self.power.get_charge_control_end_threshold()?;
@@ -364,12 +627,170 @@ impl CtrlPlatform {
         if !(20..=100).contains(&limit) {
             return Err(RogError::ChargeLimit(limit))?;
         }
-        self.power.set_charge_control_end_threshold(limit)?;
-        self.config.lock().await.charge_control_end_threshold = limit;
-        self.config.lock().await.write();
+        {
+            let mut lock = self.config.lock().await;
+            lock.charge_control_end_threshold = limit;
+            // Setting a limit ends a charge to full, but an active schedule
+            // stays in force and the new limit is used once it ends
+            lock.charge_override = None;
+            lock.write();
+        }
+        self.apply_charge_limit(true).await?;
+        Ok(())
+    }
+
+    /// True if a one-shot charge to full is in use instead of the usual limit
+    #[zbus(property)]
+    async fn charge_to_full_active(&self) -> bool {
+        *self.charge_limit_source.lock().await == ChargeLimitSource::Override
+    }
+
+    /// Charge to 100% once, returning to the usual limit on the next unplug,
+    /// or after `hours` if not `0`
+    async fn charge_to_full_once(
+        &mut self,
+        hours: u32,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> Result<(), FdoErr> {
+        if !self.power.has_charge_control_end_threshold() {
+            return Err(FdoErr::NotSupported(
+                "RogPlatform: charge_control_end_threshold not supported".to_owned(),
+            ));
+        }
+        {
+            let mut lock = self.config.lock().await;
+            lock.charge_override = Some(ChargeOverride::new(hours, ChargeTime::now().unix));
+            lock.write();
+        }
+        self.apply_charge_limit(true).await?;
+        self.charge_to_full_active_changed(&ctxt).await.ok();
         Ok(())
     }
 
+    /// End a charge to full now and return to the usual limit
+    async fn cancel_charge_to_full(
+        &mut self,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> Result<(), FdoErr> {
+        {
+            let mut lock = self.config.lock().await;
+            lock.charge_override = None;
+            lock.write();
+        }
+        self.apply_charge_limit(false).await?;
+        self.charge_to_full_active_changed(&ctxt).await.ok();
+        Ok(())
+    }
+
+    /// Returns all charge limit schedules, the first active one is used
+    async fn charge_schedules(&self) -> Vec<ChargeSchedule> {
+        self.config.lock().await.charge_schedules.clone()
+    }
+
+    /// Add a charge limit schedule, or replace the one with the same name
+    async fn set_charge_schedule(&mut self, schedule: ChargeSchedule) -> Result<(), FdoErr> {
+        if !(20..=100).contains(&schedule.limit) {
+            return Err(RogError::ChargeLimit(schedule.limit))?;
+        }
+        if schedule.start.hour > 23
+            || schedule.start.minute > 59
+            || schedule.end.hour > 23
+            || schedule.end.minute > 59
+        {
+            return Err(RogError::ChargeSchedule(format!(
+                "invalid time {}-{}",
+                schedule.start, schedule.end
+            )))?;
+        }
+        {
+            let mut lock = self.config.lock().await;
+            if let Some(old) = lock
+                .charge_schedules
+                .iter_mut()
+                .find(|s| s.name == schedule.name)
+            {
+                *old = schedule;
+            } else {
+                lock.charge_schedules.push(schedule);
+            }
+            lock.write();
+        }
+        self.apply_charge_limit(true).await?;
+        Ok(())
+    }
+
+    /// Remove the named charge limit schedule
+    async fn remove_charge_schedule(&mut self, name: String) -> Result<(), FdoErr> {
+        {
+            let mut lock = self.config.lock().await;
+            let len = lock.charge_schedules.len();
+            lock.charge_schedules.retain(|s| s.name != name);
+            if lock.charge_schedules.len() == len {
+                return Err(FdoErr::InvalidArgs(format!(
+                    "No charge schedule named {name}"
+                )));
+            }
+            lock.write();
+        }
+        self.apply_charge_limit(true).await?;
+        Ok(())
+    }
+
+    /// Battery percent charged
+    #[zbus(property)]
+    fn battery_capacity(&self) -> Result<u8, FdoErr> {
+        Ok(self.power.get_battery_info()?.capacity)
+    }
+
+    #[zbus(property)]
+    fn battery_status(&self) -> Result<BatteryStatus, FdoErr> {
+        Ok(self.power.get_battery_info()?.status)
+    }
+
+    /// Battery capacity when full in mWh
+    #[zbus(property)]
+    fn battery_energy_full(&self) -> Result<u32, FdoErr> {
+        Ok(self.power.get_battery_info()?.energy_full)
+    }
+
+    /// Battery capacity when new in mWh
+    #[zbus(property)]
+    fn battery_energy_full_design(&self) -> Result<u32, FdoErr> {
+        Ok(self.power.get_battery_info()?.energy_full_design)
+    }
+
+    /// Percentage of the design capacity lost
+    #[zbus(property)]
+    fn battery_wear(&self) -> Result<f64, FdoErr> {
+        Ok(self.power.get_battery_info()?.wear())
+    }
+
+    /// Charge cycles, `0` if not reported
+    #[zbus(property)]
+    fn battery_cycle_count(&self) -> Result<u32, FdoErr> {
+        Ok(self.power.get_battery_info()?.cycle_count)
+    }
+
+    /// Battery charge or discharge rate in mW
+    #[zbus(property)]
+    fn battery_power_now(&self) -> Result<u32, FdoErr> {
+        Ok(self.power.get_battery_info()?.power_now)
+    }
+
+    /// Estimated seconds until empty, `0` if not discharging
+    #[zbus(property)]
+    fn battery_time_to_empty(&self) -> Result<u32, FdoErr> {
+        let info = self.power.get_battery_info()?;
+        Ok(info.time_to_empty().unwrap_or_default().as_secs() as u32)
+    }
+
+    /// Estimated seconds until full, `0` if not charging
+    #[zbus(property)]
+    fn battery_time_to_full(&self) -> Result<u32, FdoErr> {
+        let info = self.power.get_battery_info()?;
+        Ok(info.time_to_full().unwrap_or_default().as_secs() as u32)
+    }
+
     #[zbus(property)]
     fn gpu_mux_mode(&self) -> Result<u8, FdoErr> {
         self.platform.get_gpu_mux_mode().map_err(|err| {
@@ -381,11 +802,16 @@ impl CtrlPlatform {
     #[zbus(property)]
     async fn set_gpu_mux_mode(&mut self, mode: u8) -> Result<(), FdoErr> {
         if self.platform.has_gpu_mux_mode() {
-            self.set_gfx_mode(mode.into()).map_err(|err| {
+            let mode: GpuMode = mode.into();
+            self.set_gfx_mode(mode).map_err(|err| {
                 warn!("set_gpu_mux_mode {}", err);
                 FdoErr::Failed(format!("RogPlatform: set_gpu_mux_mode: {err}"))
             })?;
             self.config.lock().await.write();
+            self.run_hooks(
+                HookEvent::new(HookTrigger::GpuMux).with("ASUSD_GPU_MUX_MODE", format!("{mode:?}")),
+            )
+            .await;
         } else {
             return Err(FdoErr::NotSupported(
                 "RogPlatform: set_gpu_mux_mode not supported".to_owned(),
@@ -415,6 +841,7 @@ impl CtrlPlatform {
                     warn!("throttle_thermal_policy {}", err);
                     FdoErr::Failed(format!("RogPlatform: throttle_thermal_policy: {err}"))
                 })?;
+            self.apply_tunings_for_throttle(policy).await;
             Ok(self.throttle_thermal_policy_changed(&ctxt).await?)
         } else {
             Err(FdoErr::NotSupported(
@@ -442,7 +869,9 @@ impl CtrlPlatform {
                 .map_err(|err| {
                     warn!("throttle_thermal_policy {}", err);
                     FdoErr::Failed(format!("RogPlatform: throttle_thermal_policy: {err}"))
-                })
+                })?;
+            self.apply_tunings_for_throttle(policy).await;
+            Ok(())
         } else {
             Err(FdoErr::NotSupported(
                 "RogPlatform: throttle_thermal_policy not supported".to_owned(),
@@ -539,6 +968,266 @@ impl CtrlPlatform {
         Ok(())
     }
 
+    /// If CPU boost, max frequency, and SMT are stored and applied per
+    /// throttle/platform profile
+    #[zbus(property)]
+    async fn throttle_policy_linked_cpu(&self) -> Result<bool, FdoErr> {
+        Ok(self.config.lock().await.throttle_policy_linked_cpu)
+    }
+
+    #[zbus(property)]
+    async fn set_throttle_policy_linked_cpu(&self, linked: bool) -> Result<(), zbus::Error> {
+        self.config.lock().await.throttle_policy_linked_cpu = linked;
+        self.config.lock().await.write();
+        Ok(())
+    }
+
+    /// CPU boost (AMD) or turbo (Intel) allowed
+    #[zbus(property)]
+    fn cpu_boost(&self) -> Result<bool, FdoErr> {
+        Ok(self.cpu_control()?.get_boost()?)
+    }
+
+    #[zbus(property)]
+    async fn set_cpu_boost(&mut self, on: bool) -> Result<(), FdoErr> {
+        let cpu = self.cpu_control()?;
+        let current = cpu.get_boost().ok();
+        cpu.set_boost(on)?;
+        self.store_cpu_setting(|cpu| &mut cpu.boost, on, current)
+            .await;
+        Ok(())
+    }
+
+    /// The max scaling frequency of all online cores in kHz
+    #[zbus(property)]
+    fn cpu_max_freq(&self) -> Result<u32, FdoErr> {
+        Ok(self.cpu_control()?.get_max_freq()?)
+    }
+
+    #[zbus(property)]
+    async fn set_cpu_max_freq(&mut self, khz: u32) -> Result<(), FdoErr> {
+        let cpu = self.cpu_control()?;
+        let current = cpu.get_max_freq().ok();
+        cpu.set_max_freq(khz)?;
+        self.store_cpu_setting(|cpu| &mut cpu.max_freq, khz, current)
+            .await;
+        Ok(())
+    }
+
+    /// Simultaneous multithreading on or off
+    #[zbus(property)]
+    fn smt(&self) -> Result<bool, FdoErr> {
+        Ok(self.cpu_control()?.get_smt()?)
+    }
+
+    #[zbus(property)]
+    async fn set_smt(&mut self, on: bool) -> Result<(), FdoErr> {
+        let cpu = self.cpu_control()?;
+        let current = cpu.get_smt().ok();
+        cpu.set_smt(on)?;
+        self.store_cpu_setting(|cpu| &mut cpu.smt, on, current)
+            .await;
+        Ok(())
+    }
+
+    /// CPU boost (AMD) or turbo (Intel) allowed for the quiet throttle/platform
+    /// profile
+    #[zbus(property)]
+    async fn throttle_quiet_cpu_boost(&self) -> Result<bool, FdoErr> {
+        self.throttle_cpu_setting(
+            ThrottlePolicy::Quiet,
+            |cpu| cpu.boost,
+            CPUControl::get_boost,
+        )
+        .await
+    }
+
+    #[zbus(property)]
+    async fn set_throttle_quiet_cpu_boost(&mut self, on: bool) -> Result<(), FdoErr> {
+        self.set_throttle_cpu_setting(
+            ThrottlePolicy::Quiet,
+            |cpu| &mut cpu.boost,
+            on,
+            CPUControl::get_boost,
+        )
+        .await
+    }
+
+    /// The max scaling frequency in kHz for the quiet throttle/platform profile
+    #[zbus(property)]
+    async fn throttle_quiet_cpu_max_freq(&self) -> Result<u32, FdoErr> {
+        self.throttle_cpu_setting(
+            ThrottlePolicy::Quiet,
+            |cpu| cpu.max_freq,
+            CPUControl::get_max_freq,
+        )
+        .await
+    }
+
+    #[zbus(property)]
+    async fn set_throttle_quiet_cpu_max_freq(&mut self, khz: u32) -> Result<(), FdoErr> {
+        self.set_throttle_cpu_setting(
+            ThrottlePolicy::Quiet,
+            |cpu| &mut cpu.max_freq,
+            khz,
+            CPUControl::get_max_freq,
+        )
+        .await
+    }
+
+    /// Simultaneous multithreading on or off for the quiet throttle/platform
+    /// profile
+    #[zbus(property)]
+    async fn throttle_quiet_smt(&self) -> Result<bool, FdoErr> {
+        self.throttle_cpu_setting(ThrottlePolicy::Quiet, |cpu| cpu.smt, CPUControl::get_smt)
+            .await
+    }
+
+    #[zbus(property)]
+    async fn set_throttle_quiet_smt(&mut self, on: bool) -> Result<(), FdoErr> {
+        self.set_throttle_cpu_setting(
+            ThrottlePolicy::Quiet,
+            |cpu| &mut cpu.smt,
+            on,
+            CPUControl::get_smt,
+        )
+        .await
+    }
+
+    /// CPU boost (AMD) or turbo (Intel) allowed for the balanced
+    /// throttle/platform profile
+    #[zbus(property)]
+    async fn throttle_balanced_cpu_boost(&self) -> Result<bool, FdoErr> {
+        self.throttle_cpu_setting(
+            ThrottlePolicy::Balanced,
+            |cpu| cpu.boost,
+            CPUControl::get_boost,
+        )
+        .await
+    }
+
+    #[zbus(property)]
+    async fn set_throttle_balanced_cpu_boost(&mut self, on: bool) -> Result<(), FdoErr> {
+        self.set_throttle_cpu_setting(
+            ThrottlePolicy::Balanced,
+            |cpu| &mut cpu.boost,
+            on,
+            CPUControl::get_boost,
+        )
+        .await
+    }
+
+    /// The max scaling frequency in kHz for the balanced throttle/platform
+    /// profile
+    #[zbus(property)]
+    async fn throttle_balanced_cpu_max_freq(&self) -> Result<u32, FdoErr> {
+        self.throttle_cpu_setting(
+            ThrottlePolicy::Balanced,
+            |cpu| cpu.max_freq,
+            CPUControl::get_max_freq,
+        )
+        .await
+    }
+
+    #[zbus(property)]
+    async fn set_throttle_balanced_cpu_max_freq(&mut self, khz: u32) -> Result<(), FdoErr> {
+        self.set_throttle_cpu_setting(
+            ThrottlePolicy::Balanced,
+            |cpu| &mut cpu.max_freq,
+            khz,
+            CPUControl::get_max_freq,
+        )
+        .await
+    }
+
+    /// Simultaneous multithreading on or off for the balanced throttle/platform
+    /// profile
+    #[zbus(property)]
+    async fn throttle_balanced_smt(&self) -> Result<bool, FdoErr> {
+        self.throttle_cpu_setting(ThrottlePolicy::Balanced, |cpu| cpu.smt, CPUControl::get_smt)
+            .await
+    }
+
+    #[zbus(property)]
+    async fn set_throttle_balanced_smt(&mut self, on: bool) -> Result<(), FdoErr> {
+        self.set_throttle_cpu_setting(
+            ThrottlePolicy::Balanced,
+            |cpu| &mut cpu.smt,
+            on,
+            CPUControl::get_smt,
+        )
+        .await
+    }
+
+    /// CPU boost (AMD) or turbo (Intel) allowed for the performance
+    /// throttle/platform profile
+    #[zbus(property)]
+    async fn throttle_performance_cpu_boost(&self) -> Result<bool, FdoErr> {
+        self.throttle_cpu_setting(
+            ThrottlePolicy::Performance,
+            |cpu| cpu.boost,
+            CPUControl::get_boost,
+        )
+        .await
+    }
+
+    #[zbus(property)]
+    async fn set_throttle_performance_cpu_boost(&mut self, on: bool) -> Result<(), FdoErr> {
+        self.set_throttle_cpu_setting(
+            ThrottlePolicy::Performance,
+            |cpu| &mut cpu.boost,
+            on,
+            CPUControl::get_boost,
+        )
+        .await
+    }
+
+    /// The max scaling frequency in kHz for the performance throttle/platform
+    /// profile
+    #[zbus(property)]
+    async fn throttle_performance_cpu_max_freq(&self) -> Result<u32, FdoErr> {
+        self.throttle_cpu_setting(
+            ThrottlePolicy::Performance,
+            |cpu| cpu.max_freq,
+            CPUControl::get_max_freq,
+        )
+        .await
+    }
+
+    #[zbus(property)]
+    async fn set_throttle_performance_cpu_max_freq(&mut self, khz: u32) -> Result<(), FdoErr> {
+        self.set_throttle_cpu_setting(
+            ThrottlePolicy::Performance,
+            |cpu| &mut cpu.max_freq,
+            khz,
+            CPUControl::get_max_freq,
+        )
+        .await
+    }
+
+    /// Simultaneous multithreading on or off for the performance
+    /// throttle/platform profile
+    #[zbus(property)]
+    async fn throttle_performance_smt(&self) -> Result<bool, FdoErr> {
+        self.throttle_cpu_setting(
+            ThrottlePolicy::Performance,
+            |cpu| cpu.smt,
+            CPUControl::get_smt,
+        )
+        .await
+    }
+
+    #[zbus(property)]
+    async fn set_throttle_performance_smt(&mut self, on: bool) -> Result<(), FdoErr> {
+        self.set_throttle_cpu_setting(
+            ThrottlePolicy::Performance,
+            |cpu| &mut cpu.smt,
+            on,
+            CPUControl::get_smt,
+        )
+        .await
+    }
+
     /// Get the `panel_od` value from platform. Updates the stored value in
     /// internal config also.
     #[zbus(property)]
@@ -712,10 +1401,19 @@ impl ReloadAndNotify for CtrlPlatform {
             info!("asusd.ron updated externally, reloading and updating internal copy");
 
             if self.power.has_charge_control_end_threshold() {
-                self.power
-                    .set_charge_control_end_threshold(data.charge_control_end_threshold)?;
+                let (limit, source) = charge_limit::select_limit(
+                    data.charge_control_end_threshold,
+                    data.charge_override.as_ref(),
+                    &data.charge_schedules,
+                    &ChargeTime::now(),
+                );
+                self.power.set_charge_control_end_threshold(limit)?;
+                *self.charge_limit_source.lock().await = source;
                 self.charge_control_end_threshold_changed(signal_context)
                     .await?;
+                if config.charge_override != data.charge_override {
+                    self.charge_to_full_active_changed(signal_context).await?;
+                }
             }
 
             if self.platform.has_throttle_thermal_policy()
@@ -742,15 +1440,20 @@ impl ReloadAndNotify for CtrlPlatform {
             reload_and_notify!(boot_sound, "boot_sound");
             // reload_and_notify!(throttle_thermal_policy, "throttle_thermal_policy");
 
+            // Only the tunings of the active policy are live
+            let policy = self.current_throttle_policy();
+            let old_tunings = *config.tunings_for_throttle(policy);
+            let new_tunings = *data.tunings_for_throttle(policy);
             macro_rules! ppt_reload_and_notify {
                 ($property:tt, $prop_name:literal) => {
                     concat_idents::concat_idents!(has = has_, $property {
-                        if self.platform.has() && config.$property != data.$property {
-                            concat_idents::concat_idents!(set = set_, $property {
-                            self.platform
-                                .set(data.$property.unwrap_or_default())?;});
-                            concat_idents::concat_idents!(changed = $property, _changed {
-                            self.changed(signal_context).await?;});
+                        if self.platform.has() && old_tunings.$property != new_tunings.$property {
+                            if let Some(value) = new_tunings.$property {
+                                concat_idents::concat_idents!(set = set_, $property {
+                                self.platform.set(value)?;});
+                                concat_idents::concat_idents!(changed = $property, _changed {
+                                self.changed(signal_context).await?;});
+                            }
                         }
                     })
                 }
@@ -763,6 +1466,23 @@ impl ReloadAndNotify for CtrlPlatform {
             ppt_reload_and_notify!(nv_dynamic_boost, "nv_dynamic_boost");
             ppt_reload_and_notify!(nv_temp_target, "nv_temp_target");
 
+            if let Some(cpu) = self.cpu_control.as_ref() {
+                let old_cpu = config.cpu_for_throttle(policy);
+                let new_cpu = data.cpu_for_throttle(policy);
+                if old_cpu != new_cpu {
+                    cpu.apply(&new_cpu).ok();
+                    if cpu.has_boost() {
+                        self.cpu_boost_changed(signal_context).await?;
+                    }
+                    if cpu.get_max_freq().is_ok() {
+                        self.cpu_max_freq_changed(signal_context).await?;
+                    }
+                    if cpu.has_smt() {
+                        self.smt_changed(signal_context).await?;
+                    }
+                }
+            }
+
             *config = data;
         }
 
@@ -788,30 +1508,17 @@ impl crate::Reloadable for CtrlPlatform {
         reload!(panel_od, "panel_od");
         reload!(boot_sound, "boot_sound");
 
-        macro_rules! ppt_reload {
-            ($property:tt, $prop_name:literal) => {
-                concat_idents::concat_idents!(has = has_, $property {
-                    if self.platform.has() {
-                        concat_idents::concat_idents!(set = set_, $property {
-                        self.platform
-                            .set(self.config.lock().await.$property.unwrap_or_default())?;});
-                    }
-                })
+        // If linked the CPU settings are applied with the throttle tunings
+        if let Some(cpu) = self.cpu_control.as_ref() {
+            let config = self.config.lock().await;
+            if !config.throttle_policy_linked_cpu {
+                cpu.apply(&config.cpu).ok();
             }
         }
-        ppt_reload!(ppt_pl1_spl, "ppt_pl1_spl");
-        ppt_reload!(ppt_pl2_sppt, "ppt_pl2_sppt");
-        ppt_reload!(ppt_fppt, "ppt_fppt");
-        ppt_reload!(ppt_apu_sppt, "ppt_apu_sppt");
-        ppt_reload!(ppt_platform_sppt, "ppt_platform_sppt");
-        ppt_reload!(nv_dynamic_boost, "nv_dynamic_boost");
-        ppt_reload!(nv_temp_target, "nv_temp_target");
+        self.apply_tunings_for_throttle(self.current_throttle_policy())
+            .await;
 
-        if self.power.has_charge_control_end_threshold() {
-            self.power.set_charge_control_end_threshold(
-                self.config.lock().await.charge_control_end_threshold,
-            )?;
-        }
+        self.apply_charge_limit(true).await?;
 
         if let Ok(power_plugged) = self.power.get_online() {
             self.config.lock().await.last_power_plugged = power_plugged;
@@ -820,7 +1527,8 @@ impl crate::Reloadable for CtrlPlatform {
                 self.update_policy_ac_or_bat(power_plugged > 0, change_epp)
                     .await;
             }
-            self.run_ac_or_bat_cmd(power_plugged > 0).await;
+            self.run_hooks(HookEvent::power_plugged(power_plugged > 0))
+                .await;
         }
 
         Ok(())
@@ -832,8 +1540,6 @@ impl CtrlPlatform {
 
     task_watch_item!(mini_led_mode platform);
 
-    task_watch_item!(charge_control_end_threshold power);
-
     task_watch_item_notify!(boot_sound platform);
 
     task_watch_item_notify!(dgpu_disable platform);
@@ -867,10 +1573,30 @@ impl CtrlTask for CtrlPlatform {
         let platform1 = self.clone();
         let platform2 = self.clone();
         let platform3 = self.clone();
+        let platform4 = self.clone();
+        let signal_ctxt1 = signal_ctxt.clone();
+        let signal_ctxt3 = signal_ctxt.clone();
+        // Sleep is delayed until the suspend hooks and the rest of the sleep
+        // handling are done
+        let inhibitor = Arc::new(Mutex::new(SleepInhibitor::default()));
+        inhibitor.lock().await.take(signal_ctxt.connection()).await;
         self.create_sys_event_tasks(
             move |sleeping| {
                 let platform1 = platform1.clone();
+                let signal_ctxt1 = signal_ctxt1.clone();
+                let inhibitor = inhibitor.clone();
                 async move {
+                    if sleeping {
+                        platform1
+                            .run_hooks_and_wait(HookEvent::new(HookTrigger::Suspend))
+                            .await;
+                    } else {
+                        inhibitor.lock().await.take(signal_ctxt1.connection()).await;
+                        platform1
+                            .run_hooks(HookEvent::new(HookTrigger::Resume))
+                            .await;
+                    }
+
                     info!("RogPlatform reloading panel_od");
                     if !sleeping && platform1.platform.has_panel_od() {
                         platform1
@@ -882,21 +1608,22 @@ impl CtrlTask for CtrlPlatform {
                             })
                             .ok();
                     }
-                    if sleeping && platform1.power.has_charge_control_end_threshold() {
+                    let saved_limit_in_use =
+                        *platform1.charge_limit_source.lock().await == ChargeLimitSource::Saved;
+                    if sleeping
+                        && saved_limit_in_use
+                        && platform1.power.has_charge_control_end_threshold()
+                    {
                         platform1.config.lock().await.charge_control_end_threshold = platform1
                             .power
                             .get_charge_control_end_threshold()
                             .unwrap_or(100);
-                    } else if !sleeping && platform1.power.has_charge_control_end_threshold() {
-                        platform1
-                            .power
-                            .set_charge_control_end_threshold(
-                                platform1.config.lock().await.charge_control_end_threshold,
-                            )
-                            .ok();
                     }
                     if let Ok(power_plugged) = platform1.power.get_online() {
                         if platform1.config.lock().await.last_power_plugged != power_plugged {
+                            if !sleeping && power_plugged == 0 {
+                                platform1.end_charge_override().await;
+                            }
                             if !sleeping && platform1.platform.has_throttle_thermal_policy() {
                                 let change_epp =
                                     platform1.config.lock().await.throttle_policy_linked_epp;
@@ -905,11 +1632,23 @@ impl CtrlTask for CtrlPlatform {
                                     .await;
                             }
                             if !sleeping {
-                                platform1.run_ac_or_bat_cmd(power_plugged > 0).await;
+                                platform1
+                                    .run_hooks(HookEvent::power_plugged(power_plugged > 0))
+                                    .await;
                             }
                             platform1.config.lock().await.last_power_plugged = power_plugged;
                         }
                     }
+                    if !sleeping {
+                        // Also picks up a schedule or expiry passed while asleep
+                        platform1.apply_charge_limit(true).await.ok();
+                        platform1
+                            .charge_to_full_active_changed(&signal_ctxt1)
+                            .await
+                            .ok();
+                    } else {
+                        inhibitor.lock().await.release();
+                    }
                 }
             },
             move |shutting_down| {
@@ -929,21 +1668,34 @@ impl CtrlTask for CtrlPlatform {
                     }
                 }
             },
-            move |_lid_closed| {
+            move |lid_closed| {
+                let platform4 = platform4.clone();
                 // on lid change
-                async move {}
+                async move {
+                    platform4.run_hooks(HookEvent::lid_closed(lid_closed)).await;
+                }
             },
             move |power_plugged| {
                 let platform3 = platform3.clone();
+                let signal_ctxt3 = signal_ctxt3.clone();
                 // power change
                 async move {
+                    if !power_plugged && platform3.end_charge_override().await {
+                        platform3.apply_charge_limit(false).await.ok();
+                        platform3
+                            .charge_to_full_active_changed(&signal_ctxt3)
+                            .await
+                            .ok();
+                    }
                     if platform3.platform.has_throttle_thermal_policy() {
                         let change_epp = platform3.config.lock().await.throttle_policy_linked_epp;
                         platform3
                             .update_policy_ac_or_bat(power_plugged, change_epp)
                             .await;
                     }
-                    platform3.run_ac_or_bat_cmd(power_plugged).await;
+                    platform3
+                        .run_hooks(HookEvent::power_plugged(power_plugged))
+                        .await;
                 }
             },
         )
@@ -972,6 +1724,39 @@ impl CtrlTask for CtrlPlatform {
         self.watch_nv_dynamic_boost(signal_ctxt.clone()).await?;
         self.watch_nv_temp_target(signal_ctxt.clone()).await?;
 
+        let ctrl = self.clone();
+        let signal_ctxt2 = signal_ctxt.clone();
+        tokio::spawn(async move {
+            // Check for a schedule starting or ending, or a charge to full expiring
+            let mut interval = tokio::time::interval(CHARGE_LIMIT_INTERVAL);
+            loop {
+                interval.tick().await;
+                match ctrl.apply_charge_limit(false).await {
+                    Ok(true) => {
+                        ctrl.charge_to_full_active_changed(&signal_ctxt2).await.ok();
+                    }
+                    Ok(false) => {}
+                    Err(e) => warn!("Could not set charge limit: {e}"),
+                }
+            }
+        });
+
+        if let Ok(mut last) = self.power.get_battery_info() {
+            let ctrl = self.clone();
+            let signal_ctxt3 = signal_ctxt.clone();
+            tokio::spawn(async move {
+                // The power_supply attributes don't notify, so poll them
+                let mut interval = tokio::time::interval(BATTERY_INFO_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Ok(info) = ctrl.power.get_battery_info() {
+                        ctrl.notify_battery_info(&last, &info, &signal_ctxt3).await;
+                        last = info;
+                    }
+                }
+            });
+        }
+
         let watch_throttle_thermal_policy = self.platform.monitor_throttle_thermal_policy()?;
         let ctrl = self.clone();
 
@@ -993,6 +1778,12 @@ impl CtrlTask for CtrlPlatform {
                         let change_epp = ctrl.config.lock().await.throttle_policy_linked_epp;
                         let epp = ctrl.get_config_epp_for_throttle(profile).await;
                         ctrl.check_and_set_epp(epp, change_epp);
+                        ctrl.apply_tunings_for_throttle(profile).await;
+                        ctrl.run_hooks(
+                            HookEvent::new(HookTrigger::ThrottlePolicy)
+                                .with("ASUSD_THROTTLE_POLICY", format!("{profile:?}")),
+                        )
+                        .await;
                     }
                 }
             }
@@ -12,8 +12,9 @@ use asusd::ctrl_aura::manager::AuraManager;
 use asusd::ctrl_fancurves::CtrlFanCurveZbus;
 use asusd::ctrl_platform::CtrlPlatform;
 use asusd::{print_board_info, start_tasks, CtrlTask, DBUS_NAME};
-use config_traits::{StdConfig, StdConfigLoad2, StdConfigLoad3};
+use config_traits::{StdConfig, StdConfigLoad2, StdConfigLoad4};
 use log::{error, info};
+use tokio::signal::unix::{signal, SignalKind};
 use zbus::fdo::ObjectManager;
 
 #[tokio::main]
@@ -110,8 +111,18 @@ async fn start_daemon() -> Result<(), Box<dyn Error>> {
     // Request dbus name after finishing initalizing all functions
     connection.request_name(DBUS_NAME).await?;
 
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
     loop {
         // This is just a blocker to idle and ensure the reator reacts
-        connection.executor().tick().await;
+        tokio::select! {
+            _ = connection.executor().tick() => {}
+            _ = sigterm.recv() => break,
+            _ = sigint.recv() => break,
+        }
     }
+    // Returning drops the controller tasks with the runtime, letting them
+    // clean up such as handing the fans back to automatic control
+    info!("asusd is stopping");
+    Ok(())
 }
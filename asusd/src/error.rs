@@ -27,7 +27,10 @@ pub enum RogError {
     Io(std::io::Error),
     Zbus(zbus::Error),
     ChargeLimit(u8),
+    ChargeSchedule(String),
     AuraEffectNotSupported,
+    AuraScene(String),
+    AuraRule(String),
     NoAuraKeyboard,
     NoAuraNode,
     Anime(AnimeError),
@@ -36,6 +39,7 @@ pub enum RogError {
     SystemdUnitWaitTimeout(String),
     Command(String, std::io::Error),
     ParseRon(ron::Error),
+    Hook(String, String),
 }
 
 impl fmt::Display for RogError {
@@ -68,7 +72,10 @@ impl fmt::Display for RogError {
             RogError::ChargeLimit(value) => {
                 write!(f, "Invalid charging limit, not in range 20-100%: {}", value)
             }
+            RogError::ChargeSchedule(deets) => write!(f, "Invalid charge schedule: {}", deets),
             RogError::AuraEffectNotSupported => write!(f, "Aura effect not supported"),
+            RogError::AuraScene(deets) => write!(f, "Invalid Aura scene: {}", deets),
+            RogError::AuraRule(deets) => write!(f, "Invalid Aura rule: {}", deets),
             RogError::NoAuraKeyboard => write!(f, "No supported Aura keyboard"),
             RogError::NoAuraNode => write!(f, "No Aura keyboard node found"),
             RogError::Anime(deets) => write!(f, "AniMe Matrix error: {}", deets),
@@ -85,6 +92,7 @@ impl fmt::Display for RogError {
             }
             RogError::Command(func, error) => write!(f, "Command exec error: {}: {}", func, error),
             RogError::ParseRon(error) => write!(f, "Parse config error: {}", error),
+            RogError::Hook(name, error) => write!(f, "Hook {}: {}", name, error),
         }
     }
 }
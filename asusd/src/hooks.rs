@@ -0,0 +1,386 @@
+//! User defined commands which are run when a system event happens, such as
+//! the power source changing or the laptop resuming.
+//!
+//! Each hook is run directly (not through a shell) with the event details
+//! passed in environment variables:
+//!
+//! - `ASUSD_HOOK_NAME`: the name of the hook
+//! - `ASUSD_HOOK_TRIGGER`: the [`HookTrigger`] that caused the run
+//! - `ASUSD_POWER_PLUGGED`: `1` or `0`, for `AcPlugged` and `Battery`
+//! - `ASUSD_LID_CLOSED`: `1` or `0`, for `LidClosed` and `LidOpened`
+//! - `ASUSD_THROTTLE_POLICY`: the new policy, for `ThrottlePolicy`
+//! - `ASUSD_GPU_MUX_MODE`: the requested mode, for `GpuMux`
+//! - `ASUSD_HOOK_TEST`: `1` if run with the `TestHook` dbus method
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use serde_derive::{Deserialize, Serialize};
+use zbus::zvariant::{OwnedFd, Type};
+use zbus::Connection;
+
+use crate::error::RogError;
+
+const WAIT_POLL: Duration = Duration::from_millis(50);
+/// How long to wait for the rest of stderr after a hook without a timeout
+/// exits
+const STDERR_WAIT: Duration = Duration::from_secs(1);
+
+/// The system events a hook can be run on
+#[derive(Deserialize, Serialize, Type, Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
+#[zvariant(signature = "s")]
+pub enum HookTrigger {
+    AcPlugged,
+    Battery,
+    Suspend,
+    Resume,
+    LidClosed,
+    LidOpened,
+    /// Any change of throttle/platform profile, including by the firmware
+    ThrottlePolicy,
+    /// A GPU MUX mode was requested, this takes effect after reboot
+    GpuMux,
+}
+
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq, PartialOrd)]
+pub struct Hook {
+    /// Used to identify the hook in logs and to test it over dbus
+    pub name: String,
+    pub trigger: HookTrigger,
+    /// The program followed by its arguments
+    pub argv: Vec<String>,
+    /// Extra environment variables, these are added to the event details
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Kill the hook if still running after this many seconds, `0` to never
+    /// kill
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u32,
+}
+
+fn default_timeout_secs() -> u32 {
+    30
+}
+
+/// The result of a completed hook run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookOutput {
+    /// `None` if killed by a signal
+    pub code: Option<i32>,
+    pub stderr: String,
+}
+
+/// A system event plus the details which are passed to hooks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookEvent {
+    pub trigger: HookTrigger,
+    env: Vec<(String, String)>,
+}
+
+impl HookEvent {
+    pub fn new(trigger: HookTrigger) -> Self {
+        Self {
+            trigger,
+            env: Vec::new(),
+        }
+    }
+
+    pub fn power_plugged(plugged: bool) -> Self {
+        let trigger = if plugged {
+            HookTrigger::AcPlugged
+        } else {
+            HookTrigger::Battery
+        };
+        Self::new(trigger).with("ASUSD_POWER_PLUGGED", plugged as u8)
+    }
+
+    pub fn lid_closed(closed: bool) -> Self {
+        let trigger = if closed {
+            HookTrigger::LidClosed
+        } else {
+            HookTrigger::LidOpened
+        };
+        Self::new(trigger).with("ASUSD_LID_CLOSED", closed as u8)
+    }
+
+    /// Add a detail to pass to the hook
+    pub fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.env.push((key.to_owned(), value.to_string()));
+        self
+    }
+}
+
+impl Hook {
+    /// Create from an old style `ac_command` or `bat_command`, these were
+    /// split on whitespace
+    pub fn from_command(name: &str, trigger: HookTrigger, command: &str) -> Option<Self> {
+        let argv: Vec<String> = command.split_whitespace().map(|s| s.to_owned()).collect();
+        if argv.is_empty() {
+            return None;
+        }
+        Some(Self {
+            name: name.to_owned(),
+            trigger,
+            argv,
+            env: BTreeMap::new(),
+            timeout_secs: default_timeout_secs(),
+        })
+    }
+
+    /// Run the hook and wait for it to exit. This blocks so should be used in
+    /// a thread or `spawn_blocking`.
+    pub fn run(&self, event: &HookEvent) -> Result<HookOutput, RogError> {
+        let Some((program, args)) = self.argv.split_first() else {
+            return Err(RogError::Hook(
+                self.name.clone(),
+                "argv is empty".to_owned(),
+            ));
+        };
+
+        let mut child = Command::new(program)
+            .args(args)
+            .envs(&self.env)
+            .env("ASUSD_HOOK_NAME", &self.name)
+            .env("ASUSD_HOOK_TRIGGER", format!("{:?}", event.trigger))
+            .envs(event.env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| RogError::Command(program.clone(), e))?;
+
+        // Read in another thread so that a full pipe can't block the hook
+        let pipe = child.stderr.take();
+        let (send_line, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            let Some(pipe) = pipe else {
+                return;
+            };
+            let mut pipe = BufReader::new(pipe);
+            loop {
+                let mut line = String::new();
+                match pipe.read_line(&mut line) {
+                    Ok(0) | Err(_) => return,
+                    Ok(_) => {
+                        if send_line.send(line).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+
+        let start = Instant::now();
+        let timeout = Duration::from_secs(self.timeout_secs as u64);
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if self.timeout_secs != 0 && start.elapsed() >= timeout {
+                child.kill().ok();
+                child.wait().ok();
+                return Err(RogError::Hook(
+                    self.name.clone(),
+                    format!("killed after {}s timeout", self.timeout_secs),
+                ));
+            }
+            std::thread::sleep(WAIT_POLL);
+        };
+
+        // A process left running in the background by the hook keeps stderr
+        // open, so it is only read until the timeout and then left to the
+        // reader thread
+        let deadline = if self.timeout_secs == 0 {
+            Instant::now() + STDERR_WAIT
+        } else {
+            start + timeout
+        };
+        let mut stderr = String::new();
+        loop {
+            match lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) => stderr.push_str(&line),
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    debug!("Hook {} exited with stderr still open", self.name);
+                    break;
+                }
+            }
+        }
+
+        Ok(HookOutput {
+            code: status.code(),
+            stderr,
+        })
+    }
+
+    /// As [`Hook::run()`], logging the result
+    pub fn run_and_log(&self, event: &HookEvent) -> Result<HookOutput, RogError> {
+        debug!("Running hook {}: {:?}", self.name, self.argv);
+        match self.run(event) {
+            Ok(output) => {
+                if output.code == Some(0) {
+                    info!("Hook {} exited successfully", self.name);
+                } else {
+                    warn!("Hook {} failed with code {:?}", self.name, output.code);
+                }
+                for line in output.stderr.lines() {
+                    warn!("Hook {} stderr: {line}", self.name);
+                }
+                Ok(output)
+            }
+            Err(e) => {
+                warn!("{e}");
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Run every hook for this event, without waiting for them to complete
+pub fn run_hooks(hooks: &[Hook], event: HookEvent) {
+    for hook in hooks.iter().filter(|h| h.trigger == event.trigger) {
+        let hook = hook.clone();
+        let event = event.clone();
+        tokio::task::spawn_blocking(move || hook.run_and_log(&event).ok());
+    }
+}
+
+/// Run every hook for this event and wait for all of them to exit
+pub async fn run_hooks_and_wait(hooks: &[Hook], event: HookEvent) {
+    let runs: Vec<_> = hooks
+        .iter()
+        .filter(|h| h.trigger == event.trigger)
+        .map(|hook| {
+            let hook = hook.clone();
+            let event = event.clone();
+            tokio::task::spawn_blocking(move || hook.run_and_log(&event).ok())
+        })
+        .collect();
+    for run in runs {
+        run.await.ok();
+    }
+}
+
+/// A logind delay lock on sleep. While it is held logind waits, up to its
+/// `InhibitDelayMaxSec`, before suspending so that the suspend hooks can
+/// finish. It must be taken again after each resume.
+#[derive(Debug, Default)]
+pub struct SleepInhibitor {
+    lock: Option<OwnedFd>,
+}
+
+impl SleepInhibitor {
+    /// Take the lock if it is not held. If logind refuses, the suspend hooks
+    /// still run but the laptop may sleep before they finish.
+    pub async fn take(&mut self, conn: &Connection) {
+        if self.lock.is_some() {
+            return;
+        }
+        let lock = async {
+            let proxy = zbus::Proxy::new(
+                conn,
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1",
+                "org.freedesktop.login1.Manager",
+            )
+            .await?;
+            proxy
+                .call::<_, _, OwnedFd>("Inhibit", &("sleep", "asusd", "Run suspend hooks", "delay"))
+                .await
+        };
+        match lock.await {
+            Ok(lock) => self.lock = Some(lock),
+            Err(e) => warn!("Could not delay sleep for the suspend hooks: {e}"),
+        }
+    }
+
+    /// Let the laptop sleep
+    pub fn release(&mut self) {
+        if self.lock.take().is_some() {
+            debug!("Released the sleep delay lock");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::{Duration, Instant};
+
+    use super::{run_hooks_and_wait, Hook, HookEvent, HookTrigger};
+    use crate::error::RogError;
+
+    fn hook(script: &str, timeout_secs: u32) -> Hook {
+        Hook {
+            name: "test".to_owned(),
+            trigger: HookTrigger::AcPlugged,
+            argv: vec!["sh".to_owned(), "-c".to_owned(), script.to_owned()],
+            env: BTreeMap::from([("EXTRA".to_owned(), "extra value".to_owned())]),
+            timeout_secs,
+        }
+    }
+
+    #[test]
+    fn hook_gets_event_env() {
+        let hook = hook(
+            r#"echo "$ASUSD_HOOK_TRIGGER $ASUSD_POWER_PLUGGED $EXTRA" >&2; exit 3"#,
+            5,
+        );
+        let output = hook.run(&HookEvent::power_plugged(true)).unwrap();
+        assert_eq!(output.code, Some(3));
+        assert_eq!(output.stderr, "AcPlugged 1 extra value\n");
+    }
+
+    #[test]
+    fn hook_timeout() {
+        let hook = hook("sleep 10", 1);
+        assert!(matches!(
+            hook.run(&HookEvent::new(HookTrigger::Resume)),
+            Err(RogError::Hook(_, _))
+        ));
+    }
+
+    #[test]
+    fn hook_leaves_background_process() {
+        let hook = hook("sleep 5 & echo started >&2", 1);
+        let start = Instant::now();
+        let output = hook.run(&HookEvent::new(HookTrigger::Resume)).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(3));
+        assert_eq!(output.code, Some(0));
+        assert_eq!(output.stderr, "started\n");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn wait_for_hooks() {
+        let done = std::env::temp_dir().join(format!("asusd-hook-{}", std::process::id()));
+        std::fs::remove_file(&done).ok();
+        let mut hook = hook(&format!("sleep 0.2; touch {}", done.display()), 5);
+        hook.trigger = HookTrigger::Suspend;
+        run_hooks_and_wait(&[hook], HookEvent::new(HookTrigger::Suspend)).await;
+        assert!(done.exists());
+        std::fs::remove_file(&done).ok();
+    }
+
+    #[test]
+    fn minimal_hook_config() {
+        let hook: Hook =
+            ron::from_str(r#"(name: "ac", trigger: AcPlugged, argv: ["true"])"#).unwrap();
+        assert!(hook.env.is_empty());
+        assert_eq!(hook.timeout_secs, 30);
+    }
+
+    #[test]
+    fn hook_from_command() {
+        assert!(Hook::from_command("ac", HookTrigger::AcPlugged, "  ").is_none());
+        // A single program with no args used to be ignored
+        let hook = Hook::from_command("ac", HookTrigger::AcPlugged, "/usr/bin/true").unwrap();
+        assert_eq!(hook.argv, vec!["/usr/bin/true"]);
+        let hook =
+            Hook::from_command("bat", HookTrigger::Battery, "notify-send on battery").unwrap();
+        assert_eq!(hook.argv, vec!["notify-send", "on", "battery"]);
+    }
+}
@@ -1,4 +1,6 @@
 #![deny(unused_must_use)]
+/// Pick the battery charge limit from the saved limit, overrides, and schedules
+pub mod charge_limit;
 /// Configuration loading, saving
 pub mod config;
 /// Control of anime matrix display
@@ -9,6 +11,8 @@ pub mod ctrl_aura;
 pub mod ctrl_fancurves;
 /// Control ASUS bios function such as boot sound, Optimus/Dedicated gfx mode
 pub mod ctrl_platform;
+/// Run user commands on system events
+pub mod hooks;
 
 pub mod error;
 
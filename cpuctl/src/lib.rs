@@ -1,14 +1,609 @@
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
+//! CPU control through the cpufreq, cpu hotplug, SMT, and pstate driver sysfs
+//! interfaces.
+//!
+//! All paths are found through a [`SysfsRoot`] so that the complete API can
+//! be tested against a fixture directory laid out like
+//! `/sys/devices/system/cpu`.
+
+mod settings;
+
+use std::fs;
+use std::path::PathBuf;
+
+use log::{info, warn};
+pub use rog_platform::cpu::{CPUGovernor, CPUEPP};
+use rog_platform::error::{PlatformError, Result};
+use rog_platform::sysfs::{sysfs_root, SysfsRoot};
+use rog_platform::{
+    has_attr, read_attr_bool, read_attr_string, read_attr_u32, write_attr_bool, write_attr_string,
+    write_attr_u32,
+};
+pub use settings::{AmdPstateMode, CpuSettings};
+
+const CPU_DIR: &str = "/sys/devices/system/cpu";
+
+const ATTR_ONLINE: &str = "online";
+const ATTR_THREAD_SIBLINGS: &str = "topology/thread_siblings_list";
+const ATTR_AVAILABLE_GOVERNORS: &str = "cpufreq/scaling_available_governors";
+const ATTR_GOVERNOR: &str = "cpufreq/scaling_governor";
+const ATTR_AVAILABLE_EPP: &str = "cpufreq/energy_performance_available_preferences";
+const ATTR_EPP: &str = "cpufreq/energy_performance_preference";
+const ATTR_CPUINFO_MIN_FREQ: &str = "cpufreq/cpuinfo_min_freq";
+const ATTR_CPUINFO_MAX_FREQ: &str = "cpufreq/cpuinfo_max_freq";
+const ATTR_MIN_FREQ: &str = "cpufreq/scaling_min_freq";
+const ATTR_MAX_FREQ: &str = "cpufreq/scaling_max_freq";
+
+// These are relative to `CPU_DIR`
+const ATTR_AMD_PSTATE: &str = "amd_pstate/status";
+const ATTR_INTEL_NO_TURBO: &str = "intel_pstate/no_turbo";
+const ATTR_BOOST: &str = "cpufreq/boost";
+const ATTR_SMT: &str = "smt/control";
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Clone)]
+struct Core {
+    id: u32,
+    path: PathBuf,
+    /// If this is the second thread of a physical core, which is taken
+    /// offline with SMT. `None` if the core was offline when found, as the
+    /// topology is only there for online cores.
+    smt_sibling: Option<bool>,
+}
+
+impl Core {
+    fn new(id: u32, path: PathBuf) -> Self {
+        // The list is the sorted ids of all threads of the physical core, such
+        // as `0,8` or `0-1`
+        let smt_sibling = read_attr_string(&path, ATTR_THREAD_SIBLINGS)
+            .ok()
+            .and_then(|list| {
+                let first = list.split([',', '-']).next()?;
+                first.trim().parse::<u32>().ok().map(|first| first < id)
+            });
+        Self {
+            id,
+            path,
+            smt_sibling,
+        }
+    }
+}
+
+/// Both modern AMD and Intel have cpufreq control if using `powersave`
+/// governor. What interests us the most here is `energy_performance_preference`
+/// which can drastically alter CPU performance.
+///
+/// Along with this the cores can be taken offline, frequencies limited, and
+/// boost or SMT disabled.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Clone)]
+pub struct CPUControl {
+    /// The base dir, `/sys/devices/system/cpu`
+    cpu_dir: PathBuf,
+    /// Every `cpuN`, sorted by `N`
+    cores: Vec<Core>,
+}
+
+impl CPUControl {
+    pub fn new() -> Result<Self> {
+        Self::with_sysfs_root(sysfs_root())
+    }
+
+    /// As [`CPUControl::new()`] but searching for cores under `root`
+    pub fn with_sysfs_root(root: &SysfsRoot) -> Result<Self> {
+        let cpu_dir = root.join(CPU_DIR);
+        let entries = fs::read_dir(&cpu_dir)
+            .map_err(|e| PlatformError::IoPath(cpu_dir.to_string_lossy().to_string(), e))?;
+
+        let mut cores: Vec<Core> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name();
+                let id = name.to_str()?.strip_prefix("cpu")?.parse::<u32>().ok()?;
+                Some(Core::new(id, entry.path()))
+            })
+            .collect();
+        if cores.is_empty() {
+            return Err(PlatformError::MissingFunction(format!(
+                "No CPU found in {cpu_dir:?}"
+            )));
+        }
+        cores.sort_by_key(|c| c.id);
+        info!("Found {} CPU cores at {cpu_dir:?}", cores.len());
+
+        let cpu = CPUControl { cpu_dir, cores };
+        for attr in [ATTR_GOVERNOR, ATTR_EPP] {
+            match cpu.read_first_online(attr) {
+                Ok(v) => info!("{attr}: {v:?}"),
+                Err(_) => info!("{attr} not found"),
+            }
+        }
+        Ok(cpu)
+    }
+
+    fn online_cores(&self) -> impl Iterator<Item = &Core> {
+        self.cores
+            .iter()
+            .filter(|c| read_attr_bool(&c.path, ATTR_ONLINE).unwrap_or(true))
+    }
+
+    fn core(&self, id: u32) -> Result<&Core> {
+        self.cores
+            .iter()
+            .find(|c| c.id == id)
+            .ok_or_else(|| PlatformError::CPU(format!("cpu{id} does not exist")))
+    }
+
+    /// Most of the cpufreq attributes are the same across all cores, so are
+    /// read from the first one that is online
+    fn first_online(&self) -> Result<&Core> {
+        self.online_cores()
+            .next()
+            .ok_or_else(|| PlatformError::CPU("No CPU's?".to_string()))
+        // TODO: check cpu are sync
+    }
+
+    fn read_first_online(&self, attr: &str) -> Result<String> {
+        read_attr_string(&self.first_online()?.path, attr)
+    }
+
+    fn read_first_online_u32(&self, attr: &str) -> Result<u32> {
+        read_attr_u32(&self.first_online()?.path, attr)
+    }
+
+    /// Write the attribute for all online cores. Offline cores have no
+    /// cpufreq dir so are skipped.
+    fn write_all_online(&self, attr: &str, value: &str) -> Result<()> {
+        for core in self.online_cores() {
+            write_attr_string(&core.path, attr, value)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_governor(&self) -> Result<CPUGovernor> {
+        Ok(self.read_first_online(ATTR_GOVERNOR)?.as_str().into())
+    }
+
+    pub fn get_available_governors(&self) -> Result<Vec<CPUGovernor>> {
+        self.read_first_online(ATTR_AVAILABLE_GOVERNORS)
+            .map(|s| s.split_whitespace().map(|s| s.into()).collect())
+    }
+
+    pub fn set_governor(&self, gov: CPUGovernor) -> Result<()> {
+        if !self.get_available_governors()?.contains(&gov) {
+            return Err(PlatformError::CPU(format!("{gov:?} is not available")));
+        }
+        self.write_all_online(ATTR_GOVERNOR, &String::from(gov))
+    }
+
+    pub fn get_epp(&self) -> Result<CPUEPP> {
+        Ok(self.read_first_online(ATTR_EPP)?.as_str().into())
+    }
+
+    pub fn get_available_epp(&self) -> Result<Vec<CPUEPP>> {
+        self.read_first_online(ATTR_AVAILABLE_EPP)
+            .map(|s| s.split_whitespace().map(|s| s.into()).collect())
+    }
+
+    pub fn set_epp(&self, epp: CPUEPP) -> Result<()> {
+        if !self.get_available_epp()?.contains(&epp) {
+            return Err(PlatformError::CPU(format!("{epp:?} is not available")));
+        }
+        self.write_all_online(ATTR_EPP, &String::from(epp))
+    }
+
+    /// The ID of every core, online or not
+    pub fn cores(&self) -> Vec<u32> {
+        self.cores.iter().map(|c| c.id).collect()
+    }
+
+    /// `cpu0` usually can't be taken offline and so has no `online` attribute,
+    /// in which case it is always online
+    pub fn get_core_online(&self, id: u32) -> Result<bool> {
+        let core = self.core(id)?;
+        if has_attr(&core.path, ATTR_ONLINE) {
+            read_attr_bool(&core.path, ATTR_ONLINE)
+        } else {
+            Ok(true)
+        }
+    }
+
+    pub fn set_core_online(&self, id: u32, online: bool) -> Result<()> {
+        let core = self.core(id)?;
+        if !has_attr(&core.path, ATTR_ONLINE) {
+            if online {
+                return Ok(());
+            }
+            return Err(PlatformError::CPU(format!(
+                "cpu{id} can not be taken offline"
+            )));
+        }
+        write_attr_bool(&core.path, ATTR_ONLINE, online)
+    }
+
+    pub fn get_online_core_count(&self) -> u32 {
+        self.online_cores().count() as u32
+    }
+
+    /// The cores which can be brought online. With SMT off these are only
+    /// the first thread of each physical core, or if the topology is not
+    /// known, the cores online now.
+    fn hotplug_cores(&self) -> Vec<&Core> {
+        if self.get_smt().unwrap_or(true) {
+            return self.cores.iter().collect();
+        }
+        self.cores
+            .iter()
+            .filter(|c| match c.smt_sibling {
+                Some(sibling) => !sibling,
+                None => self.get_core_online(c.id).unwrap_or(false),
+            })
+            .collect()
+    }
+
+    /// Bring the first `count` cores online and take the rest offline. While
+    /// SMT is off only the first thread of each physical core is counted and
+    /// the others are left offline.
+    pub fn set_online_core_count(&self, count: u32) -> Result<()> {
+        let cores = self.hotplug_cores();
+        if count == 0 || count as usize > cores.len() {
+            return Err(PlatformError::CPU(format!(
+                "{count} cores requested, must be 1-{}",
+                cores.len()
+            )));
+        }
+        for (i, core) in cores.into_iter().enumerate() {
+            let online = (i as u32) < count;
+            if self.get_core_online(core.id)? != online {
+                self.set_core_online(core.id, online)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The hardware frequency limits in kHz as `(min, max)`
+    pub fn get_freq_limits(&self) -> Result<(u32, u32)> {
+        Ok((
+            self.read_first_online_u32(ATTR_CPUINFO_MIN_FREQ)?,
+            self.read_first_online_u32(ATTR_CPUINFO_MAX_FREQ)?,
+        ))
+    }
+
+    fn check_freq(&self, khz: u32) -> Result<()> {
+        let (min, max) = self.get_freq_limits()?;
+        if !(min..=max).contains(&khz) {
+            return Err(PlatformError::CPU(format!(
+                "{khz}kHz is out of range {min}-{max}kHz"
+            )));
+        }
+        Ok(())
+    }
+
+    /// The scaling minimum frequency in kHz
+    pub fn get_min_freq(&self) -> Result<u32> {
+        self.read_first_online_u32(ATTR_MIN_FREQ)
+    }
+
+    pub fn set_min_freq(&self, khz: u32) -> Result<()> {
+        self.check_freq(khz)?;
+        for core in self.online_cores() {
+            write_attr_u32(&core.path, ATTR_MIN_FREQ, khz)?;
+        }
+        Ok(())
+    }
+
+    /// The scaling maximum frequency in kHz
+    pub fn get_max_freq(&self) -> Result<u32> {
+        self.read_first_online_u32(ATTR_MAX_FREQ)
+    }
+
+    pub fn set_max_freq(&self, khz: u32) -> Result<()> {
+        self.check_freq(khz)?;
+        for core in self.online_cores() {
+            write_attr_u32(&core.path, ATTR_MAX_FREQ, khz)?;
+        }
+        Ok(())
+    }
+
+    pub fn has_amd_pstate(&self) -> bool {
+        has_attr(&self.cpu_dir, ATTR_AMD_PSTATE)
+    }
+
+    pub fn get_amd_pstate(&self) -> Result<AmdPstateMode> {
+        read_attr_string(&self.cpu_dir, ATTR_AMD_PSTATE)?.parse()
+    }
+
+    pub fn set_amd_pstate(&self, mode: AmdPstateMode) -> Result<()> {
+        write_attr_string(&self.cpu_dir, ATTR_AMD_PSTATE, &String::from(mode))
+    }
+
+    /// Either of Intel `no_turbo` or the generic cpufreq `boost` is available
+    pub fn has_boost(&self) -> bool {
+        has_attr(&self.cpu_dir, ATTR_INTEL_NO_TURBO) || has_attr(&self.cpu_dir, ATTR_BOOST)
+    }
+
+    /// If turbo/boost is allowed
+    pub fn get_boost(&self) -> Result<bool> {
+        if has_attr(&self.cpu_dir, ATTR_INTEL_NO_TURBO) {
+            return read_attr_bool(&self.cpu_dir, ATTR_INTEL_NO_TURBO).map(|b| !b);
+        }
+        read_attr_bool(&self.cpu_dir, ATTR_BOOST)
+    }
+
+    pub fn set_boost(&self, on: bool) -> Result<()> {
+        if has_attr(&self.cpu_dir, ATTR_INTEL_NO_TURBO) {
+            return write_attr_bool(&self.cpu_dir, ATTR_INTEL_NO_TURBO, !on);
+        }
+        write_attr_bool(&self.cpu_dir, ATTR_BOOST, on)
+    }
+
+    /// SMT can be changed. It is not if the CPU does not support it, or it was
+    /// force disabled on the kernel cmdline
+    pub fn has_smt(&self) -> bool {
+        matches!(
+            read_attr_string(&self.cpu_dir, ATTR_SMT).as_deref(),
+            Ok("on") | Ok("off")
+        )
+    }
+
+    pub fn get_smt(&self) -> Result<bool> {
+        match read_attr_string(&self.cpu_dir, ATTR_SMT)?.as_str() {
+            "on" => Ok(true),
+            "off" | "forceoff" => Ok(false),
+            s => Err(PlatformError::CPU(format!("SMT control is {s}"))),
+        }
+    }
+
+    pub fn set_smt(&self, on: bool) -> Result<()> {
+        if !self.has_smt() {
+            return Err(PlatformError::NotSupported);
+        }
+        write_attr_string(&self.cpu_dir, ATTR_SMT, if on { "on" } else { "off" })
+    }
+
+    /// Apply all the `Some` items in `settings`. Every item is attempted even
+    /// if an earlier one failed, the last error is returned.
+    pub fn apply(&self, settings: &CpuSettings) -> Result<()> {
+        let mut res = Ok(());
+        let mut check = |name: &str, r: Result<()>| {
+            if let Err(e) = r {
+                warn!("CPU: setting {name} failed: {e}");
+                res = Err(e);
+            }
+        };
+
+        // Changing mode resets the cpufreq policies so this is done first,
+        // then SMT which changes which cores are online
+        if let Some(mode) = settings.amd_pstate {
+            if self.get_amd_pstate().ok() != Some(mode) {
+                check("amd_pstate", self.set_amd_pstate(mode));
+            }
+        }
+        if let Some(on) = settings.smt {
+            if self.get_smt().ok() != Some(on) {
+                check("smt", self.set_smt(on));
+            }
+        }
+        if let Some(count) = settings.online_cores {
+            check("online_cores", self.set_online_core_count(count));
+        }
+        if let Some(on) = settings.boost {
+            check("boost", self.set_boost(on));
+        }
+
+        // The new min can't be above the current max, nor the new max below
+        // the current min
+        let min_first = match (settings.min_freq, self.get_max_freq()) {
+            (Some(min), Ok(max)) => min <= max,
+            _ => true,
+        };
+        type SetFreq = fn(&CPUControl, u32) -> Result<()>;
+        let min = settings
+            .min_freq
+            .map(|khz| ("min_freq", Self::set_min_freq as SetFreq, khz));
+        let max = settings
+            .max_freq
+            .map(|khz| ("max_freq", Self::set_max_freq as SetFreq, khz));
+        let freqs = if min_first { [min, max] } else { [max, min] };
+        for (name, set, khz) in freqs.into_iter().flatten() {
+            check(name, set(self, khz));
+        }
+        res
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use rog_platform::sysfs::SysfsFixture;
+
+    use super::{AmdPstateMode, CPUControl, CpuSettings, CPUEPP};
+    use crate::CPUGovernor;
+
+    /// Four cores on amd_pstate, `cpu0` has no `online` attribute
+    fn fixture(name: &str) -> SysfsFixture {
+        let fixture = SysfsFixture::new(name);
+        for id in 0..4 {
+            let mut attrs = vec![
+                (
+                    "cpufreq/scaling_available_governors",
+                    "performance powersave",
+                ),
+                ("cpufreq/scaling_governor", "powersave"),
+                (
+                    "cpufreq/energy_performance_available_preferences",
+                    "default performance balance_performance balance_power power",
+                ),
+                (
+                    "cpufreq/energy_performance_preference",
+                    "balance_performance",
+                ),
+                ("cpufreq/cpuinfo_min_freq", "400000"),
+                ("cpufreq/cpuinfo_max_freq", "5000000"),
+                ("cpufreq/scaling_min_freq", "400000"),
+                ("cpufreq/scaling_max_freq", "5000000"),
+            ];
+            if id != 0 {
+                attrs.push(("online", "1"));
+            }
+            // Two physical cores, the second threads are cpu2 and cpu3
+            attrs.push((
+                "topology/thread_siblings_list",
+                ["0,2", "1,3", "0,2", "1,3"][id],
+            ));
+            fixture.device(format!("devices/system/cpu/cpu{id}"), &attrs);
+        }
+        fixture
+            .device(
+                "devices/system/cpu",
+                &[
+                    ("amd_pstate/status", "active"),
+                    ("cpufreq/boost", "1"),
+                    ("smt/control", "on"),
+                ],
+            )
+            // Not a core
+            .device("devices/system/cpu/cpuidle", &[]);
+        fixture
+    }
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn governor_and_epp() {
+        let fixture = fixture("epp");
+        let cpu = CPUControl::with_sysfs_root(&fixture.root()).unwrap();
+        assert_eq!(cpu.cores(), vec![0, 1, 2, 3]);
+        assert_eq!(cpu.get_governor().unwrap(), CPUGovernor::Powersave);
+        assert_eq!(cpu.get_epp().unwrap(), CPUEPP::BalancePerformance);
+        cpu.set_epp(CPUEPP::Power).unwrap();
+        assert_eq!(cpu.get_epp().unwrap(), CPUEPP::Power);
+        cpu.set_governor(CPUGovernor::Performance).unwrap();
+        assert_eq!(cpu.get_governor().unwrap(), CPUGovernor::Performance);
+    }
+
+    #[test]
+    fn online_cores() {
+        let fixture = fixture("online");
+        let cpu = CPUControl::with_sysfs_root(&fixture.root()).unwrap();
+        assert_eq!(cpu.get_online_core_count(), 4);
+        assert!(cpu.set_core_online(0, false).is_err());
+
+        cpu.set_online_core_count(2).unwrap();
+        assert_eq!(cpu.get_online_core_count(), 2);
+        assert!(cpu.get_core_online(1).unwrap());
+        assert!(!cpu.get_core_online(3).unwrap());
+        assert!(cpu.set_online_core_count(0).is_err());
+        assert!(cpu.set_online_core_count(5).is_err());
+    }
+
+    #[test]
+    fn frequency() {
+        let fixture = fixture("freq");
+        let cpu = CPUControl::with_sysfs_root(&fixture.root()).unwrap();
+        assert_eq!(cpu.get_freq_limits().unwrap(), (400000, 5000000));
+        cpu.set_max_freq(3000000).unwrap();
+        assert_eq!(cpu.get_max_freq().unwrap(), 3000000);
+        assert!(cpu.set_max_freq(6000000).is_err());
+        assert!(cpu.set_min_freq(100000).is_err());
+    }
+
+    #[test]
+    fn boost_smt_pstate() {
+        let fixture = fixture("boost");
+        let cpu = CPUControl::with_sysfs_root(&fixture.root()).unwrap();
+        assert!(cpu.has_boost());
+        assert!(cpu.get_boost().unwrap());
+        cpu.set_boost(false).unwrap();
+        assert!(!cpu.get_boost().unwrap());
+
+        assert!(cpu.has_smt());
+        cpu.set_smt(false).unwrap();
+        assert!(!cpu.get_smt().unwrap());
+
+        assert!(cpu.has_amd_pstate());
+        assert_eq!(cpu.get_amd_pstate().unwrap(), AmdPstateMode::Active);
+        cpu.set_amd_pstate(AmdPstateMode::Guided).unwrap();
+        assert_eq!(cpu.get_amd_pstate().unwrap(), AmdPstateMode::Guided);
+
+        // Intel no_turbo is inverted
+        fixture.attr("devices/system/cpu/intel_pstate/no_turbo", 1);
+        assert!(!cpu.get_boost().unwrap());
+        cpu.set_boost(true).unwrap();
+        assert!(cpu.get_boost().unwrap());
+    }
+
+    #[test]
+    fn apply_settings() {
+        let fixture = fixture("apply");
+        let cpu = CPUControl::with_sysfs_root(&fixture.root()).unwrap();
+        cpu.set_max_freq(1000000).unwrap();
+
+        // min is above the current max, so max must be written first
+        let settings = CpuSettings {
+            online_cores: Some(3),
+            min_freq: Some(2000000),
+            max_freq: Some(4000000),
+            amd_pstate: Some(AmdPstateMode::Passive),
+            boost: Some(false),
+            smt: None,
+        };
+        cpu.apply(&settings).unwrap();
+        assert_eq!(cpu.get_online_core_count(), 3);
+        assert_eq!(cpu.get_min_freq().unwrap(), 2000000);
+        assert_eq!(cpu.get_max_freq().unwrap(), 4000000);
+        assert_eq!(cpu.get_amd_pstate().unwrap(), AmdPstateMode::Passive);
+        assert!(!cpu.get_boost().unwrap());
+        assert!(cpu.get_smt().unwrap());
+
+        let settings = CpuSettings {
+            max_freq: Some(9000000),
+            boost: Some(true),
+            ..Default::default()
+        };
+        assert!(cpu.apply(&settings).is_err());
+        assert!(cpu.get_boost().unwrap());
+    }
+
+    #[test]
+    fn online_cores_without_smt() {
+        let fixture = fixture("nosmt");
+        let cpu = CPUControl::with_sysfs_root(&fixture.root()).unwrap();
+        let settings = CpuSettings {
+            online_cores: Some(2),
+            smt: Some(false),
+            ..Default::default()
+        };
+        // The kernel takes the second threads offline with SMT, which the
+        // fixture can't do itself
+        for id in [2, 3] {
+            fixture.attr(format!("devices/system/cpu/cpu{id}/online"), 0);
+        }
+        cpu.apply(&settings).unwrap();
+        assert_eq!(cpu.get_online_core_count(), 2);
+        assert!(cpu.get_core_online(1).unwrap());
+        assert!(!cpu.get_core_online(2).unwrap());
+        // Only the two physical cores can be counted
+        assert!(cpu.set_online_core_count(3).is_err());
+        cpu.set_online_core_count(1).unwrap();
+        assert!(!cpu.get_core_online(1).unwrap());
+        assert!(!cpu.get_core_online(3).unwrap());
+    }
+
+    #[test]
+    #[ignore = "Can't run this in a docker image"]
+    fn check_cpu() {
+        let cpu = CPUControl::new().unwrap();
+        assert_eq!(cpu.get_governor().unwrap(), CPUGovernor::Powersave);
+        assert_eq!(
+            cpu.get_available_governors().unwrap(),
+            vec![CPUGovernor::Performance, CPUGovernor::Powersave]
+        );
+
+        assert_eq!(cpu.get_epp().unwrap(), CPUEPP::BalancePower);
+        assert_eq!(
+            cpu.get_available_epp().unwrap(),
+            vec![
+                CPUEPP::Default,
+                CPUEPP::Performance,
+                CPUEPP::BalancePerformance,
+                CPUEPP::BalancePower,
+                CPUEPP::Power,
+            ]
+        );
     }
 }
@@ -0,0 +1,89 @@
+use std::str::FromStr;
+
+use rog_platform::error::PlatformError;
+use serde_derive::{Deserialize, Serialize};
+use typeshare::typeshare;
+use zbus::zvariant::{OwnedValue, Type, Value};
+
+/// The operation mode of the `amd_pstate` driver, as found in
+/// `/sys/devices/system/cpu/amd_pstate/status`
+#[typeshare]
+#[repr(u32)]
+#[derive(
+    Deserialize,
+    Serialize,
+    Type,
+    Value,
+    OwnedValue,
+    Default,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Clone,
+    Copy,
+)]
+#[zvariant(signature = "u")]
+pub enum AmdPstateMode {
+    /// The firmware picks the frequency using the EPP hint
+    #[default]
+    Active = 0,
+    /// The kernel governor requests a performance level
+    Passive = 1,
+    /// The kernel sets min/max levels and the firmware picks within them
+    Guided = 2,
+}
+
+impl FromStr for AmdPstateMode {
+    type Err = PlatformError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "active" => Ok(Self::Active),
+            "passive" => Ok(Self::Passive),
+            "guided" => Ok(Self::Guided),
+            _ => Err(PlatformError::CPU(format!("unknown amd_pstate mode: {s}"))),
+        }
+    }
+}
+
+impl From<AmdPstateMode> for String {
+    fn from(m: AmdPstateMode) -> Self {
+        match m {
+            AmdPstateMode::Active => "active".to_string(),
+            AmdPstateMode::Passive => "passive".to_string(),
+            AmdPstateMode::Guided => "guided".to_string(),
+        }
+    }
+}
+
+/// A set of CPU settings to be applied together, such as when a throttle
+/// policy is changed. Every item is optional, `None` is left as it is.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct CpuSettings {
+    /// Keep this many cores (counting SMT threads) online starting from
+    /// `cpu0`, all others are taken offline
+    pub online_cores: Option<u32>,
+    /// `scaling_min_freq` in kHz for all online cores
+    pub min_freq: Option<u32>,
+    /// `scaling_max_freq` in kHz for all online cores
+    pub max_freq: Option<u32>,
+    pub amd_pstate: Option<AmdPstateMode>,
+    /// Turbo on Intel, boost on AMD
+    pub boost: Option<bool>,
+    pub smt: Option<bool>,
+}
+
+impl CpuSettings {
+    /// Fill every item left as `None` here from `fallback`
+    pub fn or(self, fallback: &CpuSettings) -> CpuSettings {
+        CpuSettings {
+            online_cores: self.online_cores.or(fallback.online_cores),
+            min_freq: self.min_freq.or(fallback.min_freq),
+            max_freq: self.max_freq.or(fallback.max_freq),
+            amd_pstate: self.amd_pstate.or(fallback.amd_pstate),
+            boost: self.boost.or(fallback.boost),
+            smt: self.smt.or(fallback.smt),
+        }
+    }
+}
@@ -135,6 +135,25 @@ impl AnimeDiagonal {
         }
     }
 
+    /// Bars rising from the bottom, one for each of `levels` (0.0-1.0) spread
+    /// evenly across the width, such as an audio spectrum
+    pub fn from_bars(anime_type: AnimeType, levels: &[f32], bright: f32) -> Self {
+        let mut matrix = Self::new(anime_type, None);
+        if levels.is_empty() {
+            return matrix;
+        }
+        let (width, height) = (anime_type.width(), anime_type.height());
+        let value = (255.0 * bright).clamp(0.0, 255.0) as u8;
+        for x in 0..width {
+            let level = levels[x * levels.len() / width].clamp(0.0, 1.0);
+            let lit = (level * height as f32).round() as usize;
+            for row in &mut matrix.1[height - lit..] {
+                row[x] = value;
+            }
+        }
+        matrix
+    }
+
     /// Convert to a data buffer that can be sent over dbus
     #[inline]
     pub fn into_data_buffer(&self, anime_type: AnimeType) -> Result<AnimeDataBuffer> {
@@ -377,3 +396,29 @@ impl AnimeDiagonal {
         AnimeDataBuffer::from_vec(crate::AnimeType::GA402, buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::AnimeDiagonal;
+    use crate::AnimeType;
+
+    #[test]
+    fn bars_rise_from_bottom() {
+        let anime_type = AnimeType::GA401;
+        let (width, height) = (anime_type.width(), anime_type.height());
+
+        let matrix = AnimeDiagonal::from_bars(anime_type, &[1.0, 0.0], 1.0);
+        assert!(matrix.1.iter().all(|row| row[0] == 255));
+        assert!(matrix.1.iter().all(|row| row[width - 1] == 0));
+
+        let matrix = AnimeDiagonal::from_bars(anime_type, &[0.5], 0.5);
+        let lit = matrix.1.iter().filter(|row| row[0] > 0).count();
+        assert_eq!(lit, height / 2);
+        assert_eq!(matrix.1[height - 1][0], 127);
+        assert_eq!(matrix.1[0][0], 0);
+        assert!(matrix.into_data_buffer(anime_type).is_ok());
+
+        let matrix = AnimeDiagonal::from_bars(anime_type, &[], 1.0);
+        assert!(matrix.1.iter().flatten().all(|v| *v == 0));
+    }
+}
@@ -44,6 +44,12 @@ pub enum ActionLoader {
     },
     /// A pause to be used between sequences
     Pause(Duration),
+    /// Bars of the audio spectrum, drawn by the user daemon from what is
+    /// playing. Shown for `time`, or until the sequence changes if `None`.
+    AudioEq {
+        time: Option<Duration>,
+        brightness: f32,
+    },
 }
 
 /// All the possible `AniMe` actions that can be used. The enum is intended to
@@ -57,8 +63,12 @@ pub enum ActionData {
     Image(Box<AnimeDataBuffer>),
     /// A pause to be used between sequences
     Pause(Duration),
-    /// Placeholder
-    AudioEq,
+    /// Bars of the audio spectrum, shown for `time` or until the sequence
+    /// changes if `None`. Only the user daemon captures audio to draw these.
+    AudioEq {
+        time: Option<Duration>,
+        brightness: f32,
+    },
     /// Placeholder
     SystemInfo,
     /// Placeholder
@@ -162,6 +172,10 @@ impl ActionData {
                 }
             }
             ActionLoader::Pause(duration) => ActionData::Pause(*duration),
+            ActionLoader::AudioEq { time, brightness } => ActionData::AudioEq {
+                time: *time,
+                brightness: *brightness,
+            },
         };
         Ok(a)
     }
@@ -177,6 +177,134 @@ impl LedCode {
         )
     }
 
+    /// The LEDs of the key with this Linux input event code, such as
+    /// `KEY_A` (30). Keys which may have more than one LED, like the
+    /// spacebar, give each variant and layouts use whichever they have. The
+    /// numpad shares the LEDs of the digits and symbols in layouts. Empty if
+    /// there is no LED for the key.
+    pub fn from_key_code(code: u16) -> &'static [LedCode] {
+        use input_codes::*;
+        use LedCode::*;
+        match code {
+            KEY_ESC => &[Esc],
+            KEY_1 => &[N1],
+            KEY_2 => &[N2],
+            KEY_3 => &[N3],
+            KEY_4 => &[N4],
+            KEY_5 => &[N5],
+            KEY_6 => &[N6],
+            KEY_7 => &[N7],
+            KEY_8 => &[N8],
+            KEY_9 => &[N9],
+            KEY_0 => &[N0],
+            KEY_MINUS => &[Hyphen],
+            KEY_EQUAL => &[Equals],
+            KEY_BACKSPACE => &[Backspace, Backspace3_1, Backspace3_2, Backspace3_3],
+            KEY_TAB => &[Tab],
+            KEY_Q => &[Q],
+            KEY_W => &[W],
+            KEY_E => &[E],
+            KEY_R => &[R],
+            KEY_T => &[T],
+            KEY_Y => &[Y],
+            KEY_U => &[U],
+            KEY_I => &[I],
+            KEY_O => &[O],
+            KEY_P => &[P],
+            KEY_LEFTBRACE => &[LBracket],
+            KEY_RIGHTBRACE => &[RBracket],
+            KEY_ENTER => &[Return, Return3_1, Return3_2, Return3_3],
+            KEY_LEFTCTRL => &[LCtrl],
+            KEY_A => &[A],
+            KEY_S => &[S],
+            KEY_D => &[D],
+            KEY_F => &[F],
+            KEY_G => &[G],
+            KEY_H => &[H],
+            KEY_J => &[J],
+            KEY_K => &[K],
+            KEY_L => &[L],
+            KEY_SEMICOLON => &[SemiColon],
+            KEY_APOSTROPHE => &[Quote],
+            KEY_GRAVE => &[Tilde],
+            KEY_LEFTSHIFT => &[LShift, LShift3_1, LShift3_2, LShift3_3],
+            KEY_BACKSLASH => &[BackSlash],
+            KEY_Z => &[Z],
+            KEY_X => &[X],
+            KEY_C => &[C],
+            KEY_V => &[V],
+            KEY_B => &[B],
+            KEY_N => &[N],
+            KEY_M => &[M],
+            KEY_COMMA => &[Comma],
+            KEY_DOT => &[Period],
+            KEY_SLASH => &[FwdSlash],
+            KEY_RIGHTSHIFT => &[Rshift, Rshift3_1, Rshift3_2, Rshift3_3],
+            KEY_KPASTERISK => &[Star],
+            KEY_LEFTALT => &[LAlt],
+            KEY_SPACE => &[
+                Spacebar,
+                Spacebar5_1,
+                Spacebar5_2,
+                Spacebar5_3,
+                Spacebar5_4,
+                Spacebar5_5,
+            ],
+            KEY_CAPSLOCK => &[Caps],
+            KEY_F1 => &[F1],
+            KEY_F2 => &[F2],
+            KEY_F3 => &[F3],
+            KEY_F4 => &[F4],
+            KEY_F5 => &[F5],
+            KEY_F6 => &[F6],
+            KEY_F7 => &[F7],
+            KEY_F8 => &[F8],
+            KEY_F9 => &[F9],
+            KEY_F10 => &[F10],
+            KEY_NUMLOCK => &[NumLock],
+            KEY_KP7 => &[N7],
+            KEY_KP8 => &[N8],
+            KEY_KP9 => &[N9],
+            KEY_KPMINUS => &[Hyphen],
+            KEY_KP4 => &[N4],
+            KEY_KP5 => &[N5],
+            KEY_KP6 => &[N6],
+            KEY_KPPLUS => &[NumPadPlus],
+            KEY_KP1 => &[N1],
+            KEY_KP2 => &[N2],
+            KEY_KP3 => &[N3],
+            KEY_KP0 => &[N0],
+            KEY_KPDOT => &[NumPadDel],
+            KEY_F11 => &[F11],
+            KEY_F12 => &[F12],
+            KEY_KPENTER => &[NumPadEnter],
+            KEY_RIGHTCTRL => &[RCtrl],
+            KEY_KPSLASH => &[FwdSlash],
+            KEY_SYSRQ => &[PrtSc],
+            KEY_RIGHTALT => &[RAlt],
+            KEY_HOME => &[Home],
+            KEY_UP => &[Up],
+            KEY_PAGEUP => &[PgUp],
+            KEY_LEFT => &[Left],
+            KEY_RIGHT => &[Right],
+            KEY_END => &[End],
+            KEY_DOWN => &[Down],
+            KEY_PAGEDOWN => &[PgDn],
+            KEY_DELETE => &[Del],
+            KEY_VOLUMEDOWN => &[VolDown],
+            KEY_VOLUMEUP => &[VolUp],
+            KEY_PAUSE => &[Pause],
+            KEY_LEFTMETA => &[Meta],
+            KEY_PROG1 => &[RogApp],
+            KEY_NEXTSONG => &[MediaNext],
+            KEY_PLAYPAUSE => &[MediaPlay],
+            KEY_PREVIOUSSONG => &[MediaPrev],
+            KEY_STOPCD => &[MediaStop],
+            KEY_MICMUTE => &[MicMute],
+            _ => &[],
+        }
+    }
+
     pub fn is_lightbar_zone(&self) -> bool {
         matches!(
             self,
@@ -190,6 +318,119 @@ impl LedCode {
     }
 }
 
+/// The Linux input event codes of the keys with an LED, as named in
+/// `linux/input-event-codes.h`
+mod input_codes {
+    pub const KEY_ESC: u16 = 1;
+    pub const KEY_1: u16 = 2;
+    pub const KEY_2: u16 = 3;
+    pub const KEY_3: u16 = 4;
+    pub const KEY_4: u16 = 5;
+    pub const KEY_5: u16 = 6;
+    pub const KEY_6: u16 = 7;
+    pub const KEY_7: u16 = 8;
+    pub const KEY_8: u16 = 9;
+    pub const KEY_9: u16 = 10;
+    pub const KEY_0: u16 = 11;
+    pub const KEY_MINUS: u16 = 12;
+    pub const KEY_EQUAL: u16 = 13;
+    pub const KEY_BACKSPACE: u16 = 14;
+    pub const KEY_TAB: u16 = 15;
+    pub const KEY_Q: u16 = 16;
+    pub const KEY_W: u16 = 17;
+    pub const KEY_E: u16 = 18;
+    pub const KEY_R: u16 = 19;
+    pub const KEY_T: u16 = 20;
+    pub const KEY_Y: u16 = 21;
+    pub const KEY_U: u16 = 22;
+    pub const KEY_I: u16 = 23;
+    pub const KEY_O: u16 = 24;
+    pub const KEY_P: u16 = 25;
+    pub const KEY_LEFTBRACE: u16 = 26;
+    pub const KEY_RIGHTBRACE: u16 = 27;
+    pub const KEY_ENTER: u16 = 28;
+    pub const KEY_LEFTCTRL: u16 = 29;
+    pub const KEY_A: u16 = 30;
+    pub const KEY_S: u16 = 31;
+    pub const KEY_D: u16 = 32;
+    pub const KEY_F: u16 = 33;
+    pub const KEY_G: u16 = 34;
+    pub const KEY_H: u16 = 35;
+    pub const KEY_J: u16 = 36;
+    pub const KEY_K: u16 = 37;
+    pub const KEY_L: u16 = 38;
+    pub const KEY_SEMICOLON: u16 = 39;
+    pub const KEY_APOSTROPHE: u16 = 40;
+    pub const KEY_GRAVE: u16 = 41;
+    pub const KEY_LEFTSHIFT: u16 = 42;
+    pub const KEY_BACKSLASH: u16 = 43;
+    pub const KEY_Z: u16 = 44;
+    pub const KEY_X: u16 = 45;
+    pub const KEY_C: u16 = 46;
+    pub const KEY_V: u16 = 47;
+    pub const KEY_B: u16 = 48;
+    pub const KEY_N: u16 = 49;
+    pub const KEY_M: u16 = 50;
+    pub const KEY_COMMA: u16 = 51;
+    pub const KEY_DOT: u16 = 52;
+    pub const KEY_SLASH: u16 = 53;
+    pub const KEY_RIGHTSHIFT: u16 = 54;
+    pub const KEY_KPASTERISK: u16 = 55;
+    pub const KEY_LEFTALT: u16 = 56;
+    pub const KEY_SPACE: u16 = 57;
+    pub const KEY_CAPSLOCK: u16 = 58;
+    pub const KEY_F1: u16 = 59;
+    pub const KEY_F2: u16 = 60;
+    pub const KEY_F3: u16 = 61;
+    pub const KEY_F4: u16 = 62;
+    pub const KEY_F5: u16 = 63;
+    pub const KEY_F6: u16 = 64;
+    pub const KEY_F7: u16 = 65;
+    pub const KEY_F8: u16 = 66;
+    pub const KEY_F9: u16 = 67;
+    pub const KEY_F10: u16 = 68;
+    pub const KEY_NUMLOCK: u16 = 69;
+    pub const KEY_KP7: u16 = 71;
+    pub const KEY_KP8: u16 = 72;
+    pub const KEY_KP9: u16 = 73;
+    pub const KEY_KPMINUS: u16 = 74;
+    pub const KEY_KP4: u16 = 75;
+    pub const KEY_KP5: u16 = 76;
+    pub const KEY_KP6: u16 = 77;
+    pub const KEY_KPPLUS: u16 = 78;
+    pub const KEY_KP1: u16 = 79;
+    pub const KEY_KP2: u16 = 80;
+    pub const KEY_KP3: u16 = 81;
+    pub const KEY_KP0: u16 = 82;
+    pub const KEY_KPDOT: u16 = 83;
+    pub const KEY_F11: u16 = 87;
+    pub const KEY_F12: u16 = 88;
+    pub const KEY_KPENTER: u16 = 96;
+    pub const KEY_RIGHTCTRL: u16 = 97;
+    pub const KEY_KPSLASH: u16 = 98;
+    pub const KEY_SYSRQ: u16 = 99;
+    pub const KEY_RIGHTALT: u16 = 100;
+    pub const KEY_HOME: u16 = 102;
+    pub const KEY_UP: u16 = 103;
+    pub const KEY_PAGEUP: u16 = 104;
+    pub const KEY_LEFT: u16 = 105;
+    pub const KEY_RIGHT: u16 = 106;
+    pub const KEY_END: u16 = 107;
+    pub const KEY_DOWN: u16 = 108;
+    pub const KEY_PAGEDOWN: u16 = 109;
+    pub const KEY_DELETE: u16 = 111;
+    pub const KEY_VOLUMEDOWN: u16 = 114;
+    pub const KEY_VOLUMEUP: u16 = 115;
+    pub const KEY_PAUSE: u16 = 119;
+    pub const KEY_LEFTMETA: u16 = 125;
+    pub const KEY_PROG1: u16 = 148;
+    pub const KEY_NEXTSONG: u16 = 163;
+    pub const KEY_PLAYPAUSE: u16 = 164;
+    pub const KEY_PREVIOUSSONG: u16 = 165;
+    pub const KEY_STOPCD: u16 = 166;
+    pub const KEY_MICMUTE: u16 = 248;
+}
+
 /// Represents the per-key raw USB packets
 #[typeshare]
 pub type UsbPackets = Vec<Vec<u8>>;
@@ -494,6 +735,21 @@ impl From<LedUsbPackets> for UsbPackets {
 mod tests {
     use crate::advanced::{LedCode, LedUsbPackets, UsbPackets};
 
+    #[test]
+    fn led_from_key_code() {
+        assert_eq!(LedCode::from_key_code(30), &[LedCode::A]);
+        assert_eq!(LedCode::from_key_code(57)[0], LedCode::Spacebar);
+        // The numpad uses the LEDs of the digits and symbols
+        assert_eq!(LedCode::from_key_code(71), &[LedCode::N7]);
+        assert_eq!(LedCode::from_key_code(82), &[LedCode::N0]);
+        assert_eq!(LedCode::from_key_code(83), &[LedCode::NumPadDel]);
+        assert_eq!(LedCode::from_key_code(74), &[LedCode::Hyphen]);
+        assert_eq!(LedCode::from_key_code(98), &[LedCode::FwdSlash]);
+        assert_eq!(LedCode::from_key_code(96), &[LedCode::NumPadEnter]);
+        assert!(LedCode::from_key_code(0).is_empty());
+        assert!(LedCode::from_key_code(70).is_empty());
+    }
+
     macro_rules! colour_check_zoned {
         ($zone:expr, $pkt_idx_start:expr) => {
             let mut zone = LedUsbPackets::new_zoned(true);
@@ -0,0 +1,227 @@
+//! Stacks of effects blended over each other. An LED with no effect in a layer
+//! shows the layers below it.
+
+use serde::{Deserialize, Serialize};
+
+use super::{colour_mix, Effect, OFF};
+use crate::advanced::LedCode;
+use crate::Colour;
+
+/// How a layer's colour is combined with the colour below it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum BlendMode {
+    /// The layer's colour covers the one below
+    #[default]
+    Replace,
+    /// Each channel is added to the one below, capped at 255
+    Add,
+    /// Each channel is multiplied by the one below, darkening it
+    Multiply,
+    /// The layer's colour is mixed over the one below with the brightest of
+    /// its channels as the alpha, on top of the layer's opacity. An LED which
+    /// is off in the layer shows the one below.
+    AlphaOver,
+    /// Each channel is the brighter of the layer's and the one below, so an
+    /// LED which is off in the layer shows the one below
+    Lighten,
+}
+
+impl BlendMode {
+    pub fn blend(&self, below: Colour, above: Colour) -> Colour {
+        let channels = |f: fn(u8, u8) -> u8| Colour {
+            r: f(below.r, above.r),
+            g: f(below.g, above.g),
+            b: f(below.b, above.b),
+        };
+        match self {
+            BlendMode::Replace => above,
+            BlendMode::Add => channels(u8::saturating_add),
+            BlendMode::Multiply => channels(|b, a| ((b as u16 * a as u16 + 127) / 255) as u8),
+            BlendMode::AlphaOver => {
+                let alpha = above.r.max(above.g).max(above.b) as f32 / 255.0;
+                colour_mix(below, above, alpha)
+            }
+            BlendMode::Lighten => channels(u8::max),
+        }
+    }
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Layer {
+    #[serde(default)]
+    pub blend: BlendMode,
+    /// How much the blended colour shows over the layers below, 0.0-1.0
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    pub effects: Vec<Effect>,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Self {
+            blend: BlendMode::Replace,
+            opacity: 1.0,
+            effects: Vec::new(),
+        }
+    }
+}
+
+impl Layer {
+    pub fn new(blend: BlendMode, opacity: f32) -> Self {
+        Self {
+            blend,
+            opacity,
+            effects: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, effect: Effect) {
+        self.effects.push(effect);
+    }
+
+    /// Blend this layer over `colours`
+    pub(crate) fn blend_over(&self, colours: &mut Vec<(LedCode, Colour)>) {
+        blend_effects(&self.effects, self.blend, self.opacity, colours);
+    }
+}
+
+/// Blend the colours of `effects` over `colours`, adding any LEDs not in it.
+/// Within `effects` the last effect for an LED wins.
+pub(crate) fn blend_effects(
+    effects: &[Effect],
+    blend: BlendMode,
+    opacity: f32,
+    colours: &mut Vec<(LedCode, Colour)>,
+) {
+    let mut layer: Vec<(LedCode, Colour)> = Vec::new();
+    for effect in effects {
+        let led = effect.led();
+        match layer.iter_mut().find(|(l, _)| *l == led) {
+            Some((_, colour)) => *colour = effect.colour(),
+            None => layer.push((led, effect.colour())),
+        }
+    }
+
+    for (led, above) in layer {
+        match colours.iter_mut().find(|(l, _)| *l == led) {
+            Some((_, below)) => *below = colour_mix(*below, blend.blend(*below, above), opacity),
+            None => colours.push((led, colour_mix(OFF, blend.blend(OFF, above), opacity))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlendMode, Layer};
+    use crate::advanced::LedCode;
+    use crate::effects::{AdvancedEffects, Effect, Static, OFF};
+    use crate::layouts::KeyLayout;
+    use crate::Colour;
+
+    const RED: Colour = Colour { r: 200, g: 0, b: 0 };
+    const BLUE: Colour = Colour { r: 0, g: 0, b: 100 };
+
+    #[test]
+    fn blend_modes() {
+        let grey = Colour {
+            r: 128,
+            g: 128,
+            b: 128,
+        };
+        assert_eq!(BlendMode::Replace.blend(RED, BLUE), BLUE);
+        assert_eq!(
+            BlendMode::Add.blend(RED, grey),
+            Colour {
+                r: 255,
+                g: 128,
+                b: 128
+            }
+        );
+        assert_eq!(
+            BlendMode::Multiply.blend(RED, grey),
+            Colour { r: 100, g: 0, b: 0 }
+        );
+        // Off shows below, full brightness covers it and half is half way
+        assert_eq!(
+            BlendMode::AlphaOver.blend(RED, Colour { r: 0, g: 0, b: 0 }),
+            RED
+        );
+        assert_eq!(
+            BlendMode::AlphaOver.blend(RED, Colour { r: 0, g: 255, b: 0 }),
+            Colour { r: 0, g: 255, b: 0 }
+        );
+        assert_eq!(
+            BlendMode::AlphaOver.blend(BLUE, Colour { r: 0, g: 128, b: 0 }),
+            Colour { r: 0, g: 64, b: 50 }
+        );
+        // Off shows below, each brighter channel covers it
+        assert_eq!(
+            BlendMode::Lighten.blend(RED, Colour { r: 0, g: 0, b: 0 }),
+            RED
+        );
+        assert_eq!(
+            BlendMode::Lighten.blend(
+                RED,
+                Colour {
+                    r: 100,
+                    g: 255,
+                    b: 0
+                }
+            ),
+            Colour {
+                r: 200,
+                g: 255,
+                b: 0
+            }
+        );
+    }
+
+    #[test]
+    fn layers_stack() {
+        let layout = KeyLayout::default_layout();
+        let mut seq = AdvancedEffects::new(false);
+        seq.push(Effect::Static(Static::new(LedCode::D, RED)));
+        seq.push(Effect::Static(Static::new(LedCode::F, RED)));
+
+        let mut add = Layer::new(BlendMode::Add, 1.0);
+        add.push(Effect::Static(Static::new(LedCode::F, BLUE)));
+        // Only in a layer, blended over off
+        add.push(Effect::Static(Static::new(LedCode::G, BLUE)));
+        seq.push_layer(add);
+
+        let mut half = Layer::new(BlendMode::Replace, 0.5);
+        half.push(Effect::Static(Static::new(LedCode::D, BLUE)));
+        seq.push_layer(half);
+
+        // Half bright at half opacity covers a quarter, off covers nothing
+        let mut alpha = Layer::new(BlendMode::AlphaOver, 0.5);
+        alpha.push(Effect::Static(Static::new(LedCode::D, OFF)));
+        alpha.push(Effect::Static(Static::new(
+            LedCode::G,
+            Colour { r: 0, g: 128, b: 0 },
+        )));
+        seq.push_layer(alpha);
+
+        let s = ron::ser::to_string(&seq).unwrap();
+        let mut seq: AdvancedEffects = ron::from_str(&s).unwrap();
+
+        seq.next_state(&layout);
+        let packets = seq.create_packets();
+        // D, F, G
+        assert_eq!(packets[5][30..33], [100, 0, 50]);
+        assert_eq!(packets[5][33..36], [200, 0, 100]);
+        assert_eq!(packets[5][36..39], [0, 32, 75]);
+
+        // Configs without layers still load
+        let old: AdvancedEffects = ron::from_str(
+            "(effects: [Static((led: F, colour: (r: 1, g: 2, b: 3)))], zoned: false)",
+        )
+        .unwrap();
+        assert_eq!(old.create_packets()[5][33..36], [1, 2, 3]);
+    }
+}
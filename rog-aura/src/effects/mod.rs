@@ -12,6 +12,20 @@ pub use breathe::*;
 mod static_;
 pub use static_::*;
 
+#[cfg(feature = "scripting")]
+mod scripted;
+#[cfg(feature = "scripting")]
+pub use scripted::{ScriptProgram, Scripted};
+
+mod reactive;
+pub use reactive::*;
+
+mod spatial;
+pub use spatial::*;
+
+mod layer;
+pub use layer::*;
+
 use crate::advanced::{LedCode, LedUsbPackets, UsbPackets};
 use crate::layouts::KeyLayout;
 use crate::Colour;
@@ -63,12 +77,54 @@ pub(crate) trait EffectState {
     fn get_led(&self) -> LedCode;
 
     fn set_led(&mut self, address: LedCode);
+
+    /// A key on the keyboard was pressed or released. Every effect gets every
+    /// key, not only the one for its own LED.
+    fn key_event(&mut self, _led: LedCode, _pressed: bool) {}
+}
+
+/// An LED which is off. `Colour::default()` is red.
+pub const OFF: Colour = Colour { r: 0, g: 0, b: 0 };
+
+/// The position of the LED in the range 0.0-1.0 across the keyboard, or
+/// `None` if the layout doesn't have it
+pub(crate) fn scaled_position(layout: &KeyLayout, led: LedCode) -> Option<(f32, f32)> {
+    layout
+        .led_positions_scaled()
+        .into_iter()
+        .find(|(l, ..)| *l == led)
+        .map(|(_, x, y)| (x, y))
+}
+
+/// The colour with each channel multiplied by `amount`, 0.0-1.0
+pub(crate) fn colour_scaled(colour: Colour, amount: f32) -> Colour {
+    let amount = amount.clamp(0.0, 1.0);
+    Colour {
+        r: (colour.r as f32 * amount).round() as u8,
+        g: (colour.g as f32 * amount).round() as u8,
+        b: (colour.b as f32 * amount).round() as u8,
+    }
+}
+
+/// Mix from colour `a` at 0.0 to `b` at 1.0
+pub fn colour_mix(a: Colour, b: Colour, amount: f32) -> Colour {
+    let amount = amount.clamp(0.0, 1.0);
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    Colour {
+        r: mix(a.r, b.r),
+        g: mix(a.g, b.g),
+        b: mix(a.b, b.b),
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Default)]
 pub struct AdvancedEffects {
+    /// The base layer, the last effect for an LED wins
     effects: Vec<Effect>,
     zoned: bool,
+    /// Blended over the base effects in order
+    #[serde(default)]
+    layers: Vec<Layer>,
 }
 
 impl AdvancedEffects {
@@ -77,9 +133,28 @@ impl AdvancedEffects {
         Self {
             effects: Default::default(),
             zoned,
+            layers: Default::default(),
         }
     }
 
+    /// Add a layer over the others
+    #[inline]
+    pub fn push_layer(&mut self, layer: Layer) {
+        self.layers.push(layer);
+    }
+
+    #[inline]
+    pub fn layers_mut(&mut self) -> &mut Vec<Layer> {
+        &mut self.layers
+    }
+
+    /// Every effect, the base ones then those of each layer
+    fn all_effects_mut(&mut self) -> impl Iterator<Item = &mut Effect> {
+        self.effects
+            .iter_mut()
+            .chain(self.layers.iter_mut().flat_map(|l| l.effects.iter_mut()))
+    }
+
     #[inline]
     pub fn push(&mut self, action: Effect) {
         self.effects.push(action);
@@ -101,8 +176,29 @@ impl AdvancedEffects {
         None
     }
 
+    /// Compile the scripts of any `Effect::Scripted`, relative script paths
+    /// are found in `dir`. Each script file is compiled once.
+    #[cfg(feature = "scripting")]
+    pub fn load_scripts(&mut self, dir: &std::path::Path) -> Result<(), crate::error::Error> {
+        scripted::load_scripts(
+            self.all_effects_mut().filter_map(|e| match e {
+                Effect::Scripted(s) => Some(s),
+                _ => None,
+            }),
+            dir,
+        )
+    }
+
+    /// Pass a key press or release to every effect. Reactive effects act on
+    /// it in the next `next_state()`.
+    pub fn key_event(&mut self, led: LedCode, pressed: bool) {
+        for effect in self.all_effects_mut() {
+            effect.key_event(led, pressed);
+        }
+    }
+
     pub fn next_state(&mut self, layout: &KeyLayout) {
-        for effect in &mut self.effects {
+        for effect in self.all_effects_mut() {
             effect.next_state(layout);
         }
     }
@@ -116,18 +212,24 @@ impl AdvancedEffects {
             LedUsbPackets::new_per_key()
         };
 
-        for effect in &self.effects {
-            let c = effect.colour();
-            usb_packets.set(effect.led(), c.r, c.g, c.b);
+        let mut colours = Vec::new();
+        layer::blend_effects(&self.effects, BlendMode::Replace, 1.0, &mut colours);
+        for layer in &self.layers {
+            layer.blend_over(&mut colours);
+        }
+        for (led, c) in colours {
+            usb_packets.set(led, c.r, c.g, c.b);
         }
         usb_packets.into()
     }
 }
 
 // how to be lazy
+/// Any fields given, such as a cached position, are reset to their default
+/// when the LED is changed
 #[macro_export]
 macro_rules! effect_state_impl {
-    () => {
+    ($($reset:ident),*) => {
         fn get_colour(&self) -> $crate::Colour {
             self.colour
         }
@@ -139,39 +241,47 @@ macro_rules! effect_state_impl {
         /// Change the led type
         fn set_led(&mut self, address: $crate::advanced::LedCode) {
             self.led = address;
+            $(self.$reset = Default::default();)*
         }
     };
 }
 
 /// A helper macro to quickly add new effects to the matching on `Effect`
 macro_rules! effect_impl {
-    ($($effect:ident),*) => {
+    ($($(#[$meta:meta])* $effect:ident),*) => {
         impl Effect {
             /// Get the type of LED set
             pub fn led(&self) -> $crate::advanced::LedCode {
                 match self {
-                    $(Effect::$effect(c) => c.get_led(),)*
+                    $($(#[$meta])* Effect::$effect(c) => c.get_led(),)*
                 }
             }
 
             /// Change the led type (can be used to change location of the effect)
             pub fn set_led(&mut self, address: $crate::advanced::LedCode) {
                 match self {
-                    $(Effect::$effect(c) => c.set_led(address),)*
+                    $($(#[$meta])* Effect::$effect(c) => c.set_led(address),)*
                 }
             }
 
             /// Calculate the next state of the effect
             pub fn next_state(&mut self, layout: &KeyLayout) {
                 match self {
-                    $(Effect::$effect(c) => c.next_colour_state(layout),)*
+                    $($(#[$meta])* Effect::$effect(c) => c.next_colour_state(layout),)*
+                }
+            }
+
+            /// Pass a key press or release to the effect
+            pub fn key_event(&mut self, led: $crate::advanced::LedCode, pressed: bool) {
+                match self {
+                    $($(#[$meta])* Effect::$effect(c) => c.key_event(led, pressed),)*
                 }
             }
 
             /// Get the calculated colour
             pub fn colour(&self) -> $crate::Colour {
                 match self {
-                    $(Effect::$effect(c) => c.get_colour(),)*
+                    $($(#[$meta])* Effect::$effect(c) => c.get_colour(),)*
                 }
             }
         }
@@ -188,6 +298,15 @@ pub enum Effect {
     Breathe(Breathe),
     DoomFlicker(DoomFlicker),
     DoomLightFlash(DoomLightFlash),
+    #[cfg(feature = "scripting")]
+    Scripted(Scripted),
+    PressFade(PressFade),
+    PressRipple(PressRipple),
+    Heatmap(Heatmap),
+    Wave(Wave),
+    Ripple(Ripple),
+    GradientSweep(GradientSweep),
+    Rain(Rain),
 }
 
 impl Default for Effect {
@@ -196,7 +315,21 @@ impl Default for Effect {
     }
 }
 
-effect_impl!(Static, Breathe, DoomFlicker, DoomLightFlash);
+effect_impl!(
+    Static,
+    Breathe,
+    DoomFlicker,
+    DoomLightFlash,
+    #[cfg(feature = "scripting")]
+    Scripted,
+    PressFade,
+    PressRipple,
+    Heatmap,
+    Wave,
+    Ripple,
+    GradientSweep,
+    Rain
+);
 
 #[cfg(test)]
 mod tests {
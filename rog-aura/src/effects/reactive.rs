@@ -0,0 +1,303 @@
+//! Effects which react to keys being pressed. Each gets every key event
+//! through `AdvancedEffects::key_event()` and acts on them on the next frame.
+
+use serde::{Deserialize, Serialize};
+
+use super::{colour_mix, colour_scaled, EffectState, OFF};
+use crate::advanced::LedCode;
+use crate::layouts::KeyLayout;
+use crate::{effect_state_impl, Colour};
+
+/// Most ripples one LED keeps track of at once, the oldest are dropped
+const MAX_RIPPLES: usize = 16;
+
+/// Lights up while the key is held, then fades out
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PressFade {
+    led: LedCode,
+    start_colour: Colour,
+    /// Frames to fade out over after the key is released
+    fade_frames: u32,
+    #[serde(skip)]
+    pressed: bool,
+    /// The key was pressed since the last frame, catches presses shorter than
+    /// a frame
+    #[serde(skip)]
+    triggered: bool,
+    #[serde(skip)]
+    frames_left: u32,
+    #[serde(skip)]
+    colour: Colour,
+}
+
+impl PressFade {
+    pub fn new(address: LedCode, colour: Colour, fade_frames: u32) -> Self {
+        Self {
+            led: address,
+            start_colour: colour,
+            fade_frames,
+            pressed: false,
+            triggered: false,
+            frames_left: 0,
+            colour: OFF,
+        }
+    }
+}
+
+impl EffectState for PressFade {
+    effect_state_impl!();
+
+    fn next_colour_state(&mut self, _layout: &KeyLayout) {
+        if self.pressed || self.triggered {
+            self.triggered = false;
+            self.frames_left = self.fade_frames;
+            self.colour = self.start_colour;
+        } else if self.frames_left > 0 {
+            self.frames_left -= 1;
+            self.colour = colour_scaled(
+                self.start_colour,
+                self.frames_left as f32 / self.fade_frames as f32,
+            );
+        } else {
+            self.colour = OFF;
+        }
+    }
+
+    fn key_event(&mut self, led: LedCode, pressed: bool) {
+        if led == self.led {
+            self.triggered |= pressed;
+            self.pressed = pressed;
+        }
+    }
+}
+
+/// A ring of colour which spreads out across the keyboard from each key
+/// pressed. Put one on every LED the ripple should cross.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PressRipple {
+    led: LedCode,
+    start_colour: Colour,
+    /// How far the ring moves each frame, in key widths
+    speed: f32,
+    /// The width of the ring in key widths
+    width: f32,
+    #[serde(skip)]
+    colour: Colour,
+    /// Keys pressed since the last frame
+    #[serde(skip)]
+    pressed: Vec<LedCode>,
+    /// Distance from this LED to the key pressed, and the radius of the ring
+    #[serde(skip)]
+    ripples: Vec<(f32, f32)>,
+}
+
+impl PressRipple {
+    pub fn new(address: LedCode, colour: Colour, speed: f32, width: f32) -> Self {
+        Self {
+            led: address,
+            start_colour: colour,
+            speed,
+            width,
+            colour: OFF,
+            pressed: Vec::new(),
+            ripples: Vec::new(),
+        }
+    }
+}
+
+impl EffectState for PressRipple {
+    effect_state_impl!();
+
+    fn next_colour_state(&mut self, layout: &KeyLayout) {
+        if !self.pressed.is_empty() {
+            let positions = layout.led_positions();
+            let find = |led: LedCode| {
+                positions
+                    .iter()
+                    .find(|(l, ..)| *l == led)
+                    .map(|(_, x, y)| (*x, *y))
+            };
+            if let Some((x, y)) = find(self.led) {
+                for led in self.pressed.drain(..) {
+                    if let Some((px, py)) = find(led) {
+                        self.ripples
+                            .push((((x - px).powi(2) + (y - py).powi(2)).sqrt(), 0.0));
+                    }
+                }
+                let excess = self.ripples.len().saturating_sub(MAX_RIPPLES);
+                self.ripples.drain(..excess);
+            }
+            self.pressed.clear();
+        }
+
+        let width = self.width.max(f32::EPSILON);
+        let mut brightness: f32 = 0.0;
+        for (distance, radius) in &self.ripples {
+            brightness = brightness.max(1.0 - (distance - radius).abs() / width);
+        }
+        self.colour = colour_scaled(self.start_colour, brightness);
+
+        let speed = self.speed;
+        // Drop the rings which have passed this LED
+        self.ripples.retain_mut(|(distance, radius)| {
+            *radius += speed;
+            *radius < *distance + width
+        });
+    }
+
+    fn key_event(&mut self, led: LedCode, pressed: bool) {
+        if pressed && self.pressed.len() < MAX_RIPPLES {
+            self.pressed.push(led);
+        }
+    }
+}
+
+/// Warms up each time the key is pressed and slowly cools down, so the keys
+/// used most stand out
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Heatmap {
+    led: LedCode,
+    /// The colour with no presses
+    cold_colour: Colour,
+    /// The colour at full heat
+    hot_colour: Colour,
+    /// Heat added by each press, where 1.0 is full heat
+    press_heat: f32,
+    /// Frames to cool from full heat to cold
+    cool_frames: u32,
+    #[serde(skip)]
+    heat: f32,
+    /// Presses since the last frame
+    #[serde(skip)]
+    presses: u32,
+    #[serde(skip)]
+    colour: Colour,
+}
+
+impl Heatmap {
+    pub fn new(
+        address: LedCode,
+        cold_colour: Colour,
+        hot_colour: Colour,
+        press_heat: f32,
+        cool_frames: u32,
+    ) -> Self {
+        Self {
+            led: address,
+            cold_colour,
+            hot_colour,
+            press_heat,
+            cool_frames,
+            heat: 0.0,
+            presses: 0,
+            colour: cold_colour,
+        }
+    }
+}
+
+impl EffectState for Heatmap {
+    effect_state_impl!();
+
+    fn next_colour_state(&mut self, _layout: &KeyLayout) {
+        self.heat = (self.heat + self.presses as f32 * self.press_heat).min(1.0);
+        self.presses = 0;
+        self.colour = colour_mix(self.cold_colour, self.hot_colour, self.heat);
+        self.heat = (self.heat - 1.0 / self.cool_frames.max(1) as f32).max(0.0);
+    }
+
+    fn key_event(&mut self, led: LedCode, pressed: bool) {
+        if pressed && led == self.led {
+            self.presses += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Heatmap, PressFade, PressRipple};
+    use crate::advanced::LedCode;
+    use crate::effects::{AdvancedEffects, Effect, EffectState, OFF};
+    use crate::layouts::KeyLayout;
+    use crate::Colour;
+
+    const RED: Colour = Colour { r: 200, g: 0, b: 0 };
+
+    #[test]
+    fn fade_after_press() {
+        let layout = KeyLayout::default_layout();
+        let mut fade = PressFade::new(LedCode::F, RED, 4);
+        fade.next_colour_state(&layout);
+        assert_eq!(fade.get_colour(), OFF);
+
+        // Other keys are ignored
+        fade.key_event(LedCode::G, true);
+        fade.next_colour_state(&layout);
+        assert_eq!(fade.get_colour(), OFF);
+
+        // A press and release between frames still lights the key
+        fade.key_event(LedCode::F, true);
+        fade.key_event(LedCode::F, false);
+        fade.next_colour_state(&layout);
+        assert_eq!(fade.get_colour(), RED);
+        fade.next_colour_state(&layout);
+        assert_eq!(fade.get_colour(), Colour { r: 150, g: 0, b: 0 });
+        for _ in 0..3 {
+            fade.next_colour_state(&layout);
+        }
+        assert_eq!(fade.get_colour(), OFF);
+    }
+
+    #[test]
+    fn ripple_from_press() {
+        let layout = KeyLayout::default_layout();
+        let mut seq = AdvancedEffects::new(false);
+        // F is 1.2 key widths from D and 2.4 from S
+        for led in [LedCode::S, LedCode::D, LedCode::F] {
+            seq.push(Effect::PressRipple(PressRipple::new(led, RED, 1.2, 1.2)));
+        }
+        seq.key_event(LedCode::F, true);
+        let colours = |seq: &AdvancedEffects| {
+            let packets = seq.create_packets();
+            // S, D, F
+            [packets[5][27], packets[5][30], packets[5][33]]
+        };
+
+        seq.next_state(&layout);
+        assert_eq!(colours(&seq), [0, 0, 200]);
+        seq.next_state(&layout);
+        assert_eq!(colours(&seq), [0, 200, 0]);
+        seq.next_state(&layout);
+        assert_eq!(colours(&seq), [200, 0, 0]);
+        seq.next_state(&layout);
+        assert_eq!(colours(&seq), [0, 0, 0]);
+    }
+
+    #[test]
+    fn heatmap_warms_and_cools() {
+        let layout = KeyLayout::default_layout();
+        let hot = Colour { r: 255, g: 0, b: 0 };
+        let cold = Colour { r: 0, g: 0, b: 255 };
+        let mut heat = Heatmap::new(LedCode::F, cold, hot, 0.5, 4);
+        heat.next_colour_state(&layout);
+        assert_eq!(heat.get_colour(), cold);
+
+        heat.key_event(LedCode::F, true);
+        heat.key_event(LedCode::F, false);
+        heat.key_event(LedCode::F, true);
+        heat.next_colour_state(&layout);
+        assert_eq!(heat.get_colour(), hot);
+        heat.next_colour_state(&layout);
+        assert_eq!(
+            heat.get_colour(),
+            Colour {
+                r: 191,
+                g: 0,
+                b: 64
+            }
+        );
+        for _ in 0..4 {
+            heat.next_colour_state(&layout);
+        }
+        assert_eq!(heat.get_colour(), cold);
+    }
+}
@@ -0,0 +1,386 @@
+//! An effect whose colour is calculated by a small [Rhai](https://rhai.rs)
+//! script, so effects can be tried without building any Rust.
+//!
+//! The script is run for the LED once per frame and must evaluate to the
+//! colour as an array of `[r, g, b]` in the range 0-255. It can read:
+//!
+//! - `time`: seconds since the effect started
+//! - `frame`: the number of frames since the effect started
+//! - `x`, `y`: the centre of the LED on the keyboard in the range 0.0-1.0,
+//!   starting from the top left
+//! - `led`: the name of the LED, such as `"Esc"`
+//! - `pressed`: true while the key is held down
+//! - `since_press`: seconds since the key was last pressed, or -1.0 if it
+//!   hasn't been
+//!
+//! and use `hsv(hue, saturation, value)` to get an `[r, g, b]` array from a hue
+//! of 0.0-360.0 and a saturation and value of 0.0-1.0.
+//!
+//! Scripts can't touch files or anything else outside the script, and are
+//! limited in how much they can do each frame so that a runaway loop can't
+//! hang the keyboard.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use log::{debug, warn};
+use rhai::{Array, Dynamic, Engine, Scope, AST, FLOAT, INT};
+use serde::{Deserialize, Serialize};
+
+use super::{scaled_position, EffectState, OFF};
+use crate::advanced::LedCode;
+use crate::error::Error;
+use crate::layouts::KeyLayout;
+use crate::{effect_state_impl, Colour};
+
+/// The most operations a script may run for one LED in one frame
+const MAX_OPERATIONS: u64 = 10_000;
+
+/// A compiled script, shared by every effect which uses the same file
+#[derive(Debug)]
+pub struct ScriptProgram {
+    engine: Engine,
+    ast: AST,
+}
+
+impl ScriptProgram {
+    /// Compile the script. `name` is only used for errors.
+    pub fn compile(name: &str, source: &str) -> Result<Self, Error> {
+        let engine = Self::engine();
+        let ast = engine
+            .compile(source)
+            .map_err(|e| Error::Script(name.to_owned(), e.to_string()))?;
+        Ok(Self { engine, ast })
+    }
+
+    /// Read and compile a script file
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| Error::IoPath(path.to_string_lossy().to_string(), e))?;
+        Self::compile(&path.to_string_lossy(), &source)
+    }
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(16);
+        engine.set_max_expr_depths(32, 32);
+        engine.set_max_string_size(256);
+        engine.set_max_array_size(256);
+        engine.set_max_map_size(256);
+        engine.on_print(|s| debug!("Aura script: {s}"));
+        engine.on_debug(|s, _, pos| debug!("Aura script {pos}: {s}"));
+        engine.register_fn("hsv", |h: FLOAT, s: FLOAT, v: FLOAT| -> Array {
+            let c = hsv_to_colour(h, s, v);
+            vec![
+                Dynamic::from(c.r as INT),
+                Dynamic::from(c.g as INT),
+                Dynamic::from(c.b as INT),
+            ]
+        });
+        engine
+    }
+}
+
+fn hsv_to_colour(hue: FLOAT, saturation: FLOAT, value: FLOAT) -> Colour {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let saturation = saturation.clamp(0.0, 1.0);
+    let value = value.clamp(0.0, 1.0);
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    let to_u8 = |c: FLOAT| ((c + m) * 255.0).round() as u8;
+    Colour {
+        r: to_u8(r),
+        g: to_u8(g),
+        b: to_u8(b),
+    }
+}
+
+/// Take the colour from what a script returned, an array of three numbers
+fn colour_from_result(result: Dynamic) -> Option<Colour> {
+    let array = result.try_cast::<Array>()?;
+    if array.len() != 3 {
+        return None;
+    }
+    let mut channels = [0u8; 3];
+    for (channel, value) in channels.iter_mut().zip(array) {
+        let value = value
+            .as_int()
+            .map(|i| i as FLOAT)
+            .or_else(|_| value.as_float())
+            .ok()?;
+        *channel = value.clamp(0.0, 255.0) as u8;
+    }
+    Some(Colour {
+        r: channels[0],
+        g: channels[1],
+        b: channels[2],
+    })
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Scripted {
+    led: LedCode,
+    /// The script file. A relative path is found in the directory given to
+    /// `load()`.
+    script: PathBuf,
+    /// Temporary data to help keep state
+    #[serde(skip)]
+    colour: Colour,
+    #[serde(skip)]
+    program: Option<Arc<ScriptProgram>>,
+    #[serde(skip)]
+    started: Option<Instant>,
+    #[serde(skip)]
+    frame: INT,
+    /// The scaled position of the LED, found on the first frame
+    #[serde(skip)]
+    position: Option<(f32, f32)>,
+    #[serde(skip)]
+    pressed: bool,
+    #[serde(skip)]
+    last_press: Option<Instant>,
+    /// The script has errored, stops the log filling up with the same error
+    #[serde(skip)]
+    failed: bool,
+}
+
+impl Scripted {
+    pub fn new(address: LedCode, script: impl Into<PathBuf>) -> Self {
+        Self {
+            led: address,
+            script: script.into(),
+            colour: OFF,
+            program: None,
+            started: None,
+            frame: 0,
+            position: None,
+            pressed: false,
+            last_press: None,
+            failed: false,
+        }
+    }
+
+    pub fn script(&self) -> &Path {
+        &self.script
+    }
+
+    /// The path of the script, relative paths are found in `dir`
+    pub fn script_path(&self, dir: &Path) -> PathBuf {
+        dir.join(&self.script)
+    }
+
+    /// Read and compile the script. Until this is done the LED stays off.
+    pub fn load(&mut self, dir: &Path) -> Result<(), Error> {
+        let program = ScriptProgram::load(&self.script_path(dir))?;
+        self.set_program(Arc::new(program));
+        Ok(())
+    }
+
+    /// Use an already compiled script, such as one shared with other LEDs
+    pub fn set_program(&mut self, program: Arc<ScriptProgram>) {
+        self.program = Some(program);
+        self.failed = false;
+    }
+
+    /// Set the state of the key this effect is on
+    pub fn set_pressed(&mut self, pressed: bool) {
+        if pressed && !self.pressed {
+            self.last_press = Some(Instant::now());
+        }
+        self.pressed = pressed;
+    }
+
+    fn scope(&mut self, layout: &KeyLayout) -> Scope<'static> {
+        let led = self.led;
+        let (x, y) = *self
+            .position
+            .get_or_insert_with(|| scaled_position(layout, led).unwrap_or_default());
+        let time = self
+            .started
+            .get_or_insert_with(Instant::now)
+            .elapsed()
+            .as_secs_f64();
+        let since_press = self.last_press.map_or(-1.0, |t| t.elapsed().as_secs_f64());
+
+        let mut scope = Scope::new();
+        scope.push_constant("time", time as FLOAT);
+        scope.push_constant("frame", self.frame);
+        scope.push_constant("x", x as FLOAT);
+        scope.push_constant("y", y as FLOAT);
+        scope.push_constant("led", format!("{led:?}"));
+        scope.push_constant("pressed", self.pressed);
+        scope.push_constant("since_press", since_press as FLOAT);
+        scope
+    }
+}
+
+impl EffectState for Scripted {
+    effect_state_impl!(position);
+
+    fn next_colour_state(&mut self, layout: &KeyLayout) {
+        let Some(program) = self.program.clone() else {
+            return;
+        };
+        let mut scope = self.scope(layout);
+        self.frame += 1;
+
+        let colour = program
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &program.ast)
+            .map_err(|e| e.to_string())
+            .and_then(|result| {
+                colour_from_result(result)
+                    .ok_or_else(|| "the result was not an array of [r, g, b]".to_owned())
+            });
+        match colour {
+            Ok(colour) => {
+                self.colour = colour;
+                self.failed = false;
+            }
+            Err(e) => {
+                if !self.failed {
+                    warn!(
+                        "Aura script {:?} on {:?} failed: {e}",
+                        self.script, self.led
+                    );
+                    self.failed = true;
+                }
+            }
+        }
+    }
+
+    fn key_event(&mut self, led: LedCode, pressed: bool) {
+        if led == self.led {
+            self.set_pressed(pressed);
+        }
+    }
+}
+
+/// Compile each script used by the effects once, and share it between the
+/// effects which use it. A script which fails doesn't stop the others loading,
+/// the first error is returned after all are tried.
+pub(crate) fn load_scripts<'a>(
+    scripted: impl Iterator<Item = &'a mut Scripted>,
+    dir: &Path,
+) -> Result<(), Error> {
+    let mut programs: HashMap<PathBuf, Option<Arc<ScriptProgram>>> = HashMap::new();
+    let mut error = None;
+    for effect in scripted {
+        let path = effect.script_path(dir);
+        let program = programs.entry(path).or_insert_with_key(|path| {
+            ScriptProgram::load(path)
+                .map_err(|e| {
+                    warn!("{e}");
+                    error.get_or_insert(e);
+                })
+                .ok()
+                .map(Arc::new)
+        });
+        if let Some(program) = program {
+            effect.set_program(program.clone());
+        }
+    }
+    error.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{hsv_to_colour, ScriptProgram, Scripted};
+    use crate::advanced::LedCode;
+    use crate::effects::{EffectState, OFF};
+    use crate::layouts::KeyLayout;
+    use crate::Colour;
+
+    fn scripted(led: LedCode, source: &str) -> Scripted {
+        let mut effect = Scripted::new(led, "test.rhai");
+        effect.set_program(Arc::new(ScriptProgram::compile("test", source).unwrap()));
+        effect
+    }
+
+    #[test]
+    fn script_inputs() {
+        let layout = KeyLayout::default_layout();
+
+        // Esc is the first key of the first row
+        let mut effect = scripted(
+            LedCode::Esc,
+            "if led == \"Esc\" && x < 0.1 && y < 0.2 { [frame * 10, 255.7, -3] } else { [0, 0, 0] \
+             }",
+        );
+        effect.next_colour_state(&layout);
+        assert_eq!(effect.get_colour(), Colour { r: 0, g: 255, b: 0 });
+        effect.next_colour_state(&layout);
+        assert_eq!(
+            effect.get_colour(),
+            Colour {
+                r: 10,
+                g: 255,
+                b: 0
+            }
+        );
+
+        // Moving the effect moves the position the script sees
+        effect.set_led(LedCode::F12);
+        effect.next_colour_state(&layout);
+        assert_eq!(effect.get_colour(), Colour { r: 0, g: 0, b: 0 });
+
+        let mut effect = scripted(
+            LedCode::F,
+            "if pressed { hsv(120.0, 1.0, 1.0) } else if since_press >= 0.0 { [1, 1, 1] } else { \
+             [0, 0, 0] }",
+        );
+        effect.set_pressed(true);
+        effect.next_colour_state(&layout);
+        assert_eq!(effect.get_colour(), Colour { r: 0, g: 255, b: 0 });
+        effect.set_pressed(false);
+        effect.next_colour_state(&layout);
+        assert_eq!(effect.get_colour(), Colour { r: 1, g: 1, b: 1 });
+    }
+
+    #[test]
+    fn script_errors_keep_colour() {
+        let layout = KeyLayout::default_layout();
+        assert!(ScriptProgram::compile("bad", "[1, 2,").is_err());
+
+        let mut effect = scripted(LedCode::F, "if frame == 0 { [9, 9, 9] } else { [1, 2] }");
+        effect.next_colour_state(&layout);
+        effect.next_colour_state(&layout);
+        assert_eq!(effect.get_colour(), Colour { r: 9, g: 9, b: 9 });
+
+        // Runaway scripts are stopped
+        let mut effect = scripted(LedCode::F, "loop {}");
+        effect.next_colour_state(&layout);
+        assert_eq!(effect.get_colour(), OFF);
+    }
+
+    #[test]
+    fn hsv_colours() {
+        assert_eq!(hsv_to_colour(0.0, 1.0, 1.0), Colour { r: 255, g: 0, b: 0 });
+        assert_eq!(
+            hsv_to_colour(240.0, 1.0, 0.5),
+            Colour { r: 0, g: 0, b: 128 }
+        );
+        assert_eq!(
+            hsv_to_colour(-60.0, 0.0, 1.0),
+            Colour {
+                r: 255,
+                g: 255,
+                b: 255
+            }
+        );
+    }
+}
@@ -0,0 +1,390 @@
+//! Effects coloured by where the LED is on the keyboard. Positions are scaled
+//! to 0.0-1.0 across the keys so an effect looks the same on every layout.
+//! Put one on each LED the effect should cover.
+
+use std::f32::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+use super::{colour_mix, colour_scaled, scaled_position, EffectState, OFF, RNDTABLE};
+use crate::advanced::LedCode;
+use crate::layouts::KeyLayout;
+use crate::{effect_state_impl, Colour};
+
+/// Find the position of the LED on the first frame. The outer `None` is not
+/// looked for yet, the inner `None` is not on this layout.
+fn position_of(
+    position: &mut Option<Option<(f32, f32)>>,
+    layout: &KeyLayout,
+    led: LedCode,
+) -> Option<(f32, f32)> {
+    *position.get_or_insert_with(|| scaled_position(layout, led))
+}
+
+/// Bands of two colours moving across the keyboard in a direction
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Wave {
+    led: LedCode,
+    start_colour1: Colour,
+    start_colour2: Colour,
+    /// Direction the wave moves in degrees, 0 is to the right and 90 is down
+    angle: f32,
+    /// Distance from one band of `start_colour1` to the next, where 1.0 is the
+    /// width of the keyboard
+    wavelength: f32,
+    /// Distance moved each frame, where 1.0 is the width of the keyboard
+    speed: f32,
+    #[serde(skip)]
+    colour: Colour,
+    #[serde(skip)]
+    frame: u32,
+    #[serde(skip)]
+    position: Option<Option<(f32, f32)>>,
+}
+
+impl Wave {
+    pub fn new(
+        address: LedCode,
+        colour1: Colour,
+        colour2: Colour,
+        angle: f32,
+        wavelength: f32,
+        speed: f32,
+    ) -> Self {
+        Self {
+            led: address,
+            start_colour1: colour1,
+            start_colour2: colour2,
+            angle,
+            wavelength,
+            speed,
+            colour: colour1,
+            frame: 0,
+            position: None,
+        }
+    }
+}
+
+impl EffectState for Wave {
+    effect_state_impl!(position);
+
+    fn next_colour_state(&mut self, layout: &KeyLayout) {
+        let Some((x, y)) = position_of(&mut self.position, layout, self.led) else {
+            self.colour = OFF;
+            return;
+        };
+        let angle = self.angle.to_radians();
+        let distance = x * angle.cos() + y * angle.sin();
+        let phase = (distance - self.frame as f32 * self.speed) / self.wavelength.max(0.01);
+        self.colour = colour_mix(
+            self.start_colour2,
+            self.start_colour1,
+            0.5 + 0.5 * (phase * TAU).cos(),
+        );
+        self.frame = self.frame.wrapping_add(1);
+    }
+}
+
+/// Rings spreading out from a point on the keyboard
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Ripple {
+    led: LedCode,
+    start_colour: Colour,
+    /// Where the rings start from, 0.0-1.0 across and down the keyboard
+    centre_x: f32,
+    centre_y: f32,
+    /// Distance between rings, where 1.0 is the width of the keyboard
+    spacing: f32,
+    /// Width of a ring, where 1.0 is the width of the keyboard
+    width: f32,
+    /// Distance the rings move each frame
+    speed: f32,
+    #[serde(skip)]
+    colour: Colour,
+    #[serde(skip)]
+    frame: u32,
+    #[serde(skip)]
+    position: Option<Option<(f32, f32)>>,
+}
+
+impl Ripple {
+    pub fn new(
+        address: LedCode,
+        colour: Colour,
+        centre: (f32, f32),
+        spacing: f32,
+        width: f32,
+        speed: f32,
+    ) -> Self {
+        Self {
+            led: address,
+            start_colour: colour,
+            centre_x: centre.0,
+            centre_y: centre.1,
+            spacing,
+            width,
+            speed,
+            colour: OFF,
+            frame: 0,
+            position: None,
+        }
+    }
+}
+
+impl EffectState for Ripple {
+    effect_state_impl!(position);
+
+    fn next_colour_state(&mut self, layout: &KeyLayout) {
+        let Some((x, y)) = position_of(&mut self.position, layout, self.led) else {
+            self.colour = OFF;
+            return;
+        };
+        let spacing = self.spacing.max(0.01);
+        let distance = ((x - self.centre_x).powi(2) + (y - self.centre_y).powi(2)).sqrt();
+        let from_ring = (distance - self.frame as f32 * self.speed).rem_euclid(spacing);
+        let from_ring = from_ring.min(spacing - from_ring);
+        self.colour = colour_scaled(
+            self.start_colour,
+            1.0 - from_ring / self.width.max(f32::EPSILON),
+        );
+        self.frame = self.frame.wrapping_add(1);
+    }
+}
+
+/// A diagonal gradient between two colours sweeping from the top left to the
+/// bottom right
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GradientSweep {
+    led: LedCode,
+    start_colour1: Colour,
+    start_colour2: Colour,
+    /// Distance moved each frame, where 1.0 is corner to corner
+    speed: f32,
+    #[serde(skip)]
+    colour: Colour,
+    #[serde(skip)]
+    frame: u32,
+    #[serde(skip)]
+    position: Option<Option<(f32, f32)>>,
+}
+
+impl GradientSweep {
+    pub fn new(address: LedCode, colour1: Colour, colour2: Colour, speed: f32) -> Self {
+        Self {
+            led: address,
+            start_colour1: colour1,
+            start_colour2: colour2,
+            speed,
+            colour: colour1,
+            frame: 0,
+            position: None,
+        }
+    }
+}
+
+impl EffectState for GradientSweep {
+    effect_state_impl!(position);
+
+    fn next_colour_state(&mut self, layout: &KeyLayout) {
+        let Some((x, y)) = position_of(&mut self.position, layout, self.led) else {
+            self.colour = OFF;
+            return;
+        };
+        // Goes from colour1 to colour2 and back so there is no hard edge
+        let along = ((x + y) / 2.0 - self.frame as f32 * self.speed).rem_euclid(1.0);
+        self.colour = colour_mix(
+            self.start_colour1,
+            self.start_colour2,
+            1.0 - (2.0 * along - 1.0).abs(),
+        );
+        self.frame = self.frame.wrapping_add(1);
+    }
+}
+
+/// Drops falling down the keyboard with a fading trail, each column at its own
+/// speed
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Rain {
+    led: LedCode,
+    start_colour: Colour,
+    /// The keys are split into this many columns across the keyboard
+    columns: u8,
+    /// Distance a drop falls each frame, where 1.0 is the height of the
+    /// keyboard. Each column falls at between half and one and a half times
+    /// this.
+    speed: f32,
+    /// Length of the trail, where 1.0 is the height of the keyboard
+    length: f32,
+    #[serde(skip)]
+    colour: Colour,
+    #[serde(skip)]
+    frame: u32,
+    #[serde(skip)]
+    position: Option<Option<(f32, f32)>>,
+}
+
+impl Rain {
+    pub fn new(address: LedCode, colour: Colour, columns: u8, speed: f32, length: f32) -> Self {
+        Self {
+            led: address,
+            start_colour: colour,
+            columns,
+            speed,
+            length,
+            colour: OFF,
+            frame: 0,
+            position: None,
+        }
+    }
+}
+
+impl EffectState for Rain {
+    effect_state_impl!(position);
+
+    fn next_colour_state(&mut self, layout: &KeyLayout) {
+        let Some((x, y)) = position_of(&mut self.position, layout, self.led) else {
+            self.colour = OFF;
+            return;
+        };
+        // Every LED in a column has to agree on where the drop is, so the
+        // column's speed and start come from the table rather than p_random()
+        let column = (x * self.columns.saturating_sub(1) as f32).round() as usize;
+        let offset = RNDTABLE[(column * 37) & 0xff] as f32 / 255.0;
+        let length = self.length.max(0.01);
+        // The drop falls past the bottom until its trail is gone
+        let fall = 1.0 + length;
+        let head =
+            (self.frame as f32 * self.speed * (0.5 + offset) + offset * fall).rem_euclid(fall);
+        let behind = head - y;
+        self.colour = if (0.0..length).contains(&behind) {
+            colour_scaled(self.start_colour, 1.0 - behind / length)
+        } else {
+            OFF
+        };
+        self.frame = self.frame.wrapping_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{GradientSweep, Rain, Ripple, Wave};
+    use crate::advanced::LedCode;
+    use crate::effects::{EffectState, OFF};
+    use crate::layouts::KeyLayout;
+    use crate::Colour;
+
+    const RED: Colour = Colour { r: 255, g: 0, b: 0 };
+    const BLUE: Colour = Colour { r: 0, g: 0, b: 255 };
+
+    #[test]
+    fn wave_moves_across() {
+        let layout = KeyLayout::default_layout();
+        // Esc is on the left edge and Backspace the right, half a wavelength
+        // apart
+        let mut left = Wave::new(LedCode::Esc, RED, BLUE, 0.0, 2.0, 1.0);
+        let mut right = Wave::new(LedCode::Backspace, RED, BLUE, 0.0, 2.0, 1.0);
+        left.next_colour_state(&layout);
+        right.next_colour_state(&layout);
+        assert_eq!(left.get_colour(), RED);
+        assert_eq!(right.get_colour(), BLUE);
+        // The wave moves one keyboard width each frame
+        left.next_colour_state(&layout);
+        right.next_colour_state(&layout);
+        assert_eq!(left.get_colour(), BLUE);
+        assert_eq!(right.get_colour(), RED);
+
+        let mut missing = Wave::new(LedCode::LidLogo, RED, BLUE, 0.0, 1.0, 0.1);
+        missing.next_colour_state(&layout);
+        assert_eq!(missing.get_colour(), OFF);
+        // Moving the effect finds the new position
+        missing.set_led(LedCode::Esc);
+        missing.next_colour_state(&layout);
+        assert_ne!(missing.get_colour(), OFF);
+    }
+
+    #[test]
+    fn ripple_and_sweep() {
+        let layout = KeyLayout::default_layout();
+        let mut centre = Ripple::new(LedCode::Esc, RED, (0.0, 0.0), 0.5, 0.1, 0.25);
+        centre.next_colour_state(&layout);
+        assert_eq!(centre.get_colour(), RED);
+        centre.next_colour_state(&layout);
+        assert_eq!(centre.get_colour(), OFF);
+        centre.next_colour_state(&layout);
+        assert_eq!(centre.get_colour(), RED);
+
+        let mut corner = GradientSweep::new(LedCode::Esc, RED, BLUE, 0.25);
+        corner.next_colour_state(&layout);
+        assert_eq!(corner.get_colour(), RED);
+        corner.next_colour_state(&layout);
+        assert_eq!(
+            corner.get_colour(),
+            Colour {
+                r: 128,
+                g: 0,
+                b: 128
+            }
+        );
+        corner.next_colour_state(&layout);
+        assert_eq!(corner.get_colour(), BLUE);
+    }
+
+    #[test]
+    fn rain_falls_down_a_column() {
+        let layout = KeyLayout::default_layout();
+        let mut column: Vec<Rain> = [LedCode::Esc, LedCode::Tilde, LedCode::Tab, LedCode::Caps]
+            .into_iter()
+            .map(|led| Rain::new(led, BLUE, 4, 0.1, 0.5))
+            .collect();
+        let mut lit = [false; 4];
+        for _ in 0..100 {
+            let mut brightness = Vec::new();
+            for key in &mut column {
+                key.next_colour_state(&layout);
+                brightness.push(key.get_colour().b);
+            }
+            // The drop is below its trail, so lit keys get brighter going down
+            for i in 1..brightness.len() {
+                if brightness[i - 1] > 0 && brightness[i] > 0 {
+                    assert!(brightness[i] > brightness[i - 1], "{brightness:?}");
+                }
+                lit[i] |= brightness[i] > 0;
+            }
+            lit[0] |= brightness[0] > 0;
+        }
+        assert_eq!(lit, [true; 4]);
+    }
+
+    #[test]
+    fn same_on_every_layout() {
+        let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("data/layouts");
+        for path in std::fs::read_dir(dir).unwrap() {
+            let path = path.unwrap().path();
+            if !path.to_string_lossy().contains("per-key") {
+                continue;
+            }
+            let layout: KeyLayout =
+                ron::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            let positions = layout.led_positions_scaled();
+            let find = |led| {
+                positions
+                    .iter()
+                    .find(|(l, ..)| *l == led)
+                    .map(|(_, x, y)| (*x, *y))
+                    .unwrap()
+            };
+            let (ax, ay) = find(LedCode::A);
+            let (lx, ly) = find(LedCode::L);
+            let (qx, qy) = find(LedCode::Q);
+            assert!(ax < lx && (ay - ly).abs() < 0.01, "{path:?}");
+            assert!(qy < ay, "{path:?}");
+            assert!(qx < 0.2 && lx > 0.4 && lx < 0.9, "{path:?}");
+            assert!(positions
+                .iter()
+                .all(|(_, x, y)| (0.0..=1.0).contains(x) && (0.0..=1.0).contains(y)));
+        }
+    }
+}
@@ -277,6 +277,73 @@ impl KeyLayout {
         width
     }
 
+    /// The centre of each LED in key units, with x=0, y=0 at the top left of
+    /// the first row. Positions are taken from the shapes so this works before
+    /// the rows are built.
+    pub fn led_positions(&self) -> Vec<(LedCode, f32, f32)> {
+        let mut positions = Vec::new();
+        let mut y = 0.0;
+        for row in &self.key_rows {
+            y += row.pad_top;
+            let mut x = row.pad_left;
+            let mut height: f32 = 0.0;
+            for (led, shape_name) in &row.row {
+                match self.key_shapes.get(shape_name) {
+                    Some(KeyShape::Led {
+                        width,
+                        height: h,
+                        pad_left,
+                        pad_right,
+                        pad_top,
+                        pad_bottom,
+                    }) => {
+                        x += pad_left;
+                        if !led.is_placeholder() {
+                            positions.push((*led, x + width / 2.0, y + pad_top + h / 2.0));
+                        }
+                        x += width + pad_right;
+                        height = height.max(h + pad_top + pad_bottom);
+                    }
+                    Some(KeyShape::Blank { width, height: h }) => {
+                        x += width;
+                        height = height.max(*h);
+                    }
+                    None => {}
+                }
+            }
+            y += height;
+        }
+        positions
+    }
+
+    /// The centre of each LED scaled to the range 0.0-1.0, from the top left
+    /// key to the bottom right key. Lightbars don't count towards the range so
+    /// that the keys are placed the same on every layout, and are clamped to
+    /// it.
+    pub fn led_positions_scaled(&self) -> Vec<(LedCode, f32, f32)> {
+        let mut positions = self.led_positions();
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for (_, x, y) in positions.iter().filter(|(l, ..)| !l.is_lightbar_zone()) {
+            min_x = min_x.min(*x);
+            min_y = min_y.min(*y);
+            max_x = max_x.max(*x);
+            max_y = max_y.max(*y);
+        }
+        let scale = |v: f32, min: f32, max: f32| {
+            if max > min {
+                ((v - min) / (max - min)).clamp(0.0, 1.0)
+            } else {
+                0.0
+            }
+        };
+        for (_, x, y) in &mut positions {
+            *x = scale(*x, min_x, max_x);
+            *y = scale(*y, min_y, max_y);
+        }
+        positions
+    }
+
     /// Find a layout matching the name in `LaptopLedData` in the provided dir
     pub fn find_layout(led_data: LaptopLedData, mut data_path: PathBuf) -> Result<Self, Error> {
         // TODO: locales
@@ -458,9 +525,28 @@ mod tests {
     use std::io::Read;
     use std::path::PathBuf;
 
+    use crate::advanced::LedCode;
     use crate::aura_detection::LedSupportFile;
     use crate::layouts::KeyLayout;
 
+    #[test]
+    fn led_positions() {
+        let layout = KeyLayout::default_layout();
+        let positions = layout.led_positions();
+        // Row and key padding of 0.1 around keys 1.0 square
+        let find = |led| positions.iter().find(|(l, ..)| *l == led).unwrap();
+        let (_, x, y) = find(LedCode::Esc);
+        assert!((x - 0.7).abs() < 0.001 && (y - 0.7).abs() < 0.001);
+        let (_, x, y) = find(LedCode::Tilde);
+        assert!((x - 0.7).abs() < 0.001 && (y - 2.0).abs() < 0.001);
+        let (_, x, _) = find(LedCode::F1);
+        assert!((x - 1.9).abs() < 0.001);
+
+        for (_, x, y) in layout.led_positions_scaled() {
+            assert!((0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y));
+        }
+    }
+
     #[test]
     fn check_parse_all() {
         const DATA_DIR: &str = env!("CARGO_MANIFEST_DIR");
@@ -1,12 +1,14 @@
 use std::sync::{Arc, Mutex};
 
 use rog_dbus::zbus_fan_curves::FanCurvesProxy;
+use rog_dbus::zbus_platform::PlatformProxy;
 use rog_platform::platform::ThrottlePolicy;
 use rog_profiles::fan_curve_set::CurveData;
 use slint::{ComponentHandle, Model, Weak};
+use zbus::export::futures_util::StreamExt;
 
 use crate::config::Config;
-use crate::{FanPageData, FanType, MainWindow, Node};
+use crate::{FanPageData, FanStatus, FanType, MainWindow, Node};
 
 pub fn update_fan_data(
     handle: Weak<MainWindow>,
@@ -84,6 +86,59 @@ pub fn update_fan_data(
         .unwrap();
 }
 
+fn update_fan_status(handle: &Weak<MainWindow>, status: Vec<rog_profiles::fan_status::FanStatus>) {
+    handle
+        .upgrade_in_event_loop(move |handle| {
+            let global = handle.global::<FanPageData>();
+            global.set_status_available(!status.is_empty());
+            for s in status {
+                let status = FanStatus {
+                    cursor: Node {
+                        x: s.temp as f32,
+                        y: s.pwm as f32,
+                    },
+                    rpm: s.rpm as i32,
+                };
+                match s.fan {
+                    rog_profiles::FanCurvePU::CPU => global.set_cpu_status(status),
+                    rog_profiles::FanCurvePU::GPU => global.set_gpu_status(status),
+                    rog_profiles::FanCurvePU::MID => global.set_mid_status(status),
+                }
+            }
+        })
+        .ok();
+}
+
+/// Follow the active profile and the fan status to place the cursor on the
+/// curve in use
+async fn watch_fan_status(handle: Weak<MainWindow>, fans: FanCurvesProxy<'static>) {
+    let conn = zbus::Connection::system().await.unwrap();
+    let platform = PlatformProxy::new(&conn).await.unwrap();
+
+    let handle_copy = handle.clone();
+    tokio::spawn(async move {
+        let mut x = platform.receive_throttle_thermal_policy_changed().await;
+        while let Some(e) = x.next().await {
+            if let Ok(out) = e.get().await {
+                handle_copy
+                    .upgrade_in_event_loop(move |handle| {
+                        handle
+                            .global::<FanPageData>()
+                            .set_active_profile(out.into());
+                    })
+                    .ok();
+            }
+        }
+    });
+
+    let mut x = fans.receive_fan_status_changed().await;
+    while let Some(e) = x.next().await {
+        if let Ok(out) = e.get().await {
+            update_fan_status(&handle, out);
+        }
+    }
+}
+
 pub fn setup_fan_curve_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
     let handle = ui.as_weak();
 
@@ -101,6 +156,7 @@ pub fn setup_fan_curve_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
             .unwrap();
         let quiet = fans.fan_curve_data(ThrottlePolicy::Quiet).await.unwrap();
         update_fan_data(handle, balanced, perf, quiet);
+        tokio::spawn(watch_fan_status(handle_copy.clone(), fans.clone()));
 
         let handle_next1 = handle_copy.clone();
         handle_copy
@@ -122,6 +178,22 @@ pub fn setup_fan_curve_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
                         update_fan_data(handle_next, balanced, perf, quiet);
                     });
                 });
+                global.on_adjust_curve(|data, factor, degrees| {
+                    let data: Vec<Node> = data.iter().collect();
+                    let mut curve = fan_data_for(FanType::CPU, false, data);
+                    curve.scale(factor);
+                    curve.shift(degrees as i16);
+                    let nodes: Vec<Node> = curve
+                        .temp
+                        .iter()
+                        .zip(curve.pwm.iter())
+                        .map(|(x, y)| Node {
+                            x: *x as f32,
+                            y: *y as f32,
+                        })
+                        .collect();
+                    nodes.as_slice().into()
+                });
                 global.on_set_fan_data(move |fan, profile, enabled, data| {
                     let fans = fans.clone();
                     let data: Vec<Node> = data.iter().collect();
@@ -2,7 +2,7 @@ use std::sync::{Arc, Mutex};
 
 use rog_dbus::zbus_platform::{PlatformProxy, PlatformProxyBlocking};
 use rog_platform::platform::Properties;
-use slint::ComponentHandle;
+use slint::{ComponentHandle, Weak};
 
 use super::show_toast;
 use crate::config::Config;
@@ -32,11 +32,64 @@ pub fn setup_system_page(ui: &MainWindow, _config: Arc<Mutex<Config>>) {
         ppt_pl2_sppt: sys_props.contains(&Properties::PptPl2Sppt),
         ppt_platform_sppt: sys_props.contains(&Properties::PptPlatformSppt),
         throttle_thermal_policy: sys_props.contains(&Properties::ThrottlePolicy),
+        battery_info: sys_props.contains(&Properties::BatteryInfo),
     };
 
     ui.global::<SystemPageData>().set_available(props);
 }
 
+/// Read all battery properties in to the UI
+async fn set_battery_props(handle: &Weak<MainWindow>, platform: &PlatformProxy<'static>) {
+    let hours_mins = |secs: u32| format!("{}h {:02}m", secs / 3600, secs % 3600 / 60);
+    let (Ok(capacity), Ok(status)) = (
+        platform.battery_capacity().await,
+        platform.battery_status().await,
+    ) else {
+        return;
+    };
+    let power_now = platform.battery_power_now().await.unwrap_or_default();
+    let wear = platform.battery_wear().await.unwrap_or_default();
+    let cycle_count = platform.battery_cycle_count().await.unwrap_or_default();
+    let empty = platform.battery_time_to_empty().await.unwrap_or_default();
+    let full = platform.battery_time_to_full().await.unwrap_or_default();
+    let remaining = if empty != 0 {
+        format!("{} to empty", hours_mins(empty))
+    } else if full != 0 {
+        format!("{} to full", hours_mins(full))
+    } else {
+        String::new()
+    };
+
+    handle
+        .upgrade_in_event_loop(move |handle| {
+            let data = handle.global::<SystemPageData>();
+            data.set_battery_capacity(capacity as i32);
+            data.set_battery_status(format!("{status:?}").into());
+            data.set_battery_power_now(power_now as f32 / 1000.0);
+            data.set_battery_time_remaining(remaining.into());
+            data.set_battery_wear(wear as f32);
+            data.set_battery_cycle_count(cycle_count as i32);
+        })
+        .ok();
+}
+
+/// Update all battery properties in the UI when any one of them changes
+macro_rules! watch_battery_prop {
+    ($handle:ident, $platform:ident, $prop:ident) => {
+        concat_idents::concat_idents!(receive = receive_, $prop, _changed {
+            let handle = $handle.clone();
+            let platform = $platform.clone();
+            tokio::spawn(async move {
+                use zbus::export::futures_util::StreamExt;
+                let mut changed = platform.receive().await;
+                while changed.next().await.is_some() {
+                    set_battery_props(&handle, &platform).await;
+                }
+            });
+        });
+    };
+}
+
 pub fn setup_system_page_callbacks(ui: &MainWindow, _states: Arc<Mutex<Config>>) {
     // This tokio spawn exists only to prevent blocking the UI, and to enable use of
     // async zbus interfaces
@@ -73,6 +126,15 @@ pub fn setup_system_page_callbacks(ui: &MainWindow, _states: Arc<Mutex<Config>>)
         set_ui_props_async!(handle, platform, SystemPageData, nv_dynamic_boost);
         set_ui_props_async!(handle, platform, SystemPageData, nv_temp_target);
 
+        set_battery_props(&handle, &platform).await;
+        watch_battery_prop!(handle, platform, battery_capacity);
+        watch_battery_prop!(handle, platform, battery_status);
+        watch_battery_prop!(handle, platform, battery_power_now);
+        watch_battery_prop!(handle, platform, battery_wear);
+        watch_battery_prop!(handle, platform, battery_cycle_count);
+        watch_battery_prop!(handle, platform, battery_time_to_empty);
+        watch_battery_prop!(handle, platform, battery_time_to_full);
+
         let sys_props = platform.supported_properties().await.unwrap();
         log::debug!("Available system properties: {sys_props:?}");
         let props = AvailableSystemProperties {
@@ -92,6 +154,7 @@ pub fn setup_system_page_callbacks(ui: &MainWindow, _states: Arc<Mutex<Config>>)
             ppt_pl2_sppt: sys_props.contains(&Properties::PptPl2Sppt),
             ppt_platform_sppt: sys_props.contains(&Properties::PptPlatformSppt),
             throttle_thermal_policy: sys_props.contains(&Properties::ThrottlePolicy),
+            battery_info: sys_props.contains(&Properties::BatteryInfo),
         };
 
         handle
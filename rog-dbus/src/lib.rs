@@ -15,6 +15,7 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub struct DbusProxiesBlocking<'a> {
     anime: zbus_anime::AnimeProxyBlocking<'a>,
     led: zbus_aura::AuraProxyBlocking<'a>,
+    scenes: zbus_aura::AuraScenesProxyBlocking<'a>,
     profile: zbus_fan_curves::FanCurvesProxyBlocking<'a>,
     rog_bios: zbus_platform::PlatformProxyBlocking<'a>,
 }
@@ -28,6 +29,7 @@ impl<'a> DbusProxiesBlocking<'a> {
             DbusProxiesBlocking {
                 anime: zbus_anime::AnimeProxyBlocking::new(&conn)?,
                 led: zbus_aura::AuraProxyBlocking::new(&conn)?,
+                scenes: zbus_aura::AuraScenesProxyBlocking::new(&conn)?,
                 profile: zbus_fan_curves::FanCurvesProxyBlocking::new(&conn)?,
                 rog_bios: zbus_platform::PlatformProxyBlocking::new(&conn)?,
             },
@@ -43,6 +45,10 @@ impl<'a> DbusProxiesBlocking<'a> {
         &self.led
     }
 
+    pub fn aura_scenes(&self) -> &zbus_aura::AuraScenesProxyBlocking<'a> {
+        &self.scenes
+    }
+
     pub fn fan_curves(&self) -> &zbus_fan_curves::FanCurvesProxyBlocking<'a> {
         &self.profile
     }
@@ -72,6 +78,7 @@ impl<'a> RogDbusClientBlocking<'a> {
 pub struct DbusProxies<'a> {
     anime: zbus_anime::AnimeProxy<'a>,
     led: zbus_aura::AuraProxy<'a>,
+    scenes: zbus_aura::AuraScenesProxy<'a>,
     profile: zbus_fan_curves::FanCurvesProxy<'a>,
     rog_bios: zbus_platform::PlatformProxy<'a>,
 }
@@ -85,6 +92,7 @@ impl<'a> DbusProxies<'a> {
             DbusProxies {
                 anime: zbus_anime::AnimeProxy::new(&conn).await?,
                 led: zbus_aura::AuraProxy::new(&conn).await?,
+                scenes: zbus_aura::AuraScenesProxy::new(&conn).await?,
                 profile: zbus_fan_curves::FanCurvesProxy::new(&conn).await?,
                 rog_bios: zbus_platform::PlatformProxy::new(&conn).await?,
             },
@@ -100,6 +108,10 @@ impl<'a> DbusProxies<'a> {
         &self.led
     }
 
+    pub fn aura_scenes(&self) -> &zbus_aura::AuraScenesProxy<'a> {
+        &self.scenes
+    }
+
     pub fn profile(&self) -> &zbus_fan_curves::FanCurvesProxy<'a> {
         &self.profile
     }
@@ -22,6 +22,7 @@
 
 use std::collections::BTreeMap;
 
+use asusd::ctrl_aura::config::AuraRule;
 use rog_aura::advanced::UsbPackets;
 use rog_aura::aura_detection::PowerZones;
 use rog_aura::usb::{AuraDevice, AuraPowerDev};
@@ -40,9 +41,21 @@ trait Aura {
     /// AllModeData method
     fn all_mode_data(&self) -> zbus::Result<BTreeMap<AuraModeNum, AuraEffect>>;
 
+    /// AuraRules method
+    fn aura_rules(&self) -> zbus::Result<Vec<AuraRule>>;
+
     /// DirectAddressingRaw method
     fn direct_addressing_raw(&self, data: UsbPackets) -> zbus::Result<()>;
 
+    /// Show an effect without asusd storing it in the config
+    fn direct_effect(&self, effect: &AuraEffect) -> zbus::Result<()>;
+
+    /// RemoveAuraRule method
+    fn remove_aura_rule(&self, name: &str) -> zbus::Result<()>;
+
+    /// Add a rule, or replace the one with the same name
+    fn set_aura_rule(&self, rule: &AuraRule) -> zbus::Result<()>;
+
     /// Brightness property
     #[zbus(property)]
     fn brightness(&self) -> zbus::Result<LedBrightness>;
@@ -88,6 +101,25 @@ trait Aura {
     fn supported_power_zones(&self) -> zbus::Result<Vec<PowerZones>>;
 }
 
+#[proxy(
+    interface = "org.asuslinux.AuraScenes",
+    default_service = "org.asuslinux.Daemon",
+    default_path = "/org/asuslinux"
+)]
+trait AuraScenes {
+    /// Set the lighting of all Aura devices from a scene
+    fn apply_aura_scene(&self, name: &str) -> zbus::Result<()>;
+
+    /// AuraScenes method
+    fn aura_scenes(&self) -> zbus::Result<Vec<String>>;
+
+    /// DeleteAuraScene method
+    fn delete_aura_scene(&self, name: &str) -> zbus::Result<()>;
+
+    /// Save the current lighting of all Aura devices as a scene
+    fn save_aura_scene(&self, name: &str) -> zbus::Result<()>;
+}
+
 pub struct AuraProxyPerkey<'a>(AuraProxyBlocking<'a>);
 
 impl<'a> AuraProxyPerkey<'a> {
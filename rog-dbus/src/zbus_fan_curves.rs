@@ -21,7 +21,12 @@
 //! …consequently `zbus-xmlgen` did not generate code for the above interfaces.
 
 use rog_platform::platform::ThrottlePolicy;
+use rog_platform::power::PowerSource;
+use rog_profiles::fan_calibration::FanCalibration;
+use rog_profiles::fan_curve_export::FanCurveFormat;
+use rog_profiles::fan_curve_preset::FanCurvePreset;
 use rog_profiles::fan_curve_set::CurveData;
+use rog_profiles::fan_status::FanStatus;
 use rog_profiles::FanCurvePU;
 use zbus::proxy;
 
@@ -31,9 +36,69 @@ use zbus::proxy;
     default_path = "/org/asuslinux"
 )]
 trait FanCurves {
+    /// Write the curves of a preset now
+    fn apply_fan_curve_preset(&self, name: &str) -> zbus::Result<()>;
+
+    /// Use a preset in place of the curves of the profile
+    fn bind_fan_curve_preset(&self, name: &str, profile: ThrottlePolicy) -> zbus::Result<()>;
+
+    /// Create a named preset from a copy of the curves used by the profile
+    fn create_fan_curve_preset(&self, name: &str, profile: ThrottlePolicy) -> zbus::Result<()>;
+
+    /// Start measuring the speed of each fan over a range of fan powers
+    fn calibrate_fans(&self) -> zbus::Result<()>;
+
+    /// Use the AC curves of the profile on battery too
+    fn clear_battery_fan_curves(&self, profile: ThrottlePolicy) -> zbus::Result<()>;
+
+    /// DeleteFanCurvePreset method
+    fn delete_fan_curve_preset(&self, name: &str) -> zbus::Result<()>;
+
+    /// Export all fan curves and presets
+    fn export_fan_curves(&self, format: FanCurveFormat) -> zbus::Result<String>;
+
     /// Get the fan-curve data for the currently active PlatformProfile
     fn fan_curve_data(&self, profile: ThrottlePolicy) -> zbus::Result<Vec<CurveData>>;
 
+    /// Get the fan curves used for the profile on AC or on battery
+    fn fan_curve_data_for_power(
+        &self,
+        profile: ThrottlePolicy,
+        power: PowerSource,
+    ) -> zbus::Result<Vec<CurveData>>;
+
+    /// FanCalibrating property
+    #[zbus(property)]
+    fn fan_calibrating(&self) -> zbus::Result<bool>;
+
+    /// FanCalibration property
+    #[zbus(property)]
+    fn fan_calibration(&self) -> zbus::Result<Vec<FanCalibration>>;
+
+    /// FanCalibrationError property
+    #[zbus(property)]
+    fn fan_calibration_error(&self) -> zbus::Result<String>;
+
+    /// FanCurvePresets method
+    fn fan_curve_presets(&self) -> zbus::Result<Vec<FanCurvePreset>>;
+
+    /// Replace the curves and presets present in the file, leaving the rest
+    /// unchanged
+    fn import_fan_curves(&self, data: &str, format: FanCurveFormat) -> zbus::Result<()>;
+
+    /// FanStatus property
+    #[zbus(property)]
+    fn fan_status(&self) -> zbus::Result<Vec<FanStatus>>;
+
+    /// FanStatusInterval property
+    #[zbus(property)]
+    fn fan_status_interval(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_fan_status_interval(&self, value: u32) -> zbus::Result<()>;
+
+    /// RenameFanCurvePreset method
+    fn rename_fan_curve_preset(&self, name: &str, new_name: &str) -> zbus::Result<()>;
+
     /// Reset the stored (self) and device curve to the defaults of the
     /// platform.
     ///
@@ -48,6 +113,17 @@ trait FanCurves {
     /// currently in if profile == None. Will also activate the fan curve.
     fn set_fan_curve(&self, profile: ThrottlePolicy, curve: CurveData) -> zbus::Result<()>;
 
+    /// Set the fan curve for the profile on AC or on battery
+    fn set_fan_curve_for_power(
+        &self,
+        profile: ThrottlePolicy,
+        power: PowerSource,
+        curve: CurveData,
+    ) -> zbus::Result<()>;
+
+    /// Set the curve of one fan in a preset
+    fn set_preset_fan_curve(&self, name: &str, curve: CurveData) -> zbus::Result<()>;
+
     /// Set a profile fan curve enabled status. Will also activate a fan curve.
     fn set_fan_curves_enabled(&self, profile: ThrottlePolicy, enabled: bool) -> zbus::Result<()>;
 
@@ -59,4 +135,7 @@ trait FanCurves {
         fan: FanCurvePU,
         enabled: bool,
     ) -> zbus::Result<()>;
+
+    /// Return the profile to its own curves
+    fn unbind_fan_curve_preset(&self, profile: ThrottlePolicy) -> zbus::Result<()>;
 }
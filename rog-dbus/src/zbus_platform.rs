@@ -20,8 +20,11 @@
 //!
 //! …consequently `zbus-xmlgen` did not generate code for the above interfaces.
 
+use asusd::charge_limit::ChargeSchedule;
+use asusd::hooks::Hook;
 use rog_platform::cpu::CPUEPP;
 use rog_platform::platform::{GpuMode, Properties, ThrottlePolicy};
+use rog_platform::power::BatteryStatus;
 use zbus::proxy;
 
 #[proxy(
@@ -30,21 +33,94 @@ use zbus::proxy;
     default_path = "/org/asuslinux"
 )]
 trait Platform {
+    /// CancelChargeToFull method
+    fn cancel_charge_to_full(&self) -> zbus::Result<()>;
+
+    /// ChargeSchedules method
+    fn charge_schedules(&self) -> zbus::Result<Vec<ChargeSchedule>>;
+
+    /// ChargeToFullOnce method
+    fn charge_to_full_once(&self, hours: u32) -> zbus::Result<()>;
+
+    /// Hooks method
+    fn hooks(&self) -> zbus::Result<Vec<Hook>>;
+
     /// NextThrottleThermalPolicy method
     fn next_throttle_thermal_policy(&self) -> zbus::Result<()>;
 
+    /// RemoveChargeSchedule method
+    fn remove_charge_schedule(&self, name: &str) -> zbus::Result<()>;
+
+    /// SetChargeSchedule method
+    fn set_charge_schedule(&self, schedule: &ChargeSchedule) -> zbus::Result<()>;
+
     /// SupportedInterfaces method
     fn supported_interfaces(&self) -> zbus::Result<Vec<String>>;
 
     /// SupportedProperties method
     fn supported_properties(&self) -> zbus::Result<Vec<Properties>>;
 
+    /// TestHook method
+    fn test_hook(&self, name: &str) -> zbus::Result<(i32, String)>;
+
+    /// BatteryCapacity property
+    #[zbus(property)]
+    fn battery_capacity(&self) -> zbus::Result<u8>;
+
+    /// BatteryCycleCount property
+    #[zbus(property)]
+    fn battery_cycle_count(&self) -> zbus::Result<u32>;
+
+    /// BatteryEnergyFull property
+    #[zbus(property)]
+    fn battery_energy_full(&self) -> zbus::Result<u32>;
+
+    /// BatteryEnergyFullDesign property
+    #[zbus(property)]
+    fn battery_energy_full_design(&self) -> zbus::Result<u32>;
+
+    /// BatteryPowerNow property
+    #[zbus(property)]
+    fn battery_power_now(&self) -> zbus::Result<u32>;
+
+    /// BatteryStatus property
+    #[zbus(property)]
+    fn battery_status(&self) -> zbus::Result<BatteryStatus>;
+
+    /// BatteryTimeToEmpty property
+    #[zbus(property)]
+    fn battery_time_to_empty(&self) -> zbus::Result<u32>;
+
+    /// BatteryTimeToFull property
+    #[zbus(property)]
+    fn battery_time_to_full(&self) -> zbus::Result<u32>;
+
+    /// BatteryWear property
+    #[zbus(property)]
+    fn battery_wear(&self) -> zbus::Result<f64>;
+
     /// ChargeControlEndThreshold property
     #[zbus(property)]
     fn charge_control_end_threshold(&self) -> zbus::Result<u8>;
     #[zbus(property)]
     fn set_charge_control_end_threshold(&self, value: u8) -> zbus::Result<()>;
 
+    /// ChargeToFullActive property
+    #[zbus(property)]
+    fn charge_to_full_active(&self) -> zbus::Result<bool>;
+
+    /// CpuBoost property
+    #[zbus(property)]
+    fn cpu_boost(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_cpu_boost(&self, value: bool) -> zbus::Result<()>;
+
+    /// CpuMaxFreq property
+    #[zbus(property)]
+    fn cpu_max_freq(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_cpu_max_freq(&self, value: u32) -> zbus::Result<()>;
+
     /// DgpuDisable property
     #[zbus(property)]
     fn dgpu_disable(&self) -> zbus::Result<bool>;
@@ -119,18 +195,66 @@ trait Platform {
     #[zbus(property)]
     fn set_ppt_platform_sppt(&self, value: u8) -> zbus::Result<()>;
 
+    /// Smt property
+    #[zbus(property)]
+    fn smt(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_smt(&self, value: bool) -> zbus::Result<()>;
+
+    /// ThrottleBalancedCpuBoost property
+    #[zbus(property)]
+    fn throttle_balanced_cpu_boost(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_throttle_balanced_cpu_boost(&self, value: bool) -> zbus::Result<()>;
+
+    /// ThrottleBalancedCpuMaxFreq property
+    #[zbus(property)]
+    fn throttle_balanced_cpu_max_freq(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_throttle_balanced_cpu_max_freq(&self, value: u32) -> zbus::Result<()>;
+
     /// ThrottleBalancedEpp property
     #[zbus(property)]
     fn throttle_balanced_epp(&self) -> zbus::Result<CPUEPP>;
     #[zbus(property)]
     fn set_throttle_balanced_epp(&self, epp: CPUEPP) -> zbus::Result<()>;
 
+    /// ThrottleBalancedSmt property
+    #[zbus(property)]
+    fn throttle_balanced_smt(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_throttle_balanced_smt(&self, value: bool) -> zbus::Result<()>;
+
+    /// ThrottlePerformanceCpuBoost property
+    #[zbus(property)]
+    fn throttle_performance_cpu_boost(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_throttle_performance_cpu_boost(&self, value: bool) -> zbus::Result<()>;
+
+    /// ThrottlePerformanceCpuMaxFreq property
+    #[zbus(property)]
+    fn throttle_performance_cpu_max_freq(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_throttle_performance_cpu_max_freq(&self, value: u32) -> zbus::Result<()>;
+
     /// ThrottlePerformanceEpp property
     #[zbus(property)]
     fn throttle_performance_epp(&self) -> zbus::Result<CPUEPP>;
     #[zbus(property)]
     fn set_throttle_performance_epp(&self, epp: CPUEPP) -> zbus::Result<()>;
 
+    /// ThrottlePerformanceSmt property
+    #[zbus(property)]
+    fn throttle_performance_smt(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_throttle_performance_smt(&self, value: bool) -> zbus::Result<()>;
+
+    /// ThrottlePolicyLinkedCpu property
+    #[zbus(property)]
+    fn throttle_policy_linked_cpu(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_throttle_policy_linked_cpu(&self, value: bool) -> zbus::Result<()>;
+
     /// ThrottlePolicyLinkedEpp property
     #[zbus(property)]
     fn throttle_policy_linked_epp(&self) -> zbus::Result<bool>;
@@ -149,12 +273,30 @@ trait Platform {
     #[zbus(property)]
     fn set_throttle_policy_on_battery(&self, throttle_policy: ThrottlePolicy) -> zbus::Result<()>;
 
+    /// ThrottleQuietCpuBoost property
+    #[zbus(property)]
+    fn throttle_quiet_cpu_boost(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_throttle_quiet_cpu_boost(&self, value: bool) -> zbus::Result<()>;
+
+    /// ThrottleQuietCpuMaxFreq property
+    #[zbus(property)]
+    fn throttle_quiet_cpu_max_freq(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_throttle_quiet_cpu_max_freq(&self, value: u32) -> zbus::Result<()>;
+
     /// ThrottleQuietEpp property
     #[zbus(property)]
     fn throttle_quiet_epp(&self) -> zbus::Result<CPUEPP>;
     #[zbus(property)]
     fn set_throttle_quiet_epp(&self, epp: CPUEPP) -> zbus::Result<()>;
 
+    /// ThrottleQuietSmt property
+    #[zbus(property)]
+    fn throttle_quiet_smt(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_throttle_quiet_smt(&self, value: bool) -> zbus::Result<()>;
+
     /// ThrottlePolicy property
     #[zbus(property)]
     fn throttle_thermal_policy(&self) -> zbus::Result<ThrottlePolicy>;
@@ -8,6 +8,7 @@ pub type Result<T> = std::result::Result<T, PlatformError>;
 pub enum PlatformError {
     ParseVendor,
     ParseNum,
+    ParsePowerSource(String),
     Udev(String, std::io::Error),
     USB(rusb::Error),
     Path(String, std::io::Error),
@@ -30,6 +31,9 @@ impl fmt::Display for PlatformError {
         match self {
             PlatformError::ParseVendor => write!(f, "Parse gfx vendor error"),
             PlatformError::ParseNum => write!(f, "Parse number error"),
+            PlatformError::ParsePowerSource(s) => {
+                write!(f, "Invalid power source {s}, expected ac or battery")
+            }
             PlatformError::Udev(deets, error) => write!(f, "udev {}: {}", deets, error),
             PlatformError::USB(error) => write!(f, "usb {}", error),
             PlatformError::Path(path, error) => write!(f, "Path {}: {}", path, error),
@@ -72,6 +76,7 @@ impl From<PlatformError> for FdoErr {
         log::error!("PlatformError: got: {error}");
         match error {
             PlatformError::NotSupported => FdoErr::NotSupported("".to_owned()),
+            PlatformError::ParsePowerSource(_) => FdoErr::InvalidArgs(format!("{error}")),
             _ => FdoErr::Failed(format!("Failed with {error}")),
         }
     }
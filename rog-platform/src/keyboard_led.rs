@@ -1,9 +1,10 @@
 use std::path::PathBuf;
 
-use log::{info, warn};
+use log::info;
 
 use crate::error::{PlatformError, Result};
-use crate::{attr_u8, has_attr, set_attr_u8_array, to_device};
+use crate::sysfs::{sysfs_root, SysfsRoot};
+use crate::{attr_u8, has_attr, set_attr_u8_array};
 
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Clone)]
 pub struct KeyboardLed {
@@ -30,33 +31,18 @@ impl KeyboardLed {
     );
 
     pub fn new() -> Result<Self> {
-        let mut enumerator = udev::Enumerator::new().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("enumerator failed".into(), err)
-        })?;
-
-        enumerator.match_subsystem("leds").map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("match_subsystem failed".into(), err)
-        })?;
-
-        enumerator
-            .match_sysname("asus::kbd_backlight")
-            .map_err(|err| {
-                warn!("{}", err);
-                PlatformError::Udev("match_subsystem failed".into(), err)
-            })?;
+        Self::with_sysfs_root(sysfs_root())
+    }
 
-        if let Some(device) = (enumerator.scan_devices().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("scan_devices failed".into(), err)
-        })?)
-        .next()
+    /// Find the `asus::kbd_backlight` LED device under `root`
+    pub fn with_sysfs_root(root: &SysfsRoot) -> Result<Self> {
+        if let Some(path) = root
+            .scan_subsystem("leds", Some("asus::kbd_backlight"))?
+            .into_iter()
+            .next()
         {
-            info!("Found keyboard LED controls at {:?}", device.sysname());
-            return Ok(Self {
-                path: device.syspath().to_owned(),
-            });
+            info!("Found keyboard LED controls at {:?}", path);
+            return Ok(Self { path });
         }
         Err(PlatformError::MissingFunction(
             "asus::kbd_backlight not found".into(),
@@ -8,97 +8,97 @@ pub mod keyboard_led;
 pub(crate) mod macros;
 pub mod platform;
 pub mod power;
+pub mod sysfs;
 pub mod usb_raw;
 
+use std::fs;
 use std::path::Path;
 
 use error::{PlatformError, Result};
-use udev::Device;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub(crate) fn to_device(sys_path: &Path) -> Result<Device> {
-    Device::from_syspath(sys_path)
-        .map_err(|e| PlatformError::Udev("Couldn't transform syspath to device".to_owned(), e))
-}
-
-pub fn has_attr(device: &Device, attr_name: &str) -> bool {
-    for attr in device.attributes() {
-        if attr.name() == attr_name {
-            return true;
+/// Read the raw attribute at `sys_path/attr_name`. The trailing newline sysfs
+/// adds is removed.
+fn read_attr(sys_path: &Path, attr_name: &str) -> Result<String> {
+    let path = sys_path.join(attr_name);
+    match fs::read_to_string(&path) {
+        Ok(value) => Ok(value.trim_end().to_owned()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(PlatformError::AttrNotFound(attr_name.to_owned()))
         }
+        Err(e) => Err(PlatformError::IoPath(path.to_string_lossy().to_string(), e)),
     }
-    false
 }
 
-pub fn read_attr_bool(device: &Device, attr_name: &str) -> Result<bool> {
-    if let Some(value) = device.attribute_value(attr_name) {
-        let tmp = value.to_string_lossy();
-        if tmp.trim() == "0" {
-            return Ok(false);
-        }
-        return Ok(true);
-    }
-    Err(PlatformError::AttrNotFound(attr_name.to_owned()))
+fn write_attr(sys_path: &Path, attr: &str, value: &str) -> Result<()> {
+    fs::write(sys_path.join(attr), value).map_err(|e| PlatformError::IoPath(attr.into(), e))
 }
 
-pub fn write_attr_bool(device: &mut Device, attr: &str, value: bool) -> Result<()> {
-    device
-        .set_attribute_value(attr, &(value as u8).to_string())
-        .map_err(|e| PlatformError::IoPath(attr.into(), e))
+pub fn has_attr(sys_path: &Path, attr_name: &str) -> bool {
+    sys_path.join(attr_name).exists()
 }
 
-pub fn read_attr_u8(device: &Device, attr_name: &str) -> Result<u8> {
-    if let Some(value) = device.attribute_value(attr_name) {
-        let tmp = value.to_string_lossy();
-        return tmp.parse::<u8>().map_err(|_e| PlatformError::ParseNum);
+pub fn read_attr_bool(sys_path: &Path, attr_name: &str) -> Result<bool> {
+    let tmp = read_attr(sys_path, attr_name)?;
+    if tmp.trim() == "0" {
+        return Ok(false);
     }
-    Err(PlatformError::AttrNotFound(attr_name.to_owned()))
+    Ok(true)
 }
 
-pub fn write_attr_u8(device: &mut Device, attr: &str, value: u8) -> Result<()> {
-    device
-        .set_attribute_value(attr, &(value).to_string())
-        .map_err(|e| PlatformError::IoPath(attr.into(), e))
+pub fn write_attr_bool(sys_path: &Path, attr: &str, value: bool) -> Result<()> {
+    write_attr(sys_path, attr, &(value as u8).to_string())
 }
 
-pub fn read_attr_u8_array(device: &Device, attr_name: &str) -> Result<Vec<u8>> {
-    if let Some(value) = device.attribute_value(attr_name) {
-        let tmp = value.to_string_lossy();
-        let tmp = tmp
-            .split(' ')
-            .map(|v| v.parse::<u8>().unwrap_or(0))
-            .collect();
-        return Ok(tmp);
-    }
-    Err(PlatformError::AttrNotFound(attr_name.to_owned()))
+pub fn read_attr_u8(sys_path: &Path, attr_name: &str) -> Result<u8> {
+    let tmp = read_attr(sys_path, attr_name)?;
+    tmp.trim()
+        .parse::<u8>()
+        .map_err(|_e| PlatformError::ParseNum)
 }
 
-pub fn write_attr_u8_array(device: &mut Device, attr: &str, values: &[u8]) -> Result<()> {
+pub fn write_attr_u8(sys_path: &Path, attr: &str, value: u8) -> Result<()> {
+    write_attr(sys_path, attr, &(value).to_string())
+}
+
+pub fn read_attr_u32(sys_path: &Path, attr_name: &str) -> Result<u32> {
+    let tmp = read_attr(sys_path, attr_name)?;
+    tmp.trim()
+        .parse::<u32>()
+        .map_err(|_e| PlatformError::ParseNum)
+}
+
+pub fn write_attr_u32(sys_path: &Path, attr: &str, value: u32) -> Result<()> {
+    write_attr(sys_path, attr, &(value).to_string())
+}
+
+pub fn read_attr_u8_array(sys_path: &Path, attr_name: &str) -> Result<Vec<u8>> {
+    let tmp = read_attr(sys_path, attr_name)?;
+    let tmp = tmp
+        .split(' ')
+        .map(|v| v.parse::<u8>().unwrap_or(0))
+        .collect();
+    Ok(tmp)
+}
+
+pub fn write_attr_u8_array(sys_path: &Path, attr: &str, values: &[u8]) -> Result<()> {
     let mut tmp = String::new();
     for n in values {
         tmp.push_str(&n.to_string());
         tmp.push(' '); // space padding required
     }
     tmp.pop();
-    device
-        .set_attribute_value(attr, tmp.trim())
-        .map_err(|e| PlatformError::IoPath(attr.into(), e))
+    write_attr(sys_path, attr, tmp.trim())
 }
 
-pub fn read_attr_string(device: &Device, attr_name: &str) -> Result<String> {
-    if let Some(value) = device.attribute_value(attr_name) {
-        let tmp = value.to_string_lossy().to_string();
-        return Ok(tmp);
-    }
-    Err(PlatformError::AttrNotFound(attr_name.to_owned()))
+pub fn read_attr_string(sys_path: &Path, attr_name: &str) -> Result<String> {
+    read_attr(sys_path, attr_name)
 }
 
-pub fn write_attr_string(device: &mut Device, attr: &str, value: &str) -> Result<()> {
+pub fn write_attr_string(sys_path: &Path, attr: &str, value: &str) -> Result<()> {
     let tmp = value.trim();
-    device
-        .set_attribute_value(attr, tmp)
-        .map_err(|e| PlatformError::IoPath(attr.into(), e))
+    write_attr(sys_path, attr, tmp)
 }
 
 #[cfg(test)]
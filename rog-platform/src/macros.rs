@@ -4,10 +4,7 @@ macro_rules! has_attr {
         concat_idents::concat_idents!(fn_name = has_, $attr_name {
             $(#[$attr])*
             pub fn fn_name(&self) -> bool {
-                match to_device(&self.$item) {
-                    Ok(p) => $crate::has_attr(&p, $attr_name),
-                    Err(_) => false,
-                }
+                $crate::has_attr(&self.$item, $attr_name)
             }
         });
     };
@@ -45,7 +42,7 @@ macro_rules! get_attr_bool {
         concat_idents::concat_idents!(fn_name = get_, $attr_name {
             $(#[$attr])*
             pub fn fn_name(&self) -> Result<bool> {
-                $crate::read_attr_bool(&to_device(&self.$item)?, $attr_name)
+                $crate::read_attr_bool(&self.$item, $attr_name)
             }
         });
     };
@@ -57,7 +54,7 @@ macro_rules! set_attr_bool {
         concat_idents::concat_idents!(fn_name = set_, $attr_name {
             $(#[$attr])*
             pub fn fn_name(&self, value: bool) -> Result<()> {
-                $crate::write_attr_bool(&mut to_device(&self.$item)?, $attr_name, value)
+                $crate::write_attr_bool(&self.$item, $attr_name, value)
             }
         });
     };
@@ -79,7 +76,7 @@ macro_rules! get_attr_u8 {
         concat_idents::concat_idents!(fn_name = get_, $attr_name {
             $(#[$attr])*
             pub fn fn_name(&self) -> Result<u8> {
-                $crate::read_attr_u8(&to_device(&self.$item)?, $attr_name)
+                $crate::read_attr_u8(&self.$item, $attr_name)
             }
         });
     };
@@ -92,7 +89,7 @@ macro_rules! set_attr_u8 {
         concat_idents::concat_idents!(fn_name = set_, $attr_name {
             $(#[$attr])*
             pub fn fn_name(&self, value: u8) -> Result<()> {
-                $crate::write_attr_u8(&mut to_device(&self.$item)?, $attr_name, value)
+                $crate::write_attr_u8(&self.$item, $attr_name, value)
             }
         });
     };
@@ -114,7 +111,7 @@ macro_rules! get_attr_u8_array {
         concat_idents::concat_idents!(fn_name = get_, $attr_name {
             $(#[$attr])*
             pub fn fn_name(&self) -> Result<Vec<u8>> {
-                $crate::read_attr_u8_array(&to_device(&self.$item)?, $attr_name)
+                $crate::read_attr_u8_array(&self.$item, $attr_name)
             }
         });
     };
@@ -126,7 +123,7 @@ macro_rules! set_attr_u8_array {
         concat_idents::concat_idents!(fn_name = set_, $attr_name {
             $(#[$attr])*
             pub fn fn_name(&self, values: &[u8]) -> Result<()> {
-                $crate::write_attr_u8_array(&mut to_device(&self.$item)?, $attr_name, values)
+                $crate::write_attr_u8_array(&self.$item, $attr_name, values)
             }
         });
     };
@@ -148,7 +145,7 @@ macro_rules! get_attr_string {
         concat_idents::concat_idents!(fn_name = get_, $attr_name {
             $(#[$attr])*
             pub fn fn_name(&self) -> Result<String> {
-                $crate::read_attr_string(&to_device(&self.$item)?, $attr_name)
+                $crate::read_attr_string(&self.$item, $attr_name)
             }
         });
     };
@@ -160,7 +157,7 @@ macro_rules! set_attr_string {
         concat_idents::concat_idents!(fn_name = set_, $attr_name {
             $(#[$attr])*
             pub fn fn_name(&self, values: &str) -> Result<()> {
-                $crate::write_attr_string(&mut to_device(&self.$item)?, $attr_name, values)
+                $crate::write_attr_string(&self.$item, $attr_name, values)
             }
         });
     };
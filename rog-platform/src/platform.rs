@@ -8,7 +8,8 @@ use typeshare::typeshare;
 use zbus::zvariant::{OwnedValue, Type, Value};
 
 use crate::error::{PlatformError, Result};
-use crate::{attr_bool, attr_string, attr_u8, to_device};
+use crate::sysfs::{sysfs_root, SysfsRoot};
+use crate::{attr_bool, attr_string, attr_u8};
 
 /// The "platform" device provides access to things like:
 /// - `dgpu_disable`
@@ -106,29 +107,20 @@ impl RogPlatform {
     );
 
     pub fn new() -> Result<Self> {
-        let mut enumerator = udev::Enumerator::new().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("enumerator failed".into(), err)
-        })?;
-        enumerator.match_subsystem("platform").map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("match_subsystem failed".into(), err)
-        })?;
-        enumerator.match_sysname("asus-nb-wmi").map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("match_subsystem failed".into(), err)
-        })?;
-
-        if let Some(device) = (enumerator.scan_devices().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("scan_devices failed".into(), err)
-        })?)
-        .next()
+        Self::with_sysfs_root(sysfs_root())
+    }
+
+    /// Find the `asus-nb-wmi` platform device under `root`
+    pub fn with_sysfs_root(root: &SysfsRoot) -> Result<Self> {
+        if let Some(path) = root
+            .scan_subsystem("platform", Some("asus-nb-wmi"))?
+            .into_iter()
+            .next()
         {
-            info!("Found platform support at {:?}", device.sysname());
+            info!("Found platform support at {:?}", path);
             return Ok(Self {
-                path: device.syspath().to_owned(),
-                pp_path: PathBuf::from_str("/sys/firmware/acpi").unwrap(),
+                path,
+                pp_path: root.join("/sys/firmware/acpi"),
             });
         }
         Err(PlatformError::MissingFunction(
@@ -375,4 +367,8 @@ pub enum Properties {
     PptPlatformSppt,
     NvDynamicBoost,
     NvTempTarget,
+    CpuBoost,
+    CpuMaxFreq,
+    Smt,
+    BatteryInfo,
 }
@@ -1,9 +1,161 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
-use log::{info, warn};
+use log::info;
+use serde::{Deserialize, Serialize};
+use typeshare::typeshare;
+use zbus::zvariant::{OwnedValue, Type, Value};
 
 use crate::error::{PlatformError, Result};
-use crate::{attr_u8, to_device};
+use crate::sysfs::{sysfs_root, sysname, SysfsRoot};
+use crate::{attr_u8, has_attr, read_attr_string};
+
+/// The `status` attribute of a battery
+#[typeshare]
+#[repr(u32)]
+#[derive(
+    Deserialize,
+    Serialize,
+    Type,
+    Value,
+    OwnedValue,
+    Default,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Clone,
+    Copy,
+)]
+#[zvariant(signature = "u")]
+pub enum BatteryStatus {
+    #[default]
+    Unknown = 0,
+    Charging = 1,
+    Discharging = 2,
+    /// Plugged in but held at the charge limit
+    NotCharging = 3,
+    Full = 4,
+}
+
+impl FromStr for BatteryStatus {
+    type Err = PlatformError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "Unknown" => Ok(Self::Unknown),
+            "Charging" => Ok(Self::Charging),
+            "Discharging" => Ok(Self::Discharging),
+            "Not charging" => Ok(Self::NotCharging),
+            "Full" => Ok(Self::Full),
+            _ => Err(PlatformError::NotSupported),
+        }
+    }
+}
+
+/// Whether the laptop runs on mains power or on battery
+#[typeshare]
+#[derive(Deserialize, Serialize, Type, Default, Debug, PartialEq, Eq, Clone, Copy)]
+#[zvariant(signature = "s")]
+pub enum PowerSource {
+    #[default]
+    Ac,
+    Battery,
+}
+
+impl PowerSource {
+    pub fn from_plugged(plugged: bool) -> Self {
+        if plugged {
+            Self::Ac
+        } else {
+            Self::Battery
+        }
+    }
+}
+
+impl FromStr for PowerSource {
+    type Err = PlatformError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().trim() {
+            "ac" => Ok(Self::Ac),
+            "battery" | "bat" => Ok(Self::Battery),
+            _ => Err(PlatformError::ParsePowerSource(s.to_owned())),
+        }
+    }
+}
+
+/// Battery health and charge state. Energy is in mWh and power in mW, these
+/// are converted from charge and current if the battery doesn't report them.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BatteryInfo {
+    /// Percent charged
+    pub capacity: u8,
+    pub status: BatteryStatus,
+    pub energy_now: u32,
+    pub energy_full: u32,
+    pub energy_full_design: u32,
+    /// `0` if not reported
+    pub cycle_count: u32,
+    /// Always positive, check `status` for the direction
+    pub power_now: u32,
+}
+
+impl BatteryInfo {
+    /// Percentage of the design capacity lost
+    pub fn wear(&self) -> f64 {
+        if self.energy_full_design == 0 {
+            return 0.0;
+        }
+        let health = self.energy_full as f64 / self.energy_full_design as f64;
+        ((1.0 - health) * 100.0).max(0.0)
+    }
+
+    /// Estimated time until empty at the current power draw, if discharging
+    pub fn time_to_empty(&self) -> Option<Duration> {
+        if self.status != BatteryStatus::Discharging || self.power_now == 0 {
+            return None;
+        }
+        Some(hours_to_duration(
+            self.energy_now as f64 / self.power_now as f64,
+        ))
+    }
+
+    /// Estimated time until full at the current charge rate, if charging
+    pub fn time_to_full(&self) -> Option<Duration> {
+        if self.status != BatteryStatus::Charging || self.power_now == 0 {
+            return None;
+        }
+        let remaining = self.energy_full.saturating_sub(self.energy_now);
+        Some(hours_to_duration(remaining as f64 / self.power_now as f64))
+    }
+}
+
+fn hours_to_duration(hours: f64) -> Duration {
+    Duration::from_secs((hours * 3600.0).round() as u64)
+}
+
+/// Read an attribute which may be signed (`current_now` on some drivers)
+fn read_attr_u64_abs(sys_path: &Path, attr_name: &str) -> Result<u64> {
+    read_attr_string(sys_path, attr_name)?
+        .trim()
+        .parse::<i64>()
+        .map(|v| v.unsigned_abs())
+        .map_err(|_| PlatformError::ParseNum)
+}
+
+/// Read an energy attribute in µWh, or the charge equivalent in µAh
+/// converted with the design voltage, as mWh
+fn read_energy_mwh(battery: &Path, name: &str) -> Result<u32> {
+    if has_attr(battery, &format!("energy_{name}")) {
+        return Ok((read_attr_u64_abs(battery, &format!("energy_{name}"))? / 1000) as u32);
+    }
+    let charge = read_attr_u64_abs(battery, &format!("charge_{name}"))?;
+    let voltage = read_attr_u64_abs(battery, "voltage_min_design")
+        .or_else(|_| read_attr_u64_abs(battery, "voltage_now"))?;
+    Ok((charge * voltage / 1_000_000_000) as u32)
+}
 
 /// The "platform" device provides access to things like:
 /// - `dgpu_disable`
@@ -24,6 +176,38 @@ impl AsusPower {
 
     attr_u8!("online", mains);
 
+    /// Read the battery health and charge state. Items the battery doesn't
+    /// report are left as `0`, but the charge itself must be available.
+    pub fn get_battery_info(&self) -> Result<BatteryInfo> {
+        let bat = &self.battery;
+        let energy_now = read_energy_mwh(bat, "now").unwrap_or_default();
+        let energy_full = read_energy_mwh(bat, "full").unwrap_or_default();
+        let capacity = match read_attr_u64_abs(bat, "capacity") {
+            Ok(capacity) => capacity.min(100) as u8,
+            Err(_) if energy_full != 0 => (energy_now as u64 * 100 / energy_full as u64) as u8,
+            Err(e) => return Err(e),
+        };
+        let power_now = if has_attr(bat, "power_now") {
+            (read_attr_u64_abs(bat, "power_now")? / 1000) as u32
+        } else {
+            let current = read_attr_u64_abs(bat, "current_now").unwrap_or_default();
+            let voltage = read_attr_u64_abs(bat, "voltage_now").unwrap_or_default();
+            (current * voltage / 1_000_000_000) as u32
+        };
+
+        Ok(BatteryInfo {
+            capacity,
+            status: read_attr_string(bat, "status")
+                .and_then(|s| BatteryStatus::from_str(&s))
+                .unwrap_or_default(),
+            energy_now,
+            energy_full,
+            energy_full_design: read_energy_mwh(bat, "full_design").unwrap_or_default(),
+            cycle_count: read_attr_u64_abs(bat, "cycle_count").unwrap_or_default() as u32,
+            power_now,
+        })
+    }
+
     /// When checking for battery this will look in order:
     /// - if attr `manufacturer` contains `asus`
     /// - if attr `charge_control_end_threshold` exists and `energy_full_design`
@@ -31,63 +215,54 @@ impl AsusPower {
     /// - if syspath end conatins `BAT`
     /// - if attr `type` is `battery` (last resort)
     pub fn new() -> Result<Self> {
+        Self::with_sysfs_root(sysfs_root())
+    }
+
+    /// As [`AsusPower::new()`] but searching the `power_supply` devices under
+    /// `root`
+    pub fn with_sysfs_root(root: &SysfsRoot) -> Result<Self> {
         let mut mains = PathBuf::new();
         let mut battery = None;
         let mut usb = None;
 
-        let mut enumerator = udev::Enumerator::new().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("enumerator failed".into(), err)
-        })?;
-        enumerator.match_subsystem("power_supply").map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("match_subsystem failed".into(), err)
-        })?;
-
-        for device in enumerator.scan_devices().map_err(|err| {
-            warn!("{}", err);
-            PlatformError::Udev("scan_devices failed".into(), err)
-        })? {
-            if let Some(attr) = device.attribute_value("type") {
-                info!("Power: Checking {:?}", device.syspath());
-                match attr.to_string_lossy().to_ascii_lowercase().trim() {
+        for path in root.scan_subsystem("power_supply", None)? {
+            if let Ok(attr) = read_attr_string(&path, "type") {
+                info!("Power: Checking {:?}", path);
+                match attr.to_ascii_lowercase().trim() {
                     "mains" => {
-                        info!("Found mains power at {:?}", device.sysname());
-                        mains = device.syspath().to_path_buf();
+                        info!("Found mains power at {:?}", sysname(&path));
+                        mains = path;
                     }
                     "battery" => {
                         // Priortised list of checks
                         info!("Found a battery");
                         if battery.is_none() {
                             info!("Checking battery attributes");
-                            if device
-                                .attribute_value("charge_control_end_threshold")
-                                .is_some()
-                            {
+                            if path.join("charge_control_end_threshold").exists() {
                                 info!(
                                     "Found battery power at {:?}, matched \
                                      charge_control_end_threshold",
-                                    device.sysname()
+                                    sysname(&path)
                                 );
-                                battery = Some(device.syspath().to_path_buf());
-                            } else if device.sysname().to_string_lossy().starts_with("BAT") {
+                                battery = Some(path);
+                            } else if sysname(&path).starts_with("BAT") {
                                 info!(
                                     "Found battery power at {:?}, sysfs path ended with BAT<n>",
-                                    device.sysname()
+                                    sysname(&path)
                                 );
-                                battery = Some(device.syspath().to_path_buf());
+                                battery = Some(path);
                             } else {
                                 info!(
                                     "Last resort: Found battery power at {:?} using type = Battery",
-                                    device.sysname()
+                                    sysname(&path)
                                 );
-                                battery = Some(device.syspath().to_path_buf());
+                                battery = Some(path);
                             }
                         }
                     }
                     "usb" => {
-                        info!("Found USB-C power at {:?}", device.sysname());
-                        usb = Some(device.syspath().to_path_buf());
+                        info!("Found USB-C power at {:?}", sysname(&path));
+                        usb = Some(path);
                     }
                     _ => {}
                 };
@@ -107,3 +282,71 @@ impl AsusPower {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{AsusPower, BatteryStatus};
+    use crate::sysfs::SysfsFixture;
+
+    fn fixture(name: &str, attrs: &[(&str, &str)]) -> SysfsFixture {
+        let fixture = SysfsFixture::new(name);
+        fixture
+            .device("class/power_supply/BAT0", &[("type", "Battery")])
+            .device("class/power_supply/BAT0", attrs);
+        fixture
+    }
+
+    #[test]
+    fn battery_info_energy() {
+        let fixture = fixture(
+            "energy",
+            &[
+                ("status", "Discharging"),
+                ("capacity", "50"),
+                ("energy_now", "36000000"),
+                ("energy_full", "72000000"),
+                ("energy_full_design", "90000000"),
+                ("cycle_count", "123"),
+                ("power_now", "18000000"),
+            ],
+        );
+        let power = AsusPower::with_sysfs_root(&fixture.root()).unwrap();
+        let info = power.get_battery_info().unwrap();
+        assert_eq!(info.status, BatteryStatus::Discharging);
+        assert_eq!(info.capacity, 50);
+        assert_eq!(info.energy_full, 72000);
+        assert_eq!(info.cycle_count, 123);
+        assert_eq!(info.power_now, 18000);
+        assert!((info.wear() - 20.0).abs() < 0.01);
+        assert_eq!(info.time_to_empty(), Some(Duration::from_secs(2 * 3600)));
+        assert_eq!(info.time_to_full(), None);
+    }
+
+    #[test]
+    fn battery_info_charge() {
+        // Charge in µAh and current in µA, with a negative current while charging
+        let fixture = fixture(
+            "charge",
+            &[
+                ("status", "Charging"),
+                ("charge_now", "2000000"),
+                ("charge_full", "4000000"),
+                ("charge_full_design", "5000000"),
+                ("voltage_min_design", "15000000"),
+                ("voltage_now", "16000000"),
+                ("current_now", "-1500000"),
+            ],
+        );
+        let power = AsusPower::with_sysfs_root(&fixture.root()).unwrap();
+        let info = power.get_battery_info().unwrap();
+        assert_eq!(info.status, BatteryStatus::Charging);
+        assert_eq!(info.capacity, 50);
+        assert_eq!(info.energy_now, 30000);
+        assert_eq!(info.energy_full_design, 75000);
+        assert_eq!(info.cycle_count, 0);
+        assert_eq!(info.power_now, 24000);
+        assert_eq!(info.time_to_full(), Some(Duration::from_secs(4500)));
+    }
+}
@@ -0,0 +1,254 @@
+//! Selection of where sysfs devices are found and their attributes read from.
+//!
+//! By default the live `/sys` is enumerated with udev. Setting the environment
+//! variable `ROG_SYSFS_ROOT` (or calling [`set_sysfs_root()`] before any device
+//! is created) will instead use a directory laid out like `/sys`, such as a
+//! captured snapshot of a laptop, so that the whole daemon can run without any
+//! ASUS hardware present.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use log::{info, warn};
+
+use crate::error::{PlatformError, Result};
+
+/// Environment variable checked for a fake sysfs tree on first use
+pub const SYSFS_ROOT_ENV: &str = "ROG_SYSFS_ROOT";
+
+const LIVE_SYSFS: &str = "/sys";
+
+static SYSFS_ROOT: OnceLock<SysfsRoot> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SysfsRoot {
+    /// The live `/sys`, devices are enumerated with udev
+    Live,
+    /// A directory with the same layout as `/sys`. Devices are found by
+    /// walking `class/<subsystem>` and `bus/<subsystem>/devices`
+    Dir(PathBuf),
+}
+
+impl SysfsRoot {
+    /// Check `ROG_SYSFS_ROOT`, falling back to the live sysfs if not set
+    pub fn from_env() -> Self {
+        match std::env::var_os(SYSFS_ROOT_ENV) {
+            Some(path) if !path.is_empty() => Self::Dir(PathBuf::from(path)),
+            _ => Self::Live,
+        }
+    }
+
+    /// Translate an absolute sysfs path such as `/sys/firmware/acpi` in to one
+    /// under this root
+    pub fn join(&self, sys_path: &str) -> PathBuf {
+        let relative = match sys_path.strip_prefix(LIVE_SYSFS) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => rest.trim_start_matches('/'),
+            _ => sys_path.trim_start_matches('/'),
+        };
+        match self {
+            SysfsRoot::Live => Path::new(LIVE_SYSFS).join(relative),
+            SysfsRoot::Dir(root) => root.join(relative),
+        }
+    }
+
+    /// Find the syspath of all devices in `subsystem`, optionally only those
+    /// with a matching sysname.
+    pub fn scan_subsystem(&self, subsystem: &str, sysname: Option<&str>) -> Result<Vec<PathBuf>> {
+        match self {
+            SysfsRoot::Live => {
+                let mut enumerator = udev::Enumerator::new().map_err(|err| {
+                    warn!("{}", err);
+                    PlatformError::Udev("enumerator failed".into(), err)
+                })?;
+                enumerator.match_subsystem(subsystem).map_err(|err| {
+                    warn!("{}", err);
+                    PlatformError::Udev("match_subsystem failed".into(), err)
+                })?;
+                if let Some(sysname) = sysname {
+                    enumerator.match_sysname(sysname).map_err(|err| {
+                        warn!("{}", err);
+                        PlatformError::Udev("match_sysname failed".into(), err)
+                    })?;
+                }
+                Ok(enumerator
+                    .scan_devices()
+                    .map_err(|err| {
+                        warn!("{}", err);
+                        PlatformError::Udev("scan_devices failed".into(), err)
+                    })?
+                    .map(|device| device.syspath().to_owned())
+                    .collect())
+            }
+            SysfsRoot::Dir(root) => {
+                let mut found = Vec::new();
+                for dir in [
+                    root.join("class").join(subsystem),
+                    root.join("bus").join(subsystem).join("devices"),
+                ] {
+                    let Ok(entries) = fs::read_dir(&dir) else {
+                        continue;
+                    };
+                    for entry in entries.flatten() {
+                        if let Some(name) = sysname {
+                            if entry.file_name() != name {
+                                continue;
+                            }
+                        }
+                        found.push(entry.path());
+                    }
+                }
+                found.sort();
+                Ok(found)
+            }
+        }
+    }
+}
+
+/// Set the sysfs root used by all following device constructors. This can only
+/// be done once, and must be done before any device is created.
+pub fn set_sysfs_root(root: SysfsRoot) -> Result<()> {
+    info!("Using sysfs root: {root:?}");
+    SYSFS_ROOT
+        .set(root)
+        .map_err(|_| PlatformError::MissingFunction("sysfs root is already set".to_owned()))
+}
+
+/// The sysfs root in use. Initialised from `ROG_SYSFS_ROOT` on first call if
+/// [`set_sysfs_root()`] was not used.
+pub fn sysfs_root() -> &'static SysfsRoot {
+    SYSFS_ROOT.get_or_init(|| {
+        let root = SysfsRoot::from_env();
+        if root != SysfsRoot::Live {
+            info!("Using sysfs root from {SYSFS_ROOT_ENV}: {root:?}");
+        }
+        root
+    })
+}
+
+/// Name of the device at the end of a syspath, e.g `BAT0`
+pub(crate) fn sysname(sys_path: &Path) -> String {
+    sys_path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// A directory laid out like `/sys` in the temp dir, for tests of code that
+/// reads devices through a [`SysfsRoot`]. It is removed when dropped.
+#[cfg(any(test, feature = "test-support"))]
+pub struct SysfsFixture {
+    root: PathBuf,
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl SysfsFixture {
+    /// An empty tree, unique to `name` and this process
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("rog-sysfs-{name}-{}", std::process::id()));
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    /// Write `value` and a newline to the attribute at `path`, relative to the
+    /// root, creating the directories above it
+    pub fn attr(&self, path: impl AsRef<Path>, value: impl std::fmt::Display) -> &Self {
+        let path = self.root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("{value}\n")).unwrap();
+        self
+    }
+
+    /// Create the device directory `dir` with each of `attrs` in it
+    pub fn device(&self, dir: impl AsRef<Path>, attrs: &[(&str, &str)]) -> &Self {
+        let dir = dir.as_ref();
+        fs::create_dir_all(self.root.join(dir)).unwrap();
+        for (attr, value) in attrs {
+            self.attr(dir.join(attr), value);
+        }
+        self
+    }
+
+    /// The directory of the tree
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// A root to create devices from the tree with
+    pub fn root(&self) -> SysfsRoot {
+        SysfsRoot::Dir(self.root.clone())
+    }
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl Drop for SysfsFixture {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.root).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{SysfsFixture, SysfsRoot};
+    use crate::platform::RogPlatform;
+    use crate::power::AsusPower;
+
+    fn fixture(name: &str) -> SysfsFixture {
+        let fixture = SysfsFixture::new(name);
+        fixture
+            .device(
+                "bus/platform/devices/asus-nb-wmi",
+                &[("throttle_thermal_policy", "2"), ("panel_od", "0")],
+            )
+            .device(
+                "class/power_supply/BAT0",
+                &[("type", "Battery"), ("charge_control_end_threshold", "80")],
+            )
+            .device(
+                "class/power_supply/AC0",
+                &[("type", "Mains"), ("online", "1")],
+            );
+        fixture
+    }
+
+    #[test]
+    fn join_live_path() {
+        let root = SysfsRoot::Dir(PathBuf::from("/tmp/snapshot"));
+        assert_eq!(
+            root.join("/sys/firmware/acpi"),
+            PathBuf::from("/tmp/snapshot/firmware/acpi")
+        );
+        assert_eq!(
+            SysfsRoot::Live.join("/sys/firmware/acpi"),
+            PathBuf::from("/sys/firmware/acpi")
+        );
+        // Only a whole `/sys` component is taken off
+        assert_eq!(
+            root.join("/sysfs/firmware"),
+            PathBuf::from("/tmp/snapshot/sysfs/firmware")
+        );
+        assert_eq!(root.join("/sys"), PathBuf::from("/tmp/snapshot"));
+    }
+
+    #[test]
+    fn platform_from_dir() {
+        let fixture = fixture("platform");
+        let root = fixture.root();
+
+        let platform = RogPlatform::with_sysfs_root(&root).unwrap();
+        assert!(platform.has_throttle_thermal_policy());
+        assert!(!platform.has_gpu_mux_mode());
+        assert_eq!(platform.get_throttle_thermal_policy().unwrap(), 2);
+        platform.set_panel_od(true).unwrap();
+        assert!(platform.get_panel_od().unwrap());
+
+        let power = AsusPower::with_sysfs_root(&root).unwrap();
+        assert_eq!(power.get_charge_control_end_threshold().unwrap(), 80);
+        power.set_charge_control_end_threshold(60).unwrap();
+        assert_eq!(power.get_charge_control_end_threshold().unwrap(), 60);
+        assert_eq!(power.get_online().unwrap(), 1);
+    }
+}
@@ -1,8 +1,11 @@
 use std::fmt;
 
 use log::error;
+use rog_platform::error::PlatformError;
 use zbus::fdo::Error as FdoErr;
 
+use crate::FanCurvePU;
+
 #[derive(Debug)]
 pub enum ProfileError {
     Path(String, std::io::Error),
@@ -12,11 +15,29 @@ pub enum ProfileError {
     NotFound(String),
     Io(std::io::Error),
     ParseProfileName,
+    /// The name given for a [`crate::fan_curve_set::CurveResample`]
+    ParseResample(String),
     ParseFanCurveDigit(std::num::ParseIntError),
     /// (pwm/temp, prev, next)
     ParseFanCurvePrevHigher(&'static str, u8, u8),
     ParseFanCurvePercentOver100(u8),
     NotEnoughPoints,
+    /// (fan, pwm/temp, prev, next)
+    CurveNotMonotonic(FanCurvePU, &'static str, u8, u8),
+    /// (fan, temp, min pwm, pwm)
+    CurveBelowMinimum(FanCurvePU, u8, u8, u8),
+    PresetExists(String),
+    /// (policy, preset) the curves of the policy come from a preset and can
+    /// only be changed through it
+    PresetBound(String, String),
+    ParseExport(String),
+    /// The version of an exported file is newer than supported
+    ExportVersion(u32),
+    /// (fan, reason)
+    CalibrationStopped(FanCurvePU, String),
+    /// Curves can't be written while the fans are being calibrated
+    Calibrating,
+    Platform(PlatformError),
     // Zbus(zbus::Error),
 }
 
@@ -28,10 +49,14 @@ impl fmt::Display for ProfileError {
             ProfileError::Read(path, error) => write!(f, "Read {}: {}", path, error),
             ProfileError::Write(path, error) => write!(f, "Write {}: {}", path, error),
             ProfileError::NotSupported => write!(f, "Not supported"),
-            ProfileError::NotEnoughPoints => write!(f, "Less than 8 curve points supplied"),
+            ProfileError::NotEnoughPoints => write!(f, "Less than 2 curve points supplied"),
+            ProfileError::Platform(e) => write!(f, "Platform error: {}", e),
             ProfileError::NotFound(deets) => write!(f, "Not found: {}", deets),
             ProfileError::Io(detail) => write!(f, "std::io error: {}", detail),
             ProfileError::ParseProfileName => write!(f, "Invalid profile name"),
+            ProfileError::ParseResample(name) => {
+                write!(f, "Invalid resampling {name}, expected linear or cubic")
+            }
             ProfileError::ParseFanCurveDigit(e) => {
                 write!(f, "Could not parse number to 0-255: {}", e)
             }
@@ -42,7 +67,39 @@ impl fmt::Display for ProfileError {
             ),
             ProfileError::ParseFanCurvePercentOver100(value) => {
                 write!(f, "Invalid percentage, {} is higher than 100", value)
-            } // Error::Zbus(detail) => write!(f, "Zbus error: {}", detail),
+            }
+            ProfileError::CurveNotMonotonic(fan, part, prev, next) => write!(
+                f,
+                "Invalid {:?} fan curve, {} {} is followed by a lower {}",
+                fan, part, prev, next
+            ),
+            ProfileError::CurveBelowMinimum(fan, temp, min_pwm, pwm) => write!(
+                f,
+                "Unsafe {:?} fan curve, fan is {}% at {}c but must be at least {}%",
+                fan,
+                *pwm as u32 * 100 / 255,
+                temp,
+                *min_pwm as u32 * 100 / 255
+            ),
+            ProfileError::PresetExists(name) => {
+                write!(f, "A fan curve preset named {} already exists", name)
+            }
+            ProfileError::PresetBound(policy, preset) => write!(
+                f,
+                "{} uses the fan curve preset {}, change the preset or unbind it first",
+                policy, preset
+            ),
+            ProfileError::ParseExport(e) => write!(f, "Invalid fan curve file: {}", e),
+            ProfileError::Calibrating => write!(f, "The fans are being calibrated"),
+            ProfileError::CalibrationStopped(fan, reason) => {
+                write!(f, "Calibration of the {:?} fan stopped: {}", fan, reason)
+            }
+            ProfileError::ExportVersion(version) => write!(
+                f,
+                "Unsupported fan curve file version {}, newest supported is {}",
+                version,
+                crate::fan_curve_export::FAN_CURVE_EXPORT_VERSION
+            ), // Error::Zbus(detail) => write!(f, "Zbus error: {}", detail),
         }
     }
 }
@@ -56,11 +113,29 @@ impl From<std::io::Error> for ProfileError {
     }
 }
 
+impl From<PlatformError> for ProfileError {
+    fn from(err: PlatformError) -> Self {
+        match err {
+            PlatformError::NotSupported => ProfileError::NotSupported,
+            err => ProfileError::Platform(err),
+        }
+    }
+}
+
 impl From<ProfileError> for FdoErr {
     fn from(error: ProfileError) -> Self {
         error!("ProfileError: got: {error}");
         match error {
             ProfileError::NotSupported => FdoErr::NotSupported("".to_owned()),
+            ProfileError::CurveNotMonotonic(..)
+            | ProfileError::CurveBelowMinimum(..)
+            | ProfileError::PresetExists(_)
+            | ProfileError::PresetBound(..)
+            | ProfileError::ParseExport(_)
+            | ProfileError::ExportVersion(_)
+            | ProfileError::ParseProfileName
+            | ProfileError::ParseResample(_)
+            | ProfileError::NotFound(_) => FdoErr::InvalidArgs(format!("{error}")),
             _ => FdoErr::Failed(format!("Failed with {error}")),
         }
     }
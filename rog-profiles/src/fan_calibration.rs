@@ -0,0 +1,268 @@
+//! Measure how each fan responds to fan power, to find the lowest power at
+//! which it spins. Each fan is held at a series of fan powers with a flat
+//! firmware curve and the speed is read once it has settled.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use log::debug;
+use rog_platform::read_attr_u32;
+use rog_platform::sysfs::SysfsRoot;
+use serde_derive::{Deserialize, Serialize};
+use typeshare::typeshare;
+#[cfg(feature = "dbus")]
+use zbus::zvariant::{OwnedValue, Type, Value};
+
+use crate::error::ProfileError;
+use crate::fan_curve_set::CurveData;
+use crate::fan_status::find_fan_speed_node_in;
+use crate::{find_fan_curve_node_in, FanCurvePU};
+
+/// The fan powers tried, 0 to 255 in steps of 16
+pub const CALIBRATION_STEPS: [u8; 17] = [
+    0, 16, 32, 48, 64, 80, 96, 112, 128, 144, 160, 176, 192, 208, 224, 240, 255,
+];
+
+/// Readings within this fraction of the last are taken as settled
+const STEADY_TOLERANCE: f32 = 0.05;
+/// Give up waiting for the speed to settle after this many readings
+const MAX_READINGS: usize = 5;
+
+#[typeshare]
+#[cfg_attr(feature = "dbus", derive(Type, Value, OwnedValue))]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CalibrationStep {
+    /// Fan power in the range 0-255
+    pub pwm: u8,
+    pub rpm: u32,
+}
+
+#[typeshare]
+#[cfg_attr(feature = "dbus", derive(Type, Value, OwnedValue))]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct FanCalibration {
+    pub fan: FanCurvePU,
+    pub steps: Vec<CalibrationStep>,
+    /// The lowest fan power at which the fan spins, and the suggested minimum
+    /// for the points of a curve which should run the fan. 0 if it never spun.
+    /// Nothing enforces it, it is reported for the user to go by.
+    pub min_pwm: u8,
+    /// The speed at full power
+    pub max_rpm: u32,
+}
+
+impl FanCalibration {
+    fn from_steps(fan: FanCurvePU, steps: Vec<CalibrationStep>) -> Self {
+        // The fan must keep spinning at every step above, a single reading
+        // while it spins down doesn't count
+        let min_pwm = steps
+            .iter()
+            .rposition(|s| s.rpm == 0)
+            .map_or(steps.first(), |i| steps.get(i + 1))
+            .map(|s| s.pwm)
+            .unwrap_or_default();
+        Self {
+            fan,
+            min_pwm,
+            max_rpm: steps.iter().map(|s| s.rpm).max().unwrap_or_default(),
+            steps,
+        }
+    }
+
+    /// A readable summary of the calibration and each step
+    pub fn report(&self) -> String {
+        let mut report = format!("{:?} fan: ", self.fan);
+        if self.max_rpm == 0 {
+            report.push_str("did not spin at any fan power\n");
+        } else {
+            writeln!(
+                report,
+                "spins from {}% (pwm {}), {} RPM at most",
+                self.min_pwm as u32 * 100 / 255,
+                self.min_pwm,
+                self.max_rpm
+            )
+            .ok();
+        }
+        for step in &self.steps {
+            writeln!(
+                report,
+                "  {:>3}% (pwm {:>3}): {:>5} RPM",
+                step.pwm as u32 * 100 / 255,
+                step.pwm,
+                step.rpm
+            )
+            .ok();
+        }
+        report
+    }
+}
+
+/// Holds fans at set fan powers through the firmware fan curves and reads the
+/// speed from the `asus` hwmon
+#[derive(Debug, Clone)]
+pub struct FanCalibrator {
+    /// The `asus_custom_fan_curve` hwmon
+    curve_device: PathBuf,
+    /// The `asus` hwmon with `fanN_input`
+    speed_device: PathBuf,
+}
+
+impl FanCalibrator {
+    pub fn find_in(root: &SysfsRoot) -> Result<Self, ProfileError> {
+        Ok(Self {
+            curve_device: find_fan_curve_node_in(root)?,
+            speed_device: find_fan_speed_node_in(root)?
+                .ok_or_else(|| ProfileError::NotFound("fan speed hwmon".to_owned()))?,
+        })
+    }
+
+    fn read_rpm(&self, fan: FanCurvePU) -> Result<u32, ProfileError> {
+        let num: char = fan.into();
+        Ok(read_attr_u32(
+            &self.speed_device,
+            &format!("fan{num}_input"),
+        )?)
+    }
+
+    /// Hold `fan` at each of `steps` in turn and record the settled speed.
+    /// `settle` is called after each change of fan power and between
+    /// readings, it should wait for the fan to respond and may stop the
+    /// calibration by returning an error, such as if the laptop gets too hot.
+    ///
+    /// The fan is left enabled on a flat curve at the last step, the caller
+    /// must write the curves in use afterwards.
+    pub fn calibrate(
+        &self,
+        fan: FanCurvePU,
+        steps: &[u8],
+        mut settle: impl FnMut(FanCurvePU, u8) -> Result<(), ProfileError>,
+    ) -> Result<FanCalibration, ProfileError> {
+        let mut results = Vec::with_capacity(steps.len());
+        for pwm in steps {
+            let curve = CurveData {
+                fan,
+                pwm: [*pwm; 8],
+                temp: [20, 30, 40, 50, 60, 70, 80, 90],
+                enabled: true,
+            };
+            curve.write_to_device(&self.curve_device)?;
+
+            let mut rpm = None;
+            for _ in 0..MAX_READINGS {
+                settle(fan, *pwm)?;
+                let next = self.read_rpm(fan)?;
+                if let Some(last) = rpm {
+                    let diff = (next as f32 - last as f32).abs();
+                    if diff <= last as f32 * STEADY_TOLERANCE {
+                        rpm = Some(next);
+                        break;
+                    }
+                }
+                rpm = Some(next);
+            }
+            let rpm = rpm.unwrap_or_default();
+            debug!("Calibrating {fan:?}: pwm {pwm}, {rpm} RPM");
+            results.push(CalibrationStep { pwm: *pwm, rpm });
+        }
+        Ok(FanCalibration::from_steps(fan, results))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rog_platform::sysfs::SysfsFixture;
+
+    use super::{CalibrationStep, FanCalibration, FanCalibrator, CALIBRATION_STEPS};
+    use crate::error::ProfileError;
+    use crate::FanCurvePU;
+
+    fn fixture(name: &str) -> SysfsFixture {
+        let fixture = SysfsFixture::new(name);
+        fixture.device(
+            "class/hwmon/hwmon2",
+            &[("name", "asus_custom_fan_curve"), ("pwm1_enable", "2")],
+        );
+        for point in 1..=8 {
+            for attr in ["pwm", "temp"] {
+                fixture.attr(
+                    format!("class/hwmon/hwmon2/pwm1_auto_point{point}_{attr}"),
+                    0,
+                );
+            }
+        }
+        fixture.device(
+            "class/hwmon/hwmon3",
+            &[("name", "asus"), ("fan1_input", "0")],
+        );
+        fixture
+    }
+
+    #[test]
+    fn calibrate_fixture_fan() {
+        let fixture = fixture("spin");
+        let calibrator = FanCalibrator::find_in(&fixture.root()).unwrap();
+        let fan1_input = fixture.path().join("class/hwmon/hwmon3/fan1_input");
+        let curves = fixture.path().join("class/hwmon/hwmon2");
+
+        // The fan doesn't start below pwm 40 and takes a reading to settle
+        let mut last_pwm = None;
+        let calibration = calibrator
+            .calibrate(FanCurvePU::CPU, &CALIBRATION_STEPS, |_, _| {
+                let pwm: u32 = fs::read_to_string(curves.join("pwm1_auto_point8_pwm"))
+                    .unwrap()
+                    .trim()
+                    .parse()
+                    .unwrap();
+                let mut rpm = if pwm < 40 { 0 } else { pwm * 20 };
+                if last_pwm != Some(pwm) {
+                    rpm /= 2;
+                    last_pwm = Some(pwm);
+                }
+                fs::write(&fan1_input, format!("{rpm}\n")).unwrap();
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(calibration.steps.len(), CALIBRATION_STEPS.len());
+        assert_eq!(calibration.steps[2], CalibrationStep { pwm: 32, rpm: 0 });
+        assert_eq!(calibration.steps[3], CalibrationStep { pwm: 48, rpm: 960 });
+        assert_eq!(calibration.min_pwm, 48);
+        assert_eq!(calibration.max_rpm, 5100);
+        assert_eq!(
+            fs::read_to_string(curves.join("pwm1_enable"))
+                .unwrap()
+                .trim(),
+            "1"
+        );
+        assert!(calibration
+            .report()
+            .starts_with("CPU fan: spins from 18% (pwm 48)"));
+
+        // The settle callback can stop the calibration
+        let result = calibrator.calibrate(FanCurvePU::CPU, &CALIBRATION_STEPS, |fan, _| {
+            Err(ProfileError::CalibrationStopped(fan, "too hot".to_owned()))
+        });
+        assert!(matches!(
+            result,
+            Err(ProfileError::CalibrationStopped(FanCurvePU::CPU, _))
+        ));
+    }
+
+    #[test]
+    fn min_pwm_needs_steady_spin() {
+        let steps = [(0, 0), (16, 300), (32, 0), (48, 900), (64, 1300)]
+            .map(|(pwm, rpm)| CalibrationStep { pwm, rpm })
+            .to_vec();
+        let calibration = FanCalibration::from_steps(FanCurvePU::GPU, steps);
+        assert_eq!(calibration.min_pwm, 48);
+        assert_eq!(calibration.max_rpm, 1300);
+
+        let stopped =
+            FanCalibration::from_steps(FanCurvePU::GPU, vec![CalibrationStep { pwm: 0, rpm: 0 }]);
+        assert_eq!(stopped.min_pwm, 0);
+        assert!(stopped.report().contains("did not spin"));
+    }
+}
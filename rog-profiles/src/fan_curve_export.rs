@@ -0,0 +1,310 @@
+//! A portable form of a complete [`FanCurveProfiles`] set, used to share
+//! curves between identical machines. The file is JSON or TOML:
+//!
+//! ```toml
+//! version = 1
+//!
+//! [[balanced]]
+//! fan = "CPU"
+//! pwm = [0, 2, 5, 10, 20, 40, 60, 80]
+//! temp = [30, 40, 50, 60, 70, 80, 90, 100]
+//! enabled = true
+//!
+//! [[presets]]
+//! name = "Render"
+//! policies = ["Performance"]
+//!
+//! [[presets.curves]]
+//! # as above
+//! ```
+//!
+//! `pwm` is fan power in the range 0-255 and `temp` is degrees C, 8 points
+//! each. `performance`, `quiet`, the battery curves `balanced_battery`,
+//! `performance_battery`, and `quiet_battery`, and `presets` take the same
+//! form as `balanced`. Any of them may be left out, and on import only those
+//! in the file replace the stored ones.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_derive::{Deserialize, Serialize};
+use typeshare::typeshare;
+#[cfg(feature = "dbus")]
+use zbus::zvariant::Type;
+
+use crate::error::ProfileError;
+use crate::fan_curve_preset::FanCurvePreset;
+use crate::fan_curve_set::CurveData;
+use crate::FanCurveProfiles;
+
+/// The current version of [`FanCurveExport`]. Bump this if the format changes
+/// in a way older versions can't read.
+pub const FAN_CURVE_EXPORT_VERSION: u32 = 1;
+
+#[typeshare]
+#[cfg_attr(feature = "dbus", derive(Type), zvariant(signature = "s"))]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum FanCurveFormat {
+    Json,
+    Toml,
+}
+
+impl FanCurveFormat {
+    /// Pick the format from a `.json` or `.toml` file extension
+    pub fn from_path(path: &Path) -> Result<Self, ProfileError> {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| ProfileError::ParseExport("file must end in .json or .toml".to_owned()))?
+            .parse()
+    }
+}
+
+impl FromStr for FanCurveFormat {
+    type Err = ProfileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            _ => Err(ProfileError::ParseExport(format!(
+                "unknown format {s}, must be json or toml"
+            ))),
+        }
+    }
+}
+
+/// Each section is `None` if left out of the file
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq)]
+pub struct FanCurveExport {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balanced: Option<Vec<CurveData>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub performance: Option<Vec<CurveData>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet: Option<Vec<CurveData>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balanced_battery: Option<Vec<CurveData>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub performance_battery: Option<Vec<CurveData>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_battery: Option<Vec<CurveData>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presets: Option<Vec<FanCurvePreset>>,
+}
+
+impl FanCurveExport {
+    /// Parse an exported set. Each curve must be increasing; checking it
+    /// against the fans and safety rules of the machine is left to the caller.
+    pub fn parse(data: &str, format: FanCurveFormat) -> Result<Self, ProfileError> {
+        let err = |e: &dyn std::fmt::Display| ProfileError::ParseExport(e.to_string());
+        let export: Self = match format {
+            FanCurveFormat::Json => serde_json::from_str(data).map_err(|e| err(&e))?,
+            FanCurveFormat::Toml => toml::from_str(data).map_err(|e| err(&e))?,
+        };
+        if export.version == 0 || export.version > FAN_CURVE_EXPORT_VERSION {
+            return Err(ProfileError::ExportVersion(export.version));
+        }
+        for curve in export.curves() {
+            curve.check_monotonic()?;
+        }
+        let presets = export.presets.as_deref().unwrap_or_default();
+        let mut bound = Vec::new();
+        for preset in presets {
+            if presets.iter().filter(|p| p.name == preset.name).count() > 1 {
+                return Err(ProfileError::PresetExists(preset.name.clone()));
+            }
+            for policy in &preset.policies {
+                if bound.contains(policy) {
+                    return Err(ProfileError::ParseExport(format!(
+                        "{policy} is bound to more than one preset"
+                    )));
+                }
+                bound.push(*policy);
+            }
+        }
+        Ok(export)
+    }
+
+    /// Every curve in the file, including those for battery and of the
+    /// presets
+    pub fn curves(&self) -> impl Iterator<Item = &CurveData> {
+        [
+            &self.balanced,
+            &self.performance,
+            &self.quiet,
+            &self.balanced_battery,
+            &self.performance_battery,
+            &self.quiet_battery,
+        ]
+        .into_iter()
+        .flatten()
+        .flatten()
+        .chain(self.presets.iter().flatten().flat_map(|p| p.curves.iter()))
+    }
+}
+
+impl FanCurveProfiles {
+    /// Every curve stored, including those for battery and of the presets
+    pub fn all_curves(&self) -> impl Iterator<Item = &CurveData> {
+        self.balanced
+            .iter()
+            .chain(self.performance.iter())
+            .chain(self.quiet.iter())
+            .chain(self.balanced_battery.iter())
+            .chain(self.performance_battery.iter())
+            .chain(self.quiet_battery.iter())
+            .chain(self.presets.iter().flat_map(|p| p.curves.iter()))
+    }
+
+    pub fn export(&self, format: FanCurveFormat) -> Result<String, ProfileError> {
+        let export = FanCurveExport {
+            version: FAN_CURVE_EXPORT_VERSION,
+            balanced: Some(self.balanced.clone()),
+            performance: Some(self.performance.clone()),
+            quiet: Some(self.quiet.clone()),
+            balanced_battery: Some(self.balanced_battery.clone()),
+            performance_battery: Some(self.performance_battery.clone()),
+            quiet_battery: Some(self.quiet_battery.clone()),
+            presets: Some(self.presets.clone()),
+        };
+        let err = |e: &dyn std::fmt::Display| ProfileError::ParseExport(e.to_string());
+        match format {
+            FanCurveFormat::Json => serde_json::to_string_pretty(&export).map_err(|e| err(&e)),
+            // Going through a `Value` puts plain values before tables, which TOML requires
+            FanCurveFormat::Toml => toml::Value::try_from(&export)
+                .and_then(|v| toml::to_string_pretty(&v))
+                .map_err(|e| err(&e)),
+        }
+    }
+
+    /// Replace the sections that are in `export`, keeping the stored curves
+    /// and presets of those left out
+    pub fn import(&mut self, export: FanCurveExport) {
+        let sections = [
+            (&mut self.balanced, export.balanced),
+            (&mut self.performance, export.performance),
+            (&mut self.quiet, export.quiet),
+            (&mut self.balanced_battery, export.balanced_battery),
+            (&mut self.performance_battery, export.performance_battery),
+            (&mut self.quiet_battery, export.quiet_battery),
+        ];
+        for (stored, imported) in sections {
+            if let Some(imported) = imported {
+                *stored = imported;
+            }
+        }
+        if let Some(presets) = export.presets {
+            self.presets = presets;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rog_platform::platform::ThrottlePolicy;
+
+    use super::{FanCurveExport, FanCurveFormat, FAN_CURVE_EXPORT_VERSION};
+    use crate::error::ProfileError;
+    use crate::fan_curve_set::CurveData;
+    use crate::{FanCurvePU, FanCurveProfiles};
+
+    fn profiles() -> FanCurveProfiles {
+        let curve = |fan, enabled| CurveData {
+            fan,
+            pwm: [0, 2, 5, 10, 20, 40, 60, 80],
+            temp: [30, 40, 50, 60, 70, 80, 90, 100],
+            enabled,
+        };
+        let mut profiles = FanCurveProfiles {
+            balanced: vec![curve(FanCurvePU::CPU, true), curve(FanCurvePU::GPU, false)],
+            performance: vec![curve(FanCurvePU::CPU, false)],
+            quiet_battery: vec![curve(FanCurvePU::GPU, true)],
+            ..Default::default()
+        };
+        profiles
+            .create_preset("Render", ThrottlePolicy::Balanced)
+            .unwrap();
+        profiles
+            .bind_preset("Render", ThrottlePolicy::Performance)
+            .unwrap();
+        profiles
+    }
+
+    #[test]
+    fn export_import_round_trip() {
+        let profiles = profiles();
+        for format in [FanCurveFormat::Json, FanCurveFormat::Toml] {
+            let data = profiles.export(format).unwrap();
+            let mut imported = FanCurveProfiles::default();
+            imported.import(FanCurveExport::parse(&data, format).unwrap());
+            assert_eq!(imported.balanced, profiles.balanced);
+            assert_eq!(imported.performance, profiles.performance);
+            assert!(imported.quiet.is_empty());
+            assert_eq!(imported.quiet_battery, profiles.quiet_battery);
+            assert_eq!(imported.presets, profiles.presets);
+        }
+    }
+
+    #[test]
+    fn import_minimal_toml() {
+        let data = r#"
+version = 1
+
+[[quiet]]
+fan = "GPU"
+pwm = [0, 0, 0, 10, 20, 30, 40, 50]
+temp = [30, 40, 50, 60, 70, 80, 90, 100]
+enabled = false
+"#;
+        let export = FanCurveExport::parse(data, FanCurveFormat::Toml).unwrap();
+        assert!(export.balanced.is_none());
+        let mut imported = profiles();
+        imported.import(export);
+        assert_eq!(imported.quiet[0].fan, FanCurvePU::GPU);
+        // The sections left out are kept
+        let profiles = profiles();
+        assert_eq!(imported.balanced, profiles.balanced);
+        assert_eq!(imported.quiet_battery, profiles.quiet_battery);
+        assert_eq!(imported.presets, profiles.presets);
+
+        // An empty section clears it
+        imported.import(
+            FanCurveExport::parse(r#"{"version": 1, "balanced": []}"#, FanCurveFormat::Json)
+                .unwrap(),
+        );
+        assert!(imported.balanced.is_empty());
+        assert_eq!(imported.performance, profiles.performance);
+    }
+
+    #[test]
+    fn import_rejects_bad_data() {
+        let newer = format!(r#"{{"version": {}}}"#, FAN_CURVE_EXPORT_VERSION + 1);
+        assert!(matches!(
+            FanCurveExport::parse(&newer, FanCurveFormat::Json),
+            Err(ProfileError::ExportVersion(_))
+        ));
+        assert!(matches!(
+            FanCurveExport::parse(r#"{"balanced": []}"#, FanCurveFormat::Json),
+            Err(ProfileError::ParseExport(_))
+        ));
+
+        let mut profiles = profiles();
+        profiles.balanced[0].pwm[4] = 1;
+        let data = profiles.export(FanCurveFormat::Json).unwrap();
+        assert!(matches!(
+            FanCurveExport::parse(&data, FanCurveFormat::Json),
+            Err(ProfileError::CurveNotMonotonic(..))
+        ));
+    }
+
+    #[test]
+    fn format_from_path() {
+        let format = |p: &str| FanCurveFormat::from_path(std::path::Path::new(p));
+        assert_eq!(format("curves.json").unwrap(), FanCurveFormat::Json);
+        assert_eq!(format("/tmp/curves.TOML").unwrap(), FanCurveFormat::Toml);
+        assert!(format("curves.ron").is_err());
+        assert!(format("curves").is_err());
+    }
+}
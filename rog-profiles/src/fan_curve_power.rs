@@ -0,0 +1,130 @@
+//! Separate curves for when the laptop runs on battery. A throttle policy with
+//! no battery curves uses the same curves on battery as on AC.
+
+use rog_platform::platform::ThrottlePolicy;
+use rog_platform::power::PowerSource;
+
+use crate::error::ProfileError;
+use crate::fan_curve_set::CurveData;
+use crate::FanCurveProfiles;
+
+impl FanCurveProfiles {
+    pub(crate) fn battery_curves(&self, profile: ThrottlePolicy) -> &Vec<CurveData> {
+        match profile {
+            ThrottlePolicy::Balanced => &self.balanced_battery,
+            ThrottlePolicy::Performance => &self.performance_battery,
+            ThrottlePolicy::Quiet => &self.quiet_battery,
+        }
+    }
+
+    pub(crate) fn battery_curves_mut(&mut self, profile: ThrottlePolicy) -> &mut Vec<CurveData> {
+        match profile {
+            ThrottlePolicy::Balanced => &mut self.balanced_battery,
+            ThrottlePolicy::Performance => &mut self.performance_battery,
+            ThrottlePolicy::Quiet => &mut self.quiet_battery,
+        }
+    }
+
+    pub fn has_battery_curves(&self, profile: ThrottlePolicy) -> bool {
+        !self.battery_curves(profile).is_empty()
+    }
+
+    /// The curves used for the profile on this power source. On battery these
+    /// are the battery curves if there are any, else the same as on AC.
+    pub fn curves_for(&self, profile: ThrottlePolicy, power: PowerSource) -> &[CurveData] {
+        if power == PowerSource::Battery && self.has_battery_curves(profile) {
+            return self.battery_curves(profile);
+        }
+        self.get_fan_curves_for(profile)
+    }
+
+    /// Save the curve for the profile on this power source. The first battery
+    /// curve saved for a profile starts the battery curves from a copy of
+    /// those used on AC.
+    pub fn save_fan_curve_for(
+        &mut self,
+        curve: CurveData,
+        profile: ThrottlePolicy,
+        power: PowerSource,
+    ) -> Result<(), ProfileError> {
+        if power == PowerSource::Ac {
+            return self.save_fan_curve(curve, profile);
+        }
+        if !self.has_battery_curves(profile) {
+            *self.battery_curves_mut(profile) = self.get_fan_curves_for(profile).to_vec();
+        }
+        let curves = self.battery_curves_mut(profile);
+        if let Some(this_curve) = curves.iter_mut().find(|c| c.fan == curve.fan) {
+            *this_curve = curve;
+        } else {
+            curves.push(curve);
+        }
+        Ok(())
+    }
+
+    /// Remove the battery curves of the profile so that the AC curves are
+    /// used on battery. Returns false if there were none.
+    pub fn clear_battery_curves(&mut self, profile: ThrottlePolicy) -> bool {
+        let curves = self.battery_curves_mut(profile);
+        let had = !curves.is_empty();
+        curves.clear();
+        had
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rog_platform::platform::ThrottlePolicy;
+    use rog_platform::power::PowerSource;
+
+    use crate::fan_curve_set::CurveData;
+    use crate::{FanCurvePU, FanCurveProfiles};
+
+    #[test]
+    fn battery_curves_fall_back_to_ac() {
+        let curve = |fan, pwm| CurveData {
+            fan,
+            pwm: [pwm; 8],
+            temp: [30, 40, 50, 60, 70, 80, 90, 100],
+            enabled: true,
+        };
+        let mut profiles = FanCurveProfiles {
+            balanced: vec![curve(FanCurvePU::CPU, 50), curve(FanCurvePU::GPU, 50)],
+            ..Default::default()
+        };
+        let balanced = ThrottlePolicy::Balanced;
+        assert_eq!(
+            profiles.curves_for(balanced, PowerSource::Battery),
+            profiles.balanced
+        );
+
+        profiles
+            .save_fan_curve_for(curve(FanCurvePU::GPU, 10), balanced, PowerSource::Battery)
+            .unwrap();
+        assert_eq!(
+            profiles.curves_for(balanced, PowerSource::Battery),
+            [curve(FanCurvePU::CPU, 50), curve(FanCurvePU::GPU, 10)]
+        );
+        assert_eq!(
+            profiles.curves_for(balanced, PowerSource::Ac),
+            profiles.balanced
+        );
+        assert!(!profiles.has_battery_curves(ThrottlePolicy::Quiet));
+
+        // Enabling applies on both power sources
+        profiles
+            .set_profile_curves_enabled(balanced, false)
+            .unwrap();
+        assert!(profiles
+            .curves_for(balanced, PowerSource::Battery)
+            .iter()
+            .all(|c| !c.enabled));
+
+        assert!(profiles.clear_battery_curves(balanced));
+        assert!(!profiles.clear_battery_curves(balanced));
+        assert_eq!(
+            profiles.curves_for(balanced, PowerSource::Battery),
+            profiles.balanced
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Named fan curve sets which live beside the three per-policy sets. A preset
+//! can be applied at any time, or bound to one or more throttle policies in
+//! which case it is used in place of the curves stored for those policies.
+
+use std::path::Path;
+
+use rog_platform::platform::ThrottlePolicy;
+use serde_derive::{Deserialize, Serialize};
+use typeshare::typeshare;
+#[cfg(feature = "dbus")]
+use zbus::zvariant::Type;
+
+use crate::error::ProfileError;
+use crate::fan_curve_set::CurveData;
+use crate::{write_curves_to_platform, FanCurveProfiles};
+
+#[typeshare]
+#[cfg_attr(feature = "dbus", derive(Type))]
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq, Clone)]
+pub struct FanCurvePreset {
+    pub name: String,
+    pub curves: Vec<CurveData>,
+    /// The throttle policies this preset is used for. A policy can only be
+    /// bound to one preset.
+    #[serde(default)]
+    pub policies: Vec<ThrottlePolicy>,
+}
+
+impl FanCurveProfiles {
+    pub fn preset(&self, name: &str) -> Option<&FanCurvePreset> {
+        self.presets.iter().find(|p| p.name == name)
+    }
+
+    fn preset_mut(&mut self, name: &str) -> Result<&mut FanCurvePreset, ProfileError> {
+        self.presets
+            .iter_mut()
+            .find(|p| p.name == name)
+            .ok_or_else(|| ProfileError::NotFound(format!("fan curve preset {name}")))
+    }
+
+    /// The preset bound to this policy, if any
+    pub fn preset_for(&self, profile: ThrottlePolicy) -> Option<&FanCurvePreset> {
+        self.presets.iter().find(|p| p.policies.contains(&profile))
+    }
+
+    fn preset_for_mut(&mut self, profile: ThrottlePolicy) -> Option<&mut FanCurvePreset> {
+        self.presets
+            .iter_mut()
+            .find(|p| p.policies.contains(&profile))
+    }
+
+    fn check_new_name(&self, name: &str) -> Result<(), ProfileError> {
+        if name.trim().is_empty() {
+            return Err(ProfileError::ParseProfileName);
+        }
+        if self.preset(name).is_some() {
+            return Err(ProfileError::PresetExists(name.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// Create a new preset from a copy of the curves currently used by
+    /// `profile`
+    pub fn create_preset(
+        &mut self,
+        name: &str,
+        profile: ThrottlePolicy,
+    ) -> Result<(), ProfileError> {
+        self.check_new_name(name)?;
+        self.presets.push(FanCurvePreset {
+            name: name.to_owned(),
+            curves: self.get_fan_curves_for(profile).to_vec(),
+            policies: Vec::new(),
+        });
+        Ok(())
+    }
+
+    pub fn rename_preset(&mut self, name: &str, new_name: &str) -> Result<(), ProfileError> {
+        if name == new_name {
+            return Ok(());
+        }
+        self.check_new_name(new_name)?;
+        self.preset_mut(name)?.name = new_name.to_owned();
+        Ok(())
+    }
+
+    /// Remove a preset. Any policies it was bound to return to their own
+    /// curves.
+    pub fn delete_preset(&mut self, name: &str) -> Result<FanCurvePreset, ProfileError> {
+        let index = self
+            .presets
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| ProfileError::NotFound(format!("fan curve preset {name}")))?;
+        Ok(self.presets.remove(index))
+    }
+
+    /// Use the preset in place of the curves of `profile`, replacing any other
+    /// preset bound to it
+    pub fn bind_preset(&mut self, name: &str, profile: ThrottlePolicy) -> Result<(), ProfileError> {
+        self.preset_mut(name)?;
+        self.unbind_preset(profile);
+        self.preset_mut(name)?.policies.push(profile);
+        Ok(())
+    }
+
+    /// Return `profile` to its own curves. Returns false if no preset was
+    /// bound.
+    pub fn unbind_preset(&mut self, profile: ThrottlePolicy) -> bool {
+        if let Some(preset) = self.preset_for_mut(profile) {
+            preset.policies.retain(|p| *p != profile);
+            return true;
+        }
+        false
+    }
+
+    /// Replace the curve for the fan of `curve` in the preset, or add it if the
+    /// preset has no curve for that fan
+    pub fn save_preset_fan_curve(
+        &mut self,
+        name: &str,
+        curve: CurveData,
+    ) -> Result<(), ProfileError> {
+        let preset = self.preset_mut(name)?;
+        if let Some(this_curve) = preset.curves.iter_mut().find(|c| c.fan == curve.fan) {
+            *this_curve = curve;
+        } else {
+            preset.curves.push(curve);
+        }
+        Ok(())
+    }
+
+    /// Write the curves of a preset to the device, regardless of the policy
+    /// it is bound to. The curves stay active until the policy changes.
+    pub fn write_preset_to_platform(&self, name: &str, device: &Path) -> Result<(), ProfileError> {
+        let preset = self
+            .preset(name)
+            .ok_or_else(|| ProfileError::NotFound(format!("fan curve preset {name}")))?;
+        write_curves_to_platform(&preset.curves, device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rog_platform::platform::ThrottlePolicy;
+
+    use crate::error::ProfileError;
+    use crate::fan_curve_set::CurveData;
+    use crate::{FanCurvePU, FanCurveProfiles};
+
+    fn profiles() -> FanCurveProfiles {
+        let curve = |fan, enabled| CurveData {
+            fan,
+            pwm: [1, 2, 3, 4, 5, 6, 7, 8],
+            temp: [10, 20, 30, 40, 50, 60, 70, 80],
+            enabled,
+        };
+        FanCurveProfiles {
+            balanced: vec![curve(FanCurvePU::CPU, false), curve(FanCurvePU::GPU, false)],
+            performance: vec![curve(FanCurvePU::CPU, true), curve(FanCurvePU::GPU, true)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn create_rename_delete() {
+        let mut profiles = profiles();
+        profiles
+            .create_preset("Render", ThrottlePolicy::Performance)
+            .unwrap();
+        assert_eq!(
+            profiles.preset("Render").unwrap().curves,
+            profiles.performance
+        );
+        assert!(matches!(
+            profiles.create_preset("Render", ThrottlePolicy::Balanced),
+            Err(ProfileError::PresetExists(_))
+        ));
+        assert!(matches!(
+            profiles.create_preset(" ", ThrottlePolicy::Balanced),
+            Err(ProfileError::ParseProfileName)
+        ));
+
+        profiles
+            .create_preset("Silent Office", ThrottlePolicy::Balanced)
+            .unwrap();
+        assert!(matches!(
+            profiles.rename_preset("Render", "Silent Office"),
+            Err(ProfileError::PresetExists(_))
+        ));
+        profiles.rename_preset("Render", "Compile").unwrap();
+        assert!(profiles.preset("Render").is_none());
+        assert!(profiles.preset("Compile").is_some());
+
+        profiles.delete_preset("Compile").unwrap();
+        assert!(matches!(
+            profiles.delete_preset("Compile"),
+            Err(ProfileError::NotFound(_))
+        ));
+        assert_eq!(profiles.presets.len(), 1);
+    }
+
+    #[test]
+    fn bind_replaces_policy_curves() {
+        let mut profiles = profiles();
+        let balanced = profiles.balanced.clone();
+        profiles
+            .create_preset("Gaming", ThrottlePolicy::Performance)
+            .unwrap();
+        profiles
+            .create_preset("Compile", ThrottlePolicy::Performance)
+            .unwrap();
+
+        profiles
+            .bind_preset("Gaming", ThrottlePolicy::Balanced)
+            .unwrap();
+        assert_eq!(
+            profiles.get_fan_curves_for(ThrottlePolicy::Balanced),
+            profiles.performance
+        );
+        // A policy can only have one preset
+        profiles
+            .bind_preset("Compile", ThrottlePolicy::Balanced)
+            .unwrap();
+        assert!(profiles.preset("Gaming").unwrap().policies.is_empty());
+        assert_eq!(
+            profiles.preset_for(ThrottlePolicy::Balanced).unwrap().name,
+            "Compile"
+        );
+
+        // A bound policy can't be changed, as that would change the preset
+        // for every policy bound to it
+        assert!(matches!(
+            profiles.set_profile_curves_enabled(ThrottlePolicy::Balanced, false),
+            Err(ProfileError::PresetBound(..))
+        ));
+        let curve = profiles.balanced[0].clone();
+        assert!(matches!(
+            profiles.save_fan_curve(curve, ThrottlePolicy::Balanced),
+            Err(ProfileError::PresetBound(..))
+        ));
+        assert!(profiles
+            .preset("Compile")
+            .unwrap()
+            .curves
+            .iter()
+            .all(|c| c.enabled));
+
+        assert!(profiles.unbind_preset(ThrottlePolicy::Balanced));
+        assert!(!profiles.unbind_preset(ThrottlePolicy::Balanced));
+        assert_eq!(
+            profiles.get_fan_curves_for(ThrottlePolicy::Balanced),
+            balanced
+        );
+    }
+
+    #[test]
+    fn save_preset_curve() {
+        let mut profiles = profiles();
+        profiles
+            .create_preset("Render", ThrottlePolicy::Balanced)
+            .unwrap();
+        let mut curve = profiles.balanced[0].clone();
+        curve.pwm = [50, 60, 70, 80, 90, 100, 110, 120];
+        profiles
+            .save_preset_fan_curve("Render", curve.clone())
+            .unwrap();
+        let preset = profiles.preset("Render").unwrap();
+        assert_eq!(preset.curves.len(), 2);
+        assert_eq!(preset.curves[0], curve);
+
+        curve.fan = FanCurvePU::MID;
+        profiles.save_preset_fan_curve("Render", curve).unwrap();
+        assert_eq!(profiles.preset("Render").unwrap().curves.len(), 3);
+    }
+}
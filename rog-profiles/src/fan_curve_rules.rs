@@ -0,0 +1,149 @@
+//! Safety checks for fan curves set by the user, so that a bad curve (such as
+//! one zeroed by a GUI bug) can't leave the fans stopped while the laptop is
+//! hot.
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::ProfileError;
+use crate::fan_curve_set::CurveData;
+use crate::FanCurvePU;
+
+/// The fan must run at `min_pwm` or faster once the temperature reaches `temp`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinPwmRule {
+    /// Degrees C
+    pub temp: u8,
+    /// Fan power in the range 0-255
+    pub min_pwm: u8,
+}
+
+impl MinPwmRule {
+    /// Create a rule with the fan power as a percentage
+    pub fn from_percent(temp: u8, min_percent: u8) -> Self {
+        Self {
+            temp,
+            min_pwm: (min_percent.min(100) as f32 * 2.55).round() as u8,
+        }
+    }
+}
+
+/// The minimum fan speeds each fan curve must meet. The defaults are below
+/// the firmware default curves.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FanCurveRules {
+    pub cpu: Vec<MinPwmRule>,
+    pub gpu: Vec<MinPwmRule>,
+    pub mid: Vec<MinPwmRule>,
+}
+
+impl Default for FanCurveRules {
+    fn default() -> Self {
+        let rules = vec![
+            MinPwmRule::from_percent(80, 30),
+            MinPwmRule::from_percent(90, 40),
+        ];
+        Self {
+            cpu: rules.clone(),
+            gpu: rules.clone(),
+            mid: rules,
+        }
+    }
+}
+
+impl FanCurveRules {
+    pub fn rules_for(&self, fan: FanCurvePU) -> &[MinPwmRule] {
+        match fan {
+            FanCurvePU::CPU => &self.cpu,
+            FanCurvePU::GPU => &self.gpu,
+            FanCurvePU::MID => &self.mid,
+        }
+    }
+
+    /// Check a curve before it is stored or written. Every curve must be
+    /// monotonic, and an enabled curve must meet the minimums for its fan
+    /// unless `expert` is set.
+    pub fn check(&self, curve: &CurveData, expert: bool) -> Result<(), ProfileError> {
+        curve.check_monotonic()?;
+        if expert || !curve.enabled {
+            return Ok(());
+        }
+        for rule in self.rules_for(curve.fan) {
+            let pwm = curve.pwm_at(rule.temp);
+            if pwm < rule.min_pwm {
+                return Err(ProfileError::CurveBelowMinimum(
+                    curve.fan,
+                    rule.temp,
+                    rule.min_pwm,
+                    pwm,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn curve(s: &str) -> CurveData {
+        let mut curve = CurveData::from_str(s).unwrap();
+        curve.enabled = true;
+        curve
+    }
+
+    #[test]
+    fn check_default_curve() {
+        let rules = FanCurveRules::default();
+        let curve = curve("30c:1%,49c:2%,59c:3%,69c:4%,79c:31%,89c:49%,99c:56%,109c:58%");
+        assert!(rules.check(&curve, false).is_ok());
+    }
+
+    #[test]
+    fn check_zeroed_curve() {
+        let rules = FanCurveRules::default();
+        let mut curve = CurveData {
+            enabled: true,
+            ..Default::default()
+        };
+        curve.temp = [30, 40, 50, 60, 70, 80, 90, 100];
+        assert!(matches!(
+            rules.check(&curve, false),
+            Err(ProfileError::CurveBelowMinimum(FanCurvePU::CPU, 80, 77, 0))
+        ));
+        assert!(rules.check(&curve, true).is_ok());
+        curve.enabled = false;
+        assert!(rules.check(&curve, false).is_ok());
+    }
+
+    #[test]
+    fn check_between_points() {
+        // Ramping from 0% at 79c to 100% at 100c is too late even though the
+        // last point is full speed
+        let rules = FanCurveRules::default();
+        let curve = curve("30c:0%,40c:0%,50c:0%,60c:0%,70c:0%,79c:0%,100c:100%,110c:100%");
+        assert!(matches!(
+            rules.check(&curve, false),
+            Err(ProfileError::CurveBelowMinimum(FanCurvePU::CPU, 80, _, 12))
+        ));
+    }
+
+    #[test]
+    fn check_not_monotonic() {
+        let rules = FanCurveRules::default();
+        let mut curve = curve("30c:1%,49c:2%,59c:3%,69c:4%,79c:31%,89c:49%,99c:56%,109c:58%");
+        curve.pwm[6] = 0;
+        assert!(matches!(
+            rules.check(&curve, true),
+            Err(ProfileError::CurveNotMonotonic(_, "pwm", 125, 0))
+        ));
+        curve.pwm[6] = 143;
+        curve.temp[2] = 20;
+        assert!(matches!(
+            rules.check(&curve, true),
+            Err(ProfileError::CurveNotMonotonic(_, "temperature", 49, 20))
+        ));
+    }
+}
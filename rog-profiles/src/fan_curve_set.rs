@@ -1,7 +1,9 @@
+use std::path::Path;
+
 use log::trace;
+use rog_platform::{read_attr_u8, write_attr_u8};
 use serde_derive::{Deserialize, Serialize};
 use typeshare::typeshare;
-use udev::Device;
 #[cfg(feature = "dbus")]
 use zbus::zvariant::Type;
 
@@ -31,9 +33,109 @@ pub(crate) fn temp_str(fan: char, index: usize) -> String {
     string
 }
 
+/// How a curve is filled in between its points
+#[typeshare]
+#[cfg_attr(feature = "dbus", derive(Type), zvariant(signature = "s"))]
+#[derive(Deserialize, Serialize, Default, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CurveResample {
+    /// Straight lines between the points
+    #[default]
+    Linear,
+    /// A smooth curve through the points which never overshoots them, so it
+    /// never decreases if the points don't
+    Cubic,
+}
+
+impl std::str::FromStr for CurveResample {
+    type Err = ProfileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().trim() {
+            "linear" => Ok(Self::Linear),
+            "cubic" => Ok(Self::Cubic),
+            _ => Err(ProfileError::ParseResample(s.to_owned())),
+        }
+    }
+}
+
+impl CurveResample {
+    /// The value at `x` of the curve through the points `xs`, `ys`. Both must
+    /// be non-decreasing and of the same length, at least 2. Repeated `xs`
+    /// are a vertical step, taken just above that `x`. Outside the points the
+    /// value of the nearest point is used.
+    fn evaluate(self, xs: &[f32], ys: &[f32], x: f32) -> f32 {
+        let last = xs.len() - 1;
+        if x <= xs[0] {
+            return ys[0];
+        }
+        if x > xs[last] {
+            return ys[last];
+        }
+        let i = xs.windows(2).position(|w| x <= w[1]).unwrap_or(last - 1);
+        let h = xs[i + 1] - xs[i];
+        if h == 0.0 {
+            return ys[i + 1];
+        }
+        let s = (x - xs[i]) / h;
+        match self {
+            Self::Linear => ys[i] + (ys[i + 1] - ys[i]) * s,
+            Self::Cubic => {
+                let m = monotone_tangents(xs, ys);
+                let (s2, s3) = (s * s, s * s * s);
+                let y = (2.0 * s3 - 3.0 * s2 + 1.0) * ys[i]
+                    + (s3 - 2.0 * s2 + s) * h * m[i]
+                    + (-2.0 * s3 + 3.0 * s2) * ys[i + 1]
+                    + (s3 - s2) * h * m[i + 1];
+                y.clamp(ys[i], ys[i + 1])
+            }
+        }
+    }
+}
+
+/// Tangents for a Fritsch-Carlson monotone cubic through the points
+fn monotone_tangents(xs: &[f32], ys: &[f32]) -> Vec<f32> {
+    let slopes: Vec<f32> = xs
+        .windows(2)
+        .zip(ys.windows(2))
+        .map(|(x, y)| {
+            if x[1] > x[0] {
+                (y[1] - y[0]) / (x[1] - x[0])
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let mut m = Vec::with_capacity(xs.len());
+    m.push(slopes[0]);
+    for pair in slopes.windows(2) {
+        if pair[0] * pair[1] <= 0.0 {
+            m.push(0.0);
+        } else {
+            m.push((pair[0] + pair[1]) / 2.0);
+        }
+    }
+    m.push(slopes[slopes.len() - 1]);
+
+    for (i, d) in slopes.iter().enumerate() {
+        if *d == 0.0 {
+            m[i] = 0.0;
+            m[i + 1] = 0.0;
+            continue;
+        }
+        let (a, b) = (m[i] / d, m[i + 1] / d);
+        let len = a.hypot(b);
+        if len > 3.0 {
+            m[i] = 3.0 / len * a * d;
+            m[i + 1] = 3.0 / len * b * d;
+        }
+    }
+    m
+}
+
 #[typeshare]
 #[cfg_attr(feature = "dbus", derive(Type))]
-#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+#[derive(Deserialize, Serialize, Default, Debug, PartialEq, Clone)]
 pub struct CurveData {
     pub fan: FanCurvePU,
     pub pwm: [u8; 8],
@@ -67,11 +169,26 @@ impl From<&CurveData> for String {
     }
 }
 
-impl std::str::FromStr for CurveData {
+/// The points of a curve as given by the user, before they are made into a
+/// [`CurveData`] of 8 points with [`CurvePoints::curve`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurvePoints(pub Vec<(u8, u8)>);
+
+impl CurvePoints {
+    /// The curve through these points for `fan`, see [`CurveData::from_points`]
+    pub fn curve(
+        &self,
+        fan: FanCurvePU,
+        resample: CurveResample,
+    ) -> Result<CurveData, ProfileError> {
+        CurveData::from_points(fan, &self.0, resample)
+    }
+}
+
+impl std::str::FromStr for CurvePoints {
     type Err = ProfileError;
 
-    /// Parse a string to the correct values that the fan curve kernel driver
-    /// expects. The returned `CurveData` is not enabled by default.
+    /// Parse a string of `temperature:fan` points, lowest first.
     ///
     /// If the fan curve is given with percentage char '%' then the fan power
     /// values are converted otherwise the expected fan power range is
@@ -80,13 +197,14 @@ impl std::str::FromStr for CurveData {
     /// Temperature range is 0-255 in degrees C. You don't want to be setting
     /// over 100.
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let mut temp = [0u8; 8];
-        let mut pwm = [0u8; 8];
+        let count = input.split(',').count();
+        let mut temp = vec![0u8; count];
+        let mut pwm = vec![0u8; count];
         let mut temp_prev = 0;
         let mut pwm_prev = 0;
         let mut percentages = false;
 
-        if input.split(',').count() < 8 {
+        if count < 2 {
             return Err(ProfileError::NotEnoughPoints);
         }
 
@@ -128,12 +246,21 @@ impl std::str::FromStr for CurveData {
                 }
             }
         }
-        Ok(Self {
-            fan: FanCurvePU::CPU,
-            pwm,
-            temp,
-            enabled: false,
-        })
+        Ok(Self(temp.into_iter().zip(pwm).collect()))
+    }
+}
+
+impl std::str::FromStr for CurveData {
+    type Err = ProfileError;
+
+    /// Parse a string to the correct values that the fan curve kernel driver
+    /// expects, see [`CurvePoints`]. The returned `CurveData` is not enabled
+    /// by default.
+    ///
+    /// Any number of points from 2 may be given, if not 8 they are resampled
+    /// to 8 with [`CurveData::from_points`] and straight lines.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        CurvePoints::from_str(input)?.curve(FanCurvePU::CPU, CurveResample::Linear)
     }
 }
 
@@ -142,50 +269,134 @@ impl CurveData {
         self.fan = fan;
     }
 
-    fn set_val_from_attr(tmp: &str, device: &Device, buf: &mut [u8; 8]) {
-        if let Some(n) = tmp.chars().nth(15) {
-            let i = n.to_digit(10).unwrap() as usize;
-            let d = device.attribute_value(tmp).unwrap();
-            let d: u8 = d.to_string_lossy().parse().unwrap();
-            buf[i - 1] = d;
+    /// Make a curve from any number of `(temp, pwm)` points from 2. Exactly 8
+    /// points are used as they are, otherwise the curve through them is
+    /// sampled at 8 temperatures spread evenly from the first point to the
+    /// last. The points must not decrease. The curve is not enabled.
+    pub fn from_points(
+        fan: FanCurvePU,
+        points: &[(u8, u8)],
+        resample: CurveResample,
+    ) -> Result<Self, ProfileError> {
+        if points.len() < 2 {
+            return Err(ProfileError::NotEnoughPoints);
+        }
+        for pair in points.windows(2) {
+            if pair[0].0 > pair[1].0 {
+                return Err(ProfileError::CurveNotMonotonic(
+                    fan,
+                    "temperature",
+                    pair[0].0,
+                    pair[1].0,
+                ));
+            }
+            if pair[0].1 > pair[1].1 {
+                return Err(ProfileError::CurveNotMonotonic(
+                    fan, "pwm", pair[0].1, pair[1].1,
+                ));
+            }
+        }
+
+        let mut curve = Self {
+            fan,
+            ..Default::default()
+        };
+        if points.len() == curve.temp.len() {
+            for (i, (temp, pwm)) in points.iter().enumerate() {
+                curve.temp[i] = *temp;
+                curve.pwm[i] = *pwm;
+            }
+            return Ok(curve);
+        }
+
+        let xs: Vec<f32> = points.iter().map(|p| p.0 as f32).collect();
+        let ys: Vec<f32> = points.iter().map(|p| p.1 as f32).collect();
+        let (first, last) = (xs[0], xs[xs.len() - 1]);
+        let steps = (curve.temp.len() - 1) as f32;
+        for i in 0..curve.temp.len() {
+            let temp = (first + (last - first) * i as f32 / steps).round();
+            curve.temp[i] = temp as u8;
+            curve.pwm[i] = resample.evaluate(&xs, &ys, temp).round() as u8;
+        }
+        Ok(curve)
+    }
+
+    /// Check that temperature and fan speed never decrease from one point to
+    /// the next, the kernel rejects curves which do
+    pub fn check_monotonic(&self) -> Result<(), ProfileError> {
+        for (part, values) in [("temperature", &self.temp), ("pwm", &self.pwm)] {
+            for pair in values.windows(2) {
+                if pair[0] > pair[1] {
+                    return Err(ProfileError::CurveNotMonotonic(
+                        self.fan, part, pair[0], pair[1],
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The fan speed at `temp` on straight lines between the points. Below
+    /// the first point or above the last the speed of that point is used.
+    pub fn pwm_at(&self, temp: u8) -> u8 {
+        self.evaluate(temp as f32, CurveResample::Linear).round() as u8
+    }
+
+    /// The fan speed at `temp` on the curve through the points, see
+    /// [`CurveResample`]
+    pub fn evaluate(&self, temp: f32, resample: CurveResample) -> f32 {
+        let xs = self.temp.map(|t| t as f32);
+        let ys = self.pwm.map(|p| p as f32);
+        resample.evaluate(&xs, &ys, temp)
+    }
+
+    /// Multiply the fan speed of each point by `factor`, for example `0.9` for
+    /// a curve 10% quieter. Speeds are limited to 0-255.
+    pub fn scale(&mut self, factor: f32) {
+        for pwm in self.pwm.iter_mut() {
+            *pwm = (*pwm as f32 * factor.max(0.0)).round().min(255.0) as u8;
+        }
+    }
+
+    /// Move each point `degrees` hotter, or cooler if negative. A positive
+    /// shift makes the fans speed up later. Temperatures are limited to 0-255.
+    pub fn shift(&mut self, degrees: i16) {
+        for temp in self.temp.iter_mut() {
+            *temp = (*temp as i16 + degrees).clamp(0, 255) as u8;
         }
     }
 
-    pub fn read_from_device(&mut self, device: &Device) {
-        for attr in device.attributes() {
-            let tmp = attr.name().to_string_lossy();
-            let pwm_num: char = self.fan.into();
-            let pwm = format!("pwm{pwm_num}");
-            if tmp.starts_with(&pwm) && tmp.ends_with("_temp") {
-                Self::set_val_from_attr(tmp.as_ref(), device, &mut self.temp);
+    pub fn read_from_device(&mut self, device: &Path) {
+        let pwm_num: char = self.fan.into();
+        for index in 0..8 {
+            if let Ok(value) = read_attr_u8(device, &temp_str(pwm_num, index)) {
+                self.temp[index] = value;
             }
-            if tmp.starts_with(&pwm) && tmp.ends_with("_pwm") {
-                Self::set_val_from_attr(tmp.as_ref(), device, &mut self.pwm);
+            if let Ok(value) = read_attr_u8(device, &pwm_str(pwm_num, index)) {
+                self.pwm[index] = value;
             }
         }
     }
 
     /// Write this curve to the device fan specified by `self.fan`
-    pub fn write_to_device(&self, device: &mut Device) -> std::io::Result<()> {
+    pub fn write_to_device(&self, device: &Path) -> Result<(), ProfileError> {
         let pwm_num: char = self.fan.into();
-        let enable = if self.enabled { '1' } else { '2' };
+        let enable = if self.enabled { 1 } else { 2 };
 
         for (index, out) in self.pwm.iter().enumerate() {
             let pwm = pwm_str(pwm_num, index);
             trace!("writing {pwm}");
-            device.set_attribute_value(&pwm, &out.to_string())?;
+            write_attr_u8(device, &pwm, *out)?;
         }
 
         for (index, out) in self.temp.iter().enumerate() {
             let temp = temp_str(pwm_num, index);
             trace!("writing {temp}");
-            device.set_attribute_value(&temp, &out.to_string())?;
+            write_attr_u8(device, &temp, *out)?;
         }
 
         // Enable must be done *after* all points are written pwm3_enable
-        device
-            .set_attribute_value(format!("pwm{pwm_num}_enable"), enable.to_string())
-            .unwrap();
+        write_attr_u8(device, &format!("pwm{pwm_num}_enable"), enable)?;
         Ok(())
     }
 }
@@ -213,9 +424,70 @@ mod tests {
             "CPU: enabled: true, 30c:1%,49c:1%,59c:3%,69c:3%,79c:30%,89c:49%,99c:56%,109c:58%"
         );
 
-        let curve = CurveData::from_str("30c:1%,49c:2%,59c:3%,69c:4%,79c:31%,89c:49%,99c:56%");
+        let curve = CurveData::from_str("30c:1%");
+        assert!(matches!(curve, Err(ProfileError::NotEnoughPoints)));
+    }
+
+    #[test]
+    fn curve_data_from_str_resampled() {
+        let curve = CurveData::from_str("30:0,51:70,100:255").unwrap();
+        assert_eq!(curve.temp, [30, 40, 50, 60, 70, 80, 90, 100]);
+        assert_eq!(curve.pwm, [0, 33, 67, 104, 142, 179, 217, 255]);
+    }
+
+    #[test]
+    fn curve_from_points_cubic() {
+        let points = [(30, 0), (50, 20), (60, 200), (100, 255)];
+        let linear =
+            CurveData::from_points(FanCurvePU::GPU, &points, CurveResample::Linear).unwrap();
+        let cubic = CurveData::from_points(FanCurvePU::GPU, &points, CurveResample::Cubic).unwrap();
+        assert_eq!(cubic.fan, FanCurvePU::GPU);
+        assert_eq!(cubic.temp, linear.temp);
+        assert_eq!(linear.pwm, [0, 10, 20, 200, 214, 228, 241, 255]);
+        // Smooth but still never decreasing or above the next point
+        assert_eq!(cubic.pwm, [0, 3, 20, 200, 230, 245, 251, 255]);
+        cubic.check_monotonic().unwrap();
+
+        // The points are on the curve
+        assert_eq!(cubic.evaluate(60.0, CurveResample::Cubic), 200.0);
+
+        assert_eq!(
+            CurveResample::from_str(" Cubic").unwrap(),
+            CurveResample::Cubic
+        );
+        assert!(matches!(
+            CurveResample::from_str("spline"),
+            Err(ProfileError::ParseResample(_))
+        ));
+
+        assert!(matches!(
+            CurveData::from_points(FanCurvePU::CPU, &[(50, 10), (40, 20)], CurveResample::Cubic),
+            Err(ProfileError::CurveNotMonotonic(..))
+        ));
+        assert!(matches!(
+            CurveData::from_points(FanCurvePU::CPU, &[(50, 10)], CurveResample::Linear),
+            Err(ProfileError::NotEnoughPoints)
+        ));
+    }
+
+    #[test]
+    fn curve_scale_shift() {
+        let mut curve = CurveData {
+            fan: FanCurvePU::CPU,
+            pwm: [0, 10, 50, 100, 150, 200, 250, 255],
+            temp: [5, 40, 50, 60, 70, 80, 90, 250],
+            enabled: true,
+        };
+        curve.scale(0.9);
+        assert_eq!(curve.pwm, [0, 9, 45, 90, 135, 180, 225, 230]);
+        curve.scale(2.0);
+        assert_eq!(curve.pwm, [0, 18, 90, 180, 255, 255, 255, 255]);
 
-        assert!(curve.is_err());
+        curve.shift(10);
+        assert_eq!(curve.temp, [15, 50, 60, 70, 80, 90, 100, 255]);
+        curve.shift(-20);
+        assert_eq!(curve.temp, [0, 30, 40, 50, 60, 70, 80, 235]);
+        curve.check_monotonic().unwrap();
     }
 
     #[test]
@@ -251,6 +523,24 @@ mod tests {
         assert_eq!(temp_str('1', 7), "pwm1_auto_point8_temp");
     }
 
+    #[test]
+    fn curve_pwm_at() {
+        let curve = CurveData {
+            fan: FanCurvePU::CPU,
+            pwm: [0, 0, 20, 40, 100, 150, 200, 255],
+            temp: [30, 40, 50, 60, 70, 70, 90, 100],
+            enabled: true,
+        };
+        assert_eq!(curve.pwm_at(0), 0);
+        assert_eq!(curve.pwm_at(45), 10);
+        assert_eq!(curve.pwm_at(65), 70);
+        // A vertical step is taken just above its temperature
+        assert_eq!(curve.pwm_at(70), 100);
+        assert_eq!(curve.pwm_at(71), 153);
+        assert_eq!(curve.pwm_at(80), 175);
+        assert_eq!(curve.pwm_at(120), 255);
+    }
+
     // #[test]
     // fn set_to_string() {
     //     let set = FanCurveSet::default();
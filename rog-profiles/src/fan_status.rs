@@ -0,0 +1,201 @@
+//! What the fans are doing right now: speed, power, and the temperature each
+//! curve follows.
+
+use std::path::PathBuf;
+
+use log::debug;
+use rog_platform::sysfs::SysfsRoot;
+use rog_platform::{has_attr, read_attr_u32, read_attr_u8};
+use serde_derive::{Deserialize, Serialize};
+use typeshare::typeshare;
+#[cfg(feature = "dbus")]
+use zbus::zvariant::{OwnedValue, Type, Value};
+
+use crate::error::ProfileError;
+use crate::fan_curve_set::CurveData;
+use crate::software_fan::{FanSensors, SoftwareFanLoop};
+use crate::FanCurvePU;
+
+#[typeshare]
+#[cfg_attr(feature = "dbus", derive(Type, Value, OwnedValue))]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FanStatus {
+    pub fan: FanCurvePU,
+    /// 0 if the fan has no tachometer
+    pub rpm: u32,
+    /// Fan power in the range 0-255
+    pub pwm: u8,
+    /// Degrees C of the sensor the fan curve follows
+    pub temp: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MonitoredFan {
+    fan: FanCurvePU,
+    /// The hwmon with `fanN_input` and maybe `pwmN`
+    device: Option<PathBuf>,
+    index: u8,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FanMonitor {
+    fans: Vec<MonitoredFan>,
+    sensors: FanSensors,
+    /// The fans are driven by `SoftwareFanLoop`, so `pwmN` is what the curve
+    /// asked for
+    software: bool,
+}
+
+/// The `asus` hwmon, which has the fan speeds of fans using the firmware
+/// curves in `fanN_input`
+pub(crate) fn find_fan_speed_node_in(root: &SysfsRoot) -> Result<Option<PathBuf>, ProfileError> {
+    for path in root.scan_subsystem("hwmon", None)? {
+        if rog_platform::read_attr_string(&path, "name").is_ok_and(|n| n.trim() == "asus") {
+            debug!("Fan speeds are in {path:?}");
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+impl FanMonitor {
+    /// Monitor fans which use the firmware curves. The speeds are read from the
+    /// `asus` hwmon, `fan1_input` for the CPU fan, `fan2_input` for the GPU,
+    /// and `fan3_input` for mid.
+    pub fn find_in(root: &SysfsRoot, fans: &[FanCurvePU]) -> Result<Self, ProfileError> {
+        let device = find_fan_speed_node_in(root)?;
+        Ok(Self {
+            fans: fans
+                .iter()
+                .map(|fan| MonitoredFan {
+                    fan: *fan,
+                    device: device.clone(),
+                    index: char::from(*fan).to_digit(10).unwrap_or(1) as u8,
+                })
+                .collect(),
+            sensors: FanSensors::find_in(root)?,
+            software: false,
+        })
+    }
+
+    /// Monitor the fans driven by `software`, reading each from the hwmon it is
+    /// driven through
+    pub fn for_software(
+        root: &SysfsRoot,
+        software: &SoftwareFanLoop,
+    ) -> Result<Self, ProfileError> {
+        Ok(Self {
+            fans: software
+                .fans()
+                .iter()
+                .map(|fan| MonitoredFan {
+                    fan: fan.fan,
+                    device: Some(fan.device.clone()),
+                    index: fan.index,
+                })
+                .collect(),
+            sensors: FanSensors::find_in(root)?,
+            software: true,
+        })
+    }
+
+    /// Read each fan. `curves` are the curves in use, the fan power is taken
+    /// from these while a firmware curve is enabled as there is nothing to
+    /// read it from. Values which can't be read are 0.
+    pub fn read(&self, curves: &[CurveData]) -> Vec<FanStatus> {
+        self.fans
+            .iter()
+            .map(|monitored| {
+                let temp = self.sensors.read_for(monitored.fan).unwrap_or_default();
+                let read = |attr: String| {
+                    monitored
+                        .device
+                        .as_ref()
+                        .filter(|dev| has_attr(dev, &attr))
+                        .map(|dev| (dev.clone(), attr))
+                };
+                let rpm = read(format!("fan{}_input", monitored.index))
+                    .and_then(|(dev, attr)| read_attr_u32(&dev, &attr).ok())
+                    .unwrap_or_default();
+                let curve = curves.iter().find(|c| c.fan == monitored.fan && c.enabled);
+                let pwm = match curve {
+                    Some(curve) if !self.software => curve.pwm_at(temp),
+                    _ => read(format!("pwm{}", monitored.index))
+                        .and_then(|(dev, attr)| read_attr_u8(&dev, &attr).ok())
+                        .unwrap_or_default(),
+                };
+                FanStatus {
+                    fan: monitored.fan,
+                    rpm,
+                    pwm,
+                    temp,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rog_platform::sysfs::SysfsFixture;
+
+    use super::{FanMonitor, FanStatus};
+    use crate::fan_curve_set::CurveData;
+    use crate::FanCurvePU;
+
+    fn fixture(name: &str) -> SysfsFixture {
+        let fixture = SysfsFixture::new(name);
+        fixture
+            .device(
+                "class/hwmon/hwmon0",
+                &[("name", "coretemp"), ("temp1_input", "62000")],
+            )
+            .device(
+                "class/hwmon/hwmon1",
+                &[
+                    ("name", "asus"),
+                    ("fan1_input", "2400"),
+                    ("fan2_input", "3100"),
+                    ("pwm1", "77"),
+                ],
+            );
+        fixture
+    }
+
+    #[test]
+    fn read_firmware_fans() {
+        let fixture = fixture("firmware");
+        let root = fixture.root();
+        let monitor =
+            FanMonitor::find_in(&root, &[FanCurvePU::CPU, FanCurvePU::GPU, FanCurvePU::MID])
+                .unwrap();
+
+        let mut gpu = CurveData::software_default(FanCurvePU::GPU);
+        gpu.enabled = true;
+        let status = monitor.read(&[gpu]);
+        assert_eq!(
+            status,
+            vec![
+                FanStatus {
+                    fan: FanCurvePU::CPU,
+                    rpm: 2400,
+                    pwm: 77,
+                    temp: 62
+                },
+                // The GPU follows the CPU sensor, and the curve gives the power
+                FanStatus {
+                    fan: FanCurvePU::GPU,
+                    rpm: 3100,
+                    pwm: 97,
+                    temp: 62
+                },
+                FanStatus {
+                    fan: FanCurvePU::MID,
+                    rpm: 0,
+                    pwm: 0,
+                    temp: 62
+                },
+            ]
+        );
+    }
+}
@@ -1,29 +1,40 @@
 pub mod error;
+pub mod fan_calibration;
+pub mod fan_curve_export;
+pub mod fan_curve_power;
+pub mod fan_curve_preset;
+pub mod fan_curve_rules;
 pub mod fan_curve_set;
+pub mod fan_status;
+pub mod software_fan;
+
+use std::path::{Path, PathBuf};
 
 use error::ProfileError;
+use fan_curve_preset::FanCurvePreset;
 use fan_curve_set::CurveData;
 use log::debug;
 use rog_platform::platform::ThrottlePolicy;
+use rog_platform::power::PowerSource;
+use rog_platform::sysfs::{sysfs_root, SysfsRoot};
 use serde_derive::{Deserialize, Serialize};
 use typeshare::typeshare;
-pub use udev::Device;
 #[cfg(feature = "dbus")]
 use zbus::zvariant::Type;
 use zbus::zvariant::{OwnedValue, Value};
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub fn find_fan_curve_node() -> Result<Device, ProfileError> {
-    let mut enumerator = udev::Enumerator::new()?;
-    enumerator.match_subsystem("hwmon")?;
+pub fn find_fan_curve_node() -> Result<PathBuf, ProfileError> {
+    find_fan_curve_node_in(sysfs_root())
+}
 
-    for device in enumerator.scan_devices()? {
-        if device.parent_with_subsystem("platform")?.is_some() {
-            if let Some(name) = device.attribute_value("name") {
-                if name == "asus_custom_fan_curve" {
-                    return Ok(device);
-                }
+/// Find the `asus_custom_fan_curve` hwmon device under `root`
+pub fn find_fan_curve_node_in(root: &SysfsRoot) -> Result<PathBuf, ProfileError> {
+    for path in root.scan_subsystem("hwmon", None)? {
+        if let Ok(name) = rog_platform::read_attr_string(&path, "name") {
+            if name == "asus_custom_fan_curve" {
+                return Ok(path);
             }
         }
     }
@@ -45,18 +56,15 @@ pub enum FanCurvePU {
 }
 
 impl FanCurvePU {
-    fn which_fans(device: &Device) -> Vec<Self> {
+    fn which_fans(device: &Path) -> Vec<Self> {
         let mut fans = Vec::with_capacity(3);
         for fan in [Self::CPU, Self::GPU, Self::MID] {
             let pwm_num: char = fan.into();
             let pwm_enable = format!("pwm{pwm_num}_enable");
             debug!("Looking for {pwm_enable}");
-            for attr in device.attributes() {
-                let tmp = attr.name().to_string_lossy();
-                if tmp.contains(&pwm_enable) {
-                    debug!("Found {pwm_enable}");
-                    fans.push(fan);
-                }
+            if rog_platform::has_attr(device, &pwm_enable) {
+                debug!("Found {pwm_enable}");
+                fans.push(fan);
             }
         }
         fans
@@ -102,6 +110,24 @@ impl Default for FanCurvePU {
     }
 }
 
+/// Write each curve to the device, the enabled ones last
+pub(crate) fn write_curves_to_platform(
+    curves: &[CurveData],
+    device: &Path,
+) -> Result<(), ProfileError> {
+    for fan in curves.iter().filter(|f| !f.enabled) {
+        debug!("write_curves_to_platform: writing {fan:?}");
+        fan.write_to_device(device)?;
+    }
+    // Write enabled fans last because the kernel currently resets *all* if one is
+    // disabled
+    for fan in curves.iter().filter(|f| f.enabled) {
+        debug!("write_curves_to_platform: writing {fan:?}");
+        fan.write_to_device(device)?;
+    }
+    Ok(())
+}
+
 /// Main purpose of `FanCurves` is to enable restoring state on system boot
 #[typeshare]
 #[cfg_attr(feature = "dbus", derive(Type))]
@@ -110,6 +136,17 @@ pub struct FanCurveProfiles {
     pub balanced: Vec<CurveData>,
     pub performance: Vec<CurveData>,
     pub quiet: Vec<CurveData>,
+    /// Curves used on battery in place of the above, if not empty
+    #[serde(default)]
+    pub balanced_battery: Vec<CurveData>,
+    #[serde(default)]
+    pub performance_battery: Vec<CurveData>,
+    #[serde(default)]
+    pub quiet_battery: Vec<CurveData>,
+    /// User named curves which can be applied at any time, or used in place
+    /// of the curves of a throttle policy
+    #[serde(default)]
+    pub presets: Vec<FanCurvePreset>,
 }
 
 impl FanCurveProfiles {
@@ -124,9 +161,9 @@ impl FanCurveProfiles {
     pub fn read_from_dev_profile(
         &mut self,
         profile: ThrottlePolicy,
-        device: &Device,
+        device: &Path,
     ) -> Result<(), ProfileError> {
-        let fans = Self::supported_fans()?;
+        let fans = FanCurvePU::which_fans(device);
         let mut curves = Vec::with_capacity(3);
 
         for fan in fans {
@@ -152,68 +189,81 @@ impl FanCurveProfiles {
     /// platform.
     ///
     /// Each `platform_profile` has a different default and the defualt can be
-    /// read only for the currently active profile.
+    /// read only for the currently active profile. Any preset bound to the
+    /// profile is unbound so that the defaults are used.
     pub fn set_active_curve_to_defaults(
         &mut self,
         profile: ThrottlePolicy,
-        device: &mut Device,
+        device: &Path,
     ) -> Result<(), ProfileError> {
-        let fans = Self::supported_fans()?;
+        let fans = FanCurvePU::which_fans(device);
         // Do reset for all
         for fan in fans {
             let pwm_num: char = fan.into();
             let pwm = format!("pwm{pwm_num}_enable");
-            device.set_attribute_value(&pwm, "3")?;
+            rog_platform::write_attr_string(device, &pwm, "3")?;
         }
         self.read_from_dev_profile(profile, device)?;
+        self.unbind_preset(profile);
         Ok(())
     }
 
     /// Write the curves for the selected profile to the device. If the curve is
     /// in the enabled list it will become active. If the curve is zeroed it
     /// will be initialised to a default read from the system.
+    ///
+    /// If a preset is bound to the profile then its curves are written instead,
+    /// and on battery the battery curves of the profile if it has any.
     // TODO: Make this return an error if curve is zeroed
     pub fn write_profile_curve_to_platform(
         &mut self,
         profile: ThrottlePolicy,
-        device: &mut Device,
+        power: PowerSource,
+        device: &Path,
     ) -> Result<(), ProfileError> {
-        let fans = match profile {
+        match self.preset_for(profile) {
+            Some(preset) => debug!(
+                "write_profile_curve_to_platform: writing profile:{profile}, {power:?}, preset:{}",
+                preset.name
+            ),
+            None => debug!("write_profile_curve_to_platform: writing profile:{profile}, {power:?}"),
+        }
+        write_curves_to_platform(self.curves_for(profile, power), device)
+    }
+
+    /// The curves of the profile itself. These can't be changed while a preset
+    /// is bound to the profile, as that would change every profile bound to
+    /// the preset.
+    fn own_curves_mut(
+        &mut self,
+        profile: ThrottlePolicy,
+    ) -> Result<&mut Vec<CurveData>, ProfileError> {
+        if let Some(preset) = self.preset_for(profile) {
+            return Err(ProfileError::PresetBound(
+                profile.to_string(),
+                preset.name.clone(),
+            ));
+        }
+        Ok(match profile {
             ThrottlePolicy::Balanced => &mut self.balanced,
             ThrottlePolicy::Performance => &mut self.performance,
             ThrottlePolicy::Quiet => &mut self.quiet,
-        };
-        for fan in fans.iter().filter(|f| !f.enabled) {
-            debug!("write_profile_curve_to_platform: writing profile:{profile}, {fan:?}");
-            fan.write_to_device(device)?;
-        }
-        // Write enabled fans last because the kernel currently resets *all* if one is
-        // disabled
-        for fan in fans.iter().filter(|f| f.enabled) {
-            debug!("write_profile_curve_to_platform: writing profile:{profile}, {fan:?}");
-            fan.write_to_device(device)?;
-        }
-        Ok(())
+        })
     }
 
-    pub fn set_profile_curves_enabled(&mut self, profile: ThrottlePolicy, enabled: bool) {
-        match profile {
-            ThrottlePolicy::Balanced => {
-                for curve in self.balanced.iter_mut() {
-                    curve.enabled = enabled;
-                }
-            }
-            ThrottlePolicy::Performance => {
-                for curve in self.performance.iter_mut() {
-                    curve.enabled = enabled;
-                }
-            }
-            ThrottlePolicy::Quiet => {
-                for curve in self.quiet.iter_mut() {
-                    curve.enabled = enabled;
-                }
-            }
+    /// Enable or disable the curves of the profile, on AC and on battery
+    pub fn set_profile_curves_enabled(
+        &mut self,
+        profile: ThrottlePolicy,
+        enabled: bool,
+    ) -> Result<(), ProfileError> {
+        for curve in self.own_curves_mut(profile)?.iter_mut() {
+            curve.enabled = enabled;
+        }
+        for curve in self.battery_curves_mut(profile).iter_mut() {
+            curve.enabled = enabled;
         }
+        Ok(())
     }
 
     pub fn set_profile_fan_curve_enabled(
@@ -221,36 +271,30 @@ impl FanCurveProfiles {
         profile: ThrottlePolicy,
         fan: FanCurvePU,
         enabled: bool,
-    ) {
-        match profile {
-            ThrottlePolicy::Balanced => {
-                for curve in self.balanced.iter_mut() {
-                    if curve.fan == fan {
-                        curve.enabled = enabled;
-                        break;
-                    }
-                }
-            }
-            ThrottlePolicy::Performance => {
-                for curve in self.performance.iter_mut() {
-                    if curve.fan == fan {
-                        curve.enabled = enabled;
-                        break;
-                    }
-                }
-            }
-            ThrottlePolicy::Quiet => {
-                for curve in self.quiet.iter_mut() {
-                    if curve.fan == fan {
-                        curve.enabled = enabled;
-                        break;
-                    }
-                }
-            }
+    ) -> Result<(), ProfileError> {
+        if let Some(curve) = self
+            .own_curves_mut(profile)?
+            .iter_mut()
+            .find(|c| c.fan == fan)
+        {
+            curve.enabled = enabled;
         }
+        if let Some(curve) = self
+            .battery_curves_mut(profile)
+            .iter_mut()
+            .find(|c| c.fan == fan)
+        {
+            curve.enabled = enabled;
+        }
+        Ok(())
     }
 
+    /// The curves used for the profile. If a preset is bound to the profile
+    /// these are the curves of the preset.
     pub fn get_fan_curves_for(&self, name: ThrottlePolicy) -> &[CurveData] {
+        if let Some(preset) = self.preset_for(name) {
+            return &preset.curves;
+        }
         match name {
             ThrottlePolicy::Balanced => &self.balanced,
             ThrottlePolicy::Performance => &self.performance,
@@ -259,62 +303,23 @@ impl FanCurveProfiles {
     }
 
     pub fn get_fan_curve_for(&self, name: &ThrottlePolicy, pu: FanCurvePU) -> Option<&CurveData> {
-        match name {
-            ThrottlePolicy::Balanced => {
-                for this_curve in self.balanced.iter() {
-                    if this_curve.fan == pu {
-                        return Some(this_curve);
-                    }
-                }
-            }
-            ThrottlePolicy::Performance => {
-                for this_curve in self.performance.iter() {
-                    if this_curve.fan == pu {
-                        return Some(this_curve);
-                    }
-                }
-            }
-            ThrottlePolicy::Quiet => {
-                for this_curve in self.quiet.iter() {
-                    if this_curve.fan == pu {
-                        return Some(this_curve);
-                    }
-                }
-            }
-        }
-        None
+        self.get_fan_curves_for(*name).iter().find(|c| c.fan == pu)
     }
 
+    /// Save the curve for the profile. A profile with a preset bound to it
+    /// can't be changed, the preset is changed with
+    /// [`FanCurveProfiles::save_preset_fan_curve()`] instead.
     pub fn save_fan_curve(
         &mut self,
         curve: CurveData,
         profile: ThrottlePolicy,
     ) -> Result<(), ProfileError> {
-        match profile {
-            ThrottlePolicy::Balanced => {
-                for this_curve in self.balanced.iter_mut() {
-                    if this_curve.fan == curve.fan {
-                        *this_curve = curve;
-                        break;
-                    }
-                }
-            }
-            ThrottlePolicy::Performance => {
-                for this_curve in self.performance.iter_mut() {
-                    if this_curve.fan == curve.fan {
-                        *this_curve = curve;
-                        break;
-                    }
-                }
-            }
-            ThrottlePolicy::Quiet => {
-                for this_curve in self.quiet.iter_mut() {
-                    if this_curve.fan == curve.fan {
-                        *this_curve = curve;
-                        break;
-                    }
-                }
-            }
+        if let Some(this_curve) = self
+            .own_curves_mut(profile)?
+            .iter_mut()
+            .find(|c| c.fan == curve.fan)
+        {
+            *this_curve = curve;
         }
         Ok(())
     }
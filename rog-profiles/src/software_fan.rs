@@ -0,0 +1,536 @@
+//! Userspace fan control for laptops without the `asus_custom_fan_curve`
+//! firmware curves. Temperatures are read from hwmon and the stored curves are
+//! followed by writing `pwmN` directly, with `pwmN_enable` set to manual.
+//!
+//! Whoever runs the loop must call [`PwmFan::set_auto()`] on every fan when it
+//! stops, otherwise the fans stay at the last speed written.
+
+use std::fs;
+use std::path::PathBuf;
+
+use log::{debug, error, info, trace, warn};
+use rog_platform::platform::ThrottlePolicy;
+use rog_platform::sysfs::SysfsRoot;
+use rog_platform::{read_attr_string, read_attr_u8, write_attr_u8};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::ProfileError;
+use crate::fan_curve_set::CurveData;
+use crate::{FanCurvePU, FanCurveProfiles};
+
+/// hwmon device names of CPU temperature sensors
+const CPU_SENSORS: [&str; 2] = ["k10temp", "coretemp"];
+/// hwmon device names of GPU temperature sensors
+const GPU_SENSORS: [&str; 1] = ["amdgpu"];
+/// hwmon device names of the ASUS fans. Only these are driven, the `pwmN` of
+/// a GPU or Super-I/O chip may be any fan.
+const ASUS_FANS: [&str; 2] = ["asus", "asus_nb_wmi"];
+/// `pwmN_enable` value for manual control
+const PWM_MANUAL: u8 = 1;
+/// `pwmN_enable` value for automatic control used by most drivers
+const PWM_AUTO: u8 = 2;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct SoftwareFanConfig {
+    /// Run the loop when the firmware has no fan curve support
+    pub enabled: bool,
+    /// Milliseconds between each step
+    pub interval_ms: u64,
+    /// Degrees C the temperature must fall before the fans slow down
+    pub hysteresis: u8,
+    /// Largest fan power increase per step, 0 for no limit
+    pub ramp_up: u8,
+    /// Largest fan power decrease per step, 0 for no limit
+    pub ramp_down: u8,
+}
+
+impl Default for SoftwareFanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: 1000,
+            hysteresis: 3,
+            ramp_up: 40,
+            ramp_down: 10,
+        }
+    }
+}
+
+fn hwmon_devices(root: &SysfsRoot) -> Result<Vec<(String, PathBuf)>, ProfileError> {
+    let mut devices = Vec::new();
+    for path in root.scan_subsystem("hwmon", None)? {
+        if let Ok(name) = read_attr_string(&path, "name") {
+            devices.push((name.trim().to_owned(), path));
+        }
+    }
+    Ok(devices)
+}
+
+/// A hwmon `tempN_input`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TempSensor {
+    device: PathBuf,
+    attr: String,
+}
+
+impl TempSensor {
+    /// Degrees C, clamped to 0-255
+    pub fn read(&self) -> Result<u8, ProfileError> {
+        let millis: i64 = read_attr_string(&self.device, &self.attr)?
+            .trim()
+            .parse()
+            .map_err(|_| ProfileError::NotFound(format!("{} temperature", self.attr)))?;
+        Ok((millis / 1000).clamp(0, 255) as u8)
+    }
+}
+
+/// The temperatures the software curves follow
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FanSensors {
+    pub cpu: Option<TempSensor>,
+    pub gpu: Option<TempSensor>,
+}
+
+impl FanSensors {
+    pub fn find_in(root: &SysfsRoot) -> Result<Self, ProfileError> {
+        let mut sensors = Self::default();
+        for (name, device) in hwmon_devices(root)? {
+            if !rog_platform::has_attr(&device, "temp1_input") {
+                continue;
+            }
+            let sensor = Some(TempSensor {
+                device,
+                attr: "temp1_input".to_owned(),
+            });
+            if sensors.cpu.is_none() && CPU_SENSORS.contains(&name.as_str()) {
+                debug!("Found CPU temperature sensor {name}");
+                sensors.cpu = sensor;
+            } else if sensors.gpu.is_none() && GPU_SENSORS.contains(&name.as_str()) {
+                debug!("Found GPU temperature sensor {name}");
+                sensors.gpu = sensor;
+            }
+        }
+        Ok(sensors)
+    }
+
+    /// The temperature `fan` follows. The GPU fan follows the CPU if there is
+    /// no GPU sensor, and the mid fan follows the hotter of the two.
+    pub fn read_for(&self, fan: FanCurvePU) -> Result<u8, ProfileError> {
+        let cpu = self.cpu.as_ref().map(TempSensor::read).transpose()?;
+        let gpu = self.gpu.as_ref().map(TempSensor::read).transpose()?;
+        let temp = match fan {
+            FanCurvePU::CPU => cpu,
+            FanCurvePU::GPU => gpu.or(cpu),
+            FanCurvePU::MID => cpu.max(gpu),
+        };
+        temp.ok_or_else(|| ProfileError::NotFound(format!("temperature sensor for {fan:?} fan")))
+    }
+}
+
+/// The fan named by a `fanN_label` of the ASUS hwmon, such as `cpu_fan`
+fn fan_from_label(label: &str) -> Option<FanCurvePU> {
+    let label = label.trim().to_ascii_lowercase();
+    if label.starts_with("cpu") {
+        Some(FanCurvePU::CPU)
+    } else if label.starts_with("gpu") {
+        Some(FanCurvePU::GPU)
+    } else if label.starts_with("mid") {
+        Some(FanCurvePU::MID)
+    } else {
+        None
+    }
+}
+
+/// A writable hwmon `pwmN`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PwmFan {
+    pub fan: FanCurvePU,
+    pub(crate) device: PathBuf,
+    pub(crate) index: u8,
+    /// The `pwmN_enable` value found at start, restored by `set_auto()`
+    auto_mode: u8,
+}
+
+impl PwmFan {
+    /// Find the ASUS fans which can be driven directly. Each `pwmN` is
+    /// matched to the CPU, GPU, or mid fan by its `fanN_label`, and is left
+    /// alone without one.
+    pub fn find_in(root: &SysfsRoot) -> Result<Vec<Self>, ProfileError> {
+        let mut fans: Vec<Self> = Vec::new();
+        for (name, device) in hwmon_devices(root)? {
+            if !ASUS_FANS.contains(&name.as_str()) {
+                continue;
+            }
+            for index in 1..=9 {
+                let pwm = device.join(format!("pwm{index}"));
+                let enable = format!("pwm{index}_enable");
+                let writable = fs::metadata(&pwm).is_ok_and(|m| !m.permissions().readonly());
+                if !writable || !rog_platform::has_attr(&device, &enable) {
+                    continue;
+                }
+                let label = read_attr_string(&device, &format!("fan{index}_label"));
+                let Some(fan) = label.as_deref().ok().and_then(fan_from_label) else {
+                    debug!("Software fan control: {name} pwm{index} has no known fan label");
+                    continue;
+                };
+                if fans.iter().any(|f| f.fan == fan) {
+                    continue;
+                }
+                let auto_mode = match read_attr_u8(&device, &enable) {
+                    Ok(mode) if mode != PWM_MANUAL && mode != 0 => mode,
+                    _ => PWM_AUTO,
+                };
+                info!("Software fan control: {fan:?} fan is {name} pwm{index}");
+                fans.push(Self {
+                    fan,
+                    device: device.clone(),
+                    index,
+                    auto_mode,
+                });
+            }
+        }
+        Ok(fans)
+    }
+
+    pub fn set_manual(&self) -> Result<(), ProfileError> {
+        write_attr_u8(
+            &self.device,
+            &format!("pwm{}_enable", self.index),
+            PWM_MANUAL,
+        )?;
+        Ok(())
+    }
+
+    /// Hand control back to the firmware or driver
+    pub fn set_auto(&self) -> Result<(), ProfileError> {
+        write_attr_u8(
+            &self.device,
+            &format!("pwm{}_enable", self.index),
+            self.auto_mode,
+        )?;
+        Ok(())
+    }
+
+    pub fn write_pwm(&self, pwm: u8) -> Result<(), ProfileError> {
+        write_attr_u8(&self.device, &format!("pwm{}", self.index), pwm)?;
+        Ok(())
+    }
+}
+
+/// Tracks one fan between steps of the loop
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SoftwareFanState {
+    temp: Option<u8>,
+    pwm: Option<u8>,
+}
+
+impl SoftwareFanState {
+    /// The fan speed for `curve` at `temp`. Small drops in temperature are
+    /// ignored, and the change from the last speed is limited.
+    pub fn step(&mut self, curve: &CurveData, temp: u8, config: &SoftwareFanConfig) -> u8 {
+        let temp = match self.temp {
+            Some(last) if temp < last && last - temp < config.hysteresis => last,
+            _ => temp,
+        };
+        self.temp = Some(temp);
+
+        let target = curve.pwm_at(temp);
+        let pwm = match self.pwm {
+            Some(last) if target > last && config.ramp_up > 0 => {
+                target.min(last.saturating_add(config.ramp_up))
+            }
+            Some(last) if target < last && config.ramp_down > 0 => {
+                target.max(last.saturating_sub(config.ramp_down))
+            }
+            _ => target,
+        };
+        self.pwm = Some(pwm);
+        pwm
+    }
+}
+
+/// The fans and sensors under software control, and the state of each fan
+#[derive(Debug, Clone)]
+pub struct SoftwareFanLoop {
+    fans: Vec<PwmFan>,
+    sensors: FanSensors,
+    states: Vec<SoftwareFanState>,
+    manual: Vec<bool>,
+}
+
+impl SoftwareFanLoop {
+    /// Find the fans and sensors, errors if there are no writable fans or no
+    /// temperature sensors
+    pub fn find_in(root: &SysfsRoot) -> Result<Self, ProfileError> {
+        let fans = PwmFan::find_in(root)?;
+        let sensors = FanSensors::find_in(root)?;
+        if fans.is_empty() || (sensors.cpu.is_none() && sensors.gpu.is_none()) {
+            return Err(ProfileError::NotSupported);
+        }
+        Ok(Self {
+            states: vec![SoftwareFanState::default(); fans.len()],
+            manual: vec![false; fans.len()],
+            fans,
+            sensors,
+        })
+    }
+
+    pub fn fans(&self) -> &[PwmFan] {
+        &self.fans
+    }
+
+    /// Set each fan from its curve in `curves`. A fan without an enabled
+    /// curve, or whose temperature or speed can't be read or written, is
+    /// handed back to automatic control.
+    pub fn step(&mut self, curves: &[CurveData], config: &SoftwareFanConfig) {
+        for i in 0..self.fans.len() {
+            let fan = &self.fans[i];
+            let Some(curve) = curves.iter().find(|c| c.fan == fan.fan && c.enabled) else {
+                self.release(i);
+                continue;
+            };
+            let result = self.sensors.read_for(fan.fan).and_then(|temp| {
+                let pwm = self.states[i].step(curve, temp, config);
+                if !self.manual[i] {
+                    fan.set_manual()?;
+                }
+                fan.write_pwm(pwm)?;
+                trace!(
+                    "Software fan control: {:?} at {temp}c set to {pwm}",
+                    fan.fan
+                );
+                Ok(())
+            });
+            match result {
+                Ok(()) => self.manual[i] = true,
+                Err(e) => {
+                    warn!("Software fan control of {:?} fan failed: {e}", fan.fan);
+                    // Assume manual so that release() always tries to restore
+                    self.manual[i] = true;
+                    self.release(i);
+                }
+            }
+        }
+    }
+
+    fn release(&mut self, i: usize) {
+        if self.manual[i] {
+            if let Err(e) = self.fans[i].set_auto() {
+                error!(
+                    "Could not return {:?} fan to automatic control: {e}",
+                    self.fans[i].fan
+                );
+            }
+            self.manual[i] = false;
+        }
+        self.states[i] = SoftwareFanState::default();
+    }
+
+    /// Forget which fans are under manual control, after something else
+    /// handed them back to automatic, so the next step takes them again
+    pub fn reset_manual(&mut self) {
+        self.manual.iter_mut().for_each(|m| *m = false);
+    }
+
+    /// Hand all fans back to automatic control
+    pub fn stop(&mut self) {
+        for i in 0..self.fans.len() {
+            self.manual[i] = true;
+            self.release(i);
+        }
+    }
+}
+
+impl CurveData {
+    /// A starting curve for software control, where the firmware has no
+    /// defaults to read. It is disabled so the fan stays automatic until the
+    /// user enables it.
+    pub fn software_default(fan: FanCurvePU) -> Self {
+        Self {
+            fan,
+            pwm: [0, 0, 51, 89, 128, 166, 204, 255],
+            temp: [30, 40, 50, 60, 70, 80, 90, 100],
+            enabled: false,
+        }
+    }
+}
+
+impl FanCurveProfiles {
+    /// Reset the curves of `profile` to [`CurveData::software_default()`] for
+    /// each fan, and unbind any preset
+    pub fn set_software_defaults(&mut self, profile: ThrottlePolicy, fans: &[FanCurvePU]) {
+        let curves = fans
+            .iter()
+            .map(|fan| CurveData::software_default(*fan))
+            .collect();
+        match profile {
+            ThrottlePolicy::Balanced => self.balanced = curves,
+            ThrottlePolicy::Performance => self.performance = curves,
+            ThrottlePolicy::Quiet => self.quiet = curves,
+        }
+        self.unbind_preset(profile);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    use rog_platform::sysfs::SysfsFixture;
+
+    use super::{FanSensors, PwmFan, SoftwareFanConfig, SoftwareFanLoop, SoftwareFanState};
+    use crate::fan_curve_set::CurveData;
+    use crate::FanCurvePU;
+
+    fn fixture(name: &str) -> SysfsFixture {
+        let fixture = SysfsFixture::new(name);
+        fixture
+            .device(
+                "class/hwmon/hwmon0",
+                &[("name", "k10temp"), ("temp1_input", "71500")],
+            )
+            .device(
+                "class/hwmon/hwmon1",
+                &[("name", "amdgpu"), ("temp1_input", "55000")],
+            )
+            .device(
+                "class/hwmon/hwmon2",
+                &[
+                    ("name", "asus"),
+                    ("pwm1", "0"),
+                    ("pwm1_enable", "5"),
+                    ("fan1_label", "gpu_fan"),
+                    ("pwm2", "0"),
+                    ("pwm2_enable", "2"),
+                    ("fan2_label", "mid_fan"),
+                    ("pwm3", "0"),
+                    ("pwm3_enable", "2"),
+                    ("fan3_label", "cpu_fan"),
+                    ("fan3_input", "2400"),
+                    // Not a known fan
+                    ("pwm4", "0"),
+                    ("pwm4_enable", "2"),
+                ],
+            )
+            .device(
+                "class/hwmon/hwmon3",
+                &[
+                    ("name", "nct6775"),
+                    ("pwm1", "0"),
+                    ("pwm1_enable", "5"),
+                    ("fan1_label", "cpu_fan"),
+                ],
+            );
+        // Read only pwm can't be driven
+        fs::set_permissions(
+            fixture.path().join("class/hwmon/hwmon2/pwm2"),
+            fs::Permissions::from_mode(0o444),
+        )
+        .unwrap();
+        fixture
+    }
+
+    #[test]
+    fn find_sensors_and_fans() {
+        let fixture = fixture("find");
+        let root = fixture.root();
+
+        let sensors = FanSensors::find_in(&root).unwrap();
+        assert_eq!(sensors.read_for(FanCurvePU::CPU).unwrap(), 71);
+        assert_eq!(sensors.read_for(FanCurvePU::GPU).unwrap(), 55);
+        assert_eq!(sensors.read_for(FanCurvePU::MID).unwrap(), 71);
+
+        // Only the writable ASUS fans with a label, other chips are left alone
+        let fans = PwmFan::find_in(&root).unwrap();
+        assert_eq!(fans.len(), 2);
+        assert_eq!(fans[0].fan, FanCurvePU::GPU);
+        assert_eq!(fans[0].index, 1);
+        assert_eq!(fans[1].fan, FanCurvePU::CPU);
+        assert_eq!(fans[1].index, 3);
+        assert!(fans.iter().all(|f| f.device.ends_with("hwmon2")));
+
+        fans[0].set_manual().unwrap();
+        fans[0].write_pwm(128).unwrap();
+        let dev = &fans[0].device;
+        assert_eq!(fs::read_to_string(dev.join("pwm1_enable")).unwrap(), "1");
+        assert_eq!(fs::read_to_string(dev.join("pwm1")).unwrap(), "128");
+        // The mode found at start is restored
+        fans[0].set_auto().unwrap();
+        assert_eq!(fs::read_to_string(dev.join("pwm1_enable")).unwrap(), "5");
+    }
+
+    #[test]
+    fn step_hysteresis_and_ramp() {
+        let curve = CurveData {
+            fan: FanCurvePU::CPU,
+            pwm: [0, 0, 40, 80, 120, 160, 200, 255],
+            temp: [30, 40, 50, 60, 70, 80, 90, 100],
+            enabled: true,
+        };
+        let config = SoftwareFanConfig {
+            hysteresis: 3,
+            ramp_up: 30,
+            ramp_down: 10,
+            ..Default::default()
+        };
+        let mut state = SoftwareFanState::default();
+        // The first step goes straight to the curve
+        assert_eq!(state.step(&curve, 60, &config), 80);
+        // Ramping up is limited
+        assert_eq!(state.step(&curve, 80, &config), 110);
+        assert_eq!(state.step(&curve, 80, &config), 140);
+        assert_eq!(state.step(&curve, 80, &config), 160);
+        // A drop smaller than the hysteresis is ignored
+        assert_eq!(state.step(&curve, 78, &config), 160);
+        // A larger drop slows the fan, limited by the ramp
+        assert_eq!(state.step(&curve, 70, &config), 150);
+        assert_eq!(state.step(&curve, 70, &config), 140);
+
+        let unlimited = SoftwareFanConfig {
+            ramp_up: 0,
+            ramp_down: 0,
+            ..config
+        };
+        assert_eq!(state.step(&curve, 30, &unlimited), 0);
+    }
+
+    #[test]
+    fn loop_step_and_stop() {
+        let fixture = fixture("loop");
+        let root = fixture.root();
+        let mut fans = SoftwareFanLoop::find_in(&root).unwrap();
+        // Both fans are on the asus hwmon, the CPU fan at pwm3
+        let dev = fans.fans()[0].device.clone();
+        let read = |attr: &str| fs::read_to_string(dev.join(attr)).unwrap();
+
+        let mut cpu = CurveData::software_default(FanCurvePU::CPU);
+        cpu.enabled = true;
+        let gpu = CurveData::software_default(FanCurvePU::GPU);
+        let config = SoftwareFanConfig::default();
+        fans.step(&[cpu.clone(), gpu], &config);
+        // Only the enabled curve takes control, at 71c
+        assert_eq!(read("pwm3_enable"), "1");
+        assert_eq!(read("pwm3"), "132");
+        assert_eq!(read("pwm1_enable"), "5\n");
+
+        // Disabling the curve hands the fan back
+        cpu.enabled = false;
+        fans.step(&[cpu.clone()], &config);
+        assert_eq!(read("pwm3_enable"), "2");
+
+        cpu.enabled = true;
+        fans.step(&[cpu.clone()], &config);
+        assert_eq!(read("pwm3_enable"), "1");
+        // Handed back by something else, the next step takes it again
+        fans.fans()[1].set_auto().unwrap();
+        fans.reset_manual();
+        fans.step(&[cpu], &config);
+        assert_eq!(read("pwm3_enable"), "1");
+
+        fans.stop();
+        assert_eq!(read("pwm3_enable"), "2");
+        assert_eq!(read("pwm1_enable"), "5");
+    }
+}